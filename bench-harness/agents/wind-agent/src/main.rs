@@ -159,6 +159,9 @@ fn choose_payload_bytes(profile: PayloadProfile, fixed: usize, rng: &mut StdRng)
     }
 }
 
+/// Wall-clock latency of a payload from `encode_payload`. The publisher may
+/// run on another machine, so this includes the skew between the two clocks;
+/// a payload that seems to arrive before it was sent is skipped.
 fn decode_latency_us(payload: &[u8]) -> Option<u64> {
     if payload.len() < 8 {
         return None;
@@ -186,9 +189,17 @@ async fn run_publisher(
 ) -> anyhow::Result<()> {
     let publisher = Arc::new(Publisher::new(service.clone(), bind, registry.clone()));
 
-    let publisher_task = {
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let mut publisher_task = {
         let publisher = publisher.clone();
-        tokio::spawn(async move { publisher.start().await.context("publisher start failed") })
+        tokio::spawn(async move {
+            publisher
+                .start_until(async {
+                    let _ = stop_rx.await;
+                })
+                .await
+                .context("publisher start failed")
+        })
     };
 
     // Give the server time to bind and register.
@@ -240,7 +251,15 @@ async fn run_publisher(
         }
     }
 
-    publisher_task.abort();
+    // Shut down gracefully so the publisher deregisters and the next run
+    // doesn't discover this run's address; only abort if that stalls.
+    let _ = stop_tx.send(());
+    if tokio::time::timeout(Duration::from_secs(2), &mut publisher_task)
+        .await
+        .is_err()
+    {
+        publisher_task.abort();
+    }
 
     let summary = PublisherSummary {
         role: "publisher",
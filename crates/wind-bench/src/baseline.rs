@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Headline numbers of one benchmark run, saved with `--save-baseline` and
+/// compared against with `--baseline`. A metric the benchmark doesn't
+/// measure is `None` and never compared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchSummary {
+    /// Subcommand that produced the numbers, so a baseline is only compared
+    /// against the same benchmark
+    pub benchmark: String,
+    pub p50_us: Option<u64>,
+    pub p99_us: Option<u64>,
+    /// Values per second
+    pub throughput: Option<f64>,
+}
+
+impl BenchSummary {
+    pub fn new(benchmark: &str) -> Self {
+        Self {
+            benchmark: benchmark.to_string(),
+            p50_us: None,
+            p99_us: None,
+            throughput: None,
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// One metric of a run next to its baseline
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricDiff {
+    pub name: &'static str,
+    pub baseline: f64,
+    pub current: f64,
+    /// Worse than the baseline by more than the threshold
+    pub regressed: bool,
+}
+
+impl MetricDiff {
+    /// Relative change from the baseline, positive when the number went up
+    pub fn change(&self) -> f64 {
+        if self.baseline == 0.0 {
+            if self.current == 0.0 {
+                0.0
+            } else {
+                f64::INFINITY
+            }
+        } else {
+            (self.current - self.baseline) / self.baseline
+        }
+    }
+}
+
+/// Compare `current` against `baseline`. A latency regresses when it grows,
+/// and throughput when it shrinks, by more than `threshold` (0.1 is 10%).
+pub fn compare(baseline: &BenchSummary, current: &BenchSummary, threshold: f64) -> Vec<MetricDiff> {
+    let latency = |name, baseline: Option<u64>, current: Option<u64>| {
+        let (baseline, current) = (baseline? as f64, current? as f64);
+        Some(MetricDiff {
+            name,
+            baseline,
+            current,
+            regressed: current > baseline * (1.0 + threshold),
+        })
+    };
+
+    let mut diffs = Vec::new();
+    diffs.extend(latency("p50 us", baseline.p50_us, current.p50_us));
+    diffs.extend(latency("p99 us", baseline.p99_us, current.p99_us));
+    if let (Some(baseline), Some(current)) = (baseline.throughput, current.throughput) {
+        diffs.push(MetricDiff {
+            name: "throughput/s",
+            baseline,
+            current,
+            regressed: current < baseline * (1.0 - threshold),
+        });
+    }
+    diffs
+}
+
+pub fn print_comparison(diffs: &[MetricDiff]) {
+    println!("\n=== Baseline Comparison ===");
+    println!(
+        "{:>14} {:>14} {:>14} {:>9}",
+        "Metric", "Baseline", "Current", "Change"
+    );
+    for diff in diffs {
+        println!(
+            "{:>14} {:>14.1} {:>14.1} {:>+8.1}%{}",
+            diff.name,
+            diff.baseline,
+            diff.current,
+            diff.change() * 100.0,
+            if diff.regressed { "  REGRESSED" } else { "" }
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(p50_us: u64, p99_us: u64, throughput: f64) -> BenchSummary {
+        BenchSummary {
+            p50_us: Some(p50_us),
+            p99_us: Some(p99_us),
+            throughput: Some(throughput),
+            ..BenchSummary::new("latency")
+        }
+    }
+
+    #[test]
+    fn test_worse_run_regresses_and_equal_or_better_passes() {
+        let baseline = summary(100, 1000, 5000.0);
+
+        let worse = summary(100, 1200, 4000.0);
+        let regressed: Vec<_> = compare(&baseline, &worse, 0.1)
+            .into_iter()
+            .filter(|diff| diff.regressed)
+            .map(|diff| diff.name)
+            .collect();
+        assert_eq!(regressed, ["p99 us", "throughput/s"]);
+
+        // Within the threshold, equal, and better all pass
+        for current in [
+            summary(105, 1090, 4600.0),
+            baseline.clone(),
+            summary(50, 500, 9000.0),
+        ] {
+            let diffs = compare(&baseline, &current, 0.1);
+            assert_eq!(diffs.len(), 3);
+            assert!(diffs.iter().all(|diff| !diff.regressed), "{:?}", diffs);
+        }
+
+        // Metrics missing from either side aren't compared
+        let throughput_only = BenchSummary {
+            throughput: Some(1.0),
+            ..BenchSummary::new("latency")
+        };
+        let diffs = compare(&baseline, &throughput_only, 0.1);
+        assert_eq!(diffs.len(), 1);
+        assert!(diffs[0].regressed);
+    }
+
+    #[test]
+    fn test_baseline_survives_a_save_and_load() {
+        let path = std::env::temp_dir().join(format!("wind-bench-{}.json", std::process::id()));
+        let baseline = summary(120, 950, 4321.5);
+
+        baseline.save(&path).unwrap();
+        let loaded = BenchSummary::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, baseline);
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::Arc;
+use tokio::time::{Duration, Instant};
+use tracing::warn;
+use wind_client::WindClient;
+use wind_core::{SubscriptionMode, WindValue};
+use wind_registry::RegistryServer;
+use wind_server::Publisher;
+
+use crate::baseline::BenchSummary;
+
+/// Publish to many subscribers at once and report how long each fan-out takes
+pub async fn run(
+    registry_addr: &str,
+    subscribers: usize,
+    payload_bytes: usize,
+    publishes: usize,
+) -> anyhow::Result<BenchSummary> {
+    println!("=== WIND Fan-out Benchmark ===");
+    println!("Registry: {}", registry_addr);
+    println!("Subscribers: {}", subscribers);
+    println!("Payload size: {} bytes", payload_bytes);
+    println!("Publishes: {}", publishes);
+    println!();
+
+    let registry = RegistryServer::new(registry_addr.to_string());
+    tokio::spawn(async move {
+        if let Err(e) = registry.run().await {
+            warn!("Registry error: {}", e);
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let publisher = Arc::new(Publisher::new(
+        "BENCH/FANOUT".to_string(),
+        "127.0.0.1:0".to_string(),
+        registry_addr.to_string(),
+    ));
+    tokio::spawn({
+        let publisher = publisher.clone();
+        async move {
+            if let Err(e) = publisher.start().await {
+                warn!("Publisher error: {}", e);
+            }
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+
+    // Each subscriber drains its subscription so flow control never throttles
+    let mut client = WindClient::new(registry_addr.to_string());
+    for _ in 0..subscribers {
+        let mut subscription = client
+            .subscription("BENCH/FANOUT")
+            .mode(SubscriptionMode::OnChange)
+            .start()
+            .await?;
+        tokio::spawn(async move { while subscription.next().await.is_some() {} });
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut delivered = 0;
+    let started = Instant::now();
+    for i in 0..publishes {
+        let mut payload = vec![0u8; payload_bytes.max(8)];
+        payload[..8].copy_from_slice(&(i as u64).to_le_bytes());
+        delivered += publisher
+            .publish_confirmed(WindValue::Bytes(payload))
+            .await
+            .delivered;
+    }
+    let elapsed = started.elapsed();
+
+    println!("=== Fan-out Results ===");
+    println!("Deliveries: {} of {}", delivered, publishes * subscribers);
+    println!(
+        "Mean fan-out time: {:.1} us",
+        elapsed.as_micros() as f64 / publishes as f64
+    );
+    println!(
+        "Delivery rate: {:.0} values/sec",
+        delivered as f64 / elapsed.as_secs_f64()
+    );
+
+    let mut summary = BenchSummary::new("fanout");
+    summary.throughput = Some(delivered as f64 / elapsed.as_secs_f64());
+    Ok(summary)
+}
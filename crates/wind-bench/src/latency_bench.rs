@@ -1,37 +1,169 @@
 use hdrhistogram::Histogram;
 use rand::RngCore;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use tokio::time::{Duration, Instant};
 use tracing::warn;
-use wind_client::WindClient;
+use wind_client::{Subscription, WindClient};
 use wind_core::{QosParams, SubscriptionMode, WindValue};
 use wind_registry::RegistryServer;
 use wind_server::Publisher;
 
+use crate::baseline::BenchSummary;
+
+/// Load points reported by a ramp, as fractions of the measured maximum throughput
+const RAMP_LOAD_FACTORS: [f64; 3] = [0.5, 0.9, 1.1];
+
+/// Most times the publish rate is doubled while looking for the maximum throughput
+const MAX_RAMP_STEPS: u32 = 10;
+
+/// A subscriber receiving less than this fraction of the publish rate can't keep up
+const KEEP_UP_FRACTION: f64 = 0.9;
+
+/// Latency measured while publishing at a fixed rate
+pub struct LoadPoint {
+    pub publish_hz: u64,
+    /// Rate at which the subscriber actually received values
+    pub received_hz: f64,
+    pub histogram: Histogram<u64>,
+    /// Values that seemed to arrive before they were sent, left out of the
+    /// histogram; only clock skew between sender and receiver causes these
+    pub skewed: u64,
+}
+
+impl LoadPoint {
+    fn summary(&self) -> BenchSummary {
+        let mut summary = BenchSummary::new("latency");
+        if !self.histogram.is_empty() {
+            summary.p50_us = Some(self.histogram.value_at_quantile(0.50));
+            summary.p99_us = Some(self.histogram.value_at_quantile(0.99));
+        }
+        summary.throughput = Some(self.received_hz);
+        summary
+    }
+}
+
+/// Clock that stamps benchmark values when sent and is read again on receipt
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum LatencyClock {
+    /// Micros since a process-wide `Instant`: free of clock skew, but the
+    /// publisher and subscriber must run in the same process
+    Monotonic,
+    /// Unix time: works across processes and machines, but every latency
+    /// includes the skew between their clocks
+    WallClock,
+}
+
+impl LatencyClock {
+    pub fn now_us(self) -> i64 {
+        match self {
+            LatencyClock::Monotonic => {
+                static EPOCH: OnceLock<std::time::Instant> = OnceLock::new();
+                EPOCH
+                    .get_or_init(std::time::Instant::now)
+                    .elapsed()
+                    .as_micros() as i64
+            }
+            LatencyClock::WallClock => std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_micros() as i64,
+        }
+    }
+
+    /// Map key of the send time, so a stamp is never read with the other clock
+    fn field(self) -> &'static str {
+        match self {
+            LatencyClock::Monotonic => "monotonic_us",
+            LatencyClock::WallClock => "timestamp",
+        }
+    }
+}
+
 pub async fn run(
     registry_addr: &str,
     samples: usize,
     payload_bytes: usize,
     duration_secs: u64,
-) -> anyhow::Result<()> {
+    publish_hz: u64,
+    ramp: bool,
+    clock: LatencyClock,
+) -> anyhow::Result<BenchSummary> {
     println!("=== WIND Latency Benchmark ===");
     println!("Registry: {}", registry_addr);
     println!("Samples: {}", samples);
     println!("Payload size: {} bytes", payload_bytes);
     println!("Duration: {} seconds", duration_secs);
+    println!("Clock: {:?}", clock);
+    if ramp {
+        println!("Publish rate: ramping up from {} Hz", publish_hz);
+    } else {
+        println!("Publish rate: {} Hz", publish_hz);
+    }
     println!();
 
+    let (publisher, mut subscription) = start(registry_addr).await?;
+    let duration = Duration::from_secs(duration_secs);
+
+    if ramp {
+        let points = ramp_load_points(
+            &publisher,
+            &mut subscription,
+            publish_hz,
+            payload_bytes,
+            samples,
+            duration,
+            clock,
+        )
+        .await;
+        print_load_points(&points);
+        // The 90% point is the heaviest load the subscriber keeps up with
+        return Ok(points
+            .get(1)
+            .map_or_else(|| BenchSummary::new("latency"), LoadPoint::summary));
+    }
+
+    let point = measure(
+        &publisher,
+        &mut subscription,
+        publish_hz,
+        payload_bytes,
+        samples,
+        duration,
+        clock,
+    )
+    .await;
+
+    // Print results
+    let histogram = &point.histogram;
+    println!("\n=== Latency Results ===");
+    println!("Samples collected: {}", histogram.len());
+    println!("Sample rate: {:.0} Hz", point.received_hz);
+    if point.skewed > 0 {
+        println!("Skewed samples discarded: {}", point.skewed);
+    }
+    println!();
+    println!("Latency distribution (microseconds):");
+    println!("  Min:  {}", histogram.min());
+    println!("  p50:  {}", histogram.value_at_quantile(0.50));
+    println!("  p90:  {}", histogram.value_at_quantile(0.90));
+    println!("  p95:  {}", histogram.value_at_quantile(0.95));
+    println!("  p99:  {}", histogram.value_at_quantile(0.99));
+    println!("  p99.9:{}", histogram.value_at_quantile(0.999));
+    println!("  Max:  {}", histogram.max());
+
+    Ok(point.summary())
+}
+
+/// Start a registry, a publisher and one subscriber to it
+async fn start(registry_addr: &str) -> anyhow::Result<(Arc<Publisher>, Subscription)> {
     // Start registry
     let registry = Arc::new(RegistryServer::new(registry_addr.to_string()));
-    let _registry_handle = {
-        let registry = registry.clone();
-        tokio::spawn(async move {
-            if let Err(e) = registry.run().await {
-                warn!("Registry error: {}", e);
-            }
-        })
-    };
+    tokio::spawn(async move {
+        if let Err(e) = registry.run().await {
+            warn!("Registry error: {}", e);
+        }
+    });
 
     // Give registry time to start
     tokio::time::sleep(Duration::from_millis(500)).await;
@@ -42,23 +174,21 @@ pub async fn run(
         "127.0.0.1:0".to_string(),
         registry_addr.to_string(),
     ));
-
-    let _publisher_handle = {
-        let pub_ref = publisher.clone();
-
-        tokio::spawn(async move {
-            if let Err(e) = pub_ref.start().await {
+    tokio::spawn({
+        let publisher = publisher.clone();
+        async move {
+            if let Err(e) = publisher.start().await {
                 warn!("Publisher error: {}", e);
             }
-        })
-    };
+        }
+    });
 
     // Give publisher time to register
     tokio::time::sleep(Duration::from_millis(1000)).await;
 
     // Start subscriber
     let mut client = WindClient::new(registry_addr.to_string());
-    let mut subscription = client
+    let subscription = client
         .subscribe_with_options(
             "BENCH/LATENCY",
             SubscriptionMode::OnChange,
@@ -66,76 +196,233 @@ pub async fn run(
         )
         .await?;
 
-    // Start latency measurement
-    let mut histogram = Histogram::<u64>::new(3)?;
-    let mut samples_collected = 0;
-    let start_time = Instant::now();
-    let test_duration = Duration::from_secs(duration_secs);
+    Ok((publisher, subscription))
+}
 
-    // Spawn publisher task
-    let publisher_ref = publisher.clone();
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(Duration::from_millis(10)); // 100 Hz
+/// Double the publish rate from `start_hz` until the subscriber falls behind,
+/// then measure latency at fractions of the best rate it sustained
+pub async fn ramp_load_points(
+    publisher: &Arc<Publisher>,
+    subscription: &mut Subscription,
+    start_hz: u64,
+    payload_bytes: usize,
+    samples: usize,
+    step: Duration,
+    clock: LatencyClock,
+) -> Vec<LoadPoint> {
+    let mut max_hz = 0.0f64;
+    let mut publish_hz = start_hz.max(1);
+    for _ in 0..MAX_RAMP_STEPS {
+        let point = measure(
+            publisher,
+            subscription,
+            publish_hz,
+            payload_bytes,
+            samples,
+            step,
+            clock,
+        )
+        .await;
+        println!(
+            "Ramp: published at {} Hz, received {:.0} Hz",
+            publish_hz, point.received_hz
+        );
+        max_hz = max_hz.max(point.received_hz);
+        if point.received_hz < publish_hz as f64 * KEEP_UP_FRACTION {
+            break;
+        }
+        publish_hz *= 2;
+    }
 
-        loop {
-            interval.tick().await;
+    let mut points = Vec::new();
+    for factor in RAMP_LOAD_FACTORS {
+        let publish_hz = ((max_hz * factor) as u64).max(1);
+        points.push(
+            measure(
+                publisher,
+                subscription,
+                publish_hz,
+                payload_bytes,
+                samples,
+                step,
+                clock,
+            )
+            .await,
+        );
+    }
+    points
+}
 
-            let mut payload = vec![0u8; payload_bytes];
-            rand::thread_rng().fill_bytes(&mut payload);
+/// Publish at `publish_hz` and record latency until `samples` values arrive
+/// or `duration` passes
+async fn measure(
+    publisher: &Arc<Publisher>,
+    subscription: &mut Subscription,
+    publish_hz: u64,
+    payload_bytes: usize,
+    samples: usize,
+    duration: Duration,
+    clock: LatencyClock,
+) -> LoadPoint {
+    let mut histogram = Histogram::<u64>::new(3).expect("3 significant figures is valid");
+    let mut skewed = 0;
+    let start_time = Instant::now();
 
-            let now = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_micros() as i64;
+    // Spawn publisher task; at rates above the timer resolution, missed
+    // ticks fire in bursts so the average rate still holds
+    let publish_task = tokio::spawn({
+        let publisher = publisher.clone();
+        async move {
+            let mut interval =
+                tokio::time::interval(Duration::from_secs_f64(1.0 / publish_hz.max(1) as f64));
+
+            loop {
+                interval.tick().await;
 
-            let mut value_map = HashMap::new();
-            value_map.insert("timestamp".to_string(), WindValue::I64(now));
-            value_map.insert("data".to_string(), WindValue::Bytes(payload));
+                let mut payload = vec![0u8; payload_bytes];
+                rand::thread_rng().fill_bytes(&mut payload);
 
-            if let Err(e) = publisher_ref.publish(WindValue::Map(value_map)).await {
-                warn!("Publish error: {}", e);
+                let mut value_map = HashMap::new();
+                value_map.insert(clock.field().to_string(), WindValue::I64(clock.now_us()));
+                value_map.insert("data".to_string(), WindValue::Bytes(payload));
+
+                if let Err(e) = publisher.publish(WindValue::Map(value_map)).await {
+                    warn!("Publish error: {}", e);
+                }
             }
         }
     });
 
     // Collect latency samples
-    while samples_collected < samples && start_time.elapsed() < test_duration {
-        if let Some(value) = subscription.next().await {
-            if let WindValue::Map(received_map) = value {
-                if let Some(WindValue::I64(sent_ts)) = received_map.get("timestamp") {
-                    let now = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_micros() as i64;
-                    let latency_us = (now - sent_ts) as u64;
-                    histogram.record(latency_us).unwrap_or_else(|e| {
-                        warn!("Failed to record latency: {}", e);
-                    });
-                    samples_collected += 1;
-
-                    if samples_collected % 1000 == 0 {
-                        println!("Collected {} samples...", samples_collected);
-                    }
+    while (histogram.len() as usize) < samples {
+        let Some(remaining) = duration.checked_sub(start_time.elapsed()) else {
+            break;
+        };
+        let Ok(received) = tokio::time::timeout(remaining, subscription.next()).await else {
+            break;
+        };
+        match received.as_ref().and_then(|value| latency_us(value, clock)) {
+            Some(Ok(latency_us)) => {
+                histogram.record(latency_us).unwrap_or_else(|e| {
+                    warn!("Failed to record latency: {}", e);
+                });
+
+                if histogram.len().is_multiple_of(1000) {
+                    println!("Collected {} samples...", histogram.len());
                 }
             }
+            Some(Err(())) => skewed += 1,
+            None => {}
         }
     }
+    let elapsed = start_time.elapsed().as_secs_f64();
+    publish_task.abort();
 
-    // Print results
-    let duration = start_time.elapsed().as_secs_f64();
-    println!("\n=== Latency Results ===");
-    println!("Test duration: {:.2}s", duration);
-    println!("Samples collected: {}", samples_collected);
-    println!("Sample rate: {:.0} Hz", samples_collected as f64 / duration);
-    println!();
-    println!("Latency distribution (microseconds):");
-    println!("  Min:  {}", histogram.min());
-    println!("  p50:  {}", histogram.value_at_quantile(0.50));
-    println!("  p90:  {}", histogram.value_at_quantile(0.90));
-    println!("  p95:  {}", histogram.value_at_quantile(0.95));
-    println!("  p99:  {}", histogram.value_at_quantile(0.99));
-    println!("  p99.9:{}", histogram.value_at_quantile(0.999));
-    println!("  Max:  {}", histogram.max());
+    // Let the backlog drain so it doesn't count against the next load point
+    let drain_until = Instant::now() + Duration::from_millis(500);
+    while Instant::now() < drain_until
+        && tokio::time::timeout(Duration::from_millis(50), subscription.next())
+            .await
+            .is_ok()
+    {}
 
-    Ok(())
+    LoadPoint {
+        publish_hz,
+        received_hz: histogram.len() as f64 / elapsed,
+        histogram,
+        skewed,
+    }
+}
+
+/// End-to-end latency of a benchmark value, from its embedded send time.
+/// `Err` if it seems to have arrived before it was sent.
+fn latency_us(value: &WindValue, clock: LatencyClock) -> Option<Result<u64, ()>> {
+    match value {
+        WindValue::Map(map) => match map.get(clock.field()) {
+            Some(WindValue::I64(sent_us)) => {
+                let latency_us = clock.now_us() - sent_us;
+                Some(u64::try_from(latency_us).map_err(|_| ()))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn print_load_points(points: &[LoadPoint]) {
+    println!("\n=== Latency Under Load ===");
+    println!(
+        "{:>6} {:>12} {:>12} {:>10} {:>10} {:>10} {:>10}",
+        "Load", "Publish Hz", "Received Hz", "Samples", "p50 us", "p99 us", "Max us"
+    );
+    for (factor, point) in RAMP_LOAD_FACTORS.iter().zip(points) {
+        println!(
+            "{:>5.0}% {:>12} {:>12.0} {:>10} {:>10} {:>10} {:>10}",
+            factor * 100.0,
+            point.publish_hz,
+            point.received_hz,
+            point.histogram.len(),
+            point.histogram.value_at_quantile(0.50),
+            point.histogram.value_at_quantile(0.99),
+            point.histogram.max()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ramp_records_latency_at_several_rates() {
+        let (publisher, mut subscription) = start("127.0.0.1:7301").await.unwrap();
+
+        let points = ramp_load_points(
+            &publisher,
+            &mut subscription,
+            200,
+            16,
+            usize::MAX,
+            Duration::from_millis(200),
+            LatencyClock::Monotonic,
+        )
+        .await;
+
+        assert_eq!(points.len(), RAMP_LOAD_FACTORS.len());
+        let sampled: Vec<_> = points
+            .iter()
+            .filter(|point| !point.histogram.is_empty())
+            .map(|point| point.publish_hz)
+            .collect();
+        assert!(sampled.len() > 1, "{:?}", sampled);
+        assert!(sampled.windows(2).all(|rates| rates[0] < rates[1]));
+    }
+
+    #[tokio::test]
+    async fn test_monotonic_clock_gives_skew_free_latency_in_process() {
+        let (publisher, mut subscription) = start("127.0.0.1:7302").await.unwrap();
+
+        let started = Instant::now();
+        let point = measure(
+            &publisher,
+            &mut subscription,
+            500,
+            16,
+            usize::MAX,
+            Duration::from_millis(300),
+            LatencyClock::Monotonic,
+        )
+        .await;
+        let window_us = started.elapsed().as_micros() as u64;
+
+        assert!(!point.histogram.is_empty());
+        assert_eq!(point.skewed, 0);
+        // No latency can exceed the time the measurement took
+        assert!(
+            point.histogram.max() <= window_us,
+            "{} > {}",
+            point.histogram.max(),
+            window_us
+        );
+    }
 }
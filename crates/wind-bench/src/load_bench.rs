@@ -1,12 +1,14 @@
 use tokio::time::Duration;
 
+use crate::baseline::BenchSummary;
+
 pub async fn run(
     registry_addr: &str,
     services: usize,
     subscribers_per_service: usize,
     duration_secs: u64,
     publish_hz: u64,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<BenchSummary> {
     println!("=== WIND Load Test ===");
     println!("Registry: {}", registry_addr);
     println!("Services: {}", services);
@@ -46,5 +48,7 @@ pub async fn run(
     // - Resource utilization per component
     // - Error rates and recovery times
 
-    Ok(())
+    let mut summary = BenchSummary::new("load");
+    summary.throughput = Some(total_publish_rate as f64);
+    Ok(summary)
 }
@@ -1,5 +1,10 @@
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
+use baseline::BenchSummary;
+
+mod baseline;
+mod fanout_bench;
 mod latency_bench;
 mod load_bench;
 mod throughput_bench;
@@ -16,6 +21,19 @@ struct Cli {
 
     #[arg(long, default_value = "warn", global = true)]
     log_level: String,
+
+    /// Compare the run against a baseline saved by --save-baseline and exit
+    /// with an error if any metric regressed
+    #[arg(long, global = true)]
+    baseline: Option<PathBuf>,
+
+    /// Save the run's p50, p99 and throughput as a JSON baseline
+    #[arg(long, global = true)]
+    save_baseline: Option<PathBuf>,
+
+    /// Percentage by which a metric may be worse than the baseline
+    #[arg(long, default_value = "10", global = true)]
+    regression_threshold: f64,
 }
 
 #[derive(Subcommand)]
@@ -28,8 +46,22 @@ enum Commands {
         #[arg(long, default_value = "256")]
         payload_bytes: usize,
 
+        /// Time limit, per load point when ramping
         #[arg(long, default_value = "5")]
         duration_secs: u64,
+
+        #[arg(long, default_value = "100")]
+        publish_hz: u64,
+
+        /// Raise the publish rate from --publish-hz until the subscriber falls
+        /// behind, then report latency at 50%, 90% and 110% of that maximum
+        #[arg(long)]
+        ramp: bool,
+
+        /// Clock for send and receive times: `monotonic` is exact but only
+        /// valid in one process, as here; `wall-clock` includes clock skew
+        #[arg(long, value_enum, default_value = "monotonic")]
+        clock: latency_bench::LatencyClock,
     },
     /// Measure maximum throughput
     Throughput {
@@ -45,6 +77,17 @@ enum Commands {
         #[arg(long, default_value = "1000")]
         target_hz: u64,
     },
+    /// Measure the cost of publishing one value to many subscribers
+    Fanout {
+        #[arg(long, default_value = "100")]
+        subscribers: usize,
+
+        #[arg(long, default_value = "16384")]
+        payload_bytes: usize,
+
+        #[arg(long, default_value = "1000")]
+        publishes: usize,
+    },
     /// Load testing with multiple services
     Load {
         #[arg(long, default_value = "10")]
@@ -74,13 +117,25 @@ async fn main() -> anyhow::Result<()> {
         .with_line_number(false)
         .init();
 
-    match cli.command {
+    let summary = match cli.command {
         Commands::Latency {
             samples,
             payload_bytes,
             duration_secs,
+            publish_hz,
+            ramp,
+            clock,
         } => {
-            latency_bench::run(&cli.registry, samples, payload_bytes, duration_secs).await?;
+            latency_bench::run(
+                &cli.registry,
+                samples,
+                payload_bytes,
+                duration_secs,
+                publish_hz,
+                ramp,
+                clock,
+            )
+            .await?
         }
         Commands::Throughput {
             subscribers,
@@ -95,8 +150,13 @@ async fn main() -> anyhow::Result<()> {
                 duration_secs,
                 target_hz,
             )
-            .await?;
+            .await?
         }
+        Commands::Fanout {
+            subscribers,
+            payload_bytes,
+            publishes,
+        } => fanout_bench::run(&cli.registry, subscribers, payload_bytes, publishes).await?,
         Commands::Load {
             services,
             subscribers_per_service,
@@ -110,8 +170,34 @@ async fn main() -> anyhow::Result<()> {
                 duration_secs,
                 publish_hz,
             )
-            .await?;
+            .await?
         }
+    };
+
+    if let Some(path) = &cli.save_baseline {
+        summary.save(path)?;
+        println!("\nBaseline saved to {}", path.display());
+    }
+
+    if let Some(path) = &cli.baseline {
+        let baseline = BenchSummary::load(path)?;
+        anyhow::ensure!(
+            baseline.benchmark == summary.benchmark,
+            "Baseline {} is for the {} benchmark, not {}",
+            path.display(),
+            baseline.benchmark,
+            summary.benchmark
+        );
+        let diffs = baseline::compare(&baseline, &summary, cli.regression_threshold / 100.0);
+        baseline::print_comparison(&diffs);
+        let regressed = diffs.iter().filter(|diff| diff.regressed).count();
+        anyhow::ensure!(
+            regressed == 0,
+            "{} metric(s) regressed by more than {}% against {}",
+            regressed,
+            cli.regression_threshold,
+            path.display()
+        );
     }
 
     Ok(())
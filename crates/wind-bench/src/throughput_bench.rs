@@ -3,13 +3,15 @@ use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::time::{Duration, Instant};
 
+use crate::baseline::BenchSummary;
+
 pub async fn run(
     registry_addr: &str,
     subscribers: usize,
     payload_bytes: usize,
     duration_secs: u64,
     target_hz: u64,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<BenchSummary> {
     println!("=== WIND Throughput Benchmark ===");
     println!("Registry: {}", registry_addr);
     println!("Subscribers: {}", subscribers);
@@ -84,6 +86,9 @@ pub async fn run(
         total_b,
         total_b as f64 / 1_048_576.0
     );
+    let mut summary = BenchSummary::new("throughput");
+    summary.throughput = Some(total_msgs as f64 / duration);
+
     println!("Message rate: {:.0} msgs/sec", total_msgs as f64 / duration);
     println!(
         "Throughput: {:.2} MB/sec",
@@ -106,7 +111,10 @@ pub async fn run(
         println!("  p95:  {}", aggregate.value_at_quantile(0.95));
         println!("  p99:  {}", aggregate.value_at_quantile(0.99));
         println!("  Max:  {}", aggregate.max());
+
+        summary.p50_us = Some(aggregate.value_at_quantile(0.50));
+        summary.p99_us = Some(aggregate.value_at_quantile(0.99));
     }
 
-    Ok(())
+    Ok(summary)
 }
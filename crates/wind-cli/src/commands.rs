@@ -1,9 +1,33 @@
+use std::path::Path;
 use std::sync::Arc;
 use tokio::time::{interval, sleep, Duration};
 use tracing::{error, info};
 use wind_client::WindClient;
-use wind_core::{QosParams, SubscriptionMode, WindValue};
-use wind_server::Publisher;
+use wind_core::{QosParams, Schema, SubscriptionMode, WindValue};
+use wind_server::{DeliveryReport, Publisher};
+
+/// How `publish` and `call` read a value given on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueFormat {
+    /// JSON if it parses, otherwise the text as a string, so `hello` needs no quotes
+    Auto,
+    /// JSON only; anything else is an error
+    Json,
+    /// The text as a string, even if it is valid JSON
+    RawString,
+}
+
+pub fn parse_value(input: &str, format: ValueFormat) -> anyhow::Result<WindValue> {
+    match format {
+        ValueFormat::RawString => Ok(WindValue::String(input.to_string())),
+        ValueFormat::Json => serde_json::from_str(input)
+            .map(WindValue::from_json)
+            .map_err(|e| anyhow::anyhow!("Invalid JSON value: {}", e)),
+        ValueFormat::Auto => Ok(serde_json::from_str(input)
+            .map(WindValue::from_json)
+            .unwrap_or_else(|_| WindValue::String(input.to_string()))),
+    }
+}
 
 pub async fn discover(registry: &str, pattern: &str, json: bool) -> anyhow::Result<()> {
     let mut client = WindClient::new(registry.to_string());
@@ -21,10 +45,8 @@ pub async fn discover(registry: &str, pattern: &str, json: bool) -> anyhow::Resu
         );
         for service in services {
             println!(
-                "  {} -> {} ({})",
-                service.name,
-                service.address,
-                format!("{:?}", service.service_type)
+                "  {} -> {} ({:?})",
+                service.name, service.address, service.service_type
             );
             if let Some(schema) = &service.schema_id {
                 println!("    Schema: {}", schema);
@@ -81,7 +103,7 @@ pub async fn subscribe(
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
             .as_millis();
-        println!("[{}] {}: {:?}", timestamp, service, value);
+        println!("[{}] {}: {}", timestamp, service, value.to_json());
 
         if once {
             break;
@@ -96,13 +118,11 @@ pub async fn call(
     service: &str,
     method: &str,
     params: &str,
+    format: ValueFormat,
     timeout_secs: u64,
 ) -> anyhow::Result<()> {
     let mut client = WindClient::new(registry.to_string());
-
-    // Parse JSON parameters
-    let params_value: serde_json::Value = serde_json::from_str(params)?;
-    let wind_params = json_to_wind_value(params_value);
+    let wind_params = parse_value(params, format)?;
 
     info!(
         "Calling {}::{} with params: {:?}",
@@ -118,7 +138,7 @@ pub async fn call(
         )
         .await?;
 
-    println!("RPC result: {:?}", result);
+    println!("RPC result: {}", result.to_json());
     Ok(())
 }
 
@@ -141,17 +161,87 @@ pub async fn list(registry: &str, json: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
+pub async fn deregister(
+    registry: &str,
+    service: &str,
+    address: Option<&str>,
+    client_id: Option<String>,
+) -> anyhow::Result<()> {
+    let mut client = WindClient::new(registry.to_string());
+    if let Some(client_id) = client_id {
+        client = client.with_client_id(client_id);
+    }
+
+    client.force_deregister(service, address).await?;
+    println!("Deregistered {}", service);
+    Ok(())
+}
+
+pub async fn inspect(
+    registry: &str,
+    service: &str,
+    client_id: Option<String>,
+    json: bool,
+) -> anyhow::Result<()> {
+    let mut client = WindClient::new(registry.to_string());
+    if let Some(client_id) = client_id {
+        client = client.with_client_id(client_id);
+    }
+    let diagnostics = client.inspect(service).await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&diagnostics)?);
+        return Ok(());
+    }
+    println!("Publisher of {}", service);
+    println!("  Services: {}", diagnostics.services.join(", "));
+    println!("  Sequence: {}", diagnostics.sequence);
+    println!(
+        "  Traffic: {} bytes in, {} bytes out",
+        diagnostics.bytes_in, diagnostics.bytes_out
+    );
+    println!(
+        "  Throttled: {}, dropped: {} overflowed, {} expired",
+        diagnostics.throttled, diagnostics.dropped, diagnostics.expired
+    );
+    println!("  Clients ({}):", diagnostics.clients.len());
+    for client in &diagnostics.clients {
+        println!(
+            "    {} ({}) credit {}, {} pending acks, {} bytes in, {} bytes out",
+            client.connection_id,
+            client.identity.as_deref().unwrap_or("anonymous"),
+            client
+                .credit
+                .map_or("unlimited".to_string(), |credit| credit.to_string()),
+            client.pending_acks,
+            client.bytes_in,
+            client.bytes_out
+        );
+        for subscription in &client.subscriptions {
+            println!(
+                "      {} {:?}{}",
+                subscription.service,
+                subscription.mode,
+                if subscription.reliable {
+                    " reliable"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+    Ok(())
+}
+
 pub async fn publish(
     registry: &str,
     service: &str,
     value: &str,
+    format: ValueFormat,
     repeat: Option<u64>,
     interval_ms: u64,
 ) -> anyhow::Result<()> {
-    // Parse the input value as JSON, then convert to WindValue
-    let json_val: serde_json::Value = serde_json::from_str(value)
-        .map_err(|e| anyhow::anyhow!("Invalid JSON value: {}", e))?;
-    let wind_value = json_to_wind_value(json_val);
+    let wind_value = parse_value(value, format)?;
 
     // Create and start a temporary publisher
     let publisher = Arc::new(Publisher::new(
@@ -178,11 +268,27 @@ pub async fn publish(
             service, count, interval_ms
         );
         let mut ticker = interval(Duration::from_millis(interval_ms));
+        let mut totals = DeliveryReport::default();
         for i in 0..count {
             ticker.tick().await;
-            publisher.publish(wind_value.clone()).await?;
-            info!("Published message {}/{}", i + 1, count);
+            // Waits until the value has been written to every subscriber, so
+            // none is still in flight when the publisher stops
+            let report = publisher.publish_confirmed(wind_value.clone()).await;
+            info!(
+                "Published message {}/{} ({} delivered)",
+                i + 1,
+                count,
+                report.delivered
+            );
+            totals.delivered += report.delivered;
+            totals.failed += report.failed;
+            totals.throttled += report.throttled;
+            totals.expired += report.expired;
         }
+        println!(
+            "Published {} message(s) to '{}': {} delivered, {} failed, {} throttled, {} expired",
+            count, service, totals.delivered, totals.failed, totals.throttled, totals.expired
+        );
     } else {
         info!("Publishing a single message to '{}'...", service);
         publisher.publish(wind_value).await?;
@@ -195,28 +301,170 @@ pub async fn publish(
     Ok(())
 }
 
-fn json_to_wind_value(json: serde_json::Value) -> WindValue {
-    use serde_json::Value;
-    match json {
-        Value::Null => WindValue::String("null".to_string()),
-        Value::Bool(b) => WindValue::Bool(b),
-        Value::Number(n) => {
-            if n.is_i64() {
-                WindValue::I64(n.as_i64().unwrap())
-            } else if n.is_f64() {
-                WindValue::F64(n.as_f64().unwrap())
-            } else {
-                WindValue::String(n.to_string())
-            }
+pub fn schema_generate(idl: &Path, output: Option<&Path>) -> anyhow::Result<()> {
+    let code = wind_codegen::generate_rust_types(&std::fs::read_to_string(idl)?)?;
+
+    match output {
+        Some(output) => {
+            std::fs::write(output, code)?;
+            println!("Generated {}", output.display());
         }
-        Value::String(s) => WindValue::String(s),
-        Value::Array(arr) => WindValue::Array(arr.into_iter().map(json_to_wind_value).collect()),
-        Value::Object(obj) => {
-            let mut map = std::collections::HashMap::new();
-            for (k, v) in obj {
-                map.insert(k, json_to_wind_value(v));
+        None => print!("{}", code),
+    }
+    Ok(())
+}
+
+pub fn schema_validate(idl: &Path, value: &Path, type_name: &str) -> anyhow::Result<()> {
+    let idl = wind_codegen::parse_idl(&std::fs::read_to_string(idl)?)?;
+    let schema = wind_codegen::schema_for_type(&idl, type_name)?;
+
+    schema
+        .validate(&WindValue::from_json(read_json(value)?))
+        .map_err(|e| anyhow::anyhow!("{} is not a valid {}: {}", value.display(), type_name, e))?;
+    println!("{} is a valid {}", value.display(), type_name);
+    Ok(())
+}
+
+pub fn schema_infer(value: &Path, name: &str) -> anyhow::Result<()> {
+    let schema = Schema::infer(name, &WindValue::from_json(read_json(value)?));
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+fn read_json(path: &Path) -> anyhow::Result<serde_json::Value> {
+    let text = std::fs::read_to_string(path)?;
+    serde_json::from_str(&text)
+        .map_err(|e| anyhow::anyhow!("Invalid JSON in {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("wind-cli-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_schema_generate_from_example_idl() {
+        let idl = temp_file("generate.json", &wind_codegen::example_sensor_idl());
+        let output = std::env::temp_dir().join(format!("wind-cli-{}-out.rs", std::process::id()));
+
+        schema_generate(&idl, Some(&output)).unwrap();
+        let code = std::fs::read_to_string(&output).unwrap();
+        assert!(code.contains("pub struct Temperature"));
+        assert!(code.contains("pub struct CalibrationCommand"));
+    }
+
+    #[test]
+    fn test_schema_validate_conforming_and_non_conforming() {
+        let idl = temp_file("validate.json", &wind_codegen::example_sensor_idl());
+        let good = temp_file(
+            "good.json",
+            r#"{"value": 21.5, "timestamp": 1700000000, "sensor_id": "T1"}"#,
+        );
+        let bad = temp_file(
+            "bad.json",
+            r#"{"value": "warm", "timestamp": 1700000000, "sensor_id": "T1"}"#,
+        );
+
+        schema_validate(&idl, &good, "Temperature").unwrap();
+        let error = schema_validate(&idl, &bad, "Temperature")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("is not a valid Temperature"), "{}", error);
+        assert!(schema_validate(&idl, &good, "Missing").is_err());
+    }
+
+    #[test]
+    fn test_json_null_is_distinct_from_missing_and_from_text() {
+        let parse = |json: &str| WindValue::from_json(serde_json::from_str(json).unwrap());
+        let empty = parse(r#"{}"#);
+        let null = parse(r#"{"x": null}"#);
+        let text = parse(r#"{"x": "null"}"#);
+        assert_ne!(empty, null);
+        assert_ne!(null, text);
+        match &null {
+            WindValue::Map(map) => assert_eq!(map["x"], WindValue::Null),
+            other => panic!("Expected a map, got {:?}", other),
+        }
+
+        for json in [
+            r#"{"x": null}"#,
+            r#"{"x": "null"}"#,
+            r#"[1, 2.5, true, null]"#,
+        ] {
+            let expected: serde_json::Value = serde_json::from_str(json).unwrap();
+            assert_eq!(parse(json).to_json(), expected);
+        }
+    }
+
+    #[test]
+    fn test_values_parse_as_json_or_fall_back_to_text() {
+        let auto = |input: &str| parse_value(input, ValueFormat::Auto).unwrap();
+        assert_eq!(auto("hello"), WindValue::from("hello"));
+        assert_eq!(auto("42"), WindValue::I64(42));
+        assert_eq!(auto(r#""42""#), WindValue::from("42"));
+        assert_eq!(
+            auto(r#"{"a":1}"#),
+            WindValue::Map(HashMap::from([("a".to_string(), WindValue::I64(1))]))
+        );
+
+        assert_eq!(
+            parse_value(r#"{"a":1}"#, ValueFormat::RawString).unwrap(),
+            WindValue::from(r#"{"a":1}"#)
+        );
+        assert_eq!(
+            parse_value("42", ValueFormat::Json).unwrap(),
+            WindValue::I64(42)
+        );
+        let error = parse_value("hello", ValueFormat::Json).unwrap_err();
+        assert!(error.to_string().contains("Invalid JSON"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_publish_repeat_delivers_every_value() {
+        let registry_addr = "127.0.0.1:7238";
+        let registry = wind_registry::RegistryServer::new(registry_addr.to_string());
+        tokio::spawn(async move {
+            let _ = registry.run().await;
+        });
+        sleep(Duration::from_millis(100)).await;
+
+        let publishing = tokio::spawn(publish(
+            registry_addr,
+            "TEST/CLI",
+            "42",
+            ValueFormat::Auto,
+            Some(5),
+            100,
+        ));
+
+        // Subscribe as soon as the command's publisher has registered, before
+        // its first publish; every value is sent even though they are equal
+        let mut client = WindClient::new(registry_addr.to_string());
+        let mut subscription = loop {
+            match client
+                .subscribe_with_options(
+                    "TEST/CLI",
+                    SubscriptionMode::Periodic { interval_ms: 0 },
+                    QosParams::default(),
+                )
+                .await
+            {
+                Ok(subscription) => break subscription,
+                Err(_) => sleep(Duration::from_millis(20)).await,
             }
-            WindValue::Map(map)
+        };
+        publishing.await.unwrap().unwrap();
+
+        for _ in 0..5 {
+            let next = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+            assert_eq!(next.unwrap(), Some(WindValue::I64(42)));
         }
     }
 }
@@ -1,4 +1,5 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
 
 mod commands;
 
@@ -49,10 +50,13 @@ enum Commands {
         /// Method name
         method: String,
 
-        /// Parameters (JSON format)
+        /// Parameters: JSON, or plain text taken as a string
         #[arg(default_value = "{}")]
         params: String,
 
+        #[command(flatten)]
+        format: ValueFormatArgs,
+
         #[arg(long, default_value = "5")]
         timeout_secs: u64,
     },
@@ -61,20 +65,109 @@ enum Commands {
         #[arg(long)]
         json: bool,
     },
+    /// Remove a service's registration without waiting for its TTL
+    Deregister {
+        /// Service name
+        service: String,
+
+        /// Only remove the registration at this address
+        #[arg(long)]
+        address: Option<String>,
+
+        /// Client id to present to the registry's ACL
+        #[arg(long)]
+        client_id: Option<String>,
+    },
+    /// Print a running publisher's clients, subscriptions and counters
+    Inspect {
+        /// Service name
+        service: String,
+
+        /// Client id the publisher allows to inspect it
+        #[arg(long)]
+        client_id: Option<String>,
+
+        #[arg(long)]
+        json: bool,
+    },
     /// Publish test data to a service pattern
     Publish {
         /// Service name pattern
         service: String,
 
-        /// Value to publish (JSON format)
+        /// Value to publish: JSON, or plain text taken as a string
         value: String,
 
+        #[command(flatten)]
+        format: ValueFormatArgs,
+
         #[arg(long)]
         repeat: Option<u64>,
 
         #[arg(long, default_value = "1000")]
         interval_ms: u64,
     },
+    /// Generate, validate against and infer schemas
+    Schema {
+        #[command(subcommand)]
+        command: SchemaCommand,
+    },
+}
+
+#[derive(Args)]
+struct ValueFormatArgs {
+    /// Take the value as a string even if it is valid JSON
+    #[arg(long, conflicts_with = "json")]
+    raw_string: bool,
+
+    /// Require valid JSON instead of falling back to a string
+    #[arg(long)]
+    json: bool,
+}
+
+impl ValueFormatArgs {
+    fn format(&self) -> commands::ValueFormat {
+        if self.raw_string {
+            commands::ValueFormat::RawString
+        } else if self.json {
+            commands::ValueFormat::Json
+        } else {
+            commands::ValueFormat::Auto
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum SchemaCommand {
+    /// Generate Rust types from an IDL file
+    Generate {
+        /// IDL file (JSON)
+        idl: PathBuf,
+
+        /// Output file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Validate a JSON value against a struct type from an IDL file
+    Validate {
+        /// IDL file (JSON)
+        idl: PathBuf,
+
+        /// Value file (JSON)
+        value: PathBuf,
+
+        /// Struct type in the IDL to validate against
+        #[arg(long = "type")]
+        type_name: String,
+    },
+    /// Print the schema inferred from a JSON value
+    Infer {
+        /// Value file (JSON)
+        value: PathBuf,
+
+        #[arg(long, default_value = "inferred")]
+        name: String,
+    },
 }
 
 #[tokio::main]
@@ -102,21 +195,68 @@ async fn main() -> anyhow::Result<()> {
             service,
             method,
             params,
+            format,
             timeout_secs,
         } => {
-            commands::call(&cli.registry, &service, &method, &params, timeout_secs).await?;
+            commands::call(
+                &cli.registry,
+                &service,
+                &method,
+                &params,
+                format.format(),
+                timeout_secs,
+            )
+            .await?;
         }
         Commands::List { json } => {
             commands::list(&cli.registry, json).await?;
         }
+        Commands::Deregister {
+            service,
+            address,
+            client_id,
+        } => {
+            commands::deregister(&cli.registry, &service, address.as_deref(), client_id).await?;
+        }
+        Commands::Inspect {
+            service,
+            client_id,
+            json,
+        } => {
+            commands::inspect(&cli.registry, &service, client_id, json).await?;
+        }
         Commands::Publish {
             service,
             value,
+            format,
             repeat,
             interval_ms,
         } => {
-            commands::publish(&cli.registry, &service, &value, repeat, interval_ms).await?;
+            commands::publish(
+                &cli.registry,
+                &service,
+                &value,
+                format.format(),
+                repeat,
+                interval_ms,
+            )
+            .await?;
         }
+        Commands::Schema { command } => match command {
+            SchemaCommand::Generate { idl, output } => {
+                commands::schema_generate(&idl, output.as_deref())?;
+            }
+            SchemaCommand::Validate {
+                idl,
+                value,
+                type_name,
+            } => {
+                commands::schema_validate(&idl, &value, &type_name)?;
+            }
+            SchemaCommand::Infer { value, name } => {
+                commands::schema_infer(&value, &name)?;
+            }
+        },
     }
 
     Ok(())
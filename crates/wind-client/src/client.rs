@@ -1,5 +1,11 @@
-use crate::{RpcClient, Subscriber, Subscription};
-use wind_core::{QosParams, Result, SubscriptionMode, WindValue};
+use crate::{
+    DiscoverySnapshot, PatternSubscription, RpcClient, SubscribeBuilder, Subscriber, Subscription,
+    ValidatedSubscription,
+};
+use wind_core::{
+    PublisherDiagnostics, QosParams, Result, ServiceType, SocketOptions, SubscriptionMode,
+    WindValue,
+};
 
 /// High-level WIND client combining subscription and RPC capabilities
 pub struct WindClient {
@@ -16,15 +22,36 @@ impl WindClient {
         }
     }
 
+    /// Identify as `client_id` to the registry and publishers, for ACLs
+    pub fn with_client_id(mut self, client_id: String) -> Self {
+        self.subscriber = self.subscriber.with_client_id(client_id.clone());
+        self.rpc_client = self.rpc_client.with_client_id(client_id);
+        self
+    }
+
+    /// Authenticate with `token` on every connection the client opens
+    pub fn with_token(mut self, token: String) -> Self {
+        self.subscriber = self.subscriber.with_token(token.clone());
+        self.rpc_client = self.rpc_client.with_token(token);
+        self
+    }
+
+    /// TCP options for every connection the client opens
+    pub fn with_socket_options(mut self, options: SocketOptions) -> Self {
+        self.subscriber = self.subscriber.with_socket_options(options.clone());
+        self.rpc_client = self.rpc_client.with_socket_options(options);
+        self
+    }
+
+    /// Start configuring a subscription, e.g.
+    /// `client.subscription("SENSOR/TEMP").reliable().typed::<i32>().start()`
+    pub fn subscription(&mut self, service_name: &str) -> SubscribeBuilder<'_> {
+        self.subscriber.subscription(service_name)
+    }
+
     /// Subscribe to a service with default QoS
     pub async fn subscribe(&mut self, service_name: &str) -> Result<Subscription> {
-        self.subscriber
-            .subscribe(
-                service_name,
-                SubscriptionMode::OnChange,
-                QosParams::default(),
-            )
-            .await
+        self.subscription(service_name).start().await
     }
 
     /// Subscribe with custom mode and QoS
@@ -37,6 +64,62 @@ impl WindClient {
         self.subscriber.subscribe(service_name, mode, qos).await
     }
 
+    /// Subscribe with an application-defined validator; rejected values are
+    /// dropped and counted by `Subscription::rejected_count`
+    pub async fn subscribe_with_validator<F>(
+        &mut self,
+        service_name: &str,
+        mode: SubscriptionMode,
+        qos: QosParams,
+        validator: F,
+    ) -> Result<Subscription>
+    where
+        F: Fn(&WindValue) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        self.subscriber
+            .subscribe_with_validator(service_name, mode, qos, validator)
+            .await
+    }
+
+    /// Subscribe, checking every value against the service's schema from the
+    /// registry; see `Subscriber::subscribe_validated`
+    pub async fn subscribe_validated(
+        &mut self,
+        service_name: &str,
+        mode: SubscriptionMode,
+        qos: QosParams,
+    ) -> Result<ValidatedSubscription> {
+        self.subscriber
+            .subscribe_validated(service_name, mode, qos)
+            .await
+    }
+
+    /// Fetch a schema from the registry
+    pub async fn get_schema(&mut self, schema_id: &str) -> Result<wind_core::Schema> {
+        self.subscriber.get_schema(schema_id).await
+    }
+
+    /// Read a publisher's current value without subscribing
+    pub async fn get_current(&mut self, service_name: &str) -> Result<Option<WindValue>> {
+        self.subscriber.get_current(service_name).await
+    }
+
+    /// Read a diagnostic dump of a publisher that allows this client's id
+    pub async fn inspect(&mut self, service_name: &str) -> Result<PublisherDiagnostics> {
+        self.subscriber.inspect(service_name).await
+    }
+
+    /// Subscribe to every publisher matching a pattern, following publishers
+    /// as they come and go; values are tagged with their service's name
+    pub async fn subscribe_pattern(
+        &mut self,
+        pattern: &str,
+        mode: SubscriptionMode,
+        qos: QosParams,
+    ) -> Result<PatternSubscription> {
+        self.subscriber.subscribe_pattern(pattern, mode, qos).await
+    }
+
     /// Make a synchronous RPC call with 5 second timeout
     pub async fn call(
         &mut self,
@@ -67,6 +150,26 @@ impl WindClient {
             .await
     }
 
+    /// The pooled connection to an RPC service, which concurrent calls can
+    /// share; see `RpcChannel`
+    pub async fn rpc_channel(&mut self, service_name: &str) -> Result<crate::RpcChannel> {
+        self.rpc_client.channel(service_name).await
+    }
+
+    /// Start a client-streaming RPC call; see `RpcClient::call_client_stream`
+    pub async fn call_client_stream(
+        &mut self,
+        service_name: &str,
+        method: &str,
+    ) -> Result<(
+        crate::RpcStreamSender,
+        impl std::future::Future<Output = Result<WindValue>>,
+    )> {
+        self.rpc_client
+            .call_client_stream(service_name, method)
+            .await
+    }
+
     /// Make an asynchronous RPC call (fire-and-forget)
     pub async fn call_async(
         &mut self,
@@ -84,6 +187,64 @@ impl WindClient {
         self.subscriber.discover_services(pattern).await
     }
 
+    /// Discover services matching a pattern, with the registry version they
+    /// were read at
+    pub async fn discover_snapshot(&mut self, pattern: &str) -> Result<DiscoverySnapshot> {
+        self.subscriber.discover_snapshot(pattern).await
+    }
+
+    /// Whether no service has been registered or removed since `snapshot`
+    pub async fn is_current(&mut self, snapshot: &DiscoverySnapshot) -> Result<bool> {
+        self.subscriber.is_current(snapshot).await
+    }
+
+    /// Discover RPC servers matching a pattern
+    pub async fn discover_rpc(&mut self, pattern: &str) -> Result<Vec<wind_core::ServiceInfo>> {
+        self.subscriber
+            .discover_by_type(pattern, ServiceType::RpcServer)
+            .await
+    }
+
+    /// Discover publishers matching a pattern
+    pub async fn discover_publishers(
+        &mut self,
+        pattern: &str,
+    ) -> Result<Vec<wind_core::ServiceInfo>> {
+        self.subscriber
+            .discover_by_type(pattern, ServiceType::Publisher)
+            .await
+    }
+
+    /// Discover services matching a pattern that were first registered at or
+    /// after `since`
+    pub async fn discover_since(
+        &mut self,
+        pattern: &str,
+        since: std::time::SystemTime,
+    ) -> Result<Vec<wind_core::ServiceInfo>> {
+        self.subscriber.discover_since(pattern, since).await
+    }
+
+    /// Stream registrations and removals of services matching a pattern,
+    /// starting with the ones already registered
+    pub async fn watch_services(
+        &self,
+        pattern: &str,
+    ) -> Result<futures::stream::BoxStream<'static, wind_core::ServiceEvent>> {
+        self.subscriber.watch_services(pattern).await
+    }
+
+    /// Evict a service's registration without waiting for its TTL
+    pub async fn force_deregister(
+        &mut self,
+        service_name: &str,
+        address: Option<&str>,
+    ) -> Result<()> {
+        self.subscriber
+            .force_deregister(service_name, address)
+            .await
+    }
+
     /// Get number of active subscriptions
     pub async fn subscription_count(&self) -> usize {
         self.subscriber.subscription_count().await
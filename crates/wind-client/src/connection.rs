@@ -1,16 +1,92 @@
-use tokio::net::TcpStream;
-use tokio::time::Duration;
-use tracing::{error, info, warn};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+use tokio::io::{ReadHalf, WriteHalf};
+use tokio::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
 
-use wind_core::{Message, MessageCodec, Result, WindError};
+use wind_core::{
+    ByteCounters, CountingStream, Message, MessageCodec, MessagePayload, RecordingStream, Result,
+    SerializationFormat, SessionRecorder, SocketOptions, WindError, WindStream,
+};
+
+type ClientStream = CountingStream<RecordingStream<WindStream>>;
+
+/// How long `Connection::request` waits for a response by default
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Exponential backoff with jitter between reconnect attempts
+#[derive(Debug)]
+pub(crate) struct Backoff {
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+    delay: Duration,
+    rng: StdRng,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_millis(1000),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.5,
+            delay: Duration::from_millis(1000),
+            rng: StdRng::from_entropy(),
+        }
+    }
+}
+
+impl Backoff {
+    /// Returns the delay before the next attempt and doubles the nominal
+    /// delay. The nominal delay is capped so that the jittered one never
+    /// exceeds `max_delay`, which keeps capped retries spread out instead of
+    /// all landing on the cap.
+    pub(crate) fn next_delay(&mut self) -> Duration {
+        let ceiling = self.max_delay.div_f64(1.0 + self.jitter);
+        let nominal = self.delay.min(ceiling);
+        let spread = self.rng.gen_range(-self.jitter..=self.jitter);
+        self.delay = std::cmp::min(self.delay * 2, self.max_delay);
+        nominal.mul_f64(1.0 + spread)
+    }
+
+    /// Start over from the base delay
+    pub(crate) fn reset(&mut self) {
+        self.delay = self.base_delay;
+    }
+}
+
+/// Where a `Connection` is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// No stream; the next `connect`, `send` or `receive` establishes one
+    Disconnected,
+    /// Connecting, including waiting between retries
+    Connecting,
+    Connected,
+    /// The last `connect` gave up after `max_reconnect_attempts` retries
+    Failed,
+}
+
+/// Called with the new state on every `ConnectionState` change
+pub type StateObserver = Arc<dyn Fn(ConnectionState) + Send + Sync>;
 
 /// Connection manager with automatic reconnection
 pub struct Connection {
     address: String,
-    stream: Option<TcpStream>,
+    stream: Option<ClientStream>,
+    traffic: Arc<ByteCounters>,
+    client_id: Option<String>,
+    token: Option<String>,
     reconnect_attempts: u32,
     max_reconnect_attempts: u32,
-    reconnect_delay: Duration,
+    backoff: Backoff,
+    socket_options: SocketOptions,
+    recorder: Option<SessionRecorder>,
+    request_timeout: Duration,
+    codec: MessageCodec,
+    state: ConnectionState,
+    state_observer: Option<StateObserver>,
 }
 
 impl Connection {
@@ -18,59 +94,216 @@ impl Connection {
         Self {
             address,
             stream: None,
+            traffic: Arc::new(ByteCounters::new()),
+            client_id: None,
+            token: None,
             reconnect_attempts: 0,
             max_reconnect_attempts: 10,
-            reconnect_delay: Duration::from_millis(1000),
+            backoff: Backoff::default(),
+            socket_options: SocketOptions::default(),
+            recorder: None,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            codec: MessageCodec::default(),
+            state: ConnectionState::Disconnected,
+            state_observer: None,
         }
     }
 
+    /// Connect to `address` over TLS, checking the server against `config`.
+    /// A plain `host:port` gets the `tls://` prefix; `with_socket_options`
+    /// afterwards replaces `config` with the one in the new options.
+    #[cfg(feature = "tls")]
+    pub fn new_tls(address: String, config: wind_core::rustls::ClientConfig) -> Self {
+        let address = if address.starts_with(wind_core::TLS_SCHEME) {
+            address
+        } else {
+            format!("{}{}", wind_core::TLS_SCHEME, address)
+        };
+        let socket_options = SocketOptions::default().with_tls(config);
+        Self::new(address).with_socket_options(socket_options)
+    }
+
+    /// Give up connecting after this many consecutive failed attempts
+    pub fn with_max_reconnect_attempts(mut self, attempts: u32) -> Self {
+        self.max_reconnect_attempts = attempts;
+        self
+    }
+
+    /// Delay before the first retry; doubled after every failure
+    pub fn with_base_delay(mut self, delay: Duration) -> Self {
+        self.backoff.base_delay = delay;
+        self.backoff.delay = delay;
+        self
+    }
+
+    /// Upper bound on the delay between retries, jitter included
+    pub fn with_max_delay(mut self, delay: Duration) -> Self {
+        self.backoff.max_delay = delay;
+        self
+    }
+
+    /// Randomize each retry delay within ±`fraction` of its nominal value
+    pub fn with_jitter(mut self, fraction: f64) -> Self {
+        self.backoff.jitter = fraction.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Seed the jitter RNG, making the backoff sequence reproducible
+    pub fn with_jitter_seed(mut self, seed: u64) -> Self {
+        self.backoff.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Identify as `client_id` (for ACLs) every time the connection is established
+    pub fn with_client_id(mut self, client_id: Option<String>) -> Self {
+        self.client_id = client_id;
+        self
+    }
+
+    /// Authenticate with `token` every time the connection is established,
+    /// before anything else is sent
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// TCP options applied every time the connection is established
+    pub fn with_socket_options(mut self, options: SocketOptions) -> Self {
+        self.socket_options = options;
+        self
+    }
+
+    /// Record every message exchanged, across reconnects, for `replay`
+    pub fn with_recorder(mut self, recorder: SessionRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// How long `request` waits for a response before giving up
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Send messages as `format` rather than bincode; servers answer in the
+    /// format of the request
+    pub fn with_format(mut self, format: SerializationFormat) -> Self {
+        self.codec = MessageCodec::default().with_format(format);
+        self
+    }
+
+    /// Call `observer` on every state change, e.g. for health monitoring
+    pub fn with_state_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(ConnectionState) + Send + Sync + 'static,
+    {
+        self.state_observer = Some(Arc::new(observer));
+        self
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    fn set_state(&mut self, state: ConnectionState) {
+        if self.state == state {
+            return;
+        }
+        debug!(
+            "Connection to {}: {:?} -> {:?}",
+            self.address, self.state, state
+        );
+        self.state = state;
+        if let Some(observer) = &self.state_observer {
+            observer(state);
+        }
+    }
+
+    /// Drop the stream after a failure on it
+    fn lose_stream(&mut self) {
+        self.stream = None;
+        self.set_state(ConnectionState::Disconnected);
+    }
+
     pub async fn connect(&mut self) -> Result<()> {
         if self.stream.is_some() {
             return Ok(());
         }
 
+        self.set_state(ConnectionState::Connecting);
+        let mut attempts = 0;
         loop {
-            match TcpStream::connect(&self.address).await {
-                Ok(stream) => {
+            attempts += 1;
+            match WindStream::connect_with(&self.address, &self.socket_options).await {
+                Ok(mut stream) => {
                     info!("Connected to {}", self.address);
+                    // Before recording starts, so recordings never hold the token
+                    if let Some(token) = &self.token {
+                        if let Err(e) = wind_core::authenticate(&mut stream, token).await {
+                            self.set_state(ConnectionState::Disconnected);
+                            return Err(e);
+                        }
+                    }
+                    let stream = RecordingStream::new(stream, self.recorder.clone());
+                    let mut stream =
+                        CountingStream::new(stream).with_aggregate(self.traffic.clone());
+                    if let Some(client_id) = &self.client_id {
+                        let identify = Message::new(MessagePayload::Identify {
+                            client_id: client_id.clone(),
+                        });
+                        if let Err(e) = self.codec.write_message(&mut stream, &identify).await {
+                            self.set_state(ConnectionState::Disconnected);
+                            return Err(e);
+                        }
+                    }
                     self.stream = Some(stream);
                     self.reconnect_attempts = 0;
+                    self.set_state(ConnectionState::Connected);
                     return Ok(());
                 }
                 Err(e) => {
                     self.reconnect_attempts += 1;
                     if self.reconnect_attempts > self.max_reconnect_attempts {
-                        return Err(WindError::Connection(format!(
-                            "Failed to connect to {} after {} attempts: {}",
-                            self.address, self.max_reconnect_attempts, e
-                        )));
+                        self.set_state(ConnectionState::Failed);
+                        return Err(WindError::ConnectFailed {
+                            address: self.address.clone(),
+                            attempts,
+                            source: e,
+                        });
                     }
 
+                    let delay = self.next_backoff();
                     warn!(
                         "Connection attempt {} failed: {}. Retrying in {:?}...",
-                        self.reconnect_attempts, e, self.reconnect_delay
+                        self.reconnect_attempts, e, delay
                     );
 
-                    tokio::time::sleep(self.reconnect_delay).await;
-                    // Exponential backoff with jitter
-                    self.reconnect_delay =
-                        std::cmp::min(self.reconnect_delay * 2, Duration::from_secs(30));
+                    tokio::time::sleep(delay).await;
                 }
             }
         }
     }
 
+    /// Delay before the next connection attempt; see `Backoff`
+    fn next_backoff(&mut self) -> Duration {
+        self.backoff.next_delay()
+    }
+
     pub async fn send(&mut self, message: &Message) -> Result<()> {
         if self.stream.is_none() {
             self.connect().await?;
         }
 
         if let Some(stream) = &mut self.stream {
-            match MessageCodec::write(stream, message).await {
+            match self.codec.write_message(stream, message).await {
                 Ok(()) => Ok(()),
                 Err(e) => {
                     error!("Send failed: {}. Marking connection as disconnected.", e);
-                    self.stream = None;
+                    self.lose_stream();
                     Err(e)
                 }
             }
@@ -89,7 +322,7 @@ impl Connection {
                 Ok(msg) => Ok(msg),
                 Err(e) => {
                     error!("Receive failed: {}. Marking connection as disconnected.", e);
-                    self.stream = None;
+                    self.lose_stream();
                     Err(e)
                 }
             }
@@ -98,13 +331,358 @@ impl Connection {
         }
     }
 
+    /// Send `message` and wait for its response. RPC responses are matched to
+    /// the call by id, skipping any left over from an earlier call. On timeout
+    /// the connection is dropped, so a late response can't be mistaken for the
+    /// answer to the next request.
+    pub async fn request(&mut self, message: &Message) -> Result<Message> {
+        self.send(message).await?;
+
+        let deadline = Instant::now() + self.request_timeout;
+        loop {
+            let Ok(received) = tokio::time::timeout_at(deadline, self.receive()).await else {
+                self.lose_stream();
+                return Err(WindError::Timeout(format!(
+                    "No response to {} from {} within {:?}",
+                    message.payload.name(),
+                    self.address,
+                    self.request_timeout
+                )));
+            };
+            let response = received?;
+            if matches!(response.payload, MessagePayload::Ping) {
+                // A liveness check can come at any time; left unanswered,
+                // the peer would take us for dead
+                self.send(&Message::new(MessagePayload::Pong)).await?;
+                continue;
+            }
+            if responds_to(message, &response) {
+                return Ok(response);
+            }
+            debug!(
+                "Skipping {} that doesn't answer {}",
+                response.payload.name(),
+                message.payload.name()
+            );
+        }
+    }
+
+    /// Bytes exchanged over this connection, accumulated across reconnects
+    pub fn traffic(&self) -> Arc<ByteCounters> {
+        self.traffic.clone()
+    }
+
     pub fn is_connected(&self) -> bool {
         self.stream.is_some()
     }
 
     pub fn disconnect(&mut self) {
         self.stream = None;
+        self.set_state(ConnectionState::Disconnected);
         self.reconnect_attempts = 0;
-        self.reconnect_delay = Duration::from_millis(1000);
+        self.backoff.reset();
+    }
+
+    /// Split an established connection into independently owned read and
+    /// write halves, so one task can read while another writes
+    pub fn into_split(self) -> Result<(ConnectionReader, ConnectionWriter)> {
+        let stream = self
+            .stream
+            .ok_or_else(|| WindError::Connection("No active connection".to_string()))?;
+        let (reader, writer) = tokio::io::split(stream);
+        Ok((
+            ConnectionReader { stream: reader },
+            ConnectionWriter {
+                stream: writer,
+                codec: self.codec,
+            },
+        ))
+    }
+}
+
+/// Whether `response` can be the answer to `request`. Only RPC responses
+/// carry the id of the call they answer (nil if the server couldn't read the
+/// call); other replies are taken in order.
+fn responds_to(request: &Message, response: &Message) -> bool {
+    match &response.payload {
+        MessagePayload::RpcResponse { call_id, .. } => *call_id == request.id || call_id.is_nil(),
+        _ => true,
+    }
+}
+
+/// Read half of a split `Connection`
+pub struct ConnectionReader {
+    stream: ReadHalf<ClientStream>,
+}
+
+impl ConnectionReader {
+    pub async fn receive(&mut self) -> Result<Message> {
+        MessageCodec::decode(&mut self.stream).await
+    }
+}
+
+/// Write half of a split `Connection`
+pub struct ConnectionWriter {
+    stream: WriteHalf<ClientStream>,
+    codec: MessageCodec,
+}
+
+impl ConnectionWriter {
+    pub async fn send(&mut self, message: &Message) -> Result<()> {
+        self.codec.write_message(&mut self.stream, message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+    use wind_core::WindValue;
+
+    fn rpc_response(call_id: Uuid, value: i64) -> Message {
+        Message::new(MessagePayload::RpcResponse {
+            call_id,
+            result: Ok(WindValue::I64(value)),
+            schema_id: None,
+        })
+    }
+
+    fn backoff_connection(seed: u64) -> Connection {
+        Connection::new("127.0.0.1:1".to_string())
+            .with_base_delay(Duration::from_millis(100))
+            .with_max_delay(Duration::from_secs(6))
+            .with_jitter(0.5)
+            .with_jitter_seed(seed)
+    }
+
+    #[test]
+    fn test_backoff_is_jittered_and_capped() {
+        let max_delay = Duration::from_secs(6);
+        let mut connection = backoff_connection(42);
+
+        let mut nominal = Duration::from_millis(100);
+        let mut delays = Vec::new();
+        for _ in 0..12 {
+            let delay = connection.next_backoff();
+            let bounded = nominal.min(max_delay.div_f64(1.5));
+            assert!(
+                delay >= bounded.mul_f64(0.5),
+                "{:?} below {:?}",
+                delay,
+                bounded
+            );
+            assert!(
+                delay <= bounded.mul_f64(1.5),
+                "{:?} above {:?}",
+                delay,
+                bounded
+            );
+            assert!(delay <= max_delay);
+            delays.push(delay);
+            nominal *= 2;
+        }
+
+        // Capped retries keep being spread out rather than repeating the cap
+        let mut distinct = delays.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), delays.len());
+
+        // Same seed, same sequence; another seed diverges
+        let replayed: Vec<_> = {
+            let mut replay = backoff_connection(42);
+            (0..12).map(|_| replay.next_backoff()).collect()
+        };
+        assert_eq!(delays, replayed);
+        let mut other = backoff_connection(7);
+        assert_ne!(delays[0], other.next_backoff());
+    }
+
+    #[tokio::test]
+    async fn test_request_returns_matching_response_and_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        // Answers the first call after a stale response, ignores the second
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let call = MessageCodec::decode(&mut stream).await.unwrap();
+            MessageCodec::write(&mut stream, &rpc_response(Uuid::new_v4(), 1))
+                .await
+                .unwrap();
+            MessageCodec::write(&mut stream, &rpc_response(call.id, 2))
+                .await
+                .unwrap();
+            let _ignored = MessageCodec::decode(&mut stream).await;
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let mut connection =
+            Connection::new(address).with_request_timeout(Duration::from_millis(200));
+        let call = || {
+            Message::new(MessagePayload::RpcCall {
+                service: "CALC".to_string(),
+                method: "add".to_string(),
+                params: WindValue::I64(0),
+                schema_id: None,
+            })
+        };
+
+        let response = connection.request(&call()).await.unwrap();
+        match response.payload {
+            MessagePayload::RpcResponse { result, .. } => {
+                assert_eq!(result, Ok(WindValue::I64(2)))
+            }
+            other => panic!("Unexpected response {}", other.name()),
+        }
+
+        let started = Instant::now();
+        let timed_out = connection.request(&call()).await;
+        assert!(matches!(timed_out, Err(WindError::Timeout(_))));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(!connection.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_with_format_writes_every_message_in_that_format() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let (identify, identify_format) =
+                MessageCodec::decode_with_format(&mut stream).await.unwrap();
+            let (call, call_format) = MessageCodec::decode_with_format(&mut stream).await.unwrap();
+            MessageCodec::default()
+                .with_format(call_format)
+                .write_message(&mut stream, &rpc_response(call.id, 1))
+                .await
+                .unwrap();
+            (identify.payload.name(), identify_format, call_format)
+        });
+
+        let mut connection = Connection::new(address)
+            .with_client_id(Some("reader".to_string()))
+            .with_format(SerializationFormat::Json);
+        let call = Message::new(MessagePayload::RpcCall {
+            service: "CALC".to_string(),
+            method: "add".to_string(),
+            params: WindValue::I64(0),
+            schema_id: None,
+        });
+        let response = connection.request(&call).await.unwrap();
+        assert!(matches!(
+            response.payload,
+            MessagePayload::RpcResponse {
+                result: Ok(WindValue::I64(1)),
+                ..
+            }
+        ));
+        assert_eq!(
+            server.await.unwrap(),
+            (
+                "Identify",
+                SerializationFormat::Json,
+                SerializationFormat::Json
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_state_follows_connect_fail_reconnect_cycle() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let observed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut connection = Connection::new(address.clone())
+            .with_max_reconnect_attempts(0)
+            .with_state_observer({
+                let observed = observed.clone();
+                move |state| observed.lock().unwrap().push(state)
+            });
+        assert_eq!(connection.state(), ConnectionState::Disconnected);
+
+        assert!(connection.connect().await.is_err());
+        assert_eq!(connection.state(), ConnectionState::Failed);
+
+        // The peer comes up, accepts and hangs up
+        let listener = TcpListener::bind(&address).await.unwrap();
+        let peer = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            drop(stream);
+        });
+        connection.connect().await.unwrap();
+        assert_eq!(connection.state(), ConnectionState::Connected);
+        peer.await.unwrap();
+        assert!(connection.receive().await.is_err());
+        assert_eq!(connection.state(), ConnectionState::Disconnected);
+        connection.disconnect();
+
+        use ConnectionState::*;
+        assert_eq!(
+            *observed.lock().unwrap(),
+            vec![Connecting, Failed, Connecting, Connected, Disconnected]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_answers_pings_that_arrive_first() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let call = MessageCodec::decode(&mut stream).await.unwrap();
+            MessageCodec::write(&mut stream, &Message::new(MessagePayload::Ping))
+                .await
+                .unwrap();
+            let pong = MessageCodec::decode(&mut stream).await.unwrap();
+            MessageCodec::write(&mut stream, &rpc_response(call.id, 3))
+                .await
+                .unwrap();
+            pong.payload
+        });
+
+        let mut connection = Connection::new(address);
+        let call = Message::new(MessagePayload::RpcCall {
+            service: "CALC".to_string(),
+            method: "add".to_string(),
+            params: WindValue::I64(0),
+            schema_id: None,
+        });
+        let response = connection.request(&call).await.unwrap();
+        assert!(matches!(
+            response.payload,
+            MessagePayload::RpcResponse {
+                result: Ok(WindValue::I64(3)),
+                ..
+            }
+        ));
+        assert!(matches!(server.await.unwrap(), MessagePayload::Pong));
+    }
+
+    #[tokio::test]
+    async fn test_refused_connection_exposes_io_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let mut connection = Connection::new(address.clone()).with_max_reconnect_attempts(0);
+        let error = connection.connect().await.unwrap_err();
+        assert_eq!(error.io_kind(), Some(std::io::ErrorKind::ConnectionRefused));
+        let source = std::error::Error::source(&error)
+            .and_then(|source| source.downcast_ref::<std::io::Error>())
+            .expect("An io::Error source");
+        assert_eq!(source.kind(), std::io::ErrorKind::ConnectionRefused);
+        // The first attempt isn't a retry, so it counts too
+        assert!(
+            matches!(error, WindError::ConnectFailed { attempts: 1, .. }),
+            "{:?}",
+            error
+        );
+        // The io::Error is the source, not part of the message
+        assert_eq!(
+            error.to_string(),
+            format!("Failed to connect to {} after 1 attempts", address)
+        );
     }
 }
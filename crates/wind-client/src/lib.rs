@@ -2,6 +2,7 @@ pub mod client;
 pub mod connection;
 pub mod rpc_client;
 pub mod subscriber;
+mod watch;
 
 pub use client::*;
 pub use connection::*;
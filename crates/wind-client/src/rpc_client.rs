@@ -1,64 +1,206 @@
+use futures::channel::mpsc;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
 use tokio::time::Duration;
-use tracing::info;
-// use uuid::Uuid;
-// use std::collections::HashMap;
-// use std::sync::Arc;
-// use tokio::sync::{oneshot, RwLock};
-
-use crate::{Connection, Subscriber};
-use wind_core::{Message, MessagePayload, Result, WindError, WindValue};
-
-/// Pending RPC call tracking
-// #[derive(Debug)]
-// struct PendingCall {
-//     sender: oneshot::Sender<Result<WindValue>>,
-//     timeout_handle: tokio::task::JoinHandle<()>,
-// }
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{Connection, ConnectionReader, ConnectionWriter, Subscriber};
+use wind_core::{Message, MessagePayload, Result, SocketOptions, WindError, WindValue};
+
+/// Items buffered by a client-streaming call before `send` waits for the
+/// connection
+const STREAM_ITEM_BUFFER: usize = 64;
+
+/// Sending side of a client-streaming RPC call; a `Sink<WindValue>`. Closing
+/// it, or dropping every clone, ends the stream.
+pub type RpcStreamSender = mpsc::Sender<WindValue>;
+
+/// Calls waiting for their response on an `RpcChannel`, by call id. `None`
+/// once the connection is gone.
+type PendingCalls = Arc<Mutex<Option<HashMap<Uuid, oneshot::Sender<Result<WindValue>>>>>>;
 
 /// RPC client for making type-safe remote procedure calls
 pub struct RpcClient {
     subscriber: Subscriber,
-    // connections: Arc<RwLock<HashMap<String, Connection>>>,
-    // pending_calls: Arc<RwLock<HashMap<Uuid, PendingCall>>>,
+    /// Connections reused across calls, by service name
+    channels: HashMap<String, RpcChannel>,
 }
 
 impl RpcClient {
     pub fn new(registry_address: String) -> Self {
         Self {
             subscriber: Subscriber::new(registry_address),
-            // connections: Arc::new(RwLock::new(HashMap::new())),
-            // pending_calls: Arc::new(RwLock::new(HashMap::new())),
+            channels: HashMap::new(),
         }
     }
 
-    /// Make a type-safe RPC call with timeout
+    /// Identify as `client_id` to the registry, for ACLs
+    pub fn with_client_id(mut self, client_id: String) -> Self {
+        self.subscriber = self.subscriber.with_client_id(client_id);
+        self
+    }
+
+    /// Authenticate with `token` to the registry and RPC servers
+    pub fn with_token(mut self, token: String) -> Self {
+        self.subscriber = self.subscriber.with_token(token);
+        self
+    }
+
+    /// TCP options for the registry and RPC server connections
+    pub fn with_socket_options(mut self, options: SocketOptions) -> Self {
+        self.subscriber = self.subscriber.with_socket_options(options);
+        self
+    }
+
+    /// Make a type-safe RPC call with timeout. Calls to a service reuse one
+    /// connection; the service is only looked up again once that connection
+    /// fails.
     pub async fn call(
         &mut self,
         service_name: &str,
         method: &str,
         params: WindValue,
-        _timeout_duration: Duration,
+        timeout_duration: Duration,
     ) -> Result<WindValue> {
+        let channel = self.channel(service_name).await?;
+        let result = channel.call(method, params, timeout_duration).await;
+        if matches!(result, Err(WindError::Connection(_) | WindError::Io(_))) {
+            self.channels.remove(service_name);
+        }
+        result
+    }
+
+    /// The pooled connection to `service_name`, shared by concurrent calls
+    /// (see `RpcChannel`). Opened on first use, and again if it has closed.
+    pub async fn channel(&mut self, service_name: &str) -> Result<RpcChannel> {
+        match self.channels.get(service_name) {
+            Some(channel) if !channel.is_closed() => return Ok(channel.clone()),
+            Some(_) => debug!("Pooled connection to {} closed, reconnecting", service_name),
+            None => {}
+        }
+        let channel = self.open_channel(service_name).await?;
+        self.channels
+            .insert(service_name.to_string(), channel.clone());
+        Ok(channel)
+    }
+
+    /// Close the pooled connection to `service_name`. Channels handed out
+    /// earlier keep it open until they are dropped.
+    pub fn close(&mut self, service_name: &str) {
+        self.channels.remove(service_name);
+    }
+
+    /// Close every pooled connection
+    pub fn close_all(&mut self) {
+        self.channels.clear();
+    }
+
+    async fn open_channel(&mut self, service_name: &str) -> Result<RpcChannel> {
         let service_info = self.subscriber.discover_service(service_name).await?;
-        let mut connection = Connection::new(service_info.address);
+        // Every call on the channel is an RpcCall, so one check covers them all
+        let call = MessagePayload::RpcCall {
+            service: service_name.to_string(),
+            method: String::new(),
+            params: WindValue::Null,
+            schema_id: None,
+        };
+        call.check_served_by(service_name, &service_info.service_type)?;
+
+        let mut connection = Connection::new(service_info.address)
+            .with_token(self.subscriber.token().map(String::from))
+            .with_socket_options(self.subscriber.socket_options().clone());
         connection.connect().await?;
+        let (reader, writer) = connection.into_split()?;
 
-        let call_msg = Message::new(MessagePayload::RpcCall {
+        let pending: PendingCalls = Arc::new(Mutex::new(Some(HashMap::new())));
+        let dispatcher = tokio::spawn(dispatch_responses(reader, pending.clone()));
+        Ok(RpcChannel {
+            service_name: service_name.to_string(),
+            schema_id: service_info.schema_id,
+            writer: Arc::new(tokio::sync::Mutex::new(writer)),
+            pending,
+            _dispatcher: Arc::new(Dispatcher(dispatcher)),
+        })
+    }
+
+    /// Start a client-streaming RPC call. Items pushed into the returned
+    /// sink are streamed to `method`'s handler; the returned future resolves
+    /// to the handler's single result once the sink is closed (or earlier,
+    /// if the server answers without waiting for the rest).
+    pub async fn call_client_stream(
+        &mut self,
+        service_name: &str,
+        method: &str,
+    ) -> Result<(RpcStreamSender, impl Future<Output = Result<WindValue>>)> {
+        let service_info = self.subscriber.discover_service(service_name).await?;
+        let open_msg = Message::new(MessagePayload::RpcStreamOpen {
             service: service_name.to_string(),
             method: method.to_string(),
-            params,
             schema_id: service_info.schema_id,
         });
+        open_msg
+            .payload
+            .check_served_by(service_name, &service_info.service_type)?;
 
-        connection.send(&call_msg).await?;
-        let response = connection.receive().await?;
+        let mut connection = Connection::new(service_info.address)
+            .with_token(self.subscriber.token().map(String::from))
+            .with_socket_options(self.subscriber.socket_options().clone());
+        connection.connect().await?;
+        connection.send(&open_msg).await?;
 
-        match response.payload {
-            MessagePayload::RpcResponse { result, .. } => {
-                result.map_err(WindError::Protocol)
-            }
-            _ => Err(WindError::Protocol("Unexpected response".to_string())),
-        }
+        let call_id = open_msg.id;
+        let (mut reader, mut writer) = connection.into_split()?;
+        let (items_tx, mut items_rx) = mpsc::channel(STREAM_ITEM_BUFFER);
+        let (result_tx, result_rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let send = async {
+                while let Some(item) = items_rx.next().await {
+                    let item = Message::new(MessagePayload::RpcStreamItem { call_id, item });
+                    writer.send(&item).await?;
+                }
+                let end = Message::new(MessagePayload::RpcStreamEnd { call_id });
+                writer.send(&end).await
+            };
+            let receive = async {
+                loop {
+                    match reader.receive().await?.payload {
+                        MessagePayload::RpcResponse {
+                            call_id: id,
+                            result,
+                            ..
+                        } if id == call_id => return result.map_err(WindError::Protocol),
+                        MessagePayload::Error { error, .. } => {
+                            return Err(WindError::Protocol(error))
+                        }
+                        _ => continue,
+                    }
+                }
+            };
+            tokio::pin!(send, receive);
+
+            let result = tokio::select! {
+                result = &mut receive => result,
+                sent = &mut send => match sent {
+                    Ok(()) => receive.await,
+                    Err(e) => Err(e),
+                },
+            };
+            let _ = result_tx.send(result);
+        });
+
+        let result = async move {
+            result_rx.await.unwrap_or_else(|_| {
+                Err(WindError::Connection(
+                    "Streaming RPC call ended without a response".to_string(),
+                ))
+            })
+        };
+        Ok((items_tx, result))
     }
 
     /// Make an async RPC call (fire-and-forget)
@@ -69,15 +211,20 @@ impl RpcClient {
         params: WindValue,
     ) -> Result<()> {
         let service_info = self.subscriber.discover_service(service_name).await?;
-        let mut connection = Connection::new(service_info.address);
-        connection.connect().await?;
-
         let call_msg = Message::new(MessagePayload::RpcCall {
             service: service_name.to_string(),
             method: method.to_string(),
             params,
             schema_id: service_info.schema_id,
         });
+        call_msg
+            .payload
+            .check_served_by(service_name, &service_info.service_type)?;
+
+        let mut connection = Connection::new(service_info.address)
+            .with_token(self.subscriber.token().map(String::from))
+            .with_socket_options(self.subscriber.socket_options().clone());
+        connection.connect().await?;
 
         connection.send(&call_msg).await?;
         info!("Sent async RPC call to {}::{}", service_name, method);
@@ -85,3 +232,475 @@ impl RpcClient {
         Ok(())
     }
 }
+
+/// A connection to one RPC service shared by concurrent calls. Calls are
+/// pipelined and may be answered in any order; each response is routed to
+/// its call by id. Clones share the connection, which closes when the last
+/// clone is dropped.
+#[derive(Clone)]
+pub struct RpcChannel {
+    service_name: String,
+    schema_id: Option<String>,
+    writer: Arc<tokio::sync::Mutex<ConnectionWriter>>,
+    pending: PendingCalls,
+    _dispatcher: Arc<Dispatcher>,
+}
+
+impl RpcChannel {
+    /// Call `method`, waiting up to `timeout` for its response
+    pub async fn call(
+        &self,
+        method: &str,
+        params: WindValue,
+        timeout: Duration,
+    ) -> Result<WindValue> {
+        let call_msg = Message::new(MessagePayload::RpcCall {
+            service: self.service_name.clone(),
+            method: method.to_string(),
+            params,
+            schema_id: self.schema_id.clone(),
+        });
+        let call_id = call_msg.id;
+
+        let (response_tx, response_rx) = oneshot::channel();
+        match self.pending.lock().unwrap().as_mut() {
+            Some(calls) => calls.insert(call_id, response_tx),
+            None => return Err(self.closed()),
+        };
+        let sent = self.writer.lock().await.send(&call_msg).await;
+        if let Err(e) = sent {
+            self.forget(call_id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(timeout, response_rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(self.closed()),
+            Err(_) => {
+                // A late response is dropped rather than taken for another call's
+                self.forget(call_id);
+                Err(WindError::Timeout(format!(
+                    "No response from {}::{} within {:?}",
+                    self.service_name, method, timeout
+                )))
+            }
+        }
+    }
+
+    /// Whether the connection has closed, failing every further call
+    pub fn is_closed(&self) -> bool {
+        self.pending.lock().unwrap().is_none()
+    }
+
+    fn forget(&self, call_id: Uuid) {
+        if let Some(calls) = self.pending.lock().unwrap().as_mut() {
+            calls.remove(&call_id);
+        }
+    }
+
+    fn closed(&self) -> WindError {
+        WindError::Connection(format!("Connection to {} closed", self.service_name))
+    }
+}
+
+/// Task routing an `RpcChannel`'s responses, stopped with the last clone
+struct Dispatcher(JoinHandle<()>);
+
+impl Drop for Dispatcher {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+async fn dispatch_responses(mut reader: ConnectionReader, pending: PendingCalls) {
+    loop {
+        let msg = match reader.receive().await {
+            Ok(msg) => msg,
+            Err(e) => {
+                debug!("RPC channel closed: {}", e);
+                break;
+            }
+        };
+        match msg.payload {
+            MessagePayload::RpcResponse {
+                call_id, result, ..
+            } => {
+                let call = pending
+                    .lock()
+                    .unwrap()
+                    .as_mut()
+                    .and_then(|calls| calls.remove(&call_id));
+                match call {
+                    Some(call) => {
+                        let _ = call.send(result.map_err(WindError::Protocol));
+                    }
+                    None => warn!("Dropped response to unknown RPC call {}", call_id),
+                }
+            }
+            other => warn!("Ignoring {} on RPC channel", other.name()),
+        }
+    }
+    // Calls still waiting fail now instead of at their timeout
+    pending.lock().unwrap().take();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use wind_core::{MessageCodec, QosParams, SerializationFormat, SubscriptionMode};
+    use wind_registry::RegistryServer;
+    use wind_server::{Publisher, RpcServer};
+
+    async fn start_rpc_server(registry_addr: &str, server: RpcServer) -> Arc<RpcServer> {
+        let registry = RegistryServer::new(registry_addr.to_string());
+        tokio::spawn(async move {
+            let _ = registry.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        server
+            .register_function("echo".to_string(), |params| async move { Ok(params) })
+            .await
+            .unwrap();
+        let server = Arc::new(server);
+        tokio::spawn({
+            let server = server.clone();
+            async move {
+                let _ = server.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        server
+    }
+
+    #[tokio::test]
+    async fn test_oversized_params_rejected() {
+        let registry_addr = "127.0.0.1:7210";
+        let server = RpcServer::new(
+            "TEST/ECHO".to_string(),
+            "127.0.0.1:0".to_string(),
+            registry_addr.to_string(),
+        )
+        .with_max_rpc_param_bytes(16 * 1024);
+        start_rpc_server(registry_addr, server).await;
+
+        let mut client = RpcClient::new(registry_addr.to_string());
+        let timeout = Duration::from_secs(2);
+
+        let small = WindValue::Bytes(vec![1u8; 1024]);
+        let echoed = client
+            .call("TEST/ECHO", "echo", small.clone(), timeout)
+            .await
+            .unwrap();
+        assert_eq!(echoed, small);
+
+        let large = WindValue::Bytes(vec![1u8; 1024 * 1024]);
+        match client.call("TEST/ECHO", "echo", large, timeout).await {
+            Err(WindError::Protocol(error)) => {
+                assert!(
+                    error.starts_with("InvalidParams: params too large"),
+                    "{}",
+                    error
+                )
+            }
+            other => panic!("Expected InvalidParams, got {:?}", other),
+        }
+
+        // The rejection carries the call's id, so it reaches the right one
+        // of several calls waiting on a channel
+        let channel = client.channel("TEST/ECHO").await.unwrap();
+        let large = WindValue::Bytes(vec![1u8; 1024 * 1024]);
+        let (rejected, echoed) = tokio::join!(
+            channel.call("echo", large, timeout),
+            channel.call("echo", small.clone(), timeout)
+        );
+        assert!(matches!(rejected, Err(WindError::Protocol(e)) if e.starts_with("InvalidParams")));
+        assert_eq!(echoed.unwrap(), small);
+    }
+
+    #[tokio::test]
+    async fn test_rpc_server_answers_in_the_callers_format() {
+        let registry_addr = "127.0.0.1:7266";
+        let server = RpcServer::new(
+            "TEST/FORMAT".to_string(),
+            "127.0.0.1:0".to_string(),
+            registry_addr.to_string(),
+        );
+        start_rpc_server(registry_addr, server).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let address = subscriber
+            .discover_service("TEST/FORMAT")
+            .await
+            .unwrap()
+            .address;
+        let mut raw = tokio::net::TcpStream::connect(address).await.unwrap();
+        for format in [SerializationFormat::MessagePack, SerializationFormat::Json] {
+            let call = Message::new(MessagePayload::RpcCall {
+                service: "TEST/FORMAT".to_string(),
+                method: "echo".to_string(),
+                params: WindValue::String("hello".to_string()),
+                schema_id: None,
+            });
+            MessageCodec::default()
+                .with_format(format)
+                .write_message(&mut raw, &call)
+                .await
+                .unwrap();
+            let (response, response_format) =
+                MessageCodec::decode_with_format(&mut raw).await.unwrap();
+            assert_eq!(response_format, format);
+            match response.payload {
+                MessagePayload::RpcResponse {
+                    call_id, result, ..
+                } => {
+                    assert_eq!(call_id, call.id);
+                    assert_eq!(result, Ok(WindValue::String("hello".to_string())));
+                }
+                other => panic!("Unexpected response {}", other.name()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_times_out_on_slow_handler() {
+        let registry_addr = "127.0.0.1:7251";
+        let server = RpcServer::new(
+            "TEST/SLOW".to_string(),
+            "127.0.0.1:0".to_string(),
+            registry_addr.to_string(),
+        );
+        let server = start_rpc_server(registry_addr, server).await;
+        server
+            .register_function("sleep".to_string(), |params| async move {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(params)
+            })
+            .await
+            .unwrap();
+
+        let mut client = RpcClient::new(registry_addr.to_string());
+        let started = tokio::time::Instant::now();
+        let call = client.call(
+            "TEST/SLOW",
+            "sleep",
+            WindValue::I32(1),
+            Duration::from_millis(300),
+        );
+        match call.await {
+            Err(WindError::Timeout(_)) => {}
+            other => panic!("Expected a timeout, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // The next call isn't answered with the late response
+        let echoed = client
+            .call(
+                "TEST/SLOW",
+                "echo",
+                WindValue::I32(2),
+                Duration::from_secs(2),
+            )
+            .await
+            .unwrap();
+        assert_eq!(echoed, WindValue::I32(2));
+    }
+
+    #[tokio::test]
+    async fn test_channel_routes_pipelined_responses_by_call_id() {
+        let registry_addr = "127.0.0.1:7252";
+        let server = RpcServer::new(
+            "TEST/DELAYS".to_string(),
+            "127.0.0.1:0".to_string(),
+            registry_addr.to_string(),
+        );
+        let server = start_rpc_server(registry_addr, server).await;
+        server
+            .register_function("delay".to_string(), |params| async move {
+                if let WindValue::I64(ms) = params {
+                    tokio::time::sleep(Duration::from_millis(ms as u64)).await;
+                }
+                Ok(params)
+            })
+            .await
+            .unwrap();
+        // Answers only once two calls are being handled at the same time
+        let barrier = Arc::new(tokio::sync::Barrier::new(2));
+        server
+            .register_function("meet".to_string(), move |params| {
+                let barrier = barrier.clone();
+                async move {
+                    barrier.wait().await;
+                    Ok(params)
+                }
+            })
+            .await
+            .unwrap();
+
+        let mut client = RpcClient::new(registry_addr.to_string());
+        let channel = client.channel("TEST/DELAYS").await.unwrap();
+        let timeout = Duration::from_secs(2);
+
+        // Neither call is answered unless both are in flight on the channel
+        let first = channel.call("meet", WindValue::I64(1), timeout);
+        let second = channel.call("meet", WindValue::I64(2), timeout);
+        let (first, second) = tokio::join!(first, second);
+        assert_eq!(first.unwrap(), WindValue::I64(1));
+        assert_eq!(second.unwrap(), WindValue::I64(2));
+
+        // A response arriving after its call timed out isn't handed to the next call
+        let timed_out = channel
+            .call("delay", WindValue::I64(300), Duration::from_millis(50))
+            .await;
+        assert!(matches!(timed_out, Err(WindError::Timeout(_))));
+        let next = channel
+            .clone()
+            .call("delay", WindValue::I64(400), timeout)
+            .await;
+        assert_eq!(next.unwrap(), WindValue::I64(400));
+    }
+
+    #[tokio::test]
+    async fn test_sequential_calls_reuse_one_connection() {
+        let registry_addr = "127.0.0.1:7254";
+        let server = RpcServer::new(
+            "TEST/POOLED".to_string(),
+            "127.0.0.1:0".to_string(),
+            registry_addr.to_string(),
+        );
+        let server = start_rpc_server(registry_addr, server).await;
+
+        let mut client = RpcClient::new(registry_addr.to_string());
+        let timeout = Duration::from_secs(2);
+        for i in 0..1000 {
+            let echoed = client
+                .call("TEST/POOLED", "echo", WindValue::I64(i), timeout)
+                .await
+                .unwrap();
+            assert_eq!(echoed, WindValue::I64(i));
+        }
+        assert_eq!(server.accepted_connections(), 1);
+
+        // Closing the pooled connection makes the next call open another
+        client.close("TEST/POOLED");
+        client
+            .call("TEST/POOLED", "echo", WindValue::I64(0), timeout)
+            .await
+            .unwrap();
+        assert_eq!(server.accepted_connections(), 2);
+
+        client.close_all();
+        let channel = client.channel("TEST/POOLED").await.unwrap();
+        channel
+            .call("echo", WindValue::I64(0), timeout)
+            .await
+            .unwrap();
+        assert_eq!(server.accepted_connections(), 3);
+        assert!(!channel.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_wrong_service_type_gets_clear_error() {
+        let registry_addr = "127.0.0.1:7212";
+        let server = RpcServer::new(
+            "TEST/RPC_ONLY".to_string(),
+            "127.0.0.1:0".to_string(),
+            registry_addr.to_string(),
+        );
+        start_rpc_server(registry_addr, server).await;
+        let publisher = Arc::new(Publisher::new(
+            "TEST/PUB_ONLY".to_string(),
+            "127.0.0.1:0".to_string(),
+            registry_addr.to_string(),
+        ));
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let timeout = Duration::from_secs(2);
+
+        // Subscribing to an RPC service fails instead of hanging
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let subscribe = subscriber.subscribe(
+            "TEST/RPC_ONLY",
+            SubscriptionMode::OnChange,
+            QosParams::default(),
+        );
+        match tokio::time::timeout(timeout, subscribe).await.unwrap() {
+            Err(WindError::Protocol(error)) => {
+                assert!(error.contains("Subscribe"), "{}", error);
+                assert!(error.contains("RpcServer"), "{}", error);
+            }
+            other => panic!("Expected a protocol error, got {:?}", other.map(|s| s.id)),
+        }
+
+        // Calling a publisher fails the same way
+        let mut client = RpcClient::new(registry_addr.to_string());
+        let call = client.call("TEST/PUB_ONLY", "echo", WindValue::I32(1), timeout);
+        match tokio::time::timeout(timeout, call).await.unwrap() {
+            Err(WindError::Protocol(error)) => {
+                assert!(error.contains("RpcCall"), "{}", error);
+                assert!(error.contains("Publisher"), "{}", error);
+            }
+            other => panic!("Expected a protocol error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_stream_sums_items() {
+        use futures::{SinkExt, StreamExt};
+
+        let registry_addr = "127.0.0.1:7233";
+        let server = RpcServer::new(
+            "TEST/STREAM".to_string(),
+            "127.0.0.1:0".to_string(),
+            registry_addr.to_string(),
+        );
+        let server = start_rpc_server(registry_addr, server).await;
+        server
+            .register_stream_function("sum".to_string(), |items| async move {
+                let total = items
+                    .fold(Ok(0), |total, item| async move {
+                        match item {
+                            WindValue::I64(n) => total.map(|total| total + n),
+                            other => Err(WindError::Protocol(format!("Not a number: {:?}", other))),
+                        }
+                    })
+                    .await?;
+                Ok(WindValue::I64(total))
+            })
+            .await
+            .unwrap();
+
+        let mut client = RpcClient::new(registry_addr.to_string());
+        let (mut items, result) = client
+            .call_client_stream("TEST/STREAM", "sum")
+            .await
+            .unwrap();
+        for n in [3, 1, 4, 1, 5] {
+            items.send(WindValue::I64(n)).await.unwrap();
+        }
+        items.close().await.unwrap();
+        let sum = tokio::time::timeout(Duration::from_secs(2), result)
+            .await
+            .unwrap();
+        assert_eq!(sum.unwrap(), WindValue::I64(14));
+
+        // Unknown methods fail without waiting for the stream to end
+        let (_items, result) = client
+            .call_client_stream("TEST/STREAM", "product")
+            .await
+            .unwrap();
+        match tokio::time::timeout(Duration::from_secs(2), result)
+            .await
+            .unwrap()
+        {
+            Err(WindError::Protocol(error)) => assert!(error.contains("Method not found")),
+            other => panic!("Expected Method not found, got {:?}", other),
+        }
+    }
+}
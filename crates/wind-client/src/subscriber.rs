@@ -1,14 +1,47 @@
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::{broadcast, oneshot, RwLock};
-use tracing::{debug, error, info, warn};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+use tracing::field::debug as debug_field;
+use tracing::{debug, error, info, warn, Instrument};
 use uuid::Uuid;
 
-use crate::Connection;
+use crate::connection::Backoff;
+use crate::watch::SharedWatches;
+use crate::{Connection, ConnectionWriter};
+use futures::stream::{BoxStream, StreamExt};
 use wind_core::{
-    Message, MessagePayload, QosParams, Result, ServiceInfo, SubscriptionMode, WindError, WindValue,
+    message_span, unix_time_us, Message, MessagePayload, PublisherDiagnostics, QosParams,
+    ReliabilityLevel, Result, Schema, ServiceEvent, ServiceInfo, ServiceType, SessionRecorder,
+    SocketOptions, SubscriptionMode, WindError, WindValue, DEFAULT_MAX_HOPS,
 };
 
+/// Application-defined check applied to every incoming value before it is
+/// surfaced; an `Err` drops the value
+pub type ValueValidator = Arc<dyn Fn(&WindValue) -> std::result::Result<(), String> + Send + Sync>;
+
+/// Application-defined selection of the values a subscription surfaces;
+/// unlike validator rejections, filtered values aren't counted
+pub type ValueFilter = Arc<dyn Fn(&WindValue) -> bool + Send + Sync>;
+
+/// How often a subscription re-advertises its free queue space to the publisher
+const FLOW_CONTROL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long `Subscription::close` waits for the publisher to drop the subscription
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Most times a paged discovery starts over because the registry changed
+const MAX_DISCOVERY_RESTARTS: usize = 3;
+
+/// Most attempts to subscribe again after losing the publisher, for
+/// best-effort and reliable subscriptions respectively
+const BEST_EFFORT_RESUBSCRIBE_ATTEMPTS: u32 = 3;
+const RELIABLE_RESUBSCRIBE_ATTEMPTS: u32 = 10;
+
 /// Subscription handle for managing individual subscriptions
 #[derive(Debug)]
 pub struct Subscription {
@@ -17,201 +50,4741 @@ pub struct Subscription {
     pub mode: SubscriptionMode,
     pub qos: QosParams,
     pub receiver: broadcast::Receiver<WindValue>,
-    cancel_sender: oneshot::Sender<()>,
+    cancel_sender: oneshot::Sender<Stop>,
+    task: JoinHandle<()>,
+    mode_changes: mpsc::Sender<ModeChange>,
+    rejected: Arc<AtomicU64>,
+    expired: Arc<AtomicU64>,
+    throttled: Arc<AtomicU64>,
+    budget: Arc<QueueBudget>,
+    liveness: Arc<Mutex<Liveness>>,
+    connected: Arc<AtomicBool>,
 }
 
 impl Subscription {
     pub async fn next(&mut self) -> Option<WindValue> {
-        match self.receiver.recv().await {
-            Ok(value) => Some(value),
-            Err(_) => None,
-        }
+        let value = self.receiver.recv().await.ok()?;
+        self.budget.release(value.encoded_size() as u64);
+        Some(value)
+    }
+
+    /// The next value if one has already arrived, without waiting
+    pub fn try_next(&mut self) -> Option<WindValue> {
+        let value = self.receiver.try_recv().ok()?;
+        self.budget.release(value.encoded_size() as u64);
+        Some(value)
+    }
+
+    /// Change the delivery mode of the live subscription without reconnecting.
+    /// The publisher refuses a switch to `Once`.
+    pub async fn set_mode(&mut self, mode: SubscriptionMode) -> Result<()> {
+        let (reply, outcome) = oneshot::channel();
+        let change = ModeChange {
+            mode: mode.clone(),
+            qos: self.qos.clone(),
+            reply,
+        };
+        let closed = || WindError::Connection("Subscription is no longer active".to_string());
+        self.mode_changes.send(change).await.map_err(|_| closed())?;
+        outcome.await.map_err(|_| closed())??;
+        self.mode = mode;
+        Ok(())
+    }
+
+    /// Time since the publisher last sent a value (or since subscribing, if
+    /// it hasn't yet). Counts arrivals, whether or not `next` has consumed them.
+    pub fn staleness(&self) -> Duration {
+        self.liveness.lock().unwrap().last_value_at.elapsed()
+    }
+
+    /// Sequence number of the last published value received
+    pub fn last_sequence(&self) -> Option<u64> {
+        self.liveness.lock().unwrap().last_sequence
     }
 
+    /// Whether the subscription is connected to its publisher. False while
+    /// it subscribes again after losing the connection, and once it has
+    /// ended.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Number of values dropped by the subscription's validator
+    pub fn rejected_count(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Number of values dropped because they arrived after their TTL ran out
+    pub fn expired_count(&self) -> u64 {
+        self.expired.load(Ordering::Relaxed)
+    }
+
+    /// Number of values dropped for arriving faster than the subscription's
+    /// `max_rate`
+    pub fn throttled_count(&self) -> u64 {
+        self.throttled.load(Ordering::Relaxed)
+    }
+
+    /// Number of values dropped because the queue was full
+    pub fn dropped_count(&self) -> u64 {
+        self.budget.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Payload bytes of the values dropped because the queue was full
+    pub fn dropped_bytes(&self) -> u64 {
+        self.budget.dropped_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Payload bytes currently buffered and not yet returned by `next`
+    pub fn buffered_bytes(&self) -> u64 {
+        self.budget.buffered_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Stop the subscription, sending the publisher an `Unsubscribe` without
+    /// waiting for it to act on it. Dropping the subscription does the same.
     pub fn cancel(self) {
-        let _ = self.cancel_sender.send(());
+        let _ = self.cancel_sender.send(Stop::Cancel);
+    }
+
+    /// Unsubscribe and wait until the publisher has dropped the subscription
+    /// and the background task has finished. Values still in flight are
+    /// discarded.
+    pub async fn close(self) -> Result<()> {
+        let (reply, outcome) = oneshot::channel();
+        let _ = self.cancel_sender.send(Stop::Close(reply));
+        self.task
+            .await
+            .map_err(|e| WindError::Connection(format!("Subscription task failed: {}", e)))?;
+        // No reply means the connection had already ended
+        outcome.await.unwrap_or(Ok(()))
     }
 }
 
-/// High-level subscriber client with automatic reconnection and type safety
-pub struct Subscriber {
-    active_subscriptions: Arc<RwLock<HashMap<Uuid, (String, broadcast::Sender<WindValue>)>>>,
-    registry_connection: Connection,
+/// How the application ends a subscription
+#[derive(Debug)]
+enum Stop {
+    Cancel,
+    Close(oneshot::Sender<Result<()>>),
 }
 
-impl Subscriber {
-    pub fn new(registry_address: String) -> Self {
+/// A `set_mode` request handed to the subscription's background task
+#[derive(Debug)]
+struct ModeChange {
+    mode: SubscriptionMode,
+    qos: QosParams,
+    reply: oneshot::Sender<Result<()>>,
+}
+
+/// When the subscription last heard from its publisher
+#[derive(Debug)]
+struct Liveness {
+    last_value_at: Instant,
+    last_sequence: Option<u64>,
+}
+
+impl Liveness {
+    fn new() -> Self {
         Self {
-            active_subscriptions: Arc::new(RwLock::new(HashMap::new())),
-            registry_connection: Connection::new(registry_address),
+            last_value_at: Instant::now(),
+            last_sequence: None,
         }
     }
 
-    /// Subscribe to a service with type-safe value delivery
-    pub async fn subscribe(
-        &mut self,
-        service_name: &str,
-        mode: SubscriptionMode,
-        qos: QosParams,
-    ) -> Result<Subscription> {
-        // First, discover the service
-        let service_info = self.discover_service(service_name).await?;
+    fn received(&mut self, sequence: Option<u64>) {
+        self.last_value_at = Instant::now();
+        if sequence.is_some() {
+            self.last_sequence = sequence;
+        }
+    }
+}
 
-        // Connect to the service provider
-        let mut service_connection = Connection::new(service_info.address);
-        service_connection.connect().await?;
+/// Count and byte budget for values buffered between the connection task and
+/// `Subscription::next`. Values that don't fit are dropped on arrival.
+#[derive(Debug)]
+struct QueueBudget {
+    max_values: u64,
+    max_bytes: Option<u64>,
+    buffered_values: AtomicU64,
+    buffered_bytes: AtomicU64,
+    dropped: AtomicU64,
+    dropped_bytes: AtomicU64,
+}
 
-        // Create broadcast channel for this subscription
-        let (tx, rx) = broadcast::channel(qos.max_queue_size as usize);
-        let subscription_id = Uuid::new_v4();
+impl QueueBudget {
+    fn new(qos: &QosParams) -> Self {
+        Self {
+            max_values: qos.max_queue_size.max(1) as u64,
+            max_bytes: qos.max_queue_bytes,
+            buffered_values: AtomicU64::new(0),
+            buffered_bytes: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            dropped_bytes: AtomicU64::new(0),
+        }
+    }
 
-        // Send subscription request
-        let subscribe_msg = Message::new(MessagePayload::Subscribe {
-            service: service_name.to_string(),
-            mode: mode.clone(),
-            qos: qos.clone(),
-            schema_id: service_info.schema_id.clone(),
-        });
+    /// Reserve room for a value of `size` bytes, counting a drop if it doesn't fit
+    fn try_reserve(&self, size: u64) -> bool {
+        let values = self.buffered_values.load(Ordering::Relaxed);
+        let bytes = self.buffered_bytes.load(Ordering::Relaxed);
+        let full =
+            values >= self.max_values || self.max_bytes.is_some_and(|max| bytes + size > max);
 
-        service_connection.send(&subscribe_msg).await?;
+        if full {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            self.dropped_bytes.fetch_add(size, Ordering::Relaxed);
+            false
+        } else {
+            self.buffered_values.fetch_add(1, Ordering::Relaxed);
+            self.buffered_bytes.fetch_add(size, Ordering::Relaxed);
+            true
+        }
+    }
 
-        // Wait for subscription acknowledgment
-        let ack_msg = service_connection.receive().await?;
-        match ack_msg.payload {
-            MessagePayload::SubscribeAck {
-                subscription_id: _ack_id,
-                success,
-                error,
-                current_value,
-            } => {
-                if !success {
-                    return Err(WindError::Protocol(
-                        error.unwrap_or("Subscription failed".to_string()),
-                    ));
-                }
+    fn release(&self, size: u64) {
+        self.buffered_values.fetch_sub(1, Ordering::Relaxed);
+        self.buffered_bytes.fetch_sub(size, Ordering::Relaxed);
+    }
 
-                // Send current value if available
-                if let Some(value) = current_value {
-                    let _ = tx.send(value);
-                }
+    /// Free value slots, advertised to the publisher as the flow-control window
+    fn available_window(&self) -> u32 {
+        let buffered = self.buffered_values.load(Ordering::Relaxed);
+        self.max_values
+            .saturating_sub(buffered)
+            .min(u32::MAX as u64) as u32
+    }
+}
 
-                info!("Successfully subscribed to service: {}", service_name);
-            }
-            _ => {
-                return Err(WindError::Protocol(
-                    "Expected SubscribeAck message".to_string(),
-                ));
+/// Token bucket admitting at most `rate` published values per second, with
+/// bursts of up to a tenth of a second's worth
+#[derive(Debug)]
+struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    fn new(values_per_second: u32) -> Self {
+        let rate = values_per_second.max(1) as f64;
+        let burst = (rate / 10.0).max(1.0);
+        Self {
+            rate,
+            burst,
+            tokens: burst,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    fn admit(&mut self) -> bool {
+        let now = Instant::now();
+        let refill = now.duration_since(self.refilled_at).as_secs_f64() * self.rate;
+        self.tokens = (self.tokens + refill).min(self.burst);
+        self.refilled_at = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Upper bound on a payload reassembled from `PublishChunk` frames
+const MAX_REASSEMBLY_SIZE: usize = 64 * 1024 * 1024;
+
+/// Reassembles a chunked publish into the full `Bytes` payload
+#[derive(Debug, Default)]
+struct ChunkAssembler {
+    sequence: Option<u64>,
+    next_index: u32,
+    buffer: Vec<u8>,
+    discarding: bool,
+}
+
+impl ChunkAssembler {
+    /// Add a chunk, returning the payload once its last chunk arrives.
+    /// Payloads with missing chunks or over `MAX_REASSEMBLY_SIZE` are discarded.
+    fn push(
+        &mut self,
+        sequence: u64,
+        chunk_index: u32,
+        last: bool,
+        bytes: Vec<u8>,
+    ) -> Option<Vec<u8>> {
+        if chunk_index == 0 {
+            self.sequence = Some(sequence);
+            self.next_index = 0;
+            self.buffer = Vec::new();
+            self.discarding = false;
+        }
+
+        if self.sequence != Some(sequence) || chunk_index != self.next_index {
+            if !self.discarding {
+                warn!(
+                    "Out-of-order chunk {} of sequence {}, discarding payload",
+                    chunk_index, sequence
+                );
             }
+            self.discarding = true;
+        } else if self.buffer.len() + bytes.len() > MAX_REASSEMBLY_SIZE {
+            warn!(
+                "Chunked payload for sequence {} exceeds {} bytes, discarding",
+                sequence, MAX_REASSEMBLY_SIZE
+            );
+            self.discarding = true;
         }
 
-        // Store subscription info
-        {
-            let mut subs = self.active_subscriptions.write().await;
-            subs.insert(subscription_id, (service_name.to_string(), tx.clone()));
+        if self.discarding {
+            self.buffer = Vec::new();
+        } else {
+            self.buffer.extend_from_slice(&bytes);
         }
+        self.next_index = chunk_index.wrapping_add(1);
 
-        // Create cancel channel
-        let (cancel_tx, mut cancel_rx) = oneshot::channel();
+        if !last {
+            return None;
+        }
+        self.sequence = None;
+        let payload = std::mem::take(&mut self.buffer);
+        (!std::mem::take(&mut self.discarding)).then_some(payload)
+    }
+}
 
-        // Spawn background task to handle incoming data
-        let subs_map = self.active_subscriptions.clone();
-        tokio::spawn(async move {
-            loop {
-                tokio::select! {
-                    // Handle cancellation
-                    _ = &mut cancel_rx => {
-                        debug!("Subscription {} cancelled", subscription_id);
-                        break;
-                    }
+/// Send `subscribe_msg` and wait for its acknowledgment, returning the
+/// publisher's id for the subscription (used to modify it later) and the
+/// current value, if the publisher sent one
+async fn request_subscription(
+    connection: &mut Connection,
+    subscribe_msg: &Message,
+    expected_schema: &Option<String>,
+) -> Result<(Uuid, Option<WindValue>)> {
+    match connection.request(subscribe_msg).await?.payload {
+        MessagePayload::SubscribeAck {
+            subscription_id,
+            success,
+            error,
+            current_value,
+            schema_id,
+        } => {
+            if !success {
+                let error = error.unwrap_or("Subscription failed".to_string());
+                if expected_schema.is_some() && schema_id != *expected_schema {
+                    return Err(WindError::Schema(error));
+                }
+                return Err(WindError::Protocol(error));
+            }
+            Ok((subscription_id, current_value))
+        }
+        MessagePayload::Error { error, .. } => Err(WindError::Protocol(error)),
+        _ => Err(WindError::Protocol(
+            "Expected SubscribeAck message".to_string(),
+        )),
+    }
+}
 
-                    // Handle incoming messages
-                    msg_result = service_connection.receive() => {
-                        match msg_result {
-                            Ok(msg) => {
-                                match msg.payload {
-                                    MessagePayload::Publish { value, .. } => {
-                                        if tx.send(value).is_err() {
-                                            warn!("No active receivers for subscription {}", subscription_id);
-                                        }
-                                    }
-                                    MessagePayload::Error { error, .. } => {
-                                        error!("Service error: {}", error);
-                                        break;
-                                    }
-                                    _ => {
-                                        debug!("Unexpected message: {:?}", msg.payload);
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                error!("Connection error: {}. Attempting to reconnect...", e);
-                                // TODO: Implement reconnection logic here
-                                break;
-                            }
-                        }
-                    }
+/// Split a publisher connection and read it from a task of its own. Decoding
+/// is not cancel-safe, so this keeps the subscription task free to send
+/// flow-control updates.
+fn spawn_reader(
+    connection: Connection,
+) -> Result<(
+    ConnectionWriter,
+    mpsc::Receiver<Result<Message>>,
+    JoinHandle<()>,
+)> {
+    let (mut reader, writer) = connection.into_split()?;
+    let (incoming_tx, incoming_rx) = mpsc::channel(1);
+    let reader_task = tokio::spawn(async move {
+        loop {
+            let result = reader.receive().await;
+            let failed = result.is_err();
+            if incoming_tx.send(result).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+    Ok((writer, incoming_rx, reader_task))
+}
+
+/// Subscribes again on a subscription task's behalf after the connection to
+/// the publisher is lost. The service is looked up afresh on every attempt,
+/// as a restarted publisher may have moved.
+struct Resubscriber {
+    registry: Subscriber,
+    service_name: String,
+    schema_id: Option<String>,
+    max_attempts: u32,
+    backoff: Backoff,
+}
+
+impl Resubscriber {
+    async fn resubscribe(
+        &mut self,
+        mode: &SubscriptionMode,
+        qos: &QosParams,
+    ) -> Result<(Connection, Uuid, Option<WindValue>)> {
+        self.backoff.reset();
+        let mut attempt = 1;
+        loop {
+            match self.try_resubscribe(mode, qos).await {
+                Ok(resubscribed) => return Ok(resubscribed),
+                Err(e) if attempt >= self.max_attempts => return Err(e),
+                Err(e) => {
+                    let delay = self.backoff.next_delay();
+                    warn!(
+                        "Resubscribing to {} failed (attempt {}): {}. Retrying in {:?}...",
+                        self.service_name, attempt, e, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
                 }
             }
+        }
+    }
 
-            // Cleanup subscription
-            let mut subs = subs_map.write().await;
-            subs.remove(&subscription_id);
+    async fn try_resubscribe(
+        &mut self,
+        mode: &SubscriptionMode,
+        qos: &QosParams,
+    ) -> Result<(Connection, Uuid, Option<WindValue>)> {
+        let service_info = self.registry.discover_service(&self.service_name).await?;
+        // Retained history was replayed when the subscription started
+        let subscribe_msg = Message::new(MessagePayload::Subscribe {
+            service: self.service_name.clone(),
+            mode: mode.clone(),
+            qos: qos.clone(),
+            schema_id: self.schema_id.clone(),
+            replay: 0,
         });
+        subscribe_msg
+            .payload
+            .check_served_by(&self.service_name, &service_info.service_type)?;
 
-        Ok(Subscription {
-            id: subscription_id,
+        let mut connection = self
+            .registry
+            .publisher_connection(service_info.address)
+            .with_max_reconnect_attempts(0);
+        connection.connect().await?;
+        let (remote_id, current_value) =
+            request_subscription(&mut connection, &subscribe_msg, &self.schema_id).await?;
+        Ok((connection, remote_id, current_value))
+    }
+}
+
+/// Tell the publisher how many more values the subscription can buffer
+async fn send_flow_control(writer: &mut ConnectionWriter, budget: &QueueBudget) -> Result<u32> {
+    let available_window = budget.available_window();
+    writer
+        .send(&Message::new(MessagePayload::FlowControl {
+            available_window,
+        }))
+        .await?;
+    Ok(available_window)
+}
+
+/// Send `Unsubscribe` and wait for the publisher to close the connection,
+/// which it does once the subscription is gone
+async fn unsubscribe(
+    writer: &mut ConnectionWriter,
+    subscription_id: Uuid,
+    incoming: &mut mpsc::Receiver<Result<Message>>,
+) -> Result<()> {
+    writer
+        .send(&Message::new(MessagePayload::Unsubscribe {
+            subscription_id,
+        }))
+        .await?;
+
+    let closed = async { while let Some(Ok(_)) = incoming.recv().await {} };
+    tokio::time::timeout(CLOSE_TIMEOUT, closed)
+        .await
+        .map_err(|_| {
+            WindError::Timeout(format!(
+                "Publisher did not drop subscription {}",
+                subscription_id
+            ))
+        })
+}
+
+type SubscriptionMap = HashMap<Uuid, (String, broadcast::Sender<WindValue>)>;
+
+/// Services matching a discovery pattern, as of one registry version
+#[derive(Debug, Clone)]
+pub struct DiscoverySnapshot {
+    pub pattern: String,
+    pub services: Vec<ServiceInfo>,
+    /// Registry version the services were read at; see `Subscriber::is_current`
+    pub registry_version: u64,
+}
+
+/// Options for a new subscription, from `Subscriber::subscription` or
+/// `WindClient::subscription`. Defaults to `OnChange` with default QoS.
+pub struct SubscribeBuilder<'a> {
+    subscriber: &'a mut Subscriber,
+    service_name: String,
+    options: SubscribeOptions,
+}
+
+/// Everything about a subscription but the service it is to
+struct SubscribeOptions {
+    mode: SubscriptionMode,
+    qos: QosParams,
+    validator: Option<ValueValidator>,
+    filter: Option<ValueFilter>,
+    schema_id: Option<String>,
+    replay: u32,
+    max_rate: Option<u32>,
+}
+
+impl<'a> SubscribeBuilder<'a> {
+    fn new(subscriber: &'a mut Subscriber, service_name: &str) -> Self {
+        Self {
+            subscriber,
             service_name: service_name.to_string(),
-            mode,
-            qos,
-            receiver: rx,
-            cancel_sender: cancel_tx,
+            options: SubscribeOptions {
+                mode: SubscriptionMode::OnChange,
+                qos: QosParams::default(),
+                validator: None,
+                filter: None,
+                schema_id: None,
+                replay: 0,
+                max_rate: None,
+            },
+        }
+    }
+
+    pub fn mode(mut self, mode: SubscriptionMode) -> Self {
+        self.options.mode = mode;
+        self
+    }
+
+    pub fn qos(mut self, qos: QosParams) -> Self {
+        self.options.qos = qos;
+        self
+    }
+
+    /// Acknowledge every value so the publisher can confirm delivery
+    pub fn reliable(mut self) -> Self {
+        self.options.qos.reliability = ReliabilityLevel::Reliable;
+        self
+    }
+
+    /// Whether to start with the value published before subscribing, if
+    /// any (the default), or only with values published after
+    pub fn durability(mut self, durable: bool) -> Self {
+        self.options.qos.durability = durable;
+        self
+    }
+
+    /// Buffer at most this many values not yet taken with `next`
+    pub fn max_queue_size(mut self, max_queue_size: u32) -> Self {
+        self.options.qos.max_queue_size = max_queue_size;
+        self
+    }
+
+    /// Drop, and count in `Subscription::rejected_count`, every value the
+    /// validator rejects
+    pub fn validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&WindValue) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        self.options.validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Only surface values for which `filter` returns true
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&WindValue) -> bool + Send + Sync + 'static,
+    {
+        self.options.filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// Only subscribe if the publisher sends values of schema `schema_id`;
+    /// otherwise `start` fails with `WindError::Schema`
+    pub fn schema(mut self, schema_id: impl Into<String>) -> Self {
+        self.options.schema_id = Some(schema_id.into());
+        self
+    }
+
+    /// Start with up to `values` of the publisher's retained history (see
+    /// `Publisher::with_history`), delivered in order before live data
+    pub fn replay(mut self, values: u32) -> Self {
+        self.options.replay = values;
+        self
+    }
+
+    /// Take at most `values_per_second` published values, dropping (and
+    /// counting in `Subscription::throttled_count`) the excess on arrival, so
+    /// a runaway publisher can't monopolize the application. Unlike
+    /// `max_queue_size`, this caps the arrival rate however fast `next` is
+    /// called. Replayed history isn't limited.
+    pub fn max_rate(mut self, values_per_second: u32) -> Self {
+        self.options.max_rate = Some(values_per_second);
+        self
+    }
+
+    /// Decode every value into `T`
+    pub fn typed<T>(self) -> TypedSubscribeBuilder<'a, T>
+    where
+        T: TryFrom<WindValue, Error = WindError>,
+    {
+        TypedSubscribeBuilder {
+            builder: self,
+            _type: PhantomData,
+        }
+    }
+
+    pub async fn start(self) -> Result<Subscription> {
+        self.subscriber
+            .subscribe_inner(&self.service_name, self.options)
+            .await
+    }
+}
+
+/// A `SubscribeBuilder` whose subscription decodes values into `T`
+pub struct TypedSubscribeBuilder<'a, T> {
+    builder: SubscribeBuilder<'a>,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> TypedSubscribeBuilder<'_, T>
+where
+    T: TryFrom<WindValue, Error = WindError>,
+{
+    pub async fn start(self) -> Result<TypedSubscription<T>> {
+        Ok(TypedSubscription {
+            subscription: self.builder.start().await?,
+            _type: PhantomData,
         })
     }
+}
+
+/// Subscription handle yielding values decoded into `T`
+#[derive(Debug)]
+pub struct TypedSubscription<T> {
+    subscription: Subscription,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> TypedSubscription<T>
+where
+    T: TryFrom<WindValue, Error = WindError>,
+{
+    /// The next value, or an error if it doesn't decode into `T`
+    pub async fn next(&mut self) -> Option<Result<T>> {
+        self.subscription.next().await.map(T::try_from)
+    }
 
-    /// Discover a specific service by name
-    pub async fn discover_service(&mut self, service_name: &str) -> Result<ServiceInfo> {
-        self.registry_connection.connect().await?;
+    pub fn inner(&self) -> &Subscription {
+        &self.subscription
+    }
 
-        let discover_msg = Message::new(MessagePayload::DiscoverServices {
-            pattern: service_name.to_string(), // Exact match
-        });
+    pub fn into_inner(self) -> Subscription {
+        self.subscription
+    }
+}
 
-        self.registry_connection.send(&discover_msg).await?;
-        let response = self.registry_connection.receive().await?;
+/// Subscription handle checking every value against the service's schema
+#[derive(Debug)]
+pub struct ValidatedSubscription {
+    subscription: Subscription,
+    schema: Schema,
+}
 
-        match response.payload {
-            MessagePayload::ServicesDiscovered { services } => {
-                if let Some(service) = services.into_iter().find(|s| s.name == service_name) {
-                    Ok(service)
-                } else {
-                    Err(WindError::ServiceNotFound(service_name.to_string()))
-                }
+impl ValidatedSubscription {
+    /// The next value, or why it doesn't conform to the schema
+    pub async fn next(&mut self) -> Option<Result<WindValue>> {
+        let value = self.subscription.next().await?;
+        Some(self.schema.validate(&value).map(|()| value))
+    }
+
+    /// The schema values are checked against
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn inner(&self) -> &Subscription {
+        &self.subscription
+    }
+
+    pub fn into_inner(self) -> Subscription {
+        self.subscription
+    }
+}
+
+/// How a `MergedSubscription` shares turns between its subscriptions when
+/// more than one has values waiting
+#[derive(Debug, Clone, Default)]
+pub enum FairnessPolicy {
+    /// One value from each subscription in turn
+    #[default]
+    RoundRobin,
+    /// Up to the service's weight in values per turn, 1 for services not listed
+    Weighted(HashMap<String, u32>),
+}
+
+impl FairnessPolicy {
+    fn weight(&self, service_name: &str) -> u32 {
+        match self {
+            FairnessPolicy::RoundRobin => 1,
+            FairnessPolicy::Weighted(weights) => {
+                weights.get(service_name).copied().unwrap_or(1).max(1)
             }
-            MessagePayload::Error { error, .. } => Err(WindError::Registry(error)),
-            _ => Err(WindError::Protocol("Unexpected response".to_string())),
         }
     }
+}
 
-    /// Discover services matching a pattern
-    pub async fn discover_services(&mut self, pattern: &str) -> Result<Vec<ServiceInfo>> {
-        self.registry_connection.connect().await?;
+/// Values of several subscriptions as one stream of `(service, value)`,
+/// taking turns by a `FairnessPolicy` so a busy service can't starve a quiet
+/// one. Each subscription keeps its own queue and flow control, so one that
+/// is slow or idle never holds up the others.
+#[derive(Debug, Default)]
+pub struct MergedSubscription {
+    subscriptions: Vec<Subscription>,
+    policy: FairnessPolicy,
+    /// Subscription whose turn it is, and values it has had this turn
+    turn: usize,
+    taken: u32,
+}
 
-        let discover_msg = Message::new(MessagePayload::DiscoverServices {
-            pattern: pattern.to_string(),
-        });
+impl MergedSubscription {
+    pub fn new(subscriptions: Vec<Subscription>) -> Self {
+        Self {
+            subscriptions,
+            ..Self::default()
+        }
+    }
 
-        self.registry_connection.send(&discover_msg).await?;
-        let response = self.registry_connection.receive().await?;
+    pub fn with_policy(mut self, policy: FairnessPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
 
-        match response.payload {
-            MessagePayload::ServicesDiscovered { services } => Ok(services),
-            MessagePayload::Error { error, .. } => Err(WindError::Registry(error)),
-            _ => Err(WindError::Protocol("Unexpected response".to_string())),
-        }
+    /// Add a subscription; it joins the rotation at the end
+    pub fn push(&mut self, subscription: Subscription) {
+        self.subscriptions.push(subscription);
     }
 
-    /// Get the number of active subscriptions
-    pub async fn subscription_count(&self) -> usize {
-        self.active_subscriptions.read().await.len()
+    /// Take out the subscription to `service_name`, if there is one
+    pub fn remove(&mut self, service_name: &str) -> Option<Subscription> {
+        let index = self
+            .subscriptions
+            .iter()
+            .position(|subscription| subscription.service_name == service_name)?;
+        Some(self.take_out(index))
+    }
+
+    pub fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+
+    /// The next value and the service it came from. Subscriptions that end
+    /// are dropped from the merge; `None` once all of them have.
+    pub async fn next(&mut self) -> Option<(String, WindValue)> {
+        loop {
+            if let Some(next) = self.next_waiting() {
+                return Some(next);
+            }
+            if self.subscriptions.is_empty() {
+                return None;
+            }
+
+            // Nothing waiting anywhere: the first subscription to receive a
+            // value or end wins. `Subscription::next` is cancel safe, so the
+            // others lose nothing.
+            let (value, index, _) = futures::future::select_all(
+                self.subscriptions
+                    .iter_mut()
+                    .map(|subscription| Box::pin(subscription.next())),
+            )
+            .await;
+            match value {
+                Some(value) => {
+                    self.turn = index;
+                    self.taken = 0;
+                    let next = self.take_turn(value);
+                    return Some(next);
+                }
+                None => {
+                    self.take_out(index);
+                }
+            }
+        }
+    }
+
+    /// A value already received, starting with the subscription whose turn it is
+    fn next_waiting(&mut self) -> Option<(String, WindValue)> {
+        for _ in 0..self.subscriptions.len() {
+            if let Some(value) = self.subscriptions[self.turn].try_next() {
+                return Some(self.take_turn(value));
+            }
+            self.pass_turn();
+        }
+        None
+    }
+
+    /// Count `value` against the current turn, passing the turn on once the
+    /// subscription has had its share
+    fn take_turn(&mut self, value: WindValue) -> (String, WindValue) {
+        let service_name = self.subscriptions[self.turn].service_name.clone();
+        self.taken += 1;
+        if self.taken >= self.policy.weight(&service_name) {
+            self.pass_turn();
+        }
+        (service_name, value)
+    }
+
+    fn pass_turn(&mut self) {
+        self.turn = (self.turn + 1) % self.subscriptions.len().max(1);
+        self.taken = 0;
+    }
+
+    fn take_out(&mut self, index: usize) -> Subscription {
+        let subscription = self.subscriptions.remove(index);
+        if index < self.turn {
+            self.turn -= 1;
+        } else if index == self.turn {
+            // The turn passes to the subscription that moved into its place
+            self.taken = 0;
+            if self.turn >= self.subscriptions.len() {
+                self.turn = 0;
+            }
+        }
+        subscription
+    }
+}
+
+/// Values of every publisher matching a pattern as one stream of
+/// `(service, value)`. Follows the registry while it's read: services that
+/// start matching are subscribed to, and ones that leave are dropped.
+pub struct PatternSubscription {
+    pattern: String,
+    mode: SubscriptionMode,
+    qos: QosParams,
+    merged: MergedSubscription,
+    /// Registry changes; `None` once the watch has ended
+    events: Option<BoxStream<'static, ServiceEvent>>,
+    subscriber: Subscriber,
+}
+
+impl PatternSubscription {
+    /// The next value and the service it came from. `None` once every
+    /// subscription has ended and the registry can no longer be followed.
+    pub async fn next(&mut self) -> Option<(String, WindValue)> {
+        loop {
+            let Some(events) = self.events.as_mut() else {
+                return self.merged.next().await;
+            };
+            tokio::select! {
+                event = events.next() => match event {
+                    Some(ServiceEvent::Registered(service)) => self.join(service).await,
+                    Some(ServiceEvent::Removed(service)) => {
+                        if self.merged.remove(&service.name).is_some() {
+                            info!("{} left {}", service.name, self.pattern);
+                        }
+                    }
+                    None => {
+                        warn!("Stopped following {}: service watch ended", self.pattern);
+                        self.events = None;
+                    }
+                },
+                // `MergedSubscription::next` is cancel safe
+                next = self.merged.next(), if !self.merged.is_empty() => {
+                    if next.is_some() {
+                        return next;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Names of the services currently subscribed to
+    pub fn services(&self) -> impl Iterator<Item = &str> {
+        self.merged
+            .subscriptions()
+            .iter()
+            .map(|subscription| subscription.service_name.as_str())
+    }
+
+    /// Subscribe to `service` unless it's already followed or isn't a publisher
+    async fn join(&mut self, service: ServiceInfo) {
+        if !service.service_type.provides(&ServiceType::Publisher)
+            || self.services().any(|name| name == service.name)
+        {
+            return;
+        }
+        let subscribed = self
+            .subscriber
+            .subscribe(&service.name, self.mode.clone(), self.qos.clone())
+            .await;
+        match subscribed {
+            Ok(subscription) => {
+                info!("{} joined {}", service.name, self.pattern);
+                self.merged.push(subscription);
+            }
+            Err(e) => warn!("Failed to subscribe to {}: {}", service.name, e),
+        }
+    }
+}
+
+/// High-level subscriber client with automatic reconnection and type safety
+pub struct Subscriber {
+    active_subscriptions: Arc<RwLock<SubscriptionMap>>,
+    registry_connection: Connection,
+    client_id: Option<String>,
+    token: Option<String>,
+    socket_options: SocketOptions,
+    recorder: Option<SessionRecorder>,
+    /// Registry watches shared by this client's watchers, by pattern
+    watches: SharedWatches,
+}
+
+impl Subscriber {
+    pub fn new(registry_address: String) -> Self {
+        Self {
+            active_subscriptions: Arc::new(RwLock::new(HashMap::new())),
+            registry_connection: Connection::new(registry_address),
+            client_id: None,
+            token: None,
+            socket_options: SocketOptions::default(),
+            recorder: None,
+            watches: SharedWatches::default(),
+        }
+    }
+
+    /// Identify as `client_id` to the registry and publishers, for ACLs
+    pub fn with_client_id(mut self, client_id: String) -> Self {
+        self.client_id = Some(client_id);
+        self.registry_connection = self
+            .registry_connection
+            .with_client_id(self.client_id.clone());
+        self
+    }
+
+    /// Authenticate with `token` to the registry and publishers
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self.registry_connection = self.registry_connection.with_token(self.token.clone());
+        self
+    }
+
+    /// TCP options for the registry connection and every publisher connection
+    pub fn with_socket_options(mut self, options: SocketOptions) -> Self {
+        self.socket_options = options.clone();
+        self.registry_connection = self.registry_connection.with_socket_options(options);
+        self
+    }
+
+    /// Record the messages exchanged with publishers, e.g. to reproduce a
+    /// bug with `wind_core::replay`. Registry traffic isn't recorded.
+    pub fn with_recorder(mut self, recorder: SessionRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Connection to a publisher, recorded if this subscriber records
+    fn publisher_connection(&self, address: String) -> Connection {
+        let connection = Connection::new(address)
+            .with_client_id(self.client_id.clone())
+            .with_token(self.token.clone())
+            .with_socket_options(self.socket_options.clone());
+        match &self.recorder {
+            Some(recorder) => connection.with_recorder(recorder.clone()),
+            None => connection,
+        }
+    }
+
+    /// The same settings with a registry connection of its own that doesn't
+    /// retry, for a subscription's task to look its service up again
+    fn detached(&self) -> Subscriber {
+        let registry_connection = Connection::new(self.registry_connection.address().to_string())
+            .with_client_id(self.client_id.clone())
+            .with_token(self.token.clone())
+            .with_socket_options(self.socket_options.clone())
+            .with_max_reconnect_attempts(0);
+        Subscriber {
+            active_subscriptions: self.active_subscriptions.clone(),
+            registry_connection,
+            client_id: self.client_id.clone(),
+            token: self.token.clone(),
+            socket_options: self.socket_options.clone(),
+            recorder: self.recorder.clone(),
+            watches: self.watches.clone(),
+        }
+    }
+
+    pub(crate) fn socket_options(&self) -> &SocketOptions {
+        &self.socket_options
+    }
+
+    pub(crate) fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// Start configuring a subscription to `service_name`
+    pub fn subscription(&mut self, service_name: &str) -> SubscribeBuilder<'_> {
+        SubscribeBuilder::new(self, service_name)
+    }
+
+    /// Subscribe to a service with type-safe value delivery
+    pub async fn subscribe(
+        &mut self,
+        service_name: &str,
+        mode: SubscriptionMode,
+        qos: QosParams,
+    ) -> Result<Subscription> {
+        self.subscription(service_name)
+            .mode(mode)
+            .qos(qos)
+            .start()
+            .await
+    }
+
+    /// Subscribe to a service, dropping (and counting) every value the
+    /// validator rejects instead of surfacing it
+    pub async fn subscribe_with_validator<F>(
+        &mut self,
+        service_name: &str,
+        mode: SubscriptionMode,
+        qos: QosParams,
+        validator: F,
+    ) -> Result<Subscription>
+    where
+        F: Fn(&WindValue) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        self.subscription(service_name)
+            .mode(mode)
+            .qos(qos)
+            .validator(validator)
+            .start()
+            .await
+    }
+
+    /// Subscribe to a service that declares a schema, checking every value
+    /// against the schema fetched from the registry. Values that don't
+    /// conform are surfaced as errors by `ValidatedSubscription::next`.
+    pub async fn subscribe_validated(
+        &mut self,
+        service_name: &str,
+        mode: SubscriptionMode,
+        qos: QosParams,
+    ) -> Result<ValidatedSubscription> {
+        let service_info = self.discover_service(service_name).await?;
+        let schema_id = service_info.schema_id.ok_or_else(|| {
+            WindError::Schema(format!("Service {} declares no schema", service_name))
+        })?;
+        let schema = self.get_schema(&schema_id).await?;
+        let subscription = self
+            .subscription(service_name)
+            .mode(mode)
+            .qos(qos)
+            .schema(schema_id)
+            .start()
+            .await?;
+        Ok(ValidatedSubscription {
+            subscription,
+            schema,
+        })
+    }
+
+    /// Fetch a schema from the registry
+    pub async fn get_schema(&mut self, schema_id: &str) -> Result<Schema> {
+        let request = Message::new(MessagePayload::GetSchema {
+            schema_id: schema_id.to_string(),
+        });
+        match self.registry_connection.request(&request).await?.payload {
+            MessagePayload::SchemaFound {
+                schema: Some(schema),
+                ..
+            } => Ok(schema),
+            MessagePayload::SchemaFound { schema: None, .. } => Err(WindError::Schema(format!(
+                "Registry doesn't know schema {}",
+                schema_id
+            ))),
+            MessagePayload::Error { error, .. } => Err(WindError::Registry(error)),
+            _ => Err(WindError::Protocol("Unexpected response".to_string())),
+        }
+    }
+
+    async fn subscribe_inner(
+        &mut self,
+        service_name: &str,
+        options: SubscribeOptions,
+    ) -> Result<Subscription> {
+        let SubscribeOptions {
+            mode,
+            qos,
+            validator,
+            filter,
+            schema_id: expected_schema,
+            replay,
+            max_rate,
+        } = options;
+        // First, discover the service
+        let service_info = self.discover_service(service_name).await?;
+        let subscribe_msg = Message::new(MessagePayload::Subscribe {
+            service: service_name.to_string(),
+            mode: mode.clone(),
+            qos: qos.clone(),
+            schema_id: expected_schema.clone(),
+            replay,
+        });
+        subscribe_msg
+            .payload
+            .check_served_by(service_name, &service_info.service_type)?;
+
+        // Connect to the service provider
+        let mut service_connection = self.publisher_connection(service_info.address);
+        service_connection.connect().await?;
+
+        // Create broadcast channel for this subscription
+        let (tx, rx) = broadcast::channel(qos.max_queue_size as usize);
+        let subscription_id = Uuid::new_v4();
+        let rejected = Arc::new(AtomicU64::new(0));
+        let expired = Arc::new(AtomicU64::new(0));
+        let expired_for_task = expired.clone();
+        let throttled = Arc::new(AtomicU64::new(0));
+        let throttled_for_task = throttled.clone();
+        let mut rate_limiter = max_rate.map(RateLimiter::new);
+        let budget = Arc::new(QueueBudget::new(&qos));
+        let budget_for_task = budget.clone();
+        let liveness = Arc::new(Mutex::new(Liveness::new()));
+        let liveness_for_task = liveness.clone();
+        let accept = {
+            let rejected = rejected.clone();
+            move |value: &WindValue| {
+                let valid = match &validator {
+                    Some(validator) => match validator(value) {
+                        Ok(()) => true,
+                        Err(reason) => {
+                            rejected.fetch_add(1, Ordering::Relaxed);
+                            debug!(
+                                "Subscription {} rejected value: {}",
+                                subscription_id, reason
+                            );
+                            false
+                        }
+                    },
+                    None => true,
+                };
+                valid && filter.as_ref().is_none_or(|filter| filter(value))
+            }
+        };
+        // Buffer a value for `next` if it passes validation and the filter and
+        // fits the queue budget
+        let forward = {
+            let budget = budget.clone();
+            let tx = tx.clone();
+            move |value: WindValue| {
+                if !accept(&value) {
+                    return;
+                }
+                let size = value.encoded_size() as u64;
+                if !budget.try_reserve(size) {
+                    debug!(
+                        "Subscription {} queue full, dropped {} bytes",
+                        subscription_id, size
+                    );
+                    return;
+                }
+                if tx.send(value).is_err() {
+                    budget.release(size);
+                    warn!("No active receivers for subscription {}", subscription_id);
+                }
+            }
+        };
+
+        // Send subscription request and wait for its acknowledgment
+        let (mut remote_id, current_value) =
+            request_subscription(&mut service_connection, &subscribe_msg, &expected_schema).await?;
+        // Send current value if available
+        if let Some(value) = current_value {
+            liveness.lock().unwrap().received(None);
+            forward(value);
+        }
+        info!("Successfully subscribed to service: {}", service_name);
+
+        // Store subscription info
+        {
+            let mut subs = self.active_subscriptions.write().await;
+            subs.insert(subscription_id, (service_name.to_string(), tx.clone()));
+        }
+
+        // Create cancel channel
+        let (cancel_tx, mut cancel_rx) = oneshot::channel();
+        let (mode_tx, mut mode_rx) = mpsc::channel::<ModeChange>(1);
+
+        let (mut writer, mut incoming_rx, mut reader_task) = spawn_reader(service_connection)?;
+        let connected = Arc::new(AtomicBool::new(true));
+        let connected_for_task = connected.clone();
+
+        // Spawn background task to handle incoming data
+        let subs_map = self.active_subscriptions.clone();
+        let reliable = matches!(qos.reliability, ReliabilityLevel::Reliable);
+        let mut resubscriber = Resubscriber {
+            registry: self.detached(),
+            service_name: service_name.to_string(),
+            schema_id: expected_schema,
+            max_attempts: if reliable {
+                RELIABLE_RESUBSCRIBE_ATTEMPTS
+            } else {
+                BEST_EFFORT_RESUBSCRIBE_ATTEMPTS
+            },
+            backoff: Backoff::default(),
+        };
+        let mut current_mode = mode.clone();
+        let mut current_qos = qos.clone();
+        let task = tokio::spawn(async move {
+            let mut advertised = match send_flow_control(&mut writer, &budget_for_task).await {
+                Ok(window) => window,
+                Err(e) => {
+                    warn!("Failed to send initial flow control: {}", e);
+                    u32::MAX
+                }
+            };
+            let mut received_since_advert = 0u32;
+            let mut chunks = ChunkAssembler::default();
+            let mut advert_interval = tokio::time::interval(FLOW_CONTROL_INTERVAL);
+            let mut pending_change: Option<ModeChange> = None;
+
+            loop {
+                let refresh = tokio::select! {
+                    // Handle cancellation
+                    stop = &mut cancel_rx => {
+                        debug!("Subscription {} cancelled", subscription_id);
+                        match stop {
+                            Ok(Stop::Close(reply)) => {
+                                let _ = reply.send(unsubscribe(&mut writer, remote_id, &mut incoming_rx).await);
+                            }
+                            // Cancelled or dropped: tell the publisher to stop
+                            // sending, without waiting for it
+                            _ => {
+                                let unsubscribe = Message::new(MessagePayload::Unsubscribe {
+                                    subscription_id: remote_id,
+                                });
+                                let _ = writer.send(&unsubscribe).await;
+                            }
+                        }
+                        break;
+                    }
+
+                    // Ask the publisher to switch modes; one change in flight at a time
+                    Some(change) = mode_rx.recv(), if pending_change.is_none() => {
+                        let modify = Message::new(MessagePayload::ModifySubscription {
+                            subscription_id: remote_id,
+                            mode: change.mode.clone(),
+                            qos: change.qos.clone(),
+                        });
+                        match writer.send(&modify).await {
+                            Ok(()) => pending_change = Some(change),
+                            Err(e) => {
+                                let _ = change.reply.send(Err(e));
+                                break;
+                            }
+                        }
+                        false
+                    }
+
+                    // Re-open a spent window once the application drains the queue
+                    _ = advert_interval.tick() => {
+                        received_since_advert >= advertised
+                            && budget_for_task.available_window() > 0
+                    }
+
+                    // Handle incoming messages
+                    msg_result = incoming_rx.recv() => {
+                        match msg_result {
+                            Some(Ok(msg)) => {
+                                let message_id = msg.id;
+                                let published = match msg.payload {
+                                    MessagePayload::Publish {
+                                        value,
+                                        sequence,
+                                        expires_at_us,
+                                        ..
+                                    } => vec![(sequence, Arc::unwrap_or_clone(value), expires_at_us)],
+                                    MessagePayload::PublishBatch {
+                                        start_sequence,
+                                        values,
+                                        ..
+                                    } => (start_sequence..)
+                                        .zip(values)
+                                        .map(|(sequence, value)| (sequence, value, None))
+                                        .collect(),
+                                    MessagePayload::PublishChunk {
+                                        sequence,
+                                        chunk_index,
+                                        last,
+                                        bytes,
+                                        expires_at_us,
+                                        ..
+                                    } => chunks
+                                        .push(sequence, chunk_index, last, bytes)
+                                        .map(|payload| {
+                                            (sequence, WindValue::Bytes(payload), expires_at_us)
+                                        })
+                                        .into_iter()
+                                        .collect(),
+                                    MessagePayload::ReplayBatch { values, .. } => {
+                                        // Retained values come ahead of live data and
+                                        // don't use up flow-control credit
+                                        for (sequence, value) in values {
+                                            liveness_for_task.lock().unwrap().received(Some(sequence));
+                                            forward(value);
+                                        }
+                                        Vec::new()
+                                    }
+                                    MessagePayload::SubscribeAck { success, error, .. } => {
+                                        if let Some(change) = pending_change.take() {
+                                            let result = if success {
+                                                // Subscribing again keeps the new mode
+                                                current_mode = change.mode;
+                                                current_qos = change.qos;
+                                                Ok(())
+                                            } else {
+                                                Err(WindError::Protocol(error.unwrap_or(
+                                                    "Mode change failed".to_string(),
+                                                )))
+                                            };
+                                            let _ = change.reply.send(result);
+                                        }
+                                        Vec::new()
+                                    }
+                                    MessagePayload::Ping => {
+                                        // The publisher checking we're still here
+                                        let pong = Message::new(MessagePayload::Pong);
+                                        if let Err(e) = writer.send(&pong).await {
+                                            error!("Failed to answer Ping: {}", e);
+                                            break;
+                                        }
+                                        Vec::new()
+                                    }
+                                    MessagePayload::Error { error, .. } => {
+                                        error!("Service error: {}", error);
+                                        break;
+                                    }
+                                    other => {
+                                        debug!("Unexpected message: {:?}", other);
+                                        Vec::new()
+                                    }
+                                };
+
+                                let received_any = !published.is_empty();
+                                let mut ack_failed = false;
+                                for (sequence, value, expires_at_us) in published {
+                                    let span = message_span(
+                                        "receive",
+                                        message_id,
+                                        &resubscriber.service_name,
+                                        sequence,
+                                    );
+                                    if !span.is_disabled() {
+                                        span.record("payload.size", value.encoded_size());
+                                        span.record("subscription.mode", debug_field(&current_mode));
+                                    }
+                                    span.in_scope(|| {
+                                        liveness_for_task.lock().unwrap().received(Some(sequence));
+                                        let now_us = unix_time_us(SystemTime::now());
+                                        if expires_at_us.is_some_and(|at| now_us >= at) {
+                                            expired_for_task.fetch_add(1, Ordering::Relaxed);
+                                            debug!(
+                                                "Subscription {} dropped value {} past its TTL",
+                                                subscription_id, sequence
+                                            );
+                                        } else if rate_limiter.as_mut().is_some_and(|limiter| !limiter.admit()) {
+                                            throttled_for_task.fetch_add(1, Ordering::Relaxed);
+                                        } else {
+                                            forward(value);
+                                        }
+                                    });
+                                    if reliable {
+                                        let ack = Message::new(MessagePayload::PublishAck { sequence });
+                                        if let Err(e) = writer.send(&ack).instrument(span).await {
+                                            error!("Failed to acknowledge {}: {}", sequence, e);
+                                            ack_failed = true;
+                                            break;
+                                        }
+                                    }
+                                    // Credit is only replaced once spent, so nothing sent
+                                    // under the previous window is still in flight
+                                    received_since_advert += 1;
+                                }
+                                if ack_failed {
+                                    break;
+                                }
+                                received_any && received_since_advert >= advertised
+                            }
+                            Some(Err(e)) => {
+                                connected_for_task.store(false, Ordering::Relaxed);
+                                if let Some(change) = pending_change.take() {
+                                    let _ = change.reply.send(Err(WindError::Connection(
+                                        "Connection lost during mode change".to_string(),
+                                    )));
+                                }
+                                if matches!(current_mode, SubscriptionMode::Once) {
+                                    break;
+                                }
+                                warn!("Connection error: {}. Subscribing again...", e);
+
+                                let resubscribed = tokio::select! {
+                                    resubscribed = resubscriber.resubscribe(&current_mode, &current_qos) => resubscribed,
+                                    stop = &mut cancel_rx => {
+                                        // There's no publisher left to unsubscribe from
+                                        if let Ok(Stop::Close(reply)) = stop {
+                                            let _ = reply.send(Ok(()));
+                                        }
+                                        break;
+                                    }
+                                };
+                                let (connection, id, current_value) = match resubscribed {
+                                    Ok(resubscribed) => resubscribed,
+                                    Err(e) => {
+                                        error!("Giving up on subscription {}: {}", subscription_id, e);
+                                        break;
+                                    }
+                                };
+                                (writer, incoming_rx, reader_task) = match spawn_reader(connection) {
+                                    Ok(split) => split,
+                                    Err(e) => {
+                                        error!("Giving up on subscription {}: {}", subscription_id, e);
+                                        break;
+                                    }
+                                };
+                                remote_id = id;
+                                chunks = ChunkAssembler::default();
+                                if let Some(value) = current_value {
+                                    liveness_for_task.lock().unwrap().received(None);
+                                    forward(value);
+                                }
+                                connected_for_task.store(true, Ordering::Relaxed);
+                                info!("Subscribed to {} again", resubscriber.service_name);
+                                // The new publisher starts without credit
+                                true
+                            }
+                            None => break,
+                        }
+                    }
+                };
+
+                if refresh {
+                    match send_flow_control(&mut writer, &budget_for_task).await {
+                        Ok(window) => {
+                            advertised = window;
+                            received_since_advert = 0;
+                        }
+                        Err(e) => {
+                            error!("Failed to send flow control: {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+
+            reader_task.abort();
+            connected_for_task.store(false, Ordering::Relaxed);
+
+            // Cleanup subscription
+            let mut subs = subs_map.write().await;
+            subs.remove(&subscription_id);
+        });
+
+        Ok(Subscription {
+            id: subscription_id,
+            service_name: service_name.to_string(),
+            mode,
+            qos,
+            receiver: rx,
+            cancel_sender: cancel_tx,
+            task,
+            mode_changes: mode_tx,
+            rejected,
+            expired,
+            throttled,
+            budget,
+            liveness,
+            connected,
+        })
+    }
+
+    /// Subscribe to every publisher matching `pattern`, and to ones that
+    /// start matching later; see `PatternSubscription`
+    pub async fn subscribe_pattern(
+        &mut self,
+        pattern: &str,
+        mode: SubscriptionMode,
+        qos: QosParams,
+    ) -> Result<PatternSubscription> {
+        // Watching first means nothing registered or removed meanwhile is missed;
+        // the watch repeats the current matches, which are then skipped
+        let events = self.watch_services(pattern).await?;
+        let mut pattern_subscription = PatternSubscription {
+            pattern: pattern.to_string(),
+            mode,
+            qos,
+            merged: MergedSubscription::default(),
+            events: Some(events),
+            subscriber: self.detached(),
+        };
+        for service in self
+            .discover_by_type(pattern, ServiceType::Publisher)
+            .await?
+        {
+            pattern_subscription.join(service).await;
+        }
+        Ok(pattern_subscription)
+    }
+
+    /// Read a publisher's current value without establishing a subscription
+    pub async fn get_current(&mut self, service_name: &str) -> Result<Option<WindValue>> {
+        let service_info = self.discover_service(service_name).await?;
+        let request = Message::new(MessagePayload::GetCurrent {
+            service: service_name.to_string(),
+        });
+        request
+            .payload
+            .check_served_by(service_name, &service_info.service_type)?;
+
+        let mut service_connection = self.publisher_connection(service_info.address);
+        match service_connection.request(&request).await?.payload {
+            MessagePayload::CurrentValue { value, .. } => Ok(value),
+            MessagePayload::Error { error, .. } => Err(WindError::Protocol(error)),
+            _ => Err(WindError::Protocol(
+                "Expected CurrentValue message".to_string(),
+            )),
+        }
+    }
+
+    /// Read a diagnostic dump of the publisher of `service_name`; the
+    /// publisher must allow this client's id (`with_client_id`)
+    pub async fn inspect(&mut self, service_name: &str) -> Result<PublisherDiagnostics> {
+        let service_info = self.discover_service(service_name).await?;
+        let request = Message::new(MessagePayload::DiagnosticDump);
+        request
+            .payload
+            .check_served_by(service_name, &service_info.service_type)?;
+
+        let mut service_connection = self.publisher_connection(service_info.address);
+        match service_connection.request(&request).await?.payload {
+            MessagePayload::DiagnosticReport { diagnostics } => Ok(diagnostics),
+            MessagePayload::Error { error, .. } => Err(WindError::Protocol(error)),
+            _ => Err(WindError::Protocol(
+                "Expected DiagnosticReport message".to_string(),
+            )),
+        }
+    }
+
+    /// Discover a specific service by name
+    pub async fn discover_service(&mut self, service_name: &str) -> Result<ServiceInfo> {
+        self.discover_services(service_name) // Exact match
+            .await?
+            .into_iter()
+            .find(|s| s.name == service_name)
+            .ok_or_else(|| WindError::ServiceNotFound(service_name.to_string()))
+    }
+
+    /// Discover services matching a pattern, following pagination until all
+    /// matches have been fetched
+    pub async fn discover_services(&mut self, pattern: &str) -> Result<Vec<ServiceInfo>> {
+        Ok(self.discover_snapshot(pattern).await?.services)
+    }
+
+    /// Discover services matching a pattern, along with the registry version
+    /// they were read at, as a basis for caching the result
+    pub async fn discover_snapshot(&mut self, pattern: &str) -> Result<DiscoverySnapshot> {
+        let (services, registry_version) =
+            self.discover_services_filtered(pattern, None, None).await?;
+        Ok(DiscoverySnapshot {
+            pattern: pattern.to_string(),
+            services,
+            registry_version,
+        })
+    }
+
+    /// Whether no service has been registered or removed since `snapshot` was
+    /// taken, e.g. to decide whether to re-discover after a failed connect
+    pub async fn is_current(&mut self, snapshot: &DiscoverySnapshot) -> Result<bool> {
+        let request = Message::new(MessagePayload::DiscoverServices {
+            pattern: snapshot.pattern.clone(),
+            offset: 0,
+            limit: 1,
+            registered_since_us: None,
+            service_type: None,
+            max_hops: DEFAULT_MAX_HOPS,
+        });
+        match self.registry_connection.request(&request).await?.payload {
+            MessagePayload::ServicesDiscovered {
+                registry_version, ..
+            } => Ok(registry_version == snapshot.registry_version),
+            MessagePayload::Error { error, .. } => Err(WindError::Registry(error)),
+            _ => Err(WindError::Protocol("Unexpected response".to_string())),
+        }
+    }
+
+    /// Discover services matching a pattern that can serve `service_type`
+    /// requests, including services of type `Both`
+    pub async fn discover_by_type(
+        &mut self,
+        pattern: &str,
+        service_type: ServiceType,
+    ) -> Result<Vec<ServiceInfo>> {
+        Ok(self
+            .discover_services_filtered(pattern, None, Some(service_type))
+            .await?
+            .0)
+    }
+
+    /// Discover services matching a pattern that were first registered at or
+    /// after `since`
+    pub async fn discover_since(
+        &mut self,
+        pattern: &str,
+        since: SystemTime,
+    ) -> Result<Vec<ServiceInfo>> {
+        Ok(self
+            .discover_services_filtered(pattern, Some(unix_time_us(since)), None)
+            .await?
+            .0)
+    }
+
+    /// All pages of a discovery, with the registry version they were read
+    /// at. Pages read at different versions may overlap or miss a service, so
+    /// the discovery starts over when the version moves.
+    async fn discover_services_filtered(
+        &mut self,
+        pattern: &str,
+        registered_since_us: Option<u64>,
+        service_type: Option<ServiceType>,
+    ) -> Result<(Vec<ServiceInfo>, u64)> {
+        let mut services = Vec::new();
+        let mut offset = 0;
+        let mut version = None;
+        let mut restarts = 0;
+        loop {
+            let discover_msg = Message::new(MessagePayload::DiscoverServices {
+                pattern: pattern.to_string(),
+                offset,
+                limit: 0,
+                registered_since_us,
+                service_type: service_type.clone(),
+                max_hops: DEFAULT_MAX_HOPS,
+            });
+
+            match self
+                .registry_connection
+                .request(&discover_msg)
+                .await?
+                .payload
+            {
+                MessagePayload::ServicesDiscovered {
+                    services: page,
+                    next_offset,
+                    registry_version,
+                    ..
+                } => {
+                    if version.is_some_and(|version| version != registry_version)
+                        && restarts < MAX_DISCOVERY_RESTARTS
+                    {
+                        debug!(
+                            "Registry changed during discovery of {}, restarting",
+                            pattern
+                        );
+                        restarts += 1;
+                        services.clear();
+                        offset = 0;
+                        version = None;
+                        continue;
+                    }
+                    let read_at = *version.get_or_insert(registry_version);
+                    services.extend(page);
+                    match next_offset {
+                        Some(next) if next > offset => offset = next,
+                        Some(_) => {
+                            return Err(WindError::Protocol(
+                                "Registry returned a non-advancing discovery page".to_string(),
+                            ))
+                        }
+                        None => return Ok((services, read_at)),
+                    }
+                }
+                MessagePayload::Error { error, .. } => return Err(WindError::Registry(error)),
+                _ => return Err(WindError::Protocol("Unexpected response".to_string())),
+            }
+        }
+    }
+
+    /// Remove a service's registration immediately instead of waiting for
+    /// its TTL, e.g. after its provider crashed. With an `address`, only the
+    /// registration at that address is removed.
+    pub async fn force_deregister(
+        &mut self,
+        service_name: &str,
+        address: Option<&str>,
+    ) -> Result<()> {
+        let request = Message::new(MessagePayload::ForceDeregister {
+            service: service_name.to_string(),
+            address: address.map(str::to_string),
+        });
+        match self.registry_connection.request(&request).await?.payload {
+            MessagePayload::ServiceDeregistered { success: true, .. } => Ok(()),
+            MessagePayload::ServiceDeregistered { error, .. } => Err(WindError::Registry(
+                error.unwrap_or("Deregistration failed".to_string()),
+            )),
+            MessagePayload::Error { error, .. } => Err(WindError::Registry(error)),
+            _ => Err(WindError::Protocol("Unexpected response".to_string())),
+        }
+    }
+
+    /// Stream registrations and removals of services matching `pattern`,
+    /// starting with the ones already registered. Watches of one pattern
+    /// share a registry connection of their own, closed once the last of
+    /// their streams is dropped; they end when that connection does.
+    pub async fn watch_services(&self, pattern: &str) -> Result<BoxStream<'static, ServiceEvent>> {
+        let connection = Connection::new(self.registry_connection.address().to_string())
+            .with_client_id(self.client_id.clone())
+            .with_token(self.token.clone())
+            .with_socket_options(self.socket_options.clone());
+        self.watches.watch(pattern, connection).await
+    }
+
+    /// Get the number of active subscriptions
+    pub async fn subscription_count(&self) -> usize {
+        self.active_subscriptions.read().await.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpStream;
+    use tokio::time::Duration;
+    use wind_core::{MessageCodec, SerializationFormat};
+    use wind_registry::RegistryServer;
+    use wind_server::Publisher;
+
+    thread_local! {
+        /// Bytes allocated by the current thread
+        static ALLOCATED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    /// Counts allocations per thread, so a single-threaded test can measure
+    /// its own allocations while other tests run
+    struct CountingAllocator;
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let _ = ALLOCATED.try_with(|total| total.set(total.get() + layout.size()));
+            std::alloc::System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            std::alloc::System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(
+            &self,
+            ptr: *mut u8,
+            layout: std::alloc::Layout,
+            new_size: usize,
+        ) -> *mut u8 {
+            let _ = ALLOCATED.try_with(|total| total.set(total.get() + new_size));
+            std::alloc::System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAllocator = CountingAllocator;
+
+    fn allocated() -> usize {
+        ALLOCATED.with(|total| total.get())
+    }
+
+    async fn start_registry(addr: &str) {
+        let server = RegistryServer::new(addr.to_string());
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    async fn start_publisher(service: &str, registry_addr: &str) -> Arc<Publisher> {
+        let publisher = Arc::new(Publisher::new(
+            service.to_string(),
+            "127.0.0.1:0".to_string(),
+            registry_addr.to_string(),
+        ));
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        publisher
+    }
+
+    fn service(name: &str, port: u16) -> ServiceInfo {
+        ServiceInfo {
+            name: name.to_string(),
+            address: format!("127.0.0.1:{}", port),
+            service_type: ServiceType::Publisher,
+            schema_id: None,
+            ttl_ms: 60000,
+            tags: Vec::new(),
+            registered_at_us: 0,
+            last_heartbeat_us: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_discover_follows_pagination() {
+        let addr = "127.0.0.1:7201";
+        let server = RegistryServer::new(addr.to_string()).with_max_page_size(3);
+        let registry = server.registry();
+        for i in 0..10 {
+            let info = service(&format!("SENSOR/ROOM_{}/TEMP", i), 9000 + i);
+            registry.register_service(info, 60000).await.unwrap();
+        }
+        let other = service("DETECTOR/HALL_1/STATUS", 9100);
+        registry.register_service(other, 60000).await.unwrap();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // A single request only returns the first page
+        let mut raw = TcpStream::connect(addr).await.unwrap();
+        let request = Message::new(MessagePayload::DiscoverServices {
+            pattern: "SENSOR/*/TEMP".to_string(),
+            offset: 0,
+            limit: 0,
+            registered_since_us: None,
+            service_type: None,
+            max_hops: DEFAULT_MAX_HOPS,
+        });
+        MessageCodec::write(&mut raw, &request).await.unwrap();
+        match MessageCodec::decode(&mut raw).await.unwrap().payload {
+            MessagePayload::ServicesDiscovered {
+                services,
+                total,
+                next_offset,
+                ..
+            } => {
+                assert_eq!(services.len(), 3);
+                assert_eq!(total, 10);
+                assert_eq!(next_offset, Some(3));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        // The subscriber walks every page
+        let mut subscriber = Subscriber::new(addr.to_string());
+        let mut names: Vec<_> = subscriber
+            .discover_services("SENSOR/*/TEMP")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), 10);
+
+        let found = subscriber
+            .discover_service("SENSOR/ROOM_9/TEMP")
+            .await
+            .unwrap();
+        assert_eq!(found.address, "127.0.0.1:9009");
+    }
+
+    #[tokio::test]
+    async fn test_validator_drops_and_counts_rejected_values() {
+        let registry_addr = "127.0.0.1:7202";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/VALIDATED", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscribe_with_validator(
+                "TEST/VALIDATED",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+                |value| match value {
+                    WindValue::F64(v) if (0.0..=100.0).contains(v) => Ok(()),
+                    other => Err(format!("out of range: {:?}", other)),
+                },
+            )
+            .await
+            .unwrap();
+
+        for v in [50.0, 150.0, 75.0, -5.0, 20.0] {
+            publisher.publish(WindValue::F64(v)).await.unwrap();
+        }
+
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            let value = tokio::time::timeout(Duration::from_secs(2), subscription.next())
+                .await
+                .expect("Timeout waiting for value")
+                .expect("Subscription closed");
+            received.push(value);
+        }
+
+        assert_eq!(
+            received,
+            vec![
+                WindValue::F64(50.0),
+                WindValue::F64(75.0),
+                WindValue::F64(20.0)
+            ]
+        );
+        assert_eq!(subscription.rejected_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_queue_byte_cap_drops_and_accounts() {
+        let registry_addr = "127.0.0.1:7203";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/LARGE", registry_addr).await;
+
+        const PAYLOAD: usize = 100_000;
+        let qos = QosParams {
+            max_queue_bytes: Some(350_000),
+            ..QosParams::default()
+        };
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscribe("TEST/LARGE", SubscriptionMode::OnChange, qos)
+            .await
+            .unwrap();
+
+        // Values are counted at their encoded size, as for the oversize check
+        let size = WindValue::Bytes(vec![0; PAYLOAD]).encoded_size() as u64;
+        // Nothing is consumed while publishing, so only three payloads fit
+        for i in 0..10u8 {
+            publisher
+                .publish(WindValue::Bytes(vec![i; PAYLOAD]))
+                .await
+                .unwrap();
+        }
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while subscription.dropped_count() < 7 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Drops were not accounted");
+
+        assert_eq!(subscription.dropped_count(), 7);
+        assert_eq!(subscription.dropped_bytes(), 7 * size);
+        assert_eq!(subscription.buffered_bytes(), 3 * size);
+
+        for i in 0..3u8 {
+            let value = subscription.next().await.unwrap();
+            assert_eq!(value, WindValue::Bytes(vec![i; PAYLOAD]));
+        }
+        assert_eq!(subscription.buffered_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_get_current_without_subscribing() {
+        let registry_addr = "127.0.0.1:7204";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/CURRENT", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        assert_eq!(subscriber.get_current("TEST/CURRENT").await.unwrap(), None);
+
+        publisher.publish(WindValue::I32(42)).await.unwrap();
+        let value = subscriber.get_current("TEST/CURRENT").await.unwrap();
+        assert_eq!(value, Some(WindValue::I32(42)));
+
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while publisher.subscriber_count().await > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("One-off read left a client on the publisher");
+        assert_eq!(subscriber.subscription_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_flow_control_paces_slow_subscriber() {
+        let registry_addr = "127.0.0.1:7205";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/PACED", registry_addr).await;
+
+        // A slow subscriber whose window shrinks to zero as its small queue fills
+        let qos = QosParams {
+            max_queue_size: 5,
+            ..QosParams::default()
+        };
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut stalled = subscriber
+            .subscribe("TEST/PACED", SubscriptionMode::OnChange, qos)
+            .await
+            .unwrap();
+        let mut draining = subscriber
+            .subscribe(
+                "TEST/PACED",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let consumer = tokio::spawn(async move {
+            let mut received = Vec::new();
+            while received.len() < 50 {
+                match tokio::time::timeout(Duration::from_secs(5), draining.next()).await {
+                    Ok(Some(value)) => received.push(value),
+                    _ => break,
+                }
+            }
+            received
+        });
+
+        for i in 0..50 {
+            publisher.publish(WindValue::I32(i)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        // The other subscriber is unaffected and sees everything
+        let received = consumer.await.unwrap();
+        assert_eq!(received, (0..50).map(WindValue::I32).collect::<Vec<_>>());
+
+        // The stalled one is only sent what fits, so nothing is dropped on arrival
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while publisher.throttled_count() < 45 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Slow subscriber was not throttled");
+        assert_eq!(publisher.throttled_count(), 45);
+        assert_eq!(stalled.dropped_count(), 0);
+        for i in 0..5 {
+            assert_eq!(stalled.next().await, Some(WindValue::I32(i)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publisher_bounds_values_held_for_slow_subscribers() {
+        let registry_addr = "127.0.0.1:7245";
+        start_registry(registry_addr).await;
+        let best_effort = start_publisher("TEST/BOUNDED", registry_addr).await;
+        let reliable = start_publisher("TEST/BOUNDED_RELIABLE", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let qos = |max_queue_size, reliability| QosParams {
+            max_queue_size,
+            reliability,
+            ..QosParams::default()
+        };
+        let mut slow = subscriber
+            .subscribe(
+                "TEST/BOUNDED",
+                SubscriptionMode::OnChange,
+                qos(5, ReliabilityLevel::BestEffort),
+            )
+            .await
+            .unwrap();
+        let _stalled = subscriber
+            .subscribe(
+                "TEST/BOUNDED_RELIABLE",
+                SubscriptionMode::OnChange,
+                qos(2, ReliabilityLevel::Reliable),
+            )
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        for i in 0..20 {
+            best_effort.publish(WindValue::I32(i)).await.unwrap();
+            reliable.publish(WindValue::I32(i)).await.unwrap();
+        }
+
+        // Five fill the subscriber's queue and five more wait at the
+        // publisher; the ten in between are dropped, oldest first
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while best_effort.throttled_count() < 15 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Slow subscriber was not throttled");
+        assert_eq!(best_effort.dropped_count(), 10);
+        for i in (0..5).chain(15..20) {
+            let next = tokio::time::timeout(Duration::from_secs(2), slow.next()).await;
+            assert_eq!(next.unwrap(), Some(WindValue::I32(i)));
+        }
+        assert_eq!(slow.dropped_count(), 0);
+
+        // A reliable subscriber that falls behind is disconnected instead
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while reliable.subscriber_count().await > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Overflowing reliable subscriber stayed connected");
+        assert_eq!(reliable.dropped_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_only_durable_subscriptions_get_the_current_value() {
+        let registry_addr = "127.0.0.1:7246";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/DURABLE", registry_addr).await;
+        publisher.publish(WindValue::I32(1)).await.unwrap();
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut durable = subscriber
+            .subscription("TEST/DURABLE")
+            .durability(true)
+            .start()
+            .await
+            .unwrap();
+        let mut volatile = subscriber
+            .subscription("TEST/DURABLE")
+            .durability(false)
+            .start()
+            .await
+            .unwrap();
+
+        let first = tokio::time::timeout(Duration::from_millis(500), durable.next()).await;
+        assert_eq!(first.unwrap(), Some(WindValue::I32(1)));
+        let first = tokio::time::timeout(Duration::from_millis(300), volatile.next()).await;
+        assert!(first.is_err(), "Volatile subscription got {:?}", first);
+
+        // Both continue with the next publish, without repeating the first value
+        publisher.publish(WindValue::I32(2)).await.unwrap();
+        for subscription in [&mut durable, &mut volatile] {
+            let next = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+            assert_eq!(next.unwrap(), Some(WindValue::I32(2)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_periodic_subscription_ticks_without_new_publishes() {
+        let registry_addr = "127.0.0.1:7248";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/PERIODIC", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscribe(
+                "TEST/PERIODIC",
+                SubscriptionMode::Periodic { interval_ms: 100 },
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        publisher.publish(WindValue::I32(7)).await.unwrap();
+
+        // The one publish, then the same value again every interval
+        let mut received = Vec::new();
+        let window = tokio::time::sleep(Duration::from_millis(350));
+        tokio::pin!(window);
+        loop {
+            tokio::select! {
+                _ = &mut window => break,
+                value = subscription.next() => received.push(value),
+            }
+        }
+        assert!(
+            received.len() >= 3,
+            "Only {} values arrived",
+            received.len()
+        );
+        assert!(received
+            .iter()
+            .all(|value| *value == Some(WindValue::I32(7))));
+
+        // The timer stops with the subscription
+        drop(subscription);
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while publisher.subscriber_count().await > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Periodic subscriber stayed connected");
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_and_dropped_subscriptions_leave_the_publisher() {
+        let registry_addr = "127.0.0.1:7249";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/CANCEL", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscriptions = Vec::new();
+        for _ in 0..2 {
+            let subscription = subscriber
+                .subscribe(
+                    "TEST/CANCEL",
+                    SubscriptionMode::OnChange,
+                    QosParams::default(),
+                )
+                .await
+                .unwrap();
+            subscriptions.push(subscription);
+        }
+        let (dropped, cancelled) = (subscriptions.pop().unwrap(), subscriptions.pop().unwrap());
+        assert_eq!(publisher.subscriber_count().await, 2);
+
+        let count_drops_to = |expected| {
+            let publisher = publisher.clone();
+            async move {
+                tokio::time::timeout(Duration::from_secs(2), async {
+                    while publisher.subscriber_count().await > expected {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                })
+                .await
+                .is_ok()
+            }
+        };
+        cancelled.cancel();
+        assert!(
+            count_drops_to(1).await,
+            "Cancelled subscription still served"
+        );
+        drop(dropped);
+        assert!(count_drops_to(0).await, "Dropped subscription still served");
+    }
+
+    #[tokio::test]
+    async fn test_token_authenticates_to_registry_and_publisher() {
+        let registry_addr = "127.0.0.1:7258";
+        let server =
+            RegistryServer::new(registry_addr.to_string()).with_token("s3cret".to_string());
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let publisher = Arc::new(
+            Publisher::new(
+                "SENSOR/VAULT/TEMP".to_string(),
+                "127.0.0.1:0".to_string(),
+                registry_addr.to_string(),
+            )
+            .with_token("s3cret".to_string()),
+        );
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        publisher.publish(WindValue::I32(7)).await.unwrap();
+
+        let mut intruder =
+            Subscriber::new(registry_addr.to_string()).with_token("guess".to_string());
+        match intruder.discover_services("SENSOR/**").await {
+            Err(WindError::Unauthorized(error)) => assert_eq!(error, "Invalid token"),
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+
+        let mut subscriber =
+            Subscriber::new(registry_addr.to_string()).with_token("s3cret".to_string());
+        let service = subscriber
+            .discover_service("SENSOR/VAULT/TEMP")
+            .await
+            .unwrap();
+
+        // The publisher refuses a subscriber without the token, even one
+        // that found its address
+        let mut raw = TcpStream::connect(&service.address).await.unwrap();
+        let subscribe = Message::new(MessagePayload::Subscribe {
+            service: "SENSOR/VAULT/TEMP".to_string(),
+            mode: SubscriptionMode::OnChange,
+            qos: QosParams::default(),
+            schema_id: None,
+            replay: 0,
+        });
+        MessageCodec::write(&mut raw, &subscribe).await.unwrap();
+        assert!(matches!(
+            MessageCodec::decode(&mut raw).await.unwrap().payload,
+            MessagePayload::Error { .. }
+        ));
+        assert!(MessageCodec::decode(&mut raw).await.is_err());
+
+        let mut subscription = subscriber
+            .subscribe(
+                "SENSOR/VAULT/TEMP",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        let value = tokio::time::timeout(Duration::from_secs(2), subscription.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(value, WindValue::I32(7));
+    }
+
+    #[tokio::test]
+    async fn test_publisher_enforces_subscribe_acl() {
+        let registry_addr = "127.0.0.1:7206";
+        let acl = wind_registry::Acl::new()
+            .allow(
+                "CONTROL/**",
+                &["operator"],
+                &[
+                    wind_registry::AclOperation::Register,
+                    wind_registry::AclOperation::Subscribe,
+                ],
+            )
+            .unwrap();
+        let server = RegistryServer::new(registry_addr.to_string()).with_acl(acl);
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let publisher = Arc::new(
+            Publisher::new(
+                "CONTROL/VALVE_1".to_string(),
+                "127.0.0.1:0".to_string(),
+                registry_addr.to_string(),
+            )
+            .with_client_id("operator".to_string()),
+        );
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        publisher.publish(WindValue::Bool(true)).await.unwrap();
+
+        let mut guest =
+            Subscriber::new(registry_addr.to_string()).with_client_id("guest".to_string());
+        let denied = guest
+            .subscribe(
+                "CONTROL/VALVE_1",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await;
+        match denied {
+            Err(WindError::Protocol(error)) => assert!(error.contains("Permission denied")),
+            other => panic!("Expected a permission error, got {:?}", other.map(|s| s.id)),
+        }
+        assert!(guest.get_current("CONTROL/VALVE_1").await.is_err());
+
+        let mut operator =
+            Subscriber::new(registry_addr.to_string()).with_client_id("operator".to_string());
+        let mut subscription = operator
+            .subscribe(
+                "CONTROL/VALVE_1",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        let value = tokio::time::timeout(Duration::from_secs(2), subscription.next())
+            .await
+            .unwrap();
+        assert_eq!(value, Some(WindValue::Bool(true)));
+    }
+
+    #[tokio::test]
+    async fn test_staleness_grows_and_resets() {
+        let registry_addr = "127.0.0.1:7207";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/FRESHNESS", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscribe(
+                "TEST/FRESHNESS",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(subscription.last_sequence(), None);
+
+        publisher.publish(WindValue::I32(1)).await.unwrap();
+        subscription.next().await.unwrap();
+        assert_eq!(subscription.last_sequence(), Some(1));
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let stale = subscription.staleness();
+        assert!(stale >= Duration::from_millis(300));
+
+        publisher.publish(WindValue::I32(2)).await.unwrap();
+        subscription.next().await.unwrap();
+        assert!(subscription.staleness() < stale);
+        assert!(subscription.staleness() < Duration::from_millis(200));
+        assert_eq!(subscription.last_sequence(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_large_payload_chunked_and_reassembled() {
+        let registry_addr = "127.0.0.1:7208";
+        start_registry(registry_addr).await;
+        let publisher = Arc::new(
+            Publisher::new(
+                "TEST/FRAME".to_string(),
+                "127.0.0.1:0".to_string(),
+                registry_addr.to_string(),
+            )
+            .with_chunk_size(1024 * 1024),
+        );
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscribe(
+                "TEST/FRAME",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+
+        // A raw client sees the individual chunks on the wire
+        let address = subscriber
+            .discover_service("TEST/FRAME")
+            .await
+            .unwrap()
+            .address;
+        let mut raw = TcpStream::connect(address).await.unwrap();
+        let subscribe = Message::new(MessagePayload::Subscribe {
+            service: "TEST/FRAME".to_string(),
+            mode: SubscriptionMode::OnChange,
+            qos: QosParams::default(),
+            schema_id: None,
+            replay: 0,
+        });
+        MessageCodec::write(&mut raw, &subscribe).await.unwrap();
+        MessageCodec::decode(&mut raw).await.unwrap();
+
+        let frame: Vec<u8> = (0..5 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        publisher
+            .publish(WindValue::Bytes(frame.clone()))
+            .await
+            .unwrap();
+
+        let mut chunks = Vec::new();
+        loop {
+            match MessageCodec::decode(&mut raw).await.unwrap().payload {
+                MessagePayload::PublishChunk {
+                    chunk_index, last, ..
+                } => {
+                    chunks.push(chunk_index);
+                    if last {
+                        break;
+                    }
+                }
+                other => panic!("Expected a chunk, got {:?}", other),
+            }
+        }
+        assert_eq!(chunks, vec![0, 1, 2, 3, 4]);
+
+        let value = tokio::time::timeout(Duration::from_secs(5), subscription.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            value == WindValue::Bytes(frame),
+            "Reassembled payload differs"
+        );
+    }
+
+    #[test]
+    fn test_chunk_assembler_discards_incomplete_payloads() {
+        let mut chunks = ChunkAssembler::default();
+        assert_eq!(chunks.push(1, 0, false, vec![1, 2]), None);
+        assert_eq!(chunks.push(1, 1, true, vec![3]), Some(vec![1, 2, 3]));
+
+        // Chunk 1 of sequence 2 went missing
+        assert_eq!(chunks.push(2, 0, false, vec![1]), None);
+        assert_eq!(chunks.push(2, 2, true, vec![3]), None);
+
+        // The next payload starts cleanly
+        assert_eq!(chunks.push(3, 0, true, vec![9]), Some(vec![9]));
+    }
+
+    #[tokio::test]
+    async fn test_discover_since_returns_recent_registrations() {
+        let addr = "127.0.0.1:7209";
+        let server = RegistryServer::new(addr.to_string());
+        let registry = server.registry();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+
+        for i in 0..3 {
+            let info = service(&format!("SENSOR/OLD_{}/TEMP", i), 9000 + i);
+            registry.register_service(info, 60000).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let since = SystemTime::now();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        for i in 0..2 {
+            let info = service(&format!("SENSOR/NEW_{}/TEMP", i), 9100 + i);
+            registry.register_service(info, 60000).await.unwrap();
+        }
+        // A heartbeat re-registration doesn't make an old service look new
+        let renewed = service("SENSOR/OLD_0/TEMP", 9000);
+        registry.register_service(renewed, 60000).await.unwrap();
+
+        let mut client = crate::WindClient::new(addr.to_string());
+        let mut recent: Vec<_> = client
+            .discover_since("SENSOR/*/TEMP", since)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        recent.sort();
+        assert_eq!(recent, vec!["SENSOR/NEW_0/TEMP", "SENSOR/NEW_1/TEMP"]);
+
+        let all = client.discover("SENSOR/*/TEMP").await.unwrap();
+        assert_eq!(all.len(), 5);
+        let old = all.iter().find(|s| s.name == "SENSOR/OLD_0/TEMP").unwrap();
+        assert!(old.registered_at_us < unix_time_us(since));
+        assert!(old.last_heartbeat_us > unix_time_us(since));
+    }
+
+    #[tokio::test]
+    async fn test_publish_confirmed_reports_per_subscriber() {
+        let registry_addr = "127.0.0.1:7213";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/CONFIRMED", registry_addr).await;
+        let reliable = QosParams {
+            reliability: wind_core::ReliabilityLevel::Reliable,
+            ..QosParams::default()
+        };
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscribe(
+                "TEST/CONFIRMED",
+                SubscriptionMode::OnChange,
+                reliable.clone(),
+            )
+            .await
+            .unwrap();
+
+        // A reliable subscriber that goes away without acknowledging
+        let address = subscriber
+            .discover_service("TEST/CONFIRMED")
+            .await
+            .unwrap()
+            .address;
+        let mut raw = TcpStream::connect(address).await.unwrap();
+        let subscribe = Message::new(MessagePayload::Subscribe {
+            service: "TEST/CONFIRMED".to_string(),
+            mode: SubscriptionMode::OnChange,
+            qos: reliable,
+            schema_id: None,
+            replay: 0,
+        });
+        MessageCodec::write(&mut raw, &subscribe).await.unwrap();
+        MessageCodec::decode(&mut raw).await.unwrap();
+        let crashing = tokio::spawn(async move {
+            let received = MessageCodec::decode(&mut raw).await.unwrap();
+            assert!(matches!(received.payload, MessagePayload::Publish { .. }));
+        });
+
+        let report = tokio::time::timeout(
+            Duration::from_secs(2),
+            publisher.publish_confirmed(WindValue::I32(7)),
+        )
+        .await
+        .unwrap();
+        crashing.await.unwrap();
+
+        assert_eq!(report.sequence, 1);
+        assert_eq!(report.delivered, 1);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.throttled, 0);
+        assert_eq!(subscription.next().await, Some(WindValue::I32(7)));
+    }
+
+    #[tokio::test]
+    async fn test_late_subscriber_gets_current_value_once() {
+        let registry_addr = "127.0.0.1:7215";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/LATE", registry_addr).await;
+        publisher.publish(WindValue::I32(5)).await.unwrap();
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut once = subscriber
+            .subscribe("TEST/LATE", SubscriptionMode::Once, QosParams::default())
+            .await
+            .unwrap();
+        let mut on_change = subscriber
+            .subscribe(
+                "TEST/LATE",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        let mut periodic = subscriber
+            .subscribe(
+                "TEST/LATE",
+                SubscriptionMode::Periodic {
+                    interval_ms: 60_000,
+                },
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+
+        let prompt = Duration::from_millis(200);
+        for subscription in [&mut once, &mut on_change, &mut periodic] {
+            let value = tokio::time::timeout(prompt, subscription.next()).await;
+            assert_eq!(value.unwrap(), Some(WindValue::I32(5)));
+        }
+
+        // The value delivered on subscribe counts as sent: republishing it
+        // doesn't reach the on-change subscriber again, and Once is done
+        publisher.publish(WindValue::I32(5)).await.unwrap();
+        publisher.publish(WindValue::I32(6)).await.unwrap();
+        let value = tokio::time::timeout(prompt, on_change.next()).await;
+        assert_eq!(value.unwrap(), Some(WindValue::I32(6)));
+        let value = tokio::time::timeout(prompt, once.next()).await;
+        assert!(!matches!(value, Ok(Some(_))), "{:?}", value);
+    }
+
+    #[tokio::test]
+    async fn test_close_waits_for_publisher_to_drop_subscription() {
+        let registry_addr = "127.0.0.1:7216";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/CLOSE", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let subscription = subscriber
+            .subscribe(
+                "TEST/CLOSE",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(publisher.subscriber_count().await, 1);
+
+        subscription.close().await.unwrap();
+        assert_eq!(publisher.subscriber_count().await, 0);
+        assert!(subscriber.active_subscriptions.read().await.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pub_sub_over_unix_sockets() {
+        let dir = std::env::temp_dir();
+        let socket = |name: &str| {
+            let path = dir.join(format!("wind-{}-{}.sock", std::process::id(), name));
+            format!("unix:{}", path.display())
+        };
+        let registry_addr = socket("registry");
+        start_registry(&registry_addr).await;
+
+        let publisher = Arc::new(Publisher::new(
+            "TEST/UNIX".to_string(),
+            socket("publisher"),
+            registry_addr.clone(),
+        ));
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut subscriber = Subscriber::new(registry_addr);
+        assert_eq!(
+            subscriber
+                .discover_service("TEST/UNIX")
+                .await
+                .unwrap()
+                .address,
+            socket("publisher")
+        );
+        let mut subscription = subscriber
+            .subscribe(
+                "TEST/UNIX",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+
+        publisher.publish(WindValue::I32(42)).await.unwrap();
+        let value = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+        assert_eq!(value.unwrap(), Some(WindValue::I32(42)));
+    }
+
+    #[tokio::test]
+    async fn test_multi_topic_publisher_routes_by_service() {
+        let registry_addr = "127.0.0.1:7217";
+        start_registry(registry_addr).await;
+        let publisher = Arc::new(Publisher::new_multi(
+            vec![
+                "SENSOR/ROOM_A/TEMP".to_string(),
+                "SENSOR/ROOM_A/HUMIDITY".to_string(),
+            ],
+            "127.0.0.1:0".to_string(),
+            registry_addr.to_string(),
+        ));
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let temp = subscriber.discover_service("SENSOR/ROOM_A/TEMP").await;
+        let humidity = subscriber.discover_service("SENSOR/ROOM_A/HUMIDITY").await;
+        assert_eq!(temp.unwrap().address, humidity.unwrap().address);
+
+        let mut temp = subscriber
+            .subscribe(
+                "SENSOR/ROOM_A/TEMP",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        let mut humidity = subscriber
+            .subscribe(
+                "SENSOR/ROOM_A/HUMIDITY",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+
+        publisher
+            .publish_to("SENSOR/ROOM_A/TEMP", WindValue::F64(21.5))
+            .await
+            .unwrap();
+        publisher
+            .publish_to("SENSOR/ROOM_A/HUMIDITY", WindValue::F64(40.0))
+            .await
+            .unwrap();
+        assert!(publisher
+            .publish_to("SENSOR/ROOM_B/TEMP", WindValue::F64(0.0))
+            .await
+            .is_err());
+
+        let within = Duration::from_secs(2);
+        let value = tokio::time::timeout(within, temp.next()).await.unwrap();
+        assert_eq!(value, Some(WindValue::F64(21.5)));
+        let value = tokio::time::timeout(within, humidity.next()).await.unwrap();
+        assert_eq!(value, Some(WindValue::F64(40.0)));
+        assert_eq!(publisher.current_value().await, Some(WindValue::F64(21.5)));
+        assert_eq!(
+            publisher.current_value_of("SENSOR/ROOM_A/HUMIDITY").await,
+            Some(WindValue::F64(40.0))
+        );
+
+        // Each subscription only sees its own service
+        let stray = tokio::time::timeout(Duration::from_millis(200), temp.next()).await;
+        assert!(stray.is_err(), "{:?}", stray);
+    }
+
+    #[tokio::test]
+    async fn test_one_connection_subscribes_to_services_in_turn() {
+        let registry_addr = "127.0.0.1:7247";
+        start_registry(registry_addr).await;
+        let publisher = Arc::new(Publisher::new_multi(
+            vec!["TEST/FIRST".to_string(), "TEST/SECOND".to_string()],
+            "127.0.0.1:0".to_string(),
+            registry_addr.to_string(),
+        ));
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let address = subscriber
+            .discover_service("TEST/FIRST")
+            .await
+            .unwrap()
+            .address;
+        let mut raw = TcpStream::connect(address).await.unwrap();
+        let subscribe = |service: &str| {
+            Message::new(MessagePayload::Subscribe {
+                service: service.to_string(),
+                mode: SubscriptionMode::OnChange,
+                qos: QosParams::default(),
+                schema_id: None,
+                replay: 0,
+            })
+        };
+        let within = Duration::from_secs(2);
+
+        MessageCodec::write(&mut raw, &subscribe("TEST/FIRST"))
+            .await
+            .unwrap();
+        let ack = MessageCodec::decode(&mut raw).await.unwrap().payload;
+        assert!(matches!(
+            ack,
+            MessagePayload::SubscribeAck { success: true, .. }
+        ));
+        publisher
+            .publish_to("TEST/FIRST", WindValue::I32(1))
+            .await
+            .unwrap();
+        let update = tokio::time::timeout(within, MessageCodec::decode(&mut raw)).await;
+        match update.unwrap().unwrap().payload {
+            MessagePayload::Publish { service, value, .. } => {
+                assert_eq!(service, "TEST/FIRST");
+                assert_eq!(*value, WindValue::I32(1));
+            }
+            other => panic!("Unexpected message: {:?}", other),
+        }
+
+        // The connection keeps being read after the first exchange
+        MessageCodec::write(&mut raw, &Message::new(MessagePayload::Ping))
+            .await
+            .unwrap();
+        let pong = tokio::time::timeout(within, MessageCodec::decode(&mut raw)).await;
+        assert!(matches!(
+            pong.unwrap().unwrap().payload,
+            MessagePayload::Pong
+        ));
+        MessageCodec::write(&mut raw, &subscribe("TEST/SECOND"))
+            .await
+            .unwrap();
+        let ack = tokio::time::timeout(within, MessageCodec::decode(&mut raw)).await;
+        assert!(matches!(
+            ack.unwrap().unwrap().payload,
+            MessagePayload::SubscribeAck { success: true, .. }
+        ));
+
+        publisher
+            .publish_to("TEST/SECOND", WindValue::I32(2))
+            .await
+            .unwrap();
+        publisher
+            .publish_to("TEST/FIRST", WindValue::I32(3))
+            .await
+            .unwrap();
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            let update = tokio::time::timeout(within, MessageCodec::decode(&mut raw)).await;
+            match update.unwrap().unwrap().payload {
+                MessagePayload::Publish { service, value, .. } => {
+                    received.push((service, Arc::unwrap_or_clone(value)))
+                }
+                other => panic!("Unexpected message: {:?}", other),
+            }
+        }
+        assert_eq!(
+            received,
+            [
+                ("TEST/SECOND".to_string(), WindValue::I32(2)),
+                ("TEST/FIRST".to_string(), WindValue::I32(3)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_queued_updates_are_batched_into_fewer_writes() {
+        let registry_addr = "127.0.0.1:7214";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/BATCHED", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscribe(
+                "TEST/BATCHED",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let writes_before = publisher.traffic().writes();
+
+        // Published back to back, the updates pile up before the sender runs
+        for v in 0..200 {
+            publisher.publish(WindValue::I32(v)).await.unwrap();
+        }
+        for v in 0..200 {
+            let next = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+            assert_eq!(next.unwrap(), Some(WindValue::I32(v)));
+        }
+        assert_eq!(subscription.last_sequence(), Some(200));
+
+        let writes = publisher.traffic().writes() - writes_before;
+        assert!(writes < 20, "{} writes for 200 updates", writes);
+
+        // A lone update is still sent on its own, without waiting for company
+        publisher.publish(WindValue::I32(-1)).await.unwrap();
+        let next = tokio::time::timeout(Duration::from_millis(500), subscription.next()).await;
+        assert_eq!(next.unwrap(), Some(WindValue::I32(-1)));
+    }
+
+    #[tokio::test]
+    async fn test_set_mode_switches_live_subscription() {
+        let registry_addr = "127.0.0.1:7211";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/MODE", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscribe(
+                "TEST/MODE",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+
+        async fn publish_burst(publisher: &Publisher, from: i32) {
+            for v in from..from + 20 {
+                publisher.publish(WindValue::I32(v)).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(30)).await;
+            }
+        }
+        async fn drain(subscription: &mut Subscription) -> usize {
+            let mut received = 0;
+            while let Ok(Some(_)) =
+                tokio::time::timeout(Duration::from_millis(200), subscription.next()).await
+            {
+                received += 1;
+            }
+            received
+        }
+
+        // On change: every distinct value is delivered
+        publish_burst(&publisher, 0).await;
+        assert_eq!(drain(&mut subscription).await, 20);
+
+        // Periodic: the same burst is thinned to one value per interval
+        subscription
+            .set_mode(SubscriptionMode::Periodic { interval_ms: 250 })
+            .await
+            .unwrap();
+        assert!(matches!(
+            subscription.mode,
+            SubscriptionMode::Periodic { interval_ms: 250 }
+        ));
+        publish_burst(&publisher, 100).await;
+        let periodic = drain(&mut subscription).await;
+        assert!((1..=4).contains(&periodic), "received {}", periodic);
+
+        // A live subscription can't go back to Once
+        let error = subscription
+            .set_mode(SubscriptionMode::Once)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Once"), "{}", error);
+        assert!(matches!(
+            subscription.mode,
+            SubscriptionMode::Periodic { .. }
+        ));
+
+        // Still the same connection, delivering once the interval has passed;
+        // meanwhile the last value is re-sent every interval
+        assert_eq!(publisher.subscriber_count().await, 1);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        publisher.publish(WindValue::I32(-1)).await.unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                match subscription.next().await {
+                    Some(WindValue::I32(v)) if v >= 100 => continue,
+                    other => return other,
+                }
+            }
+        })
+        .await;
+        assert_eq!(next.unwrap(), Some(WindValue::I32(-1)));
+    }
+
+    #[tokio::test]
+    async fn test_modify_changes_only_the_named_subscription() {
+        let registry_addr = "127.0.0.1:7263";
+        start_registry(registry_addr).await;
+        let publisher = Arc::new(
+            Publisher::new_multi(
+                vec!["TEST/LEFT".to_string(), "TEST/RIGHT".to_string()],
+                "127.0.0.1:0".to_string(),
+                registry_addr.to_string(),
+            )
+            .with_diagnostic_access(vec!["operator".to_string()]),
+        );
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut operator =
+            Subscriber::new(registry_addr.to_string()).with_client_id("operator".to_string());
+        let address = operator
+            .discover_service("TEST/LEFT")
+            .await
+            .unwrap()
+            .address;
+        let mut raw = TcpStream::connect(address).await.unwrap();
+        let mut subscription_ids = Vec::new();
+        for service in ["TEST/LEFT", "TEST/RIGHT"] {
+            let subscribe = Message::new(MessagePayload::Subscribe {
+                service: service.to_string(),
+                mode: SubscriptionMode::OnChange,
+                qos: QosParams::default(),
+                schema_id: None,
+                replay: 0,
+            });
+            MessageCodec::write(&mut raw, &subscribe).await.unwrap();
+            match MessageCodec::decode(&mut raw).await.unwrap().payload {
+                MessagePayload::SubscribeAck {
+                    success: true,
+                    subscription_id,
+                    ..
+                } => subscription_ids.push(subscription_id),
+                other => panic!("Unexpected message: {:?}", other),
+            }
+        }
+        assert_ne!(subscription_ids[0], subscription_ids[1]);
+
+        let modify = |subscription_id| {
+            Message::new(MessagePayload::ModifySubscription {
+                subscription_id,
+                mode: SubscriptionMode::Periodic { interval_ms: 250 },
+                qos: QosParams {
+                    reliability: ReliabilityLevel::Reliable,
+                    ..QosParams::default()
+                },
+            })
+        };
+        MessageCodec::write(&mut raw, &modify(subscription_ids[0]))
+            .await
+            .unwrap();
+        let ack = MessageCodec::decode(&mut raw).await.unwrap().payload;
+        assert!(
+            matches!(
+                ack,
+                MessagePayload::SubscribeAck { success: true, subscription_id, .. }
+                    if subscription_id == subscription_ids[0]
+            ),
+            "{:?}",
+            ack
+        );
+        MessageCodec::write(&mut raw, &modify(Uuid::new_v4()))
+            .await
+            .unwrap();
+        let ack = MessageCodec::decode(&mut raw).await.unwrap().payload;
+        assert!(
+            matches!(ack, MessagePayload::SubscribeAck { success: false, .. }),
+            "{:?}",
+            ack
+        );
+
+        // Only the named subscription took the new mode and QoS
+        let diagnostics = operator.inspect("TEST/LEFT").await.unwrap();
+        assert_eq!(diagnostics.clients.len(), 1);
+        let subscriptions = &diagnostics.clients[0].subscriptions;
+        assert!(
+            matches!(
+                &subscriptions[..],
+                [left, right]
+                    if left.service == "TEST/LEFT"
+                        && matches!(left.mode, SubscriptionMode::Periodic { interval_ms: 250 })
+                        && left.reliable
+                        && right.service == "TEST/RIGHT"
+                        && matches!(right.mode, SubscriptionMode::OnChange)
+                        && !right.reliable
+            ),
+            "{:?}",
+            subscriptions
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_keeps_the_connection_other_subscriptions_use() {
+        let registry_addr = "127.0.0.1:7264";
+        start_registry(registry_addr).await;
+        let publisher = Arc::new(Publisher::new_multi(
+            vec!["TEST/LEFT".to_string(), "TEST/RIGHT".to_string()],
+            "127.0.0.1:0".to_string(),
+            registry_addr.to_string(),
+        ));
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let address = subscriber
+            .discover_service("TEST/LEFT")
+            .await
+            .unwrap()
+            .address;
+        let mut raw = TcpStream::connect(address).await.unwrap();
+        let mut subscription_ids = Vec::new();
+        for service in ["TEST/LEFT", "TEST/RIGHT"] {
+            let subscribe = Message::new(MessagePayload::Subscribe {
+                service: service.to_string(),
+                mode: SubscriptionMode::OnChange,
+                qos: QosParams::default(),
+                schema_id: None,
+                replay: 0,
+            });
+            MessageCodec::write(&mut raw, &subscribe).await.unwrap();
+            match MessageCodec::decode(&mut raw).await.unwrap().payload {
+                MessagePayload::SubscribeAck {
+                    success: true,
+                    subscription_id,
+                    ..
+                } => subscription_ids.push(subscription_id),
+                other => panic!("Unexpected message: {:?}", other),
+            }
+        }
+
+        let unsubscribe = Message::new(MessagePayload::Unsubscribe {
+            subscription_id: subscription_ids[0],
+        });
+        MessageCodec::write(&mut raw, &unsubscribe).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(publisher.subscriber_count().await, 1);
+
+        // Only the remaining subscription is sent updates
+        publisher
+            .publish_to("TEST/LEFT", WindValue::I64(1))
+            .await
+            .unwrap();
+        publisher
+            .publish_to("TEST/RIGHT", WindValue::I64(2))
+            .await
+            .unwrap();
+        let update = tokio::time::timeout(Duration::from_secs(2), MessageCodec::decode(&mut raw))
+            .await
+            .unwrap()
+            .unwrap();
+        match update.payload {
+            MessagePayload::Publish { service, value, .. } => {
+                assert_eq!(service, "TEST/RIGHT");
+                assert_eq!(*value, WindValue::I64(2));
+            }
+            other => panic!("Unexpected message: {:?}", other),
+        }
+
+        // Dropping the last subscription closes the connection
+        let unsubscribe = Message::new(MessagePayload::Unsubscribe {
+            subscription_id: subscription_ids[1],
+        });
+        MessageCodec::write(&mut raw, &unsubscribe).await.unwrap();
+        let closed = tokio::time::timeout(Duration::from_secs(2), MessageCodec::decode(&mut raw))
+            .await
+            .unwrap();
+        assert!(closed.is_err());
+        assert_eq!(publisher.subscriber_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_publisher_writes_to_each_client_in_its_format() {
+        let registry_addr = "127.0.0.1:7265";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/FORMATS", registry_addr).await;
+
+        // A bincode subscriber alongside the JSON one gets the same values
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscribe(
+                "TEST/FORMATS",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        let address = subscriber
+            .discover_service("TEST/FORMATS")
+            .await
+            .unwrap()
+            .address;
+        let mut raw = TcpStream::connect(address).await.unwrap();
+        let subscribe = Message::new(MessagePayload::Subscribe {
+            service: "TEST/FORMATS".to_string(),
+            mode: SubscriptionMode::OnChange,
+            qos: QosParams::default(),
+            schema_id: None,
+            replay: 0,
+        });
+        MessageCodec::default()
+            .with_format(SerializationFormat::Json)
+            .write_message(&mut raw, &subscribe)
+            .await
+            .unwrap();
+        let (ack, format) = MessageCodec::decode_with_format(&mut raw).await.unwrap();
+        assert!(matches!(
+            ack.payload,
+            MessagePayload::SubscribeAck { success: true, .. }
+        ));
+        assert_eq!(format, SerializationFormat::Json);
+
+        publisher.publish(WindValue::I64(1)).await.unwrap();
+        publisher
+            .publish_batch(vec![WindValue::I64(2), WindValue::I64(3)])
+            .await
+            .unwrap();
+        let mut received = Vec::new();
+        while received.len() < 3 {
+            let (update, format) = tokio::time::timeout(
+                Duration::from_secs(2),
+                MessageCodec::decode_with_format(&mut raw),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+            assert_eq!(format, SerializationFormat::Json);
+            match update.payload {
+                MessagePayload::Publish { value, .. } => received.push((*value).clone()),
+                MessagePayload::PublishBatch { values, .. } => received.extend(values),
+                other => panic!("Unexpected message: {:?}", other),
+            }
+        }
+        assert_eq!(
+            received,
+            vec![WindValue::I64(1), WindValue::I64(2), WindValue::I64(3)]
+        );
+        for expected in 1..=3 {
+            let next = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+            assert_eq!(next.unwrap(), Some(WindValue::I64(expected)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_discover_filters_by_service_type() {
+        let addr = "127.0.0.1:7218";
+        let server = RegistryServer::new(addr.to_string());
+        let registry = server.registry();
+        for (name, service_type) in [
+            ("TYPED/PUB", ServiceType::Publisher),
+            ("TYPED/RPC", ServiceType::RpcServer),
+            ("TYPED/BOTH", ServiceType::Both),
+        ] {
+            let info = ServiceInfo {
+                service_type,
+                ..service(name, 9200)
+            };
+            registry.register_service(info, 60000).await.unwrap();
+        }
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let names = |services: Vec<ServiceInfo>| {
+            let mut names: Vec<_> = services.into_iter().map(|s| s.name).collect();
+            names.sort();
+            names
+        };
+        let mut client = crate::WindClient::new(addr.to_string());
+        let rpc = client.discover_rpc("TYPED/*").await.unwrap();
+        assert_eq!(names(rpc), vec!["TYPED/BOTH", "TYPED/RPC"]);
+        let publishers = client.discover_publishers("TYPED/*").await.unwrap();
+        assert_eq!(names(publishers), vec!["TYPED/BOTH", "TYPED/PUB"]);
+        assert_eq!(client.discover("TYPED/*").await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_to_rpc_service_fails_before_connecting() {
+        let addr = "127.0.0.1:7219";
+        let server = RegistryServer::new(addr.to_string());
+        // Nothing listens at the advertised address, so only an early
+        // type check can produce a protocol error
+        let info = ServiceInfo {
+            service_type: ServiceType::RpcServer,
+            ..service("TYPED/RPC_ONLY", 1)
+        };
+        server
+            .registry()
+            .register_service(info, 60000)
+            .await
+            .unwrap();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut subscriber = Subscriber::new(addr.to_string());
+        let result = subscriber
+            .subscribe(
+                "TYPED/RPC_ONLY",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await;
+        match result {
+            Err(WindError::Protocol(error)) => {
+                assert!(error.contains("Subscribe"), "{}", error);
+                assert!(error.contains("RpcServer"), "{}", error);
+            }
+            other => panic!("Expected a protocol error, got {:?}", other.map(|s| s.id)),
+        }
+        assert!(matches!(
+            subscriber.get_current("TYPED/RPC_ONLY").await,
+            Err(WindError::Protocol(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_large_publish_is_not_copied_per_subscriber() {
+        const SUBSCRIBERS: usize = 4;
+        const PAYLOAD: usize = 512 * 1024;
+        let registry_addr = "127.0.0.1:7220";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/LARGE", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let address = subscriber
+            .discover_service("TEST/LARGE")
+            .await
+            .unwrap()
+            .address;
+        let mut raws = Vec::new();
+        for _ in 0..SUBSCRIBERS {
+            let mut raw = TcpStream::connect(&address).await.unwrap();
+            let subscribe = Message::new(MessagePayload::Subscribe {
+                service: "TEST/LARGE".to_string(),
+                mode: SubscriptionMode::OnChange,
+                qos: QosParams::default(),
+                schema_id: None,
+                replay: 0,
+            });
+            MessageCodec::write(&mut raw, &subscribe).await.unwrap();
+            MessageCodec::decode(&mut raw).await.unwrap();
+            raws.push(raw);
+        }
+
+        // Frames are read into buffers allocated up front, so everything
+        // counted below is allocated by the publisher
+        let payload: Vec<u8> = (0..PAYLOAD).map(|i| (i % 251) as u8).collect();
+        let mut frames = vec![vec![0u8; PAYLOAD + 1024]; SUBSCRIBERS];
+        let value = WindValue::Bytes(payload.clone());
+        let before = allocated();
+        publisher.publish(value).await.unwrap();
+        for (raw, frame) in raws.iter_mut().zip(&mut frames) {
+            let len = raw.read_u32().await.unwrap() as usize;
+            frame[..4].copy_from_slice(&(len as u32).to_be_bytes());
+            raw.read_exact(&mut frame[4..4 + len]).await.unwrap();
+        }
+        let publisher_allocated = allocated() - before;
+
+        for frame in &frames {
+            match MessageCodec::decode(&mut frame.as_slice())
+                .await
+                .unwrap()
+                .payload
+            {
+                MessagePayload::Publish { value, .. } => {
+                    assert_eq!(*value, WindValue::Bytes(payload.clone()))
+                }
+                other => panic!("Expected a publish, got {:?}", other),
+            }
+        }
+        // One encoded frame per subscriber; the value itself is shared by the
+        // current value, the broadcast and every subscription
+        assert!(
+            publisher_allocated < (SUBSCRIBERS + 1) * PAYLOAD,
+            "{} bytes allocated to publish {} bytes to {} subscribers",
+            publisher_allocated,
+            PAYLOAD,
+            SUBSCRIBERS
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscription_builder_applies_options() {
+        let registry_addr = "127.0.0.1:7224";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/BUILDER", registry_addr).await;
+
+        let mut client = crate::WindClient::new(registry_addr.to_string());
+        let mut subscription = client
+            .subscription("TEST/BUILDER")
+            .mode(SubscriptionMode::OnChange)
+            .max_queue_size(16)
+            .reliable()
+            .validator(|value| match value {
+                WindValue::I32(n) if *n < 0 => Err("negative".to_string()),
+                _ => Ok(()),
+            })
+            .filter(|value| !matches!(value, WindValue::I32(n) if n % 2 != 0))
+            .typed::<i32>()
+            .start()
+            .await
+            .unwrap();
+        assert_eq!(subscription.inner().qos.max_queue_size, 16);
+        assert!(matches!(
+            subscription.inner().qos.reliability,
+            ReliabilityLevel::Reliable
+        ));
+
+        for value in [-2, 1, 2] {
+            publisher.publish(WindValue::I32(value)).await.unwrap();
+        }
+        publisher
+            .publish(WindValue::String("four".to_string()))
+            .await
+            .unwrap();
+
+        // -2 is rejected by the validator, 1 filtered out, and the string
+        // passes both but doesn't decode as an i32
+        let timeout = Duration::from_secs(2);
+        let next = tokio::time::timeout(timeout, subscription.next()).await;
+        assert_eq!(next.unwrap().unwrap().unwrap(), 2);
+        let next = tokio::time::timeout(timeout, subscription.next()).await;
+        assert!(next.unwrap().unwrap().is_err());
+        assert_eq!(subscription.inner().rejected_count(), 1);
+    }
+
+    fn published_values(messages: &[wind_core::RecordedMessage]) -> Vec<(u64, WindValue)> {
+        messages
+            .iter()
+            .filter_map(|recorded| match &recorded.message.payload {
+                MessagePayload::Publish { value, .. } => {
+                    Some((recorded.offset_us, (**value).clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_recorded_session_replays_against_fresh_publisher() {
+        let registry_addr = "127.0.0.1:7227";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/REPLAY", registry_addr).await;
+
+        // Record a subscriber's session with the publisher
+        let recorder = wind_core::SessionRecorder::new();
+        let mut subscriber =
+            Subscriber::new(registry_addr.to_string()).with_recorder(recorder.clone());
+        let mut subscription = subscriber
+            .subscribe(
+                "TEST/REPLAY",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        // The recorded gap lets the replayed subscribe land before the
+        // first replayed publish
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        for value in [3, 1, 4, 1, 5] {
+            publisher.publish(WindValue::I32(value)).await.unwrap();
+            let next = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+            assert_eq!(next.unwrap(), Some(WindValue::I32(value)));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        subscription.close().await.unwrap();
+
+        let path = std::env::temp_dir().join(format!("wind-replay-{}.bin", std::process::id()));
+        recorder.save(&path).unwrap();
+        let recording = wind_core::SessionRecorder::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let recorded = published_values(&recording);
+        assert_eq!(recorded.len(), 5);
+
+        // Replay the subscriber's side against a fresh publisher, which
+        // publishes the recorded values at their recorded times
+        let registry_addr = "127.0.0.1:7228";
+        start_registry(registry_addr).await;
+        let fresh = start_publisher("TEST/REPLAY", registry_addr).await;
+        let address = Subscriber::new(registry_addr.to_string())
+            .discover_service("TEST/REPLAY")
+            .await
+            .unwrap()
+            .address;
+        let stream = wind_core::WindStream::connect(&address).await.unwrap();
+        let values = recorded.clone();
+        let publishing = tokio::spawn(async move {
+            let started = tokio::time::Instant::now();
+            for (offset_us, value) in values {
+                tokio::time::sleep_until(started + Duration::from_micros(offset_us)).await;
+                fresh.publish(value).await.unwrap();
+            }
+        });
+        let replayed = wind_core::replay(stream, &recording, Duration::from_millis(200))
+            .await
+            .unwrap();
+        publishing.await.unwrap();
+
+        let replayed: Vec<_> = published_values(&replayed)
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect();
+        let recorded: Vec<_> = recorded.into_iter().map(|(_, value)| value).collect();
+        assert_eq!(replayed, recorded);
+    }
+
+    #[tokio::test]
+    async fn test_stopped_publisher_deregisters_before_next_run() {
+        let registry_addr = "127.0.0.1:7229";
+        start_registry(registry_addr).await;
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+
+        let mut dead_addresses = Vec::new();
+        for run in 0..2 {
+            let publisher = Arc::new(Publisher::new(
+                "TEST/RERUN".to_string(),
+                "127.0.0.1:0".to_string(),
+                registry_addr.to_string(),
+            ));
+            let (stop_tx, stop_rx) = oneshot::channel::<()>();
+            let serving = tokio::spawn({
+                let publisher = publisher.clone();
+                async move {
+                    publisher
+                        .start_until(async {
+                            let _ = stop_rx.await;
+                        })
+                        .await
+                }
+            });
+            tokio::time::sleep(Duration::from_millis(200)).await;
+
+            // Only this run's publisher is registered, so subscribing reaches it
+            let services = subscriber.discover_services("TEST/*").await.unwrap();
+            assert_eq!(services.len(), 1, "run {}: {:?}", run, services);
+            assert!(!dead_addresses.contains(&services[0].address));
+            let mut subscription = subscriber
+                .subscribe(
+                    "TEST/RERUN",
+                    SubscriptionMode::OnChange,
+                    QosParams::default(),
+                )
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            publisher.publish(WindValue::I32(run)).await.unwrap();
+            let next = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+            assert_eq!(next.unwrap(), Some(WindValue::I32(run)));
+
+            stop_tx.send(()).unwrap();
+            serving.await.unwrap().unwrap();
+            dead_addresses.push(services[0].address.clone());
+            assert!(subscriber
+                .discover_services("TEST/*")
+                .await
+                .unwrap()
+                .is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_expired_value_is_dropped_for_slow_subscriber() {
+        const BACKLOG: usize = 32;
+        const PAYLOAD: usize = 512 * 1024;
+        let registry_addr = "127.0.0.1:7230";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/TTL", registry_addr).await;
+
+        let address = Subscriber::new(registry_addr.to_string())
+            .discover_service("TEST/TTL")
+            .await
+            .unwrap()
+            .address;
+        let mut slow = TcpStream::connect(&address).await.unwrap();
+        let subscribe = Message::new(MessagePayload::Subscribe {
+            service: "TEST/TTL".to_string(),
+            mode: SubscriptionMode::OnChange,
+            qos: QosParams::default(),
+            schema_id: None,
+            replay: 0,
+        });
+        MessageCodec::write(&mut slow, &subscribe).await.unwrap();
+        MessageCodec::decode(&mut slow).await.unwrap();
+
+        // More than the socket buffers hold, so the publisher stalls writing
+        // to the subscriber that isn't reading
+        for i in 0..BACKLOG {
+            let payload = vec![i as u8; PAYLOAD];
+            publisher.publish(WindValue::Bytes(payload)).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        publisher
+            .publish_with_ttl(WindValue::I32(1), Duration::from_millis(50))
+            .await
+            .unwrap();
+        publisher.publish(WindValue::I32(2)).await.unwrap();
+        assert_eq!(publisher.current_value().await, Some(WindValue::I32(2)));
+
+        // Catch up long after the short-lived value expired
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let mut received = Vec::new();
+        loop {
+            let next =
+                tokio::time::timeout(Duration::from_secs(5), MessageCodec::decode(&mut slow));
+            match next.await.unwrap().unwrap().payload {
+                MessagePayload::Publish { value, .. } => match *value {
+                    WindValue::Bytes(_) => {}
+                    WindValue::I32(n) => {
+                        received.push(n);
+                        if n == 2 {
+                            break;
+                        }
+                    }
+                    ref other => panic!("Unexpected value {:?}", other),
+                },
+                other => panic!("Expected a publish, got {:?}", other),
+            }
+        }
+        assert_eq!(received, vec![2]);
+        assert_eq!(publisher.expired_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_shares_one_encoding() {
+        const SUBSCRIBERS: usize = 50;
+        let registry_addr = "127.0.0.1:7234";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/FANOUT", registry_addr).await;
+
+        let address = Subscriber::new(registry_addr.to_string())
+            .discover_service("TEST/FANOUT")
+            .await
+            .unwrap()
+            .address;
+        let mut raws = Vec::new();
+        for _ in 0..SUBSCRIBERS {
+            let mut raw = TcpStream::connect(&address).await.unwrap();
+            let subscribe = Message::new(MessagePayload::Subscribe {
+                service: "TEST/FANOUT".to_string(),
+                mode: SubscriptionMode::OnChange,
+                qos: QosParams::default(),
+                schema_id: None,
+                replay: 0,
+            });
+            MessageCodec::write(&mut raw, &subscribe).await.unwrap();
+            MessageCodec::decode(&mut raw).await.unwrap();
+            raws.push(raw);
+        }
+
+        let value = WindValue::Map(HashMap::from([
+            ("reading".to_string(), WindValue::F64(21.5)),
+            ("label".to_string(), WindValue::String("hall".to_string())),
+        ]));
+        let report = publisher.publish_confirmed(value.clone()).await;
+        assert_eq!(report.delivered, SUBSCRIBERS);
+
+        let mut message_ids = Vec::new();
+        for raw in &mut raws {
+            let received = tokio::time::timeout(Duration::from_secs(2), MessageCodec::decode(raw))
+                .await
+                .unwrap()
+                .unwrap();
+            match received.payload {
+                MessagePayload::Publish {
+                    value: received, ..
+                } => assert_eq!(*received, value),
+                other => panic!("Expected a publish, got {:?}", other),
+            }
+            message_ids.push(received.id);
+        }
+        // Every subscriber got a copy of the same encoded message
+        message_ids.dedup();
+        assert_eq!(message_ids.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_with_mismatched_schema_fails() {
+        let registry_addr = "127.0.0.1:7235";
+        start_registry(registry_addr).await;
+        let publisher = Arc::new(
+            Publisher::new(
+                "TEST/SCHEMA".to_string(),
+                "127.0.0.1:0".to_string(),
+                registry_addr.to_string(),
+            )
+            .with_schema("Temperature@2.0".to_string()),
+        );
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        match subscriber
+            .subscription("TEST/SCHEMA")
+            .schema("Temperature@1.0")
+            .start()
+            .await
+        {
+            Err(WindError::Schema(error)) => {
+                assert!(error.contains("Temperature@1.0"), "{}", error);
+                assert!(error.contains("Temperature@2.0"), "{}", error);
+            }
+            other => panic!("Expected a schema error, got {:?}", other.map(|s| s.id)),
+        }
+        // The publisher hangs up on the refused subscriber
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while publisher.subscriber_count().await > 0 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let mut subscription = subscriber
+            .subscription("TEST/SCHEMA")
+            .schema("Temperature@2.0")
+            .start()
+            .await
+            .unwrap();
+        publisher.publish(WindValue::F64(21.5)).await.unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+        assert_eq!(next.unwrap(), Some(WindValue::F64(21.5)));
+    }
+
+    #[tokio::test]
+    async fn test_validated_subscription_flags_nonconforming_values() {
+        let registry_addr = "127.0.0.1:7241";
+        let server = RegistryServer::new(registry_addr.to_string());
+        server
+            .registry()
+            .register_schema(Schema {
+                id: "Temperature@1.0".to_string(),
+                version: 1,
+                name: "Temperature".to_string(),
+                description: None,
+                fields: HashMap::from([("celsius".to_string(), wind_core::WindType::F64)]),
+            })
+            .unwrap();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let publisher = Arc::new(
+            Publisher::new(
+                "TEST/VALIDATED".to_string(),
+                "127.0.0.1:0".to_string(),
+                registry_addr.to_string(),
+            )
+            .with_schema("Temperature@1.0".to_string()),
+        );
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscribe_validated(
+                "TEST/VALIDATED",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(subscription.schema().name, "Temperature");
+
+        let reading =
+            |celsius: WindValue| WindValue::Map(HashMap::from([("celsius".to_string(), celsius)]));
+        publisher
+            .publish(reading(WindValue::F64(21.5)))
+            .await
+            .unwrap();
+        publisher
+            .publish(reading(WindValue::from("warm")))
+            .await
+            .unwrap();
+
+        let next = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+        assert_eq!(
+            next.unwrap().unwrap().unwrap(),
+            reading(WindValue::F64(21.5))
+        );
+        let next = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+        match next.unwrap().unwrap() {
+            Err(WindError::TypeMismatch { expected, actual }) => {
+                assert_eq!(expected, "F64");
+                assert_eq!(actual, "String");
+            }
+            other => panic!("Expected a type mismatch, got {:?}", other),
+        }
+
+        // Services without a schema can't be subscribed to this way
+        let _plain = start_publisher("TEST/UNVALIDATED", registry_addr).await;
+        assert!(matches!(
+            subscriber
+                .subscribe_validated(
+                    "TEST/UNVALIDATED",
+                    SubscriptionMode::OnChange,
+                    QosParams::default()
+                )
+                .await,
+            Err(WindError::Schema(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_max_rate_throttles_runaway_publisher() {
+        const PUBLISHED: i64 = 10_000;
+        let registry_addr = "127.0.0.1:7242";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/RUNAWAY", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscription("TEST/RUNAWAY")
+            .max_queue_size(PUBLISHED as u32)
+            .max_rate(1000)
+            .start()
+            .await
+            .unwrap();
+
+        // 10 values every millisecond, i.e. 10kHz for a second
+        let started = Instant::now();
+        let mut ticks = tokio::time::interval(Duration::from_millis(1));
+        for i in 0..PUBLISHED {
+            if i % 10 == 0 {
+                ticks.tick().await;
+            }
+            publisher.publish(WindValue::I64(i)).await.unwrap();
+        }
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while subscription.last_sequence() < Some(PUBLISHED as u64) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Not every value arrived");
+        let elapsed = started.elapsed().as_secs_f64();
+
+        let mut received = 0;
+        while let Ok(Some(_)) =
+            tokio::time::timeout(Duration::from_millis(100), subscription.next()).await
+        {
+            received += 1;
+        }
+        // 1000 per second plus the initial burst of 100, with some slack
+        let ceiling = (1000.0 * elapsed + 100.0) * 1.1;
+        assert!(
+            (received as f64) < ceiling,
+            "{} values in {:.2}s",
+            received,
+            elapsed
+        );
+        assert!(
+            received >= 500,
+            "Only {} values in {:.2}s",
+            received,
+            elapsed
+        );
+        assert_eq!(subscription.throttled_count(), PUBLISHED as u64 - received);
+        assert_eq!(subscription.dropped_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_inspect_reports_subscribers_and_modes() {
+        let registry_addr = "127.0.0.1:7244";
+        start_registry(registry_addr).await;
+        let publisher = Arc::new(
+            Publisher::new(
+                "TEST/INSPECT".to_string(),
+                "127.0.0.1:0".to_string(),
+                registry_addr.to_string(),
+            )
+            .with_diagnostic_access(vec!["operator".to_string()]),
+        );
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut on_change = Subscriber::new(registry_addr.to_string());
+        let _on_change = on_change
+            .subscription("TEST/INSPECT")
+            .mode(SubscriptionMode::OnChange)
+            .start()
+            .await
+            .unwrap();
+        let mut periodic =
+            Subscriber::new(registry_addr.to_string()).with_client_id("dashboard".to_string());
+        let _periodic = periodic
+            .subscription("TEST/INSPECT")
+            .mode(SubscriptionMode::Periodic { interval_ms: 250 })
+            .reliable()
+            .start()
+            .await
+            .unwrap();
+        publisher.publish(WindValue::from("secret")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut operator =
+            Subscriber::new(registry_addr.to_string()).with_client_id("operator".to_string());
+        let diagnostics = operator.inspect("TEST/INSPECT").await.unwrap();
+        assert_eq!(diagnostics.services, ["TEST/INSPECT"]);
+        assert_eq!(diagnostics.sequence, 1);
+        assert_eq!(diagnostics.clients.len(), 2);
+        let modes: Vec<_> = diagnostics
+            .clients
+            .iter()
+            .map(|client| {
+                assert_eq!(client.subscriptions.len(), 1);
+                let subscription = &client.subscriptions[0];
+                assert_eq!(subscription.service, "TEST/INSPECT");
+                (
+                    client.identity.clone(),
+                    subscription.mode.clone(),
+                    subscription.reliable,
+                )
+            })
+            .collect();
+        assert!(modes
+            .iter()
+            .any(|mode| matches!(mode, (None, SubscriptionMode::OnChange, false))));
+        assert!(modes.iter().any(|mode| matches!(
+            mode,
+            (Some(id), SubscriptionMode::Periodic { interval_ms: 250 }, true) if id == "dashboard"
+        )));
+        // Counters and settings only, never the published value
+        let dump = format!("{:?}", diagnostics);
+        assert!(!dump.contains("secret"), "{}", dump);
+
+        // Anyone else is refused
+        let mut guest = Subscriber::new(registry_addr.to_string());
+        let error = guest.inspect("TEST/INSPECT").await.unwrap_err();
+        assert!(error.to_string().contains("Permission denied"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_merged_subscription_takes_turns() {
+        let registry_addr = "127.0.0.1:7243";
+        start_registry(registry_addr).await;
+        let fast = start_publisher("TEST/MERGE/FAST", registry_addr).await;
+        let slow = start_publisher("TEST/MERGE/SLOW", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscriptions = Vec::new();
+        for service in ["TEST/MERGE/FAST", "TEST/MERGE/SLOW"] {
+            let subscription = subscriber
+                .subscription(service)
+                .max_queue_size(1000)
+                .start()
+                .await
+                .unwrap();
+            subscriptions.push(subscription);
+        }
+        let mut merged = MergedSubscription::new(subscriptions);
+
+        for i in 0..200 {
+            fast.publish(WindValue::I64(i)).await.unwrap();
+            if i % 20 == 0 {
+                slow.publish(WindValue::I64(i)).await.unwrap();
+            }
+        }
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while merged.subscriptions()[0].last_sequence() < Some(200)
+                || merged.subscriptions()[1].last_sequence() < Some(10)
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Not every value arrived");
+
+        // With 200 fast values queued, the 10 slow ones still come every other turn
+        let mut services = Vec::new();
+        for _ in 0..20 {
+            let (service, _) = merged.next().await.unwrap();
+            services.push(service);
+        }
+        let slow_count = services.iter().filter(|s| *s == "TEST/MERGE/SLOW").count();
+        assert_eq!(slow_count, 10, "{:?}", services);
+
+        // The quiet service holds nothing up, and gets the next turn once it publishes
+        for _ in 0..10 {
+            let (service, _) = merged.next().await.unwrap();
+            assert_eq!(service, "TEST/MERGE/FAST");
+        }
+        slow.publish(WindValue::I64(-1)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let mut next = Vec::new();
+        for _ in 0..2 {
+            next.push(merged.next().await.unwrap());
+        }
+        assert!(
+            next.contains(&("TEST/MERGE/SLOW".to_string(), WindValue::I64(-1))),
+            "{:?}",
+            next
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replay_arrives_in_bulk_ahead_of_live_data() {
+        let registry_addr = "127.0.0.1:7236";
+        start_registry(registry_addr).await;
+        let publisher = Arc::new(
+            Publisher::new(
+                "TEST/HISTORY".to_string(),
+                "127.0.0.1:0".to_string(),
+                registry_addr.to_string(),
+            )
+            .with_history(1000),
+        );
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        for value in 0..600 {
+            publisher.publish(WindValue::I64(value)).await.unwrap();
+        }
+
+        let recorder = wind_core::SessionRecorder::new();
+        let mut subscriber =
+            Subscriber::new(registry_addr.to_string()).with_recorder(recorder.clone());
+        let mut subscription = subscriber
+            .subscription("TEST/HISTORY")
+            .replay(500)
+            .start()
+            .await
+            .unwrap();
+        for value in 600..610 {
+            publisher.publish(WindValue::I64(value)).await.unwrap();
+        }
+
+        let mut received = Vec::new();
+        while received.len() < 510 {
+            let next = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+            received.push(next.unwrap().unwrap());
+        }
+        let expected: Vec<_> = (100..610).map(WindValue::I64).collect();
+        assert_eq!(received, expected);
+
+        let received_payloads: Vec<_> = recorder
+            .messages()
+            .into_iter()
+            .filter(|recorded| recorded.direction == wind_core::Direction::Received)
+            .map(|recorded| recorded.message.payload.name())
+            .collect();
+        let count = |name| received_payloads.iter().filter(|&&n| n == name).count();
+        assert_eq!(count("ReplayBatch"), 1, "{:?}", received_payloads);
+        assert_eq!(count("Publish"), 10, "{:?}", received_payloads);
+    }
+
+    #[tokio::test]
+    async fn test_large_replay_is_sent_compressed() {
+        let registry_addr = "127.0.0.1:7262";
+        start_registry(registry_addr).await;
+        let publisher = Arc::new(
+            Publisher::new(
+                "TEST/COMPRESSED_HISTORY".to_string(),
+                "127.0.0.1:0".to_string(),
+                registry_addr.to_string(),
+            )
+            .with_history(1000),
+        );
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        for value in 0..600 {
+            publisher.publish(WindValue::I64(value)).await.unwrap();
+        }
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let address = subscriber
+            .discover_service("TEST/COMPRESSED_HISTORY")
+            .await
+            .unwrap()
+            .address;
+        let mut raw = TcpStream::connect(&address).await.unwrap();
+        let subscribe = Message::new(MessagePayload::Subscribe {
+            service: "TEST/COMPRESSED_HISTORY".to_string(),
+            mode: SubscriptionMode::OnChange,
+            qos: QosParams::default(),
+            schema_id: None,
+            replay: 500,
+        });
+        MessageCodec::write(&mut raw, &subscribe).await.unwrap();
+
+        // Frames as they came off the wire, to see how each was compressed
+        loop {
+            let mut frame = vec![0; 4];
+            raw.read_exact(&mut frame).await.unwrap();
+            let len = u32::from_be_bytes(frame[..4].try_into().unwrap()) as usize;
+            frame.resize(4 + len, 0);
+            raw.read_exact(&mut frame[4..]).await.unwrap();
+            let (msg, _) = MessageCodec::decode_from_slice(&frame).unwrap();
+            if let MessagePayload::ReplayBatch { from_seq, values } = msg.payload {
+                assert_eq!(frame[4], wind_core::Codec::Lz4 as u8);
+                assert_eq!(values.len(), 500);
+                assert_eq!(values[0], (from_seq, WindValue::I64(100)));
+                let raw_len =
+                    MessageCodec::encode_to_vec(&Message::new(MessagePayload::ReplayBatch {
+                        from_seq,
+                        values,
+                    }))
+                    .unwrap()
+                    .len();
+                assert!(frame.len() < raw_len, "{} >= {}", frame.len(), raw_len);
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_beyond_client_limit_are_refused() {
+        let registry_addr = "127.0.0.1:7237";
+        start_registry(registry_addr).await;
+        let topics = ["TEST/LIMIT/A", "TEST/LIMIT/B", "TEST/LIMIT/C"];
+        let publisher = Arc::new(
+            Publisher::new_multi(
+                topics.iter().map(|topic| topic.to_string()).collect(),
+                "127.0.0.1:0".to_string(),
+                registry_addr.to_string(),
+            )
+            .with_max_subscriptions_per_client(2),
+        );
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let address = Subscriber::new(registry_addr.to_string())
+            .discover_service("TEST/LIMIT/A")
+            .await
+            .unwrap()
+            .address;
+
+        async fn subscribe(raw: &mut TcpStream, service: &str) -> (bool, Option<String>) {
+            let subscribe = Message::new(MessagePayload::Subscribe {
+                service: service.to_string(),
+                mode: SubscriptionMode::OnChange,
+                qos: QosParams::default(),
+                schema_id: None,
+                replay: 0,
+            });
+            MessageCodec::write(raw, &subscribe).await.unwrap();
+            match MessageCodec::decode(raw).await.unwrap().payload {
+                MessagePayload::SubscribeAck { success, error, .. } => (success, error),
+                other => panic!("Expected a SubscribeAck, got {:?}", other),
+            }
+        }
+
+        let mut raw = TcpStream::connect(&address).await.unwrap();
+        assert_eq!(subscribe(&mut raw, topics[0]).await, (true, None));
+        assert_eq!(subscribe(&mut raw, topics[1]).await, (true, None));
+        let (success, error) = subscribe(&mut raw, topics[2]).await;
+        assert!(!success);
+        assert!(error.unwrap().contains("limit"));
+        // Resubscribing replaces a subscription rather than adding one
+        assert_eq!(subscribe(&mut raw, topics[0]).await, (true, None));
+
+        // The refusal leaves the client's subscriptions in place
+        publisher
+            .publish_to(topics[1], WindValue::I32(7))
+            .await
+            .unwrap();
+        match MessageCodec::decode(&mut raw).await.unwrap().payload {
+            MessagePayload::Publish { service, value, .. } => {
+                assert_eq!(service, topics[1]);
+                assert_eq!(*value, WindValue::I32(7));
+            }
+            other => panic!("Expected a Publish, got {:?}", other),
+        }
+
+        // The limit is per connection
+        let mut other = TcpStream::connect(&address).await.unwrap();
+        assert_eq!(subscribe(&mut other, topics[2]).await, (true, None));
+    }
+
+    #[tokio::test]
+    async fn test_stale_discovery_snapshot_is_detected() {
+        let registry_addr = "127.0.0.1:7239";
+        start_registry(registry_addr).await;
+        let _first = start_publisher("TEST/VERSION/A", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let snapshot = subscriber
+            .discover_snapshot("TEST/VERSION/*")
+            .await
+            .unwrap();
+        assert_eq!(snapshot.services.len(), 1);
+        assert!(subscriber.is_current(&snapshot).await.unwrap());
+
+        let _second = start_publisher("TEST/VERSION/B", registry_addr).await;
+        assert!(!subscriber.is_current(&snapshot).await.unwrap());
+
+        let refreshed = subscriber
+            .discover_snapshot("TEST/VERSION/*")
+            .await
+            .unwrap();
+        assert_eq!(refreshed.services.len(), 2);
+        assert!(refreshed.registry_version > snapshot.registry_version);
+        assert!(subscriber.is_current(&refreshed).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_watch_services_streams_registrations_and_removals() {
+        let addr = "127.0.0.1:7250";
+        let server = RegistryServer::new(addr.to_string());
+        let registry = server.registry();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        registry
+            .register_service(service("WATCH/EXISTING", 9200), 60000)
+            .await
+            .unwrap();
+
+        let subscriber = Subscriber::new(addr.to_string());
+        let mut events = subscriber.watch_services("WATCH/*").await.unwrap();
+        async fn next_event(events: &mut BoxStream<'static, ServiceEvent>) -> ServiceEvent {
+            let event = tokio::time::timeout(Duration::from_secs(5), events.next()).await;
+            event.unwrap().unwrap()
+        }
+
+        // Services registered before the watch are reported first
+        match next_event(&mut events).await {
+            ServiceEvent::Registered(info) => assert_eq!(info.name, "WATCH/EXISTING"),
+            other => panic!("Expected the existing service, got {:?}", other),
+        }
+
+        registry
+            .register_service(service("ELSEWHERE/IGNORED", 9201), 60000)
+            .await
+            .unwrap();
+        registry
+            .register_service(service("WATCH/NEW", 9202), 60000)
+            .await
+            .unwrap();
+        match next_event(&mut events).await {
+            ServiceEvent::Registered(info) => assert_eq!(info.name, "WATCH/NEW"),
+            other => panic!("Expected the new service, got {:?}", other),
+        }
+
+        registry
+            .deregister_service("WATCH/EXISTING", None)
+            .await
+            .unwrap();
+        match next_event(&mut events).await {
+            ServiceEvent::Removed(info) => assert_eq!(info.name, "WATCH/EXISTING"),
+            other => panic!("Expected the removal, got {:?}", other),
+        }
+
+        // An invalid pattern ends the stream instead of hanging it
+        let mut rejected = subscriber.watch_services("WATCH/[").await.unwrap();
+        let ended = tokio::time::timeout(Duration::from_secs(5), rejected.next()).await;
+        assert!(ended.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_subscription_resumes_after_publisher_restart() {
+        let registry_addr = "127.0.0.1:7253";
+        start_registry(registry_addr).await;
+        let start = |stop: oneshot::Receiver<()>| {
+            let publisher = Arc::new(Publisher::new(
+                "TEST/RESTARTED".to_string(),
+                "127.0.0.1:0".to_string(),
+                registry_addr.to_string(),
+            ));
+            let running = publisher.clone();
+            tokio::spawn(async move {
+                let _ = running
+                    .start_until(async {
+                        let _ = stop.await;
+                    })
+                    .await;
+            });
+            publisher
+        };
+        async fn wait_until_connected(subscription: &Subscription, connected: bool) {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            while subscription.is_connected() != connected {
+                assert!(Instant::now() < deadline, "never became {}", connected);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        }
+
+        let (stop_first, stopped) = oneshot::channel();
+        let first = start(stopped);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscribe(
+                "TEST/RESTARTED",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        assert!(subscription.is_connected());
+        first.publish(WindValue::I32(1)).await.unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(5), subscription.next()).await;
+        assert_eq!(received.unwrap(), Some(WindValue::I32(1)));
+
+        // The restarted publisher listens on a different port
+        stop_first.send(()).unwrap();
+        wait_until_connected(&subscription, false).await;
+        let (_stop_second, stopped) = oneshot::channel();
+        let second = start(stopped);
+        wait_until_connected(&subscription, true).await;
+
+        second.publish(WindValue::I32(2)).await.unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(5), subscription.next()).await;
+        assert_eq!(received.unwrap(), Some(WindValue::I32(2)));
+    }
+
+    #[tokio::test]
+    async fn test_batched_publishing_writes_fewer_frames() {
+        const VALUES: usize = 5000;
+        const ROUND: usize = 500;
+        let registry_addr = "127.0.0.1:7256";
+        start_registry(registry_addr).await;
+
+        // Writes and bytes it takes to deliver VALUES 256-byte values
+        async fn measure(registry_addr: &str, service: &str, batched: bool) -> (u64, u64) {
+            let publisher = start_publisher(service, registry_addr).await;
+            let mut subscriber = Subscriber::new(registry_addr.to_string());
+            let mut subscription = subscriber
+                .subscription(service)
+                .max_queue_size(VALUES as u32)
+                .start()
+                .await
+                .unwrap();
+            let traffic = publisher.traffic();
+            let (writes_before, bytes_before) = (traffic.writes(), traffic.bytes_out());
+
+            for round in 0..VALUES / ROUND {
+                let values: Vec<_> = (round * ROUND..(round + 1) * ROUND)
+                    .map(|i| {
+                        let mut payload = vec![0u8; 256];
+                        payload[..8].copy_from_slice(&(i as u64).to_le_bytes());
+                        WindValue::Bytes(payload)
+                    })
+                    .collect();
+                if batched {
+                    publisher.publish_batch(values.clone()).await.unwrap();
+                } else {
+                    for value in values.clone() {
+                        publisher.publish(value).await.unwrap();
+                    }
+                }
+                for value in values {
+                    let next =
+                        tokio::time::timeout(Duration::from_secs(5), subscription.next()).await;
+                    assert_eq!(next.unwrap(), Some(value));
+                }
+            }
+            (
+                traffic.writes() - writes_before,
+                traffic.bytes_out() - bytes_before,
+            )
+        }
+
+        let (_, single_bytes) = measure(registry_addr, "TEST/SINGLE", false).await;
+        let (batched_writes, batched_bytes) = measure(registry_addr, "TEST/BATCH", true).await;
+
+        // Each single value is a frame of its own
+        let frame = Message::new(MessagePayload::Publish {
+            service: "TEST/SINGLE".to_string(),
+            sequence: 1,
+            value: Arc::new(WindValue::Bytes(vec![0u8; 256])),
+            schema_id: None,
+            expires_at_us: None,
+        });
+        let frame_bytes = MessageCodec::encode(&frame).unwrap().len() as u64;
+        assert_eq!(single_bytes, VALUES as u64 * frame_bytes);
+        // Batched values go out 64 to a frame, in one write each, and share
+        // its header instead of carrying one each
+        assert_eq!(batched_writes, (VALUES / ROUND * ROUND.div_ceil(64)) as u64);
+        assert!(batched_bytes < single_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_batch_skips_repeats_for_on_change() {
+        let registry_addr = "127.0.0.1:7257";
+        start_registry(registry_addr).await;
+        let publisher = start_publisher("TEST/BATCH_REPEATS", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscribe(
+                "TEST/BATCH_REPEATS",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        publisher.publish(WindValue::I32(1)).await.unwrap();
+        publisher
+            .publish_batch([1, 1, 2, 2, 3].map(WindValue::I32).to_vec())
+            .await
+            .unwrap();
+
+        for expected in 1..=3 {
+            let next = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+            assert_eq!(next.unwrap(), Some(WindValue::I32(expected)));
+        }
+        assert_eq!(subscription.last_sequence(), Some(6));
+        assert_eq!(publisher.current_value().await, Some(WindValue::I32(3)));
+    }
+
+    #[tokio::test]
+    async fn test_pattern_subscription_follows_new_services() {
+        let registry_addr = "127.0.0.1:7255";
+        start_registry(registry_addr).await;
+        let room_a = start_publisher("SENSOR/ROOM_A/TEMP", registry_addr).await;
+        let room_b = start_publisher("SENSOR/ROOM_B/TEMP", registry_addr).await;
+        let humidity = start_publisher("SENSOR/ROOM_A/HUMIDITY", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut temperatures = subscriber
+            .subscribe_pattern(
+                "SENSOR/*/TEMP",
+                SubscriptionMode::OnChange,
+                QosParams::default(),
+            )
+            .await
+            .unwrap();
+        let mut services: Vec<&str> = temperatures.services().collect();
+        services.sort();
+        assert_eq!(services, ["SENSOR/ROOM_A/TEMP", "SENSOR/ROOM_B/TEMP"]);
+
+        room_a.publish(WindValue::F64(21.0)).await.unwrap();
+        room_b.publish(WindValue::F64(19.5)).await.unwrap();
+        humidity.publish(WindValue::F64(40.0)).await.unwrap();
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            let next = tokio::time::timeout(Duration::from_secs(5), temperatures.next()).await;
+            received.push(next.unwrap().unwrap());
+        }
+        received.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            received,
+            [
+                ("SENSOR/ROOM_A/TEMP".to_string(), WindValue::F64(21.0)),
+                ("SENSOR/ROOM_B/TEMP".to_string(), WindValue::F64(19.5)),
+            ]
+        );
+
+        // A third room appears mid-stream and is picked up
+        let room_c = start_publisher("SENSOR/ROOM_C/TEMP", registry_addr).await;
+        room_c.publish(WindValue::F64(23.0)).await.unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(5), temperatures.next()).await;
+        assert_eq!(
+            next.unwrap(),
+            Some(("SENSOR/ROOM_C/TEMP".to_string(), WindValue::F64(23.0)))
+        );
+        assert_eq!(temperatures.services().count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_pattern_subscriptions_share_one_registry_watch() {
+        let registry_addr = "127.0.0.1:7261";
+        let server = RegistryServer::new(registry_addr.to_string());
+        let registry = server.registry();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let watches = || registry.metrics().active_watches.load(Ordering::Relaxed);
+        let room_a = start_publisher("SHARED/ROOM_A/TEMP", registry_addr).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscriptions = Vec::new();
+        for _ in 0..3 {
+            let subscription = subscriber
+                .subscribe_pattern(
+                    "SHARED/*/TEMP",
+                    SubscriptionMode::OnChange,
+                    QosParams::default(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                subscription.services().collect::<Vec<_>>(),
+                ["SHARED/ROOM_A/TEMP"]
+            );
+            subscriptions.push(subscription);
+        }
+        assert_eq!(watches(), 1);
+
+        // Each registry event reaches every local subscription
+        let room_b = start_publisher("SHARED/ROOM_B/TEMP", registry_addr).await;
+        room_a.publish(WindValue::F64(21.0)).await.unwrap();
+        room_b.publish(WindValue::F64(19.5)).await.unwrap();
+        for subscription in &mut subscriptions {
+            let mut received = Vec::new();
+            for _ in 0..2 {
+                let next = tokio::time::timeout(Duration::from_secs(5), subscription.next()).await;
+                received.push(next.unwrap().unwrap().0);
+            }
+            received.sort();
+            assert_eq!(received, ["SHARED/ROOM_A/TEMP", "SHARED/ROOM_B/TEMP"]);
+        }
+
+        // The watch outlives all but the last of its subscriptions
+        subscriptions.truncate(1);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        registry.cleanup_expired().await;
+        assert_eq!(watches(), 1);
+        subscriptions.clear();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        registry.cleanup_expired().await;
+        assert_eq!(watches(), 0);
+    }
+
+    /// Fields of each closed `wind_message` span, for following a value
+    /// through the spans it passed through
+    #[derive(Clone, Default)]
+    struct SpanCapture {
+        closed: Arc<Mutex<Vec<HashMap<String, String>>>>,
+    }
+
+    #[derive(Default)]
+    struct SpanFields(HashMap<String, String>);
+
+    impl tracing::field::Visit for SpanFields {
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl<S> tracing_subscriber::Layer<S> for SpanCapture
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            id: &tracing::span::Id,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if attrs.metadata().name() != wind_core::MESSAGE_SPAN {
+                return;
+            }
+            let mut fields = SpanFields::default();
+            attrs.record(&mut fields);
+            ctx.span(id).unwrap().extensions_mut().insert(fields);
+        }
+
+        fn on_record(
+            &self,
+            id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let span = ctx.span(id).unwrap();
+            let mut extensions = span.extensions_mut();
+            if let Some(fields) = extensions.get_mut::<SpanFields>() {
+                values.record(fields);
+            }
+        }
+
+        fn on_close(&self, id: tracing::span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+            let span = ctx.span(&id).unwrap();
+            let fields = span.extensions_mut().remove::<SpanFields>();
+            if let Some(fields) = fields {
+                self.closed.lock().unwrap().push(fields.0);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_spans_correlate_with_receive_spans() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let capture = SpanCapture::default();
+        // The test runs on one thread, so this sees the publisher and the
+        // subscriber alike, and no other test
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+
+        let registry_addr = "127.0.0.1:7259";
+        start_registry(registry_addr).await;
+        let publisher = Arc::new(Publisher::new(
+            "TEST/TRACED".to_string(),
+            "127.0.0.1:0".to_string(),
+            registry_addr.to_string(),
+        ));
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscription("TEST/TRACED")
+            .start()
+            .await
+            .unwrap();
+        publisher.publish(WindValue::F64(21.5)).await.unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+        assert_eq!(next.unwrap(), Some(WindValue::F64(21.5)));
+
+        let span = |stage: &str| {
+            let closed = capture.closed.lock().unwrap();
+            closed
+                .iter()
+                .find(|fields| fields["stage"] == stage)
+                .cloned()
+        };
+        let receive = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                if let Some(receive) = span("receive") {
+                    return receive;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let publish = span("publish").unwrap();
+        let send = span("send").unwrap();
+
+        for fields in [&publish, &send, &receive] {
+            assert_eq!(fields["message.id"], publish["message.id"], "{:?}", fields);
+            assert_eq!(fields["sequence"], publish["sequence"], "{:?}", fields);
+            assert_eq!(fields["service"], "TEST/TRACED");
+            assert_eq!(fields["payload.size"], publish["payload.size"]);
+        }
+        assert_eq!(send["subscription.mode"], "OnChange");
+        assert_eq!(receive["subscription.mode"], "OnChange");
+    }
+
+    #[tokio::test]
+    async fn test_publisher_reaps_subscriber_that_stops_answering_pings() {
+        const PING_INTERVAL: Duration = Duration::from_millis(100);
+        const MAX_MISSED: u32 = 2;
+        let registry_addr = "127.0.0.1:7260";
+        start_registry(registry_addr).await;
+        let publisher = Arc::new(
+            Publisher::new(
+                "TEST/LIVENESS".to_string(),
+                "127.0.0.1:0".to_string(),
+                registry_addr.to_string(),
+            )
+            .with_liveness(PING_INTERVAL, MAX_MISSED),
+        );
+        tokio::spawn({
+            let publisher = publisher.clone();
+            async move {
+                let _ = publisher.start().await;
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // A subscriber that answers pings
+        let mut subscriber = Subscriber::new(registry_addr.to_string());
+        let mut subscription = subscriber
+            .subscription("TEST/LIVENESS")
+            .start()
+            .await
+            .unwrap();
+
+        // And one that stops reading without telling the publisher, as if
+        // its host vanished: shutting down the read side sends nothing
+        let address = subscriber
+            .discover_service("TEST/LIVENESS")
+            .await
+            .unwrap()
+            .address;
+        let mut raw = TcpStream::connect(&address).await.unwrap();
+        let subscribe = Message::new(MessagePayload::Subscribe {
+            service: "TEST/LIVENESS".to_string(),
+            mode: SubscriptionMode::Once,
+            qos: QosParams::default(),
+            schema_id: None,
+            replay: 0,
+        });
+        MessageCodec::write(&mut raw, &subscribe).await.unwrap();
+        MessageCodec::decode(&mut raw).await.unwrap();
+        let raw = raw.into_std().unwrap();
+        raw.shutdown(std::net::Shutdown::Read).unwrap();
+        assert_eq!(publisher.subscriber_count().await, 2);
+
+        // Reaped on the tick after its last missed ping, plus some slack
+        let started = Instant::now();
+        let window = PING_INTERVAL * (MAX_MISSED + 1) + Duration::from_millis(200);
+        tokio::time::timeout(window, async {
+            while publisher.subscriber_count().await > 1 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        // Not on sight: it was given its pings to miss, the first of which
+        // may have gone out before the clock started
+        assert!(started.elapsed() >= PING_INTERVAL * (MAX_MISSED - 1));
+
+        // The subscriber that answers is kept, and still gets updates
+        tokio::time::sleep(PING_INTERVAL * (MAX_MISSED + 2)).await;
+        assert_eq!(publisher.subscriber_count().await, 1);
+        publisher.publish(WindValue::I32(1)).await.unwrap();
+        let next = tokio::time::timeout(Duration::from_secs(2), subscription.next()).await;
+        assert_eq!(next.unwrap(), Some(WindValue::I32(1)));
+        drop(raw);
     }
 }
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::Connection;
+use futures::stream::{BoxStream, StreamExt};
+use wind_core::{Message, MessagePayload, Result, ServiceEvent, ServiceInfo, DEFAULT_MAX_HOPS};
+
+/// Events a local watcher may fall behind a shared watch by before losing some
+const SHARED_WATCH_CAPACITY: usize = 1024;
+
+/// Registry watches of one client, shared by pattern: every local watcher of
+/// a pattern reads the same registry connection, which closes once the last
+/// of them is dropped.
+#[derive(Clone, Default)]
+pub(crate) struct SharedWatches {
+    // An async lock, so concurrent first watchers of a pattern open one watch
+    watches: Arc<tokio::sync::Mutex<HashMap<String, Weak<SharedWatch>>>>,
+}
+
+impl SharedWatches {
+    /// Events of the watch of `pattern`, starting with the services it
+    /// currently matches. A new watch is opened over `connection` only if
+    /// none of this client's is still running.
+    pub(crate) async fn watch(
+        &self,
+        pattern: &str,
+        connection: Connection,
+    ) -> Result<BoxStream<'static, ServiceEvent>> {
+        let mut watches = self.watches.lock().await;
+        watches.retain(|_, watch| watch.strong_count() > 0);
+        let running = watches
+            .get(pattern)
+            .and_then(Weak::upgrade)
+            .filter(|watch| watch.is_running());
+        let watch = match running {
+            Some(watch) => watch,
+            None => {
+                let watch = Arc::new(SharedWatch::open(pattern, connection).await?);
+                watches.insert(pattern.to_string(), Arc::downgrade(&watch));
+                watch
+            }
+        };
+        Ok(watch.events())
+    }
+}
+
+/// One registry watch, fanned out to every local watcher holding it
+struct SharedWatch {
+    state: Arc<Mutex<WatchState>>,
+    reader: JoinHandle<()>,
+}
+
+struct WatchState {
+    /// `None` once the registry connection has ended
+    events: Option<broadcast::Sender<ServiceEvent>>,
+    /// Services the pattern currently matches, by name and address, for
+    /// watchers that join after the registry sent them
+    services: HashMap<(String, String), ServiceInfo>,
+}
+
+impl SharedWatch {
+    async fn open(pattern: &str, mut connection: Connection) -> Result<Self> {
+        let request = Message::new(MessagePayload::WatchServices {
+            pattern: pattern.to_string(),
+            max_hops: DEFAULT_MAX_HOPS,
+        });
+        connection.send(&request).await?;
+
+        let (events, _) = broadcast::channel(SHARED_WATCH_CAPACITY);
+        let state = Arc::new(Mutex::new(WatchState {
+            events: Some(events),
+            services: HashMap::new(),
+        }));
+        let reader = tokio::spawn(Self::read(connection, state.clone()));
+        Ok(Self { state, reader })
+    }
+
+    /// Pass the registry's events on until the connection ends. A reconnect
+    /// wouldn't carry the watch over, so the first failure ends it.
+    async fn read(mut connection: Connection, state: Arc<Mutex<WatchState>>) {
+        loop {
+            match connection.receive().await {
+                Ok(msg) => match msg.payload {
+                    MessagePayload::ServiceChanged { event } => {
+                        let mut state = state.lock().unwrap();
+                        match &event {
+                            ServiceEvent::Registered(service) => {
+                                let key = (service.name.clone(), service.address.clone());
+                                state.services.insert(key, service.clone());
+                            }
+                            ServiceEvent::Removed(service) => {
+                                let key = (service.name.clone(), service.address.clone());
+                                state.services.remove(&key);
+                            }
+                        }
+                        if let Some(events) = &state.events {
+                            let _ = events.send(event);
+                        }
+                    }
+                    MessagePayload::Error { error, .. } => {
+                        warn!("Service watch rejected: {}", error);
+                        break;
+                    }
+                    other => debug!("Ignoring {} on service watch", other.name()),
+                },
+                Err(e) => {
+                    debug!("Service watch ended: {}", e);
+                    break;
+                }
+            }
+        }
+        // Dropping the sender ends every watcher's stream
+        state.lock().unwrap().events = None;
+    }
+
+    fn is_running(&self) -> bool {
+        self.state.lock().unwrap().events.is_some()
+    }
+
+    /// The current matches, then every later event, holding the watch open
+    /// for as long as the stream lives
+    fn events(self: Arc<Self>) -> BoxStream<'static, ServiceEvent> {
+        // Taken together, so no event is both in the snapshot and received
+        let (current, receiver) = {
+            let state = self.state.lock().unwrap();
+            let current: Vec<_> = state.services.values().cloned().collect();
+            (
+                current,
+                state.events.as_ref().map(broadcast::Sender::subscribe),
+            )
+        };
+        let live = futures::stream::unfold((self, receiver), |(watch, receiver)| async move {
+            let mut receiver = receiver?;
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, (watch, Some(receiver)))),
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Service watcher fell behind, missed {} events", missed);
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        futures::stream::iter(current.into_iter().map(ServiceEvent::Registered))
+            .chain(live)
+            .boxed()
+    }
+}
+
+impl Drop for SharedWatch {
+    fn drop(&mut self) {
+        // Closes the registry connection along with the reader
+        self.reader.abort();
+    }
+}
@@ -1,5 +1,7 @@
+use serde::de::{Deserializer, MapAccess, Visitor};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 
 /// WIND Interface Definition Language (IDL) schema
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -7,7 +9,9 @@ pub struct WindIdl {
     pub name: String,
     pub version: String,
     pub description: Option<String>,
+    #[serde(deserialize_with = "unique_keys")]
     pub types: HashMap<String, TypeDefinition>,
+    #[serde(deserialize_with = "unique_keys")]
     pub services: HashMap<String, ServiceDefinition>,
 }
 
@@ -29,6 +33,14 @@ pub enum TypeDefinition {
     Optional {
         inner_type: Box<TypeDefinition>,
     },
+    /// A named type declared in `WindIdl::types`
+    Reference {
+        name: String,
+    },
+    /// A value of any one of the member types
+    Union {
+        members: Vec<TypeDefinition>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,6 +52,8 @@ pub enum PrimitiveType {
     F64,
     String,
     Bytes,
+    /// Microseconds since the Unix epoch
+    Timestamp,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -68,3 +82,37 @@ pub struct PublicationDefinition {
     pub description: Option<String>,
     pub data_type: TypeDefinition,
 }
+
+/// Deserialize a JSON object into a map, rejecting duplicate keys instead of
+/// silently keeping the last one
+fn unique_keys<'de, D, V>(deserializer: D) -> Result<HashMap<String, V>, D::Error>
+where
+    D: Deserializer<'de>,
+    V: Deserialize<'de>,
+{
+    struct UniqueKeys<V>(std::marker::PhantomData<V>);
+
+    impl<'de, V: Deserialize<'de>> Visitor<'de> for UniqueKeys<V> {
+        type Value = HashMap<String, V>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a map with unique names")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+            let mut map = HashMap::new();
+            while let Some((key, value)) = access.next_entry::<String, V>()? {
+                if map.contains_key(&key) {
+                    return Err(serde::de::Error::custom(format!(
+                        "duplicate name `{}`",
+                        key
+                    )));
+                }
+                map.insert(key, value);
+            }
+            Ok(map)
+        }
+    }
+
+    deserializer.deserialize_map(UniqueKeys(std::marker::PhantomData))
+}
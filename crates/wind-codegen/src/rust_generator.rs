@@ -56,19 +56,69 @@ impl RustGenerator {
         match type_def {
             TypeDefinition::Struct { fields } => {
                 let mut field_tokens = Vec::new();
+                let mut insertions = Vec::new();
+                let mut extractions = Vec::new();
 
                 for (field_name, field_def) in fields {
                     let field_ident = format_ident!("{}", field_name);
-                    let field_type_tokens = self.type_to_rust(&field_def.field_type)?;
+                    let field_type = &field_def.field_type;
+                    let field_type_tokens = self.type_to_rust(field_type)?;
+                    let location = format!("field '{}'", field_name);
+                    let extracted = self.convert_from_wind(quote! { v }, field_type)?;
+                    let extracted = quote! {
+                        (#extracted).map_err(|e| WindError::InvalidElement {
+                            location: #location.to_string(),
+                            source: Box::new(e),
+                        })?
+                    };
 
                     if field_def.optional {
                         field_tokens.push(quote! {
                             pub #field_ident: Option<#field_type_tokens>
                         });
+                        // A missing optional field is left out of the map
+                        let inserted = self.convert_to_wind(quote! { v }, field_type);
+                        insertions.push(quote! {
+                            if let Some(v) = val.#field_ident {
+                                map.insert(#field_name.to_string(), #inserted);
+                            }
+                        });
+                        extractions.push(quote! {
+                            #field_ident: match map.remove(#field_name) {
+                                None | Some(WindValue::Null) => None,
+                                Some(v) => Some(#extracted),
+                            }
+                        });
                     } else {
                         field_tokens.push(quote! {
                             pub #field_ident: #field_type_tokens
                         });
+                        let inserted =
+                            self.convert_to_wind(quote! { val.#field_ident }, field_type);
+                        insertions.push(quote! {
+                            map.insert(#field_name.to_string(), #inserted);
+                        });
+                        if matches!(field_type, TypeDefinition::Optional { .. }) {
+                            // Like an optional field, one of optional type may be missing
+                            extractions.push(quote! {
+                                #field_ident: {
+                                    let v = map.remove(#field_name).unwrap_or(WindValue::Null);
+                                    #extracted
+                                }
+                            });
+                            continue;
+                        }
+                        extractions.push(quote! {
+                            #field_ident: match map.remove(#field_name) {
+                                Some(v) => #extracted,
+                                None => {
+                                    return Err(WindError::Schema(format!(
+                                        "Missing required field: {}",
+                                        #field_name
+                                    )))
+                                }
+                            }
+                        });
                     }
                 }
 
@@ -81,7 +131,7 @@ impl RustGenerator {
                     impl From<#type_name> for WindValue {
                         fn from(val: #type_name) -> Self {
                             let mut map = HashMap::new();
-                            // TODO: Add field conversions
+                            #(#insertions)*
                             WindValue::Map(map)
                         }
                     }
@@ -91,17 +141,13 @@ impl RustGenerator {
 
                         fn try_from(value: WindValue) -> Result<Self> {
                             match value {
-                                WindValue::Map(map) => {
-                                    // TODO: Add field extractions
-                                    Err(WindError::TypeMismatch {
-                                        expected: stringify!(#type_name).to_string(),
-                                        actual: format!("{:?}", value),
-                                    })
-                                }
-                                _ => Err(WindError::TypeMismatch {
+                                WindValue::Map(mut map) => Ok(#type_name {
+                                    #(#extractions,)*
+                                }),
+                                other => Err(WindError::TypeMismatch {
                                     expected: stringify!(#type_name).to_string(),
-                                    actual: format!("{:?}", value),
-                                })
+                                    actual: other.type_name().to_string(),
+                                }),
                             }
                         }
                     }
@@ -111,6 +157,13 @@ impl RustGenerator {
             TypeDefinition::Enum { variants } => {
                 let variant_tokens: Vec<_> =
                     variants.iter().map(|v| format_ident!("{}", v)).collect();
+                // Values carry the enum's name too, so `Mode::Off` never
+                // reads as a same-named variant of another enum
+                let qualified: Vec<_> = variants
+                    .iter()
+                    .map(|v| format!("{}::{}", name, v))
+                    .collect();
+                let prefix = format!("{}::", name);
 
                 Ok(quote! {
                     #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -120,19 +173,94 @@ impl RustGenerator {
 
                     impl From<#type_name> for WindValue {
                         fn from(val: #type_name) -> Self {
-                            WindValue::String(format!("{:?}", val))
+                            let name = match val {
+                                #(#type_name::#variant_tokens => #qualified,)*
+                            };
+                            WindValue::String(name.to_string())
                         }
                     }
 
                     impl TryFrom<WindValue> for #type_name {
                         type Error = WindError;
 
+                        /// Accepts the qualified names `From` writes, and bare
+                        /// variant names from publishers that don't qualify them
                         fn try_from(value: WindValue) -> Result<Self> {
-                            // TODO: Implement enum conversion
-                            Err(WindError::TypeMismatch {
-                                expected: stringify!(#type_name).to_string(),
-                                actual: format!("{:?}", value),
-                            })
+                            match value {
+                                WindValue::String(name) => {
+                                    match name.strip_prefix(#prefix).unwrap_or(&name) {
+                                        #(#variants => Ok(#type_name::#variant_tokens),)*
+                                        _ => Err(WindError::Schema(format!(
+                                            "Unknown {} variant: {}",
+                                            stringify!(#type_name),
+                                            name
+                                        ))),
+                                    }
+                                }
+                                other => Err(WindError::TypeMismatch {
+                                    expected: stringify!(#type_name).to_string(),
+                                    actual: other.type_name().to_string(),
+                                }),
+                            }
+                        }
+                    }
+                })
+            }
+
+            TypeDefinition::Union { members } => {
+                let mut variants = Vec::new();
+                let mut to_wind = Vec::new();
+                let mut from_wind = Vec::new();
+                let mut alternatives = Vec::new();
+
+                for (index, member) in members.iter().enumerate() {
+                    let member_type = self.type_to_rust(member)?;
+                    let variant = match member {
+                        TypeDefinition::Primitive { primitive_type } => {
+                            format_ident!("{}", format!("{:?}", primitive_type))
+                        }
+                        TypeDefinition::Reference { name } => format_ident!("{}", name),
+                        _ => format_ident!("Variant{}", index),
+                    };
+                    alternatives.push(variant.to_string());
+
+                    variants.push(quote! { #variant(#member_type) });
+                    // Members convert as struct fields of their type do
+                    let to_value = self.convert_to_wind(quote! { v }, member);
+                    to_wind.push(quote! { #type_name::#variant(v) => #to_value });
+                    let from_value = self.convert_from_wind(quote! { value.clone() }, member)?;
+                    from_wind.push(quote! {
+                        if let Ok(v) = #from_value {
+                            Ok(#type_name::#variant(v))
+                        }
+                    });
+                }
+                let expected = format!("one of {}", alternatives.join(" | "));
+
+                Ok(quote! {
+                    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+                    pub enum #type_name {
+                        #(#variants,)*
+                    }
+
+                    impl From<#type_name> for WindValue {
+                        fn from(val: #type_name) -> Self {
+                            match val {
+                                #(#to_wind,)*
+                            }
+                        }
+                    }
+
+                    impl TryFrom<WindValue> for #type_name {
+                        type Error = WindError;
+
+                        fn try_from(value: WindValue) -> Result<Self> {
+                            #(#from_wind else)* {
+                                Err(WindError::TypeMismatch {
+                                    expected: #expected.to_string(),
+                                    actual: value.type_name().to_string(),
+                                })
+                            }
                         }
                     }
                 })
@@ -188,6 +316,89 @@ impl RustGenerator {
         })
     }
 
+    /// Expression converting `value`, a Rust value of `type_def`, into a
+    /// `WindValue`. Optional values, which have no `Into<WindValue>`, become
+    /// `Null` when absent, including inside arrays.
+    fn convert_to_wind(&self, value: TokenStream, type_def: &TypeDefinition) -> TokenStream {
+        match type_def {
+            TypeDefinition::Optional { inner_type } => {
+                let inner = self.convert_to_wind(quote! { v }, inner_type);
+                quote! {
+                    match #value {
+                        Some(v) => #inner,
+                        None => WindValue::Null,
+                    }
+                }
+            }
+            TypeDefinition::Array { element_type } if Self::needs_mapping(element_type) => {
+                let element = self.convert_to_wind(quote! { item }, element_type);
+                quote! {
+                    WindValue::Array(#value.into_iter().map(|item| #element).collect())
+                }
+            }
+            _ => quote! { #value.into() },
+        }
+    }
+
+    /// Expression converting `value`, a `WindValue`, into a
+    /// `Result` of the Rust type of `type_def`, checking its type on the way
+    fn convert_from_wind(
+        &self,
+        value: TokenStream,
+        type_def: &TypeDefinition,
+    ) -> Result<TokenStream> {
+        Ok(match type_def {
+            TypeDefinition::Optional { inner_type } => {
+                let inner = self.convert_from_wind(quote! { v }, inner_type)?;
+                quote! {
+                    match #value {
+                        WindValue::Null => Ok(None),
+                        v => (#inner).map(Some),
+                    }
+                }
+            }
+            TypeDefinition::Array { element_type } if Self::needs_mapping(element_type) => {
+                let element = self.convert_from_wind(quote! { item }, element_type)?;
+                quote! {
+                    match #value {
+                        WindValue::Array(items) => items
+                            .into_iter()
+                            .enumerate()
+                            .map(|(index, item)| {
+                                (#element).map_err(|e| WindError::InvalidElement {
+                                    location: format!("index {}", index),
+                                    source: Box::new(e),
+                                })
+                            })
+                            .collect::<Result<Vec<_>>>(),
+                        other => Err(WindError::TypeMismatch {
+                            expected: "Array".to_string(),
+                            actual: other.type_name().to_string(),
+                        }),
+                    }
+                }
+            }
+            // Inline types stay `WindValue`s; see `type_to_rust`
+            TypeDefinition::Struct { .. }
+            | TypeDefinition::Enum { .. }
+            | TypeDefinition::Union { .. } => quote! { Ok::<WindValue, WindError>(#value) },
+            _ => {
+                let rust_type = self.type_to_rust(type_def)?;
+                quote! { <#rust_type>::try_from(#value) }
+            }
+        })
+    }
+
+    /// Whether values of `type_def` need converting one by one rather than
+    /// through the `WindValue` conversions of their Rust type
+    fn needs_mapping(type_def: &TypeDefinition) -> bool {
+        match type_def {
+            TypeDefinition::Optional { .. } => true,
+            TypeDefinition::Array { element_type } => Self::needs_mapping(element_type),
+            _ => false,
+        }
+    }
+
     fn type_to_rust(&self, type_def: &TypeDefinition) -> Result<TokenStream> {
         match type_def {
             TypeDefinition::Primitive { primitive_type } => {
@@ -199,6 +410,7 @@ impl RustGenerator {
                     PrimitiveType::F64 => quote! { f64 },
                     PrimitiveType::String => quote! { String },
                     PrimitiveType::Bytes => quote! { Vec<u8> },
+                    PrimitiveType::Timestamp => quote! { std::time::SystemTime },
                 };
                 Ok(rust_type)
             }
@@ -218,6 +430,14 @@ impl RustGenerator {
                 // For enum references, we'd need the name from context
                 Ok(quote! { WindValue }) // Fallback
             }
+            TypeDefinition::Reference { name } => {
+                let type_name = format_ident!("{}", name);
+                Ok(quote! { #type_name })
+            }
+            TypeDefinition::Union { .. } => {
+                // Inline unions have no name to generate an enum under
+                Ok(quote! { WindValue })
+            }
         }
     }
 }
@@ -1,10 +1,186 @@
 use crate::idl::*;
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use serde_json;
+use std::collections::HashMap;
+use wind_core::{Schema, WindType};
 
-/// Parse WIND IDL from JSON format
+/// Parse WIND IDL from JSON format.
+///
+/// Syntax errors report the line, column and (where serde can tell) the path
+/// of the offending element, e.g. `types.Temperature.fields.value`. The
+/// parsed IDL is then checked for dangling type references.
 pub fn parse_idl(idl_json: &str) -> Result<WindIdl> {
-    serde_json::from_str(idl_json).map_err(|e| anyhow!("Failed to parse IDL: {}", e))
+    let deserializer = &mut serde_json::Deserializer::from_str(idl_json);
+    let idl: WindIdl = serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        let inner = e.into_inner();
+        // serde_json appends the position itself; report it once, up front
+        let message = inner.to_string();
+        let position = format!(" at line {} column {}", inner.line(), inner.column());
+        let message = message.strip_suffix(&position).unwrap_or(&message);
+        let location = if path == "." {
+            String::new()
+        } else {
+            format!(" (at `{}`)", path)
+        };
+        anyhow!(
+            "Failed to parse IDL at line {}, column {}{}: {}",
+            inner.line(),
+            inner.column(),
+            location,
+            message
+        )
+    })?;
+
+    validate_idl(&idl)?;
+    Ok(idl)
+}
+
+/// Check invariants serde can't express: every `Reference` must name a
+/// declared type and every `Union` needs members
+fn validate_idl(idl: &WindIdl) -> Result<()> {
+    let mut type_names: Vec<_> = idl.types.keys().collect();
+    type_names.sort();
+    for name in type_names {
+        check_references(idl, &idl.types[name], &format!("types.{}", name))?;
+    }
+
+    let mut service_names: Vec<_> = idl.services.keys().collect();
+    service_names.sort();
+    for service_name in service_names {
+        let service = &idl.services[service_name];
+        let path = format!("services.{}", service_name);
+
+        let mut methods: Vec<_> = service.methods.iter().collect();
+        methods.sort_by_key(|(name, _)| *name);
+        for (method_name, method) in methods {
+            let method_path = format!("{}.methods.{}", path, method_name);
+            check_references(idl, &method.params, &format!("{}.params", method_path))?;
+            check_references(idl, &method.returns, &format!("{}.returns", method_path))?;
+        }
+
+        let mut publications: Vec<_> = service.publications.iter().collect();
+        publications.sort_by_key(|(name, _)| *name);
+        for (publication_name, publication) in publications {
+            let publication_path = format!("{}.publications.{}.data_type", path, publication_name);
+            check_references(idl, &publication.data_type, &publication_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Build the wind-core `Schema` for the struct type `name`, for validating
+/// values at runtime. Optional fields become `WindType::Optional`, so they may
+/// be missing or null but are checked when present; nested structs only check
+/// that the field is a map.
+pub fn schema_for_type(idl: &WindIdl, name: &str) -> Result<Schema> {
+    let fields = match idl.types.get(name) {
+        Some(TypeDefinition::Struct { fields }) => fields,
+        Some(_) => bail!("Type `{}` is not a struct", name),
+        None => bail!("Type `{}` is not defined in {}", name, idl.name),
+    };
+
+    let mut schema_fields = HashMap::new();
+    for (field_name, field) in fields {
+        let mut field_type = wind_type(idl, &field.field_type, &mut Vec::new())?;
+        if field.optional && !matches!(field_type, WindType::Optional(_)) {
+            field_type = WindType::Optional(Box::new(field_type));
+        }
+        schema_fields.insert(field_name.clone(), field_type);
+    }
+
+    Ok(Schema {
+        id: format!("{}@{}", name, idl.version),
+        version: 1,
+        name: name.to_string(),
+        description: idl.description.clone(),
+        fields: schema_fields,
+    })
+}
+
+/// `resolving` holds the references being expanded, to reject cycles
+fn wind_type<'a>(
+    idl: &'a WindIdl,
+    type_def: &'a TypeDefinition,
+    resolving: &mut Vec<&'a str>,
+) -> Result<WindType> {
+    Ok(match type_def {
+        TypeDefinition::Primitive { primitive_type } => match primitive_type {
+            PrimitiveType::Bool => WindType::Bool,
+            PrimitiveType::I32 => WindType::I32,
+            PrimitiveType::I64 => WindType::I64,
+            PrimitiveType::F32 => WindType::F32,
+            PrimitiveType::F64 => WindType::F64,
+            PrimitiveType::String => WindType::String,
+            PrimitiveType::Bytes => WindType::Bytes,
+            PrimitiveType::Timestamp => WindType::Timestamp,
+        },
+        TypeDefinition::Struct { .. } => WindType::Map(Box::new(WindType::Any)),
+        TypeDefinition::Enum { .. } => WindType::String,
+        TypeDefinition::Array { element_type } => {
+            WindType::Array(Box::new(wind_type(idl, element_type, resolving)?))
+        }
+        TypeDefinition::Optional { inner_type } => {
+            WindType::Optional(Box::new(wind_type(idl, inner_type, resolving)?))
+        }
+        TypeDefinition::Reference { name } => {
+            let Some(referenced) = idl.types.get(name) else {
+                bail!("Reference to undefined type `{}`", name);
+            };
+            if resolving.contains(&name.as_str()) {
+                bail!("Type `{}` refers to itself", name);
+            }
+            resolving.push(name);
+            let resolved = wind_type(idl, referenced, resolving)?;
+            resolving.pop();
+            resolved
+        }
+        TypeDefinition::Union { members } => WindType::Union(
+            members
+                .iter()
+                .map(|member| wind_type(idl, member, resolving))
+                .collect::<Result<_>>()?,
+        ),
+    })
+}
+
+fn check_references(idl: &WindIdl, type_def: &TypeDefinition, path: &str) -> Result<()> {
+    match type_def {
+        TypeDefinition::Reference { name } => {
+            if !idl.types.contains_key(name) {
+                bail!(
+                    "Invalid IDL at `{}`: reference to undefined type `{}`",
+                    path,
+                    name
+                );
+            }
+        }
+        TypeDefinition::Struct { fields } => {
+            let mut fields: Vec<_> = fields.iter().collect();
+            fields.sort_by_key(|(name, _)| *name);
+            for (field_name, field) in fields {
+                let field_path = format!("{}.fields.{}", path, field_name);
+                check_references(idl, &field.field_type, &field_path)?;
+            }
+        }
+        TypeDefinition::Array { element_type } => {
+            check_references(idl, element_type, &format!("{}.element_type", path))?;
+        }
+        TypeDefinition::Optional { inner_type } => {
+            check_references(idl, inner_type, &format!("{}.inner_type", path))?;
+        }
+        TypeDefinition::Union { members } => {
+            if members.is_empty() {
+                bail!("Invalid IDL at `{}`: union has no members", path);
+            }
+            for (index, member) in members.iter().enumerate() {
+                check_references(idl, member, &format!("{}.members.{}", path, index))?;
+            }
+        }
+        TypeDefinition::Primitive { .. } | TypeDefinition::Enum { .. } => {}
+    }
+    Ok(())
 }
 
 /// Example IDL schema for a temperature sensor
@@ -146,3 +322,165 @@ pub fn example_sensor_idl() -> String {
     })
     .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wind_core::WindValue;
+
+    #[test]
+    fn test_example_idl_parses() {
+        let idl = parse_idl(&example_sensor_idl()).unwrap();
+        assert_eq!(idl.name, "TemperatureSensor");
+        assert_eq!(idl.types.len(), 2);
+    }
+
+    #[test]
+    fn test_syntax_error_reports_location() {
+        let idl = r#"{
+  "name": "Broken",
+  "version": "1.0.0",
+  "description": null,
+  "types": {
+    "Temperature": {
+      "type": "Struct",
+      "fields": {
+        "value": { "field_type": { "type": "Primitive", "primitive_type": "F64" } "optional": false }
+      }
+    }
+  },
+  "services": {}
+}"#;
+        let error = parse_idl(idl).unwrap_err().to_string();
+        assert!(error.contains("line 9, column"), "{}", error);
+        assert!(error.contains("`types.Temperature"), "{}", error);
+    }
+
+    #[test]
+    fn test_duplicate_type_name_rejected() {
+        let idl = r#"{
+  "name": "Dupes",
+  "version": "1.0.0",
+  "description": null,
+  "types": {
+    "Reading": { "type": "Enum", "variants": ["A"] },
+    "Reading": { "type": "Enum", "variants": ["B"] }
+  },
+  "services": {}
+}"#;
+        let error = parse_idl(idl).unwrap_err().to_string();
+        assert!(error.contains("duplicate name `Reading`"), "{}", error);
+        assert!(error.contains("(at `types`)"), "{}", error);
+    }
+
+    #[test]
+    fn test_union_type_generates_enum() {
+        let idl = r#"{
+  "name": "Polymorphic",
+  "version": "1.0.0",
+  "description": null,
+  "types": {
+    "Reading": {
+      "type": "Union",
+      "members": [
+        { "type": "Primitive", "primitive_type": "F64" },
+        { "type": "Primitive", "primitive_type": "String" }
+      ]
+    },
+    "Empty": { "type": "Union", "members": [] }
+  },
+  "services": {}
+}"#;
+        let error = parse_idl(idl).unwrap_err().to_string();
+        assert!(
+            error.contains("`types.Empty`: union has no members"),
+            "{}",
+            error
+        );
+
+        let idl = parse_idl(&idl.replace(
+            r#",
+    "Empty": { "type": "Union", "members": [] }"#,
+            "",
+        ))
+        .unwrap();
+        let code = crate::RustGenerator::new().generate(&idl).unwrap();
+        assert!(
+            code.contains("pub enum Reading { F64 (f64) , String (String) , }"),
+            "{}",
+            code
+        );
+        assert!(code.contains("\"one of F64 | String\""), "{}", code);
+    }
+
+    #[test]
+    fn test_dangling_reference_reported() {
+        let idl = r#"{
+  "name": "Dangling",
+  "version": "1.0.0",
+  "description": null,
+  "types": {
+    "Temperature": { "type": "Struct", "fields": {} }
+  },
+  "services": {
+    "Sensor": {
+      "description": null,
+      "methods": {
+        "calibrate": {
+          "description": null,
+          "params": { "type": "Reference", "name": "CalibrationCommand" },
+          "returns": { "type": "Primitive", "primitive_type": "Bool" }
+        }
+      },
+      "publications": {
+        "temperature": {
+          "description": null,
+          "data_type": { "type": "Reference", "name": "Temperature" }
+        }
+      }
+    }
+  }
+}"#;
+        let error = parse_idl(idl).unwrap_err().to_string();
+        assert!(
+            error.contains("`services.Sensor.methods.calibrate.params`"),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains("undefined type `CalibrationCommand`"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_schema_for_type_checks_optional_fields_when_present() {
+        let idl = parse_idl(&example_sensor_idl()).unwrap();
+
+        let schema = schema_for_type(&idl, "CalibrationCommand").unwrap();
+        assert_eq!(schema.id, "CalibrationCommand@1.0.0");
+        assert_eq!(schema.fields.len(), 2);
+        assert_eq!(schema.fields["offset"], WindType::F64);
+        assert_eq!(
+            schema.fields["scale"],
+            WindType::Optional(Box::new(WindType::F64))
+        );
+
+        let command = |scale: Option<WindValue>| {
+            let mut fields = HashMap::from([("offset".to_string(), WindValue::F64(0.5))]);
+            fields.extend(scale.map(|scale| ("scale".to_string(), scale)));
+            WindValue::Map(fields)
+        };
+        assert!(schema.validate(&command(None)).is_ok());
+        assert!(schema.validate(&command(Some(WindValue::Null))).is_ok());
+        assert!(schema.validate(&command(Some(WindValue::F64(1.5)))).is_ok());
+        assert!(schema
+            .validate(&command(Some(WindValue::from("double"))))
+            .is_err());
+
+        let temperature = schema_for_type(&idl, "Temperature").unwrap();
+        assert_eq!(temperature.fields["timestamp"], WindType::I64);
+        assert!(schema_for_type(&idl, "Humidity").is_err());
+    }
+}
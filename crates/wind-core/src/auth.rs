@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{Message, MessageCodec, MessagePayload, Result, WindError};
+
+/// Context of the `Error` a server replies with when it refuses a connection
+const AUTH_CONTEXT: &str = "Authenticating";
+
+/// Whether `given` is `expected`, taking as long wherever they first differ
+pub fn token_matches(expected: &str, given: &str) -> bool {
+    let (expected, given) = (expected.as_bytes(), given.as_bytes());
+    expected.len() == given.len()
+        && expected
+            .iter()
+            .zip(given)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Present `token` on a fresh connection and wait for the server to accept
+/// it. A refusal is `WindError::Unauthorized`; the server then hangs up.
+pub async fn authenticate<S>(stream: &mut S, token: &str) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let request = Message::new(MessagePayload::Authenticate {
+        token: token.to_string(),
+    });
+    MessageCodec::write(stream, &request).await?;
+    match MessageCodec::decode(stream).await?.payload {
+        MessagePayload::Authenticated => Ok(()),
+        MessagePayload::Error { error, .. } => Err(WindError::Unauthorized(error)),
+        other => Err(WindError::Protocol(format!(
+            "Unexpected reply to Authenticate: {}",
+            other.name()
+        ))),
+    }
+}
+
+/// What a server does with a message, see `TokenAuth::check`
+#[derive(Debug)]
+pub enum AuthCheck {
+    /// Handle the message as usual
+    Proceed,
+    /// Send this reply to an `Authenticate` and carry on
+    Accepted(Message),
+    /// Send this reply and close the connection
+    Rejected(Message),
+}
+
+/// Server side of one connection: with a token configured, every message
+/// before a matching `Authenticate` is refused. Without one, `Authenticate`
+/// is acknowledged whatever its token, so clients can always send it.
+///
+/// A client token authenticates the connection as its client id, which the
+/// connection can't `Identify` away from and no other connection can claim.
+#[derive(Debug, Clone)]
+pub struct TokenAuth {
+    token: Option<String>,
+    /// Tokens that authenticate as one client, by client id
+    client_tokens: Arc<HashMap<String, String>>,
+    authenticated: bool,
+    identity: Option<String>,
+}
+
+impl TokenAuth {
+    pub fn new(token: Option<String>) -> Self {
+        Self {
+            authenticated: token.is_none(),
+            token,
+            client_tokens: Arc::default(),
+            identity: None,
+        }
+    }
+
+    /// Also accept these per-client tokens, by client id
+    pub fn with_client_tokens(mut self, client_tokens: Arc<HashMap<String, String>>) -> Self {
+        self.client_tokens = client_tokens;
+        self
+    }
+
+    /// Client id the connection's token authenticated it as, if it was a
+    /// client token
+    pub fn identity(&self) -> Option<&str> {
+        self.identity.as_deref()
+    }
+
+    pub fn check(&mut self, payload: &MessagePayload) -> AuthCheck {
+        if let MessagePayload::Authenticate { token: given } = payload {
+            let client = self
+                .client_tokens
+                .iter()
+                .find(|(_, token)| token_matches(token, given));
+            if let Some((client_id, _)) = client {
+                self.identity = Some(client_id.clone());
+                self.authenticated = true;
+                return AuthCheck::Accepted(Message::new(MessagePayload::Authenticated));
+            }
+        }
+        match (payload, &self.token) {
+            (MessagePayload::Authenticate { token: given }, Some(token))
+                if !token_matches(token, given) =>
+            {
+                AuthCheck::Rejected(Self::refusal("Invalid token".to_string()))
+            }
+            (MessagePayload::Authenticate { .. }, _) => {
+                self.authenticated = true;
+                AuthCheck::Accepted(Message::new(MessagePayload::Authenticated))
+            }
+            (MessagePayload::Identify { client_id }, _)
+                if self.authenticated && !self.may_identify_as(client_id) =>
+            {
+                AuthCheck::Rejected(Self::refusal(format!(
+                    "Identifying as {} requires its token",
+                    client_id
+                )))
+            }
+            _ if self.authenticated => AuthCheck::Proceed,
+            _ => AuthCheck::Rejected(Self::refusal(format!(
+                "Authentication required before {}",
+                payload.name()
+            ))),
+        }
+    }
+
+    fn may_identify_as(&self, client_id: &str) -> bool {
+        match &self.identity {
+            Some(identity) => identity == client_id,
+            None => !self.client_tokens.contains_key(client_id),
+        }
+    }
+
+    fn refusal(error: String) -> Message {
+        Message::new(MessagePayload::Error {
+            error,
+            context: Some(AUTH_CONTEXT.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn authenticate(token: &str) -> MessagePayload {
+        MessagePayload::Authenticate {
+            token: token.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_token_is_required_before_anything_else() {
+        let mut auth = TokenAuth::new(Some("s3cret".to_string()));
+        assert!(matches!(
+            auth.check(&MessagePayload::Ping),
+            AuthCheck::Rejected(_)
+        ));
+        assert!(matches!(
+            auth.check(&authenticate("s3cre")),
+            AuthCheck::Rejected(_)
+        ));
+        assert!(matches!(
+            auth.check(&authenticate("s3cret")),
+            AuthCheck::Accepted(_)
+        ));
+        assert!(matches!(
+            auth.check(&MessagePayload::Ping),
+            AuthCheck::Proceed
+        ));
+    }
+
+    #[test]
+    fn test_servers_without_a_token_accept_any() {
+        let mut auth = TokenAuth::new(None);
+        assert!(matches!(
+            auth.check(&MessagePayload::Ping),
+            AuthCheck::Proceed
+        ));
+        assert!(matches!(
+            auth.check(&authenticate("anything")),
+            AuthCheck::Accepted(_)
+        ));
+    }
+
+    #[test]
+    fn test_client_tokens_fix_the_identity() {
+        let client_tokens = Arc::new(HashMap::from([(
+            "operator".to_string(),
+            "op-s3cret".to_string(),
+        )]));
+        let identify = |client_id: &str| MessagePayload::Identify {
+            client_id: client_id.to_string(),
+        };
+
+        let mut auth =
+            TokenAuth::new(Some("s3cret".to_string())).with_client_tokens(client_tokens.clone());
+        assert!(matches!(
+            auth.check(&authenticate("op-s3cret")),
+            AuthCheck::Accepted(_)
+        ));
+        assert_eq!(auth.identity(), Some("operator"));
+        assert!(matches!(
+            auth.check(&identify("operator")),
+            AuthCheck::Proceed
+        ));
+        assert!(matches!(
+            auth.check(&identify("sensor-gw")),
+            AuthCheck::Rejected(_)
+        ));
+
+        // The shared token, or none at all, can't claim a client token's id
+        for token in [Some("s3cret".to_string()), None] {
+            let mut auth = TokenAuth::new(token).with_client_tokens(client_tokens.clone());
+            assert!(matches!(
+                auth.check(&authenticate("s3cret")),
+                AuthCheck::Accepted(_)
+            ));
+            assert_eq!(auth.identity(), None);
+            assert!(matches!(
+                auth.check(&identify("sensor-gw")),
+                AuthCheck::Proceed
+            ));
+            assert!(matches!(
+                auth.check(&identify("operator")),
+                AuthCheck::Rejected(_)
+            ));
+        }
+    }
+}
@@ -1,33 +1,336 @@
-use crate::{Message, Result};
+use crate::{Message, Result, SerializationFormat, WindError};
 use bytes::{BufMut, BytesMut};
+use std::borrow::Cow;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::trace;
+use uuid::Uuid;
 
-const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024; // 16MB limit
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024; // 16MB limit
 
-pub struct MessageCodec;
+/// zstd level for compressed frames, favouring speed over ratio
+const ZSTD_LEVEL: i32 = 1;
+
+/// Bytes added to a message by its frame: length prefix, flag and checksum
+const FRAME_OVERHEAD: usize = 4 + 1 + 4;
+
+/// Largest length prefix of a valid frame
+const MAX_FRAME_LEN: usize = MAX_MESSAGE_SIZE + FRAME_OVERHEAD - 4;
+
+/// First byte of a frame from a peer that predates the flag byte, the
+/// serialized length of the message id
+const UNFLAGGED_FRAME_START: u8 = 16;
+
+/// Bytes of a raw bincode frame up to the end of its message id: the flag,
+/// then the id's length and bytes
+const BINCODE_HEADER_LEN: usize = 1 + 8 + 16;
+
+/// The flag byte holds the `Codec` in its low bits and the
+/// `SerializationFormat` from this bit up
+const FORMAT_SHIFT: u32 = 5;
+
+/// How a frame's message is compressed, sent in the flag byte after the
+/// length prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Raw = 0,
+    Zstd = 1,
+    Lz4 = 2,
+}
+
+impl Codec {
+    fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            0 => Some(Codec::Raw),
+            1 => Some(Codec::Zstd),
+            2 => Some(Codec::Lz4),
+            _ => None,
+        }
+    }
+}
+
+/// Trace an encoded message, inside the span of the value it carries if any
+fn trace_encoded(msg: &Message, bytes: usize) {
+    trace!(
+        message.id = %msg.id,
+        kind = msg.payload.name(),
+        bytes,
+        "Encoded message"
+    );
+}
+
+fn flag(codec: Codec, format: SerializationFormat) -> u8 {
+    codec as u8 | (format as u8) << FORMAT_SHIFT
+}
+
+/// A frame `decode_within` refused for its length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OversizedFrame {
+    /// Length of the frame, from its prefix
+    pub len: usize,
+    /// Id of the message, if the frame is uncompressed bincode and so its
+    /// header could be read without the rest
+    pub id: Option<Uuid>,
+}
+
+/// The message id at the start of a frame's contents, for raw bincode frames
+/// with or without the flag byte
+fn bincode_message_id(header: &[u8]) -> Option<Uuid> {
+    let serialized = match *header.first()? {
+        UNFLAGGED_FRAME_START => header,
+        flag_byte if flag_byte == flag(Codec::Raw, SerializationFormat::Bincode) => &header[1..],
+        _ => return None,
+    };
+    bincode::deserialize(serialized).ok()
+}
+
+/// A message's frame contents, ready to go after the length prefix
+enum FrameBody {
+    /// Uncompressed bincode of this many bytes, serialized straight into the
+    /// output
+    Bincode(usize),
+    /// Bytes already serialized, and maybe compressed, with their flag
+    Encoded(u8, Vec<u8>),
+}
+
+impl FrameBody {
+    fn len(&self) -> usize {
+        match self {
+            FrameBody::Bincode(len) => *len,
+            FrameBody::Encoded(_, bytes) => bytes.len(),
+        }
+    }
+}
+
+/// Frames are a big-endian `u32` length, then that many bytes: a flag, the
+/// message serialized and compressed as the flag says, and a big-endian
+/// CRC32C of the flag and message. The associated functions write raw
+/// bincode frames; a codec built by `with_compression` compresses the larger
+/// messages it encodes, and one given `with_format` writes that format.
+/// Decoding handles every format and codec.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessageCodec {
+    /// Codec for messages serializing to more than the threshold's bytes
+    compression: Option<(Codec, usize)>,
+    format: SerializationFormat,
+}
 
 impl MessageCodec {
+    /// A codec compressing messages over `threshold` serialized bytes with
+    /// `codec`. Messages that don't shrink are sent raw.
+    pub fn with_compression(codec: Codec, threshold: usize) -> Self {
+        Self {
+            compression: Some((codec, threshold)),
+            ..Self::default()
+        }
+    }
+
+    /// Serialize messages as `format` rather than bincode
+    pub fn with_format(mut self, format: SerializationFormat) -> Self {
+        self.format = format;
+        self
+    }
+
     /// Encode message to bytes with length prefix
     pub fn encode(msg: &Message) -> Result<BytesMut> {
-        let data = bincode::serialize(msg)?;
-        if data.len() > MAX_MESSAGE_SIZE {
+        Self::default().encode_message(msg)
+    }
+
+    /// Append a length-prefixed message to `buf`, serializing straight into
+    /// it so a large payload isn't copied through an intermediate buffer
+    pub fn encode_into(buf: &mut Vec<u8>, msg: &Message) -> Result<()> {
+        Self::default().encode_message_into(buf, msg)
+    }
+
+    /// Encode a message into a new buffer, for code that has no `BytesMut`
+    pub fn encode_to_vec(msg: &Message) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        Self::encode_into(&mut buf, msg)?;
+        Ok(buf)
+    }
+
+    /// Encode a message as `encode` does, in the codec's format and
+    /// compressing it if configured to
+    pub fn encode_message(&self, msg: &Message) -> Result<BytesMut> {
+        let body = self.frame_body(msg)?;
+        trace_encoded(msg, body.len());
+        let mut buf = BytesMut::with_capacity(FRAME_OVERHEAD + body.len());
+        Self::put_frame(&mut buf, msg, body)?;
+        Ok(buf)
+    }
+
+    /// Append a message to `buf` as `encode_into` does, in the codec's
+    /// format and compressing it if configured to
+    pub fn encode_message_into(&self, buf: &mut Vec<u8>, msg: &Message) -> Result<()> {
+        let body = self.frame_body(msg)?;
+        trace_encoded(msg, body.len());
+        buf.reserve(FRAME_OVERHEAD + body.len());
+        Self::put_frame(buf, msg, body)
+    }
+
+    /// Serialize and compress `msg` as configured, except that uncompressed
+    /// bincode is left to be written straight into the output
+    fn frame_body(&self, msg: &Message) -> Result<FrameBody> {
+        let serialized = match self.format {
+            SerializationFormat::Bincode => None,
+            format => Some(format.serializer().serialize(msg)?),
+        };
+        let len = match &serialized {
+            Some(serialized) => serialized.len(),
+            None => bincode::serialized_size(msg)? as usize,
+        };
+        if len > MAX_MESSAGE_SIZE {
             return Err(crate::WindError::Protocol(format!(
                 "Message too large: {} bytes",
-                data.len()
+                len
             )));
         }
 
-        let mut buf = BytesMut::with_capacity(4 + data.len());
-        buf.put_u32(data.len() as u32);
-        buf.extend_from_slice(&data);
-        Ok(buf)
+        let codec = match self.compression {
+            Some((codec, threshold)) if len > threshold => codec,
+            _ => Codec::Raw,
+        };
+        if codec == Codec::Raw {
+            return Ok(match serialized {
+                Some(serialized) => FrameBody::Encoded(flag(Codec::Raw, self.format), serialized),
+                None => FrameBody::Bincode(len),
+            });
+        }
+        let serialized = match serialized {
+            Some(serialized) => serialized,
+            None => bincode::serialize(msg)?,
+        };
+        let compressed = match codec {
+            Codec::Zstd => zstd::bulk::compress(&serialized, ZSTD_LEVEL)?,
+            _ => lz4_flex::compress_prepend_size(&serialized),
+        };
+        Ok(if compressed.len() < len {
+            FrameBody::Encoded(flag(codec, self.format), compressed)
+        } else {
+            FrameBody::Encoded(flag(Codec::Raw, self.format), serialized)
+        })
+    }
+
+    /// Write the frame for `msg`, with contents `body`, into `buf`
+    fn put_frame<B: BufMut + AsRef<[u8]>>(
+        buf: &mut B,
+        msg: &Message,
+        body: FrameBody,
+    ) -> Result<()> {
+        buf.put_u32((FRAME_OVERHEAD - 4 + body.len()) as u32);
+        let contents_start = buf.as_ref().len();
+        match body {
+            FrameBody::Bincode(_) => {
+                buf.put_u8(flag(Codec::Raw, SerializationFormat::Bincode));
+                bincode::serialize_into((&mut *buf).writer(), msg)?;
+            }
+            FrameBody::Encoded(flag, bytes) => {
+                buf.put_u8(flag);
+                buf.put_slice(&bytes);
+            }
+        }
+        let checksum = crc32c::crc32c(&buf.as_ref()[contents_start..]);
+        buf.put_u32(checksum);
+        Ok(())
+    }
+
+    /// Decode a frame's contents, after its length prefix, failing with
+    /// `WindError::Corruption` if they don't match their checksum. During
+    /// the move to flagged frames, one without a flag or checksum (from a
+    /// peer that predates them) is read as raw.
+    pub fn decode_frame(frame: &[u8]) -> Result<Message> {
+        Self::decode_frame_with_format(frame).map(|(msg, _)| msg)
+    }
+
+    /// Decode a frame's contents as `decode_frame` does, along with the
+    /// format its sender chose, e.g. to reply in kind
+    pub fn decode_frame_with_format(frame: &[u8]) -> Result<(Message, SerializationFormat)> {
+        let Some((contents, checksum)) = frame.split_last_chunk::<4>() else {
+            return Err(WindError::Protocol(format!(
+                "Frame too short: {} bytes",
+                frame.len()
+            )));
+        };
+        if crc32c::crc32c(contents) != u32::from_be_bytes(*checksum) {
+            if frame[0] == UNFLAGGED_FRAME_START {
+                return Ok((bincode::deserialize(frame)?, SerializationFormat::Bincode));
+            }
+            return Err(WindError::Corruption(format!(
+                "Checksum mismatch in a {} byte frame",
+                frame.len()
+            )));
+        }
+        let (&flag, data) = contents
+            .split_first()
+            .ok_or_else(|| WindError::Protocol("Frame without a codec flag".to_string()))?;
+        let unknown = || WindError::Protocol(format!("Unknown frame flag {:#04x}", flag));
+        let codec = Codec::from_bits(flag & ((1 << FORMAT_SHIFT) - 1)).ok_or_else(unknown)?;
+        let format = SerializationFormat::from_bits(flag >> FORMAT_SHIFT).ok_or_else(unknown)?;
+
+        let serialized = match codec {
+            Codec::Raw => Cow::Borrowed(data),
+            Codec::Zstd => Cow::Owned(zstd::bulk::decompress(data, MAX_MESSAGE_SIZE)?),
+            Codec::Lz4 => {
+                let invalid = |e: lz4_flex::block::DecompressError| {
+                    WindError::Protocol(format!("Invalid lz4 frame: {}", e))
+                };
+                let (len, compressed) =
+                    lz4_flex::block::uncompressed_size(data).map_err(invalid)?;
+                if len > MAX_MESSAGE_SIZE {
+                    return Err(WindError::Protocol(format!(
+                        "Message too large: {} bytes",
+                        len
+                    )));
+                }
+                Cow::Owned(lz4_flex::block::decompress(compressed, len).map_err(invalid)?)
+            }
+        };
+        let msg = format.serializer().deserialize(&serialized)?;
+        trace!(
+            message.id = %msg.id,
+            kind = msg.payload.name(),
+            bytes = frame.len(),
+            "Decoded message"
+        );
+        Ok((msg, format))
+    }
+
+    /// Decode the frame at the start of `buf`, returning the message and the
+    /// number of bytes the frame took up. Fails with `WindError::Incomplete`
+    /// if `buf` ends before the frame does.
+    pub fn decode_from_slice(buf: &[u8]) -> Result<(Message, usize)> {
+        let Some((prefix, rest)) = buf.split_first_chunk::<4>() else {
+            return Err(WindError::Incomplete {
+                needed: 4 - buf.len(),
+            });
+        };
+        let len = u32::from_be_bytes(*prefix) as usize;
+        if len > MAX_FRAME_LEN {
+            return Err(WindError::Protocol(format!(
+                "Message too large: {} bytes",
+                len
+            )));
+        }
+        let Some(frame) = rest.get(..len) else {
+            return Err(WindError::Incomplete {
+                needed: len - rest.len(),
+            });
+        };
+        Ok((Self::decode_frame(frame)?, 4 + len))
     }
 
     /// Decode message from reader
     pub async fn decode<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Message> {
+        Self::decode_with_format(reader).await.map(|(msg, _)| msg)
+    }
+
+    /// Decode a message as `decode` does, along with the format its sender
+    /// chose
+    pub async fn decode_with_format<R: AsyncRead + Unpin>(
+        reader: &mut R,
+    ) -> Result<(Message, SerializationFormat)> {
         // Read length prefix
         let len = reader.read_u32().await? as usize;
-        if len > MAX_MESSAGE_SIZE {
+        if len > MAX_FRAME_LEN {
             return Err(crate::WindError::Protocol(format!(
                 "Message too large: {} bytes",
                 len
@@ -38,15 +341,267 @@ impl MessageCodec {
         let mut data = vec![0u8; len];
         reader.read_exact(&mut data).await?;
 
-        let msg = bincode::deserialize(&data)?;
-        Ok(msg)
+        Self::decode_frame_with_format(&data)
+    }
+
+    /// Decode a message whose frame is at most `max_len` bytes, along with
+    /// its format. Longer frames are read and discarded without being
+    /// buffered past their header, returning an `OversizedFrame` so the
+    /// caller can reject the request.
+    pub async fn decode_within<R: AsyncRead + Unpin>(
+        reader: &mut R,
+        max_len: usize,
+    ) -> Result<std::result::Result<(Message, SerializationFormat), OversizedFrame>> {
+        let len = reader.read_u32().await? as usize;
+        if len > MAX_FRAME_LEN {
+            return Err(crate::WindError::Protocol(format!(
+                "Message too large: {} bytes",
+                len
+            )));
+        }
+
+        if len > max_len {
+            let mut header = vec![0u8; len.min(BINCODE_HEADER_LEN)];
+            reader.read_exact(&mut header).await?;
+            let rest = (len - header.len()) as u64;
+            let skipped = tokio::io::copy(&mut reader.take(rest), &mut tokio::io::sink()).await?;
+            if skipped < rest {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+            return Ok(Err(OversizedFrame {
+                len,
+                id: bincode_message_id(&header),
+            }));
+        }
+
+        let mut data = vec![0u8; len];
+        reader.read_exact(&mut data).await?;
+        Ok(Ok(Self::decode_frame_with_format(&data)?))
     }
 
     /// Write encoded message to writer
     pub async fn write<W: AsyncWrite + Unpin>(writer: &mut W, msg: &Message) -> Result<()> {
-        let encoded = Self::encode(msg)?;
-        writer.write_all(&encoded).await?;
+        Self::default().write_message(writer, msg).await
+    }
+
+    /// Write a message as `write` does, compressing it if configured to
+    pub async fn write_message<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        msg: &Message,
+    ) -> Result<()> {
+        let encoded = self.encode_message(msg)?;
+        Self::write_encoded(writer, &encoded).await
+    }
+
+    /// Write frames already produced by `encode` or `encode_into`, so a
+    /// message sent to many peers is serialized only once
+    pub async fn write_encoded<W: AsyncWrite + Unpin>(
+        writer: &mut W,
+        encoded: &[u8],
+    ) -> Result<()> {
+        writer.write_all(encoded).await?;
         writer.flush().await?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MessagePayload, WindValue};
+    use std::sync::Arc;
+
+    const THRESHOLD: usize = 1024;
+
+    fn publish(bytes: usize) -> Message {
+        Message::new(MessagePayload::Publish {
+            service: "TEST/COMPRESSED".to_string(),
+            sequence: 1,
+            // Mostly zeros, like a padded sensor frame
+            value: Arc::new(WindValue::Bytes(vec![0u8; bytes])),
+            schema_id: None,
+            expires_at_us: None,
+        })
+    }
+
+    fn value(msg: &Message) -> &WindValue {
+        match &msg.payload {
+            MessagePayload::Publish { value, .. } => value,
+            other => panic!("Expected a publish, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_with_every_codec() {
+        for codec in [Codec::Raw, Codec::Zstd, Codec::Lz4] {
+            let compressing = MessageCodec::with_compression(codec, THRESHOLD);
+            for (bytes, compressed) in [(100, false), (32 * 1024, codec != Codec::Raw)] {
+                let msg = publish(bytes);
+                let encoded = compressing.encode_message(&msg).unwrap();
+                let expected_flag = if compressed { codec } else { Codec::Raw };
+                assert_eq!(
+                    encoded[4], expected_flag as u8,
+                    "{:?}, {} bytes",
+                    codec, bytes
+                );
+                let raw_len = MessageCodec::encode(&msg).unwrap().len();
+                assert_eq!(encoded.len() < raw_len, compressed);
+
+                let decoded = MessageCodec::decode(&mut &encoded[..]).await.unwrap();
+                assert_eq!(decoded.id, msg.id);
+                assert_eq!(value(&decoded), value(&msg));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frame_without_flag_is_read_as_raw() {
+        let msg = publish(100);
+        let body = bincode::serialize(&msg).unwrap();
+        let mut legacy = (body.len() as u32).to_be_bytes().to_vec();
+        legacy.extend_from_slice(&body);
+
+        let decoded = MessageCodec::decode(&mut &legacy[..]).await.unwrap();
+        assert_eq!(decoded.id, msg.id);
+        assert_eq!(value(&decoded), value(&msg));
+    }
+
+    #[tokio::test]
+    async fn test_flipped_byte_is_reported_as_corruption() {
+        for codec in [Codec::Raw, Codec::Zstd, Codec::Lz4] {
+            let msg = publish(32 * 1024);
+            let encoded = MessageCodec::with_compression(codec, THRESHOLD)
+                .encode_message(&msg)
+                .unwrap();
+            // A byte picked by the random message id, anywhere past the length prefix
+            let index = 4 + (msg.id.as_u128() % (encoded.len() as u128 - 4)) as usize;
+            for position in [index, 4, encoded.len() - 1] {
+                let mut corrupted = encoded.to_vec();
+                corrupted[position] ^= 0x5a;
+
+                let result = MessageCodec::decode(&mut &corrupted[..]).await;
+                assert!(
+                    matches!(result, Err(WindError::Corruption(_))),
+                    "{:?} at byte {}: {:?}",
+                    codec,
+                    position,
+                    result.map(|_| ())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_slice_round_trip_reports_bytes_consumed() {
+        let first = publish(100);
+        let second = publish(32 * 1024);
+        let mut buf = MessageCodec::encode_to_vec(&first).unwrap();
+        let first_len = buf.len();
+        MessageCodec::with_compression(Codec::Lz4, THRESHOLD)
+            .encode_message_into(&mut buf, &second)
+            .unwrap();
+
+        let (decoded, used) = MessageCodec::decode_from_slice(&buf).unwrap();
+        assert_eq!((decoded.id, used), (first.id, first_len));
+        let (decoded, used) = MessageCodec::decode_from_slice(&buf[first_len..]).unwrap();
+        assert_eq!((decoded.id, used), (second.id, buf.len() - first_len));
+        assert_eq!(value(&decoded), value(&second));
+    }
+
+    #[test]
+    fn test_partial_slice_needs_more_bytes() {
+        let encoded = MessageCodec::encode_to_vec(&publish(100)).unwrap();
+
+        for (available, expected) in [(2, 2), (4, encoded.len() - 4), (encoded.len() - 1, 1)] {
+            let result = MessageCodec::decode_from_slice(&encoded[..available]);
+            match result {
+                Err(WindError::Incomplete { needed }) => assert_eq!(needed, expected),
+                other => panic!("Expected an incomplete frame, got {:?}", other.map(|_| ())),
+            }
+        }
+    }
+
+    /// A frame with a valid checksum around `contents`
+    fn frame(contents: &[u8]) -> Vec<u8> {
+        let mut frame = ((contents.len() + 4) as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(contents);
+        frame.extend_from_slice(&crc32c::crc32c(contents).to_be_bytes());
+        frame
+    }
+
+    #[tokio::test]
+    async fn test_each_frame_is_decoded_in_its_own_format() {
+        let formats = [
+            SerializationFormat::Json,
+            SerializationFormat::MessagePack,
+            SerializationFormat::Bincode,
+        ];
+        let mut stream = Vec::new();
+        let mut sent = Vec::new();
+        for format in formats {
+            for codec in [Codec::Raw, Codec::Zstd] {
+                let msg = publish(32 * 1024);
+                MessageCodec::with_compression(codec, THRESHOLD)
+                    .with_format(format)
+                    .encode_message_into(&mut stream, &msg)
+                    .unwrap();
+                sent.push((msg, format));
+            }
+        }
+
+        let mut reader = &stream[..];
+        for (msg, format) in &sent {
+            let (decoded, decoded_format) =
+                MessageCodec::decode_with_format(&mut reader).await.unwrap();
+            assert_eq!((decoded.id, decoded_format), (msg.id, *format));
+            assert_eq!(value(&decoded), value(msg));
+        }
+        assert!(reader.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_format_is_an_error() {
+        let bincode = bincode::serialize(&publish(100)).unwrap();
+
+        // Labelled JSON, but bincode inside
+        let mut contents = vec![flag(Codec::Raw, SerializationFormat::Json)];
+        contents.extend_from_slice(&bincode);
+        let result = MessageCodec::decode(&mut &frame(&contents)[..]).await;
+        assert!(matches!(result, Err(WindError::Protocol(_))));
+
+        // A format from a newer peer
+        contents[0] = 3 << FORMAT_SHIFT;
+        let result = MessageCodec::decode(&mut &frame(&contents)[..]).await;
+        assert!(matches!(result, Err(WindError::Protocol(e)) if e.contains("Unknown frame flag")));
+    }
+
+    #[tokio::test]
+    async fn test_frame_over_the_limit_reports_its_message_id() {
+        let msg = publish(1024);
+        let mut stream = Vec::new();
+        MessageCodec::encode_into(&mut stream, &msg).unwrap();
+        MessageCodec::with_compression(Codec::Zstd, THRESHOLD)
+            .encode_message_into(&mut stream, &msg)
+            .unwrap();
+
+        let mut reader = &stream[..];
+        let raw = MessageCodec::decode_within(&mut reader, 512).await.unwrap();
+        assert!(matches!(raw, Err(OversizedFrame { id: Some(id), .. }) if id == msg.id));
+        // Compressed, so the id isn't read, but the frame is still skipped
+        let compressed = MessageCodec::decode_within(&mut reader, 16).await.unwrap();
+        assert!(matches!(compressed, Err(OversizedFrame { id: None, .. })));
+        assert!(reader.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_lz4_frame_is_rejected() {
+        let mut contents = vec![Codec::Lz4 as u8];
+        contents.extend_from_slice(&(MAX_MESSAGE_SIZE as u32 + 1).to_le_bytes());
+        contents.extend_from_slice(&[0u8; 16]);
+
+        assert!(MessageCodec::decode(&mut &frame(&contents)[..])
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Lightweight byte counters for a connection (or an aggregate of connections)
+#[derive(Debug, Default)]
+pub struct ByteCounters {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    writes: AtomicU64,
+}
+
+impl ByteCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total bytes read from the peer
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in.load(Ordering::Relaxed)
+    }
+
+    /// Total bytes written to the peer
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out.load(Ordering::Relaxed)
+    }
+
+    /// Number of writes that transferred data to the peer
+    pub fn writes(&self) -> u64 {
+        self.writes.load(Ordering::Relaxed)
+    }
+
+    fn add_in(&self, n: usize) {
+        self.bytes_in.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    fn add_out(&self, n: usize) {
+        self.bytes_out.fetch_add(n as u64, Ordering::Relaxed);
+        self.writes.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Stream wrapper that counts bytes read and written.
+///
+/// Every transfer is recorded in the per-connection counters and, when set,
+/// in a shared aggregate (e.g. a server-wide total).
+#[derive(Debug)]
+pub struct CountingStream<S> {
+    inner: S,
+    counters: Arc<ByteCounters>,
+    aggregate: Option<Arc<ByteCounters>>,
+}
+
+impl<S> CountingStream<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            counters: Arc::new(ByteCounters::new()),
+            aggregate: None,
+        }
+    }
+
+    /// Also record traffic in a shared aggregate
+    pub fn with_aggregate(mut self, aggregate: Arc<ByteCounters>) -> Self {
+        self.aggregate = Some(aggregate);
+        self
+    }
+
+    /// Counters for this stream only
+    pub fn counters(&self) -> Arc<ByteCounters> {
+        self.counters.clone()
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn record_in(&self, n: usize) {
+        self.counters.add_in(n);
+        if let Some(aggregate) = &self.aggregate {
+            aggregate.add_in(n);
+        }
+    }
+
+    fn record_out(&self, n: usize) {
+        self.counters.add_out(n);
+        if let Some(aggregate) = &self.aggregate {
+            aggregate.add_out(n);
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for CountingStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            let read = buf.filled().len() - before;
+            self.record_in(read);
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for CountingStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = result {
+            self.record_out(written);
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Message, MessageCodec, MessagePayload, WindValue};
+
+    #[tokio::test]
+    async fn test_counts_match_encoded_frames() {
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let total = Arc::new(ByteCounters::new());
+        let mut writer = CountingStream::new(a).with_aggregate(total.clone());
+        let mut reader = CountingStream::new(b);
+
+        let mut expected = 0u64;
+        for i in 0..10 {
+            let msg = Message::new(MessagePayload::Publish {
+                service: "TEST/BYTES".to_string(),
+                sequence: i,
+                value: Arc::new(WindValue::Bytes(vec![7u8; 100 * (i as usize + 1)])),
+                schema_id: None,
+                expires_at_us: None,
+            });
+            expected += MessageCodec::encode(&msg).unwrap().len() as u64;
+            MessageCodec::write(&mut writer, &msg).await.unwrap();
+            MessageCodec::decode(&mut reader).await.unwrap();
+        }
+
+        assert_eq!(writer.counters().bytes_out(), expected);
+        assert_eq!(writer.counters().writes(), 10);
+        assert_eq!(writer.counters().bytes_in(), 0);
+        assert_eq!(total.bytes_out(), expected);
+        assert_eq!(reader.counters().bytes_in(), expected);
+    }
+}
@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::SubscriptionMode;
+
+/// Snapshot of a running publisher's state, answered to a `DiagnosticDump`.
+/// Holds counts and settings only, never published values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublisherDiagnostics {
+    /// Service names the publisher serves
+    pub services: Vec<String>,
+    /// Sequence number of the latest publish, shared by all services
+    pub sequence: u64,
+    pub clients: Vec<ClientDiagnostics>,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Updates held back because a subscriber had no free buffer
+    pub throttled: u64,
+    /// Held-back updates dropped because a subscriber fell too far behind
+    pub dropped: u64,
+    /// Updates dropped because they went stale before being sent
+    pub expired: u64,
+}
+
+/// One client connection of a publisher
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientDiagnostics {
+    pub connection_id: Uuid,
+    /// Identity the client presented, if any
+    pub identity: Option<String>,
+    pub subscriptions: Vec<SubscriptionDiagnostics>,
+    /// Send credit left from the client's flow control, None if it has none
+    pub credit: Option<u64>,
+    /// Confirmed publishes still waiting for the client's ack
+    pub pending_acks: usize,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriptionDiagnostics {
+    pub service: String,
+    pub mode: SubscriptionMode,
+    pub reliable: bool,
+}
@@ -1,11 +1,13 @@
 use thiserror::Error;
 
+/// Variants wrapping another error leave it out of their message, as
+/// `source()` already reports it
 #[derive(Error, Debug)]
 pub enum WindError {
-    #[error("IO error: {0}")]
+    #[error("IO error")]
     Io(#[from] std::io::Error),
 
-    #[error("Serialization error: {0}")]
+    #[error("Serialization error")]
     Serialization(#[from] bincode::Error),
 
     #[error("Service not found: {0}")]
@@ -17,17 +19,66 @@ pub enum WindError {
     #[error("Connection error: {0}")]
     Connection(String),
 
+    #[error("Failed to connect to {address} after {attempts} attempts")]
+    ConnectFailed {
+        address: String,
+        attempts: u32,
+        source: std::io::Error,
+    },
+
     #[error("Registry error: {0}")]
     Registry(String),
 
     #[error("Protocol error: {0}")]
     Protocol(String),
 
+    /// A buffer that ends partway through a frame
+    #[error("Incomplete frame: need {needed} more bytes")]
+    Incomplete { needed: usize },
+
+    /// A frame whose checksum doesn't match its contents
+    #[error("Corrupted frame: {0}")]
+    Corruption(String),
+
+    /// A server refused the connection's token
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
     #[error("Schema error: {0}")]
     Schema(String),
 
     #[error("Timeout: {0}")]
     Timeout(String),
+
+    /// An element of a collection that failed to convert
+    #[error("Invalid element at {location}: {source}")]
+    InvalidElement {
+        location: String,
+        source: Box<WindError>,
+    },
+}
+
+// Lets infallible conversions, e.g. of a `WindValue` into itself, be used
+// where a `WindError` is expected
+impl From<std::convert::Infallible> for WindError {
+    fn from(never: std::convert::Infallible) -> Self {
+        match never {}
+    }
+}
+
+impl WindError {
+    /// Kind of the I/O error at the root of this one, if any, e.g. to tell a
+    /// refused connection from a timed out one
+    pub fn io_kind(&self) -> Option<std::io::ErrorKind> {
+        let mut error: Option<&(dyn std::error::Error + 'static)> = Some(self);
+        while let Some(current) = error {
+            if let Some(io) = current.downcast_ref::<std::io::Error>() {
+                return Some(io.kind());
+            }
+            error = current.source();
+        }
+        None
+    }
 }
 
 pub type Result<T> = std::result::Result<T, WindError>;
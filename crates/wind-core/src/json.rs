@@ -0,0 +1,196 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{Map, Number, Value};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::WindValue;
+
+/// Key of the single-entry JSON object standing in for `WindValue::Bytes`:
+/// `{"$bytes": "<base64>"}`
+pub const JSON_BYTES_KEY: &str = "$bytes";
+
+impl WindValue {
+    /// Convert JSON to a `WindValue`. Integers become `I64` and other numbers
+    /// `F64`, so `to_json` gives back the same JSON; an integer beyond the
+    /// range of `i64` becomes the nearest `F64`. An object with `JSON_BYTES_KEY`
+    /// as its only key and a base64 string value becomes `Bytes`.
+    pub fn from_json(json: Value) -> WindValue {
+        Self::convert_json(json, false)
+    }
+
+    /// Convert JSON as `from_json` does, except that strings holding an RFC
+    /// 3339 time, e.g. `2024-05-01T12:00:00.25Z`, become `Timestamp`s. For
+    /// input known to use such strings for times, since a string field that
+    /// merely happens to hold one would be converted too.
+    pub fn from_json_with_timestamps(json: Value) -> WindValue {
+        Self::convert_json(json, true)
+    }
+
+    fn convert_json(json: Value, timestamps: bool) -> WindValue {
+        let convert = |json| Self::convert_json(json, timestamps);
+        match json {
+            Value::Null => WindValue::Null,
+            Value::Bool(b) => WindValue::Bool(b),
+            Value::Number(n) => match n.as_i64() {
+                Some(n) => WindValue::I64(n),
+                None => WindValue::F64(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            Value::String(s) => match parse_rfc3339(&s).filter(|_| timestamps) {
+                Some(us) => WindValue::Timestamp(us),
+                None => WindValue::String(s),
+            },
+            Value::Array(items) => WindValue::Array(items.into_iter().map(convert).collect()),
+            Value::Object(object) => match decode_bytes(&object) {
+                Some(bytes) => WindValue::Bytes(bytes),
+                None => WindValue::Map(object.into_iter().map(|(k, v)| (k, convert(v))).collect()),
+            },
+        }
+    }
+
+    /// Convert to JSON, writing `Bytes` as `{"$bytes": "<base64>"}` and a
+    /// `Timestamp` as an RFC 3339 string in UTC (or its micros, if past the
+    /// year 9999). `I32` and `F32` come back from `from_json` as `I64` and
+    /// `F64`, and non-finite floats, which JSON can't represent, become `null`.
+    pub fn to_json(&self) -> Value {
+        match self {
+            WindValue::Null => Value::Null,
+            WindValue::Bool(b) => Value::Bool(*b),
+            WindValue::I32(n) => Value::from(*n),
+            WindValue::I64(n) => Value::from(*n),
+            WindValue::F32(n) => float(*n as f64),
+            WindValue::F64(n) => float(*n),
+            WindValue::String(s) => Value::String(s.clone()),
+            WindValue::Bytes(bytes) => {
+                let mut object = Map::new();
+                object.insert(
+                    JSON_BYTES_KEY.to_string(),
+                    Value::String(STANDARD.encode(bytes)),
+                );
+                Value::Object(object)
+            }
+            WindValue::Array(items) => Value::Array(items.iter().map(WindValue::to_json).collect()),
+            WindValue::Map(map) => {
+                Value::Object(map.iter().map(|(k, v)| (k.clone(), v.to_json())).collect())
+            }
+            WindValue::Timestamp(us) => {
+                format_rfc3339(*us).map_or_else(|| Value::from(*us), Value::String)
+            }
+        }
+    }
+}
+
+/// Microseconds since the Unix epoch of an RFC 3339 time
+fn parse_rfc3339(s: &str) -> Option<i64> {
+    let at = OffsetDateTime::parse(s, &Rfc3339).ok()?;
+    i64::try_from(at.unix_timestamp_nanos() / 1000).ok()
+}
+
+/// An RFC 3339 time in UTC, `None` if its year doesn't have four digits
+fn format_rfc3339(us: i64) -> Option<String> {
+    OffsetDateTime::from_unix_timestamp_nanos(us as i128 * 1000)
+        .ok()?
+        .format(&Rfc3339)
+        .ok()
+}
+
+/// A JSON float, even for integral values, so it reads back as `F64`
+fn float(n: f64) -> Value {
+    Number::from_f64(n).map_or(Value::Null, Value::Number)
+}
+
+fn decode_bytes(object: &Map<String, Value>) -> Option<Vec<u8>> {
+    if object.len() != 1 {
+        return None;
+    }
+    match object.get(JSON_BYTES_KEY)? {
+        Value::String(encoded) => STANDARD.decode(encoded).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_round_trip_is_lossless() {
+        let original = json!({
+            "flag": true,
+            "count": 42,
+            "negative": -7,
+            "ratio": 0.25,
+            "whole_float": 2.0,
+            "name": "sensor",
+            "nothing": null,
+            "payload": { "$bytes": "AAEC/w==" },
+            "readings": [1, 2.5, "three", null, [false]],
+            "nested": {
+                "empty": {},
+                "blob": { "$bytes": "" },
+                "not_bytes": { "$bytes": "***" },
+            },
+        });
+
+        let value = WindValue::from_json(original.clone());
+        match &value {
+            WindValue::Map(map) => {
+                assert_eq!(map["count"], WindValue::I64(42));
+                assert_eq!(map["whole_float"], WindValue::F64(2.0));
+                assert_eq!(map["payload"], WindValue::Bytes(vec![0, 1, 2, 255]));
+            }
+            other => panic!("Expected a map, got {:?}", other),
+        }
+        assert_eq!(value.to_json(), original);
+    }
+
+    #[test]
+    fn test_rfc3339_strings_become_timestamps_on_request() {
+        let original = json!({
+            "taken_at": "2023-11-14T22:13:20.123456Z",
+            "offset": "2023-11-14T23:13:20+01:00",
+            "label": "not a time",
+        });
+
+        // Plain conversion leaves strings alone
+        let value = WindValue::from_json(original.clone());
+        assert_eq!(
+            value.get_path("taken_at"),
+            Some(&WindValue::from("2023-11-14T22:13:20.123456Z"))
+        );
+
+        let value = WindValue::from_json_with_timestamps(original);
+        assert_eq!(
+            value.get_path("taken_at"),
+            Some(&WindValue::Timestamp(1_700_000_000_123_456))
+        );
+        assert_eq!(
+            value.get_path("offset"),
+            Some(&WindValue::Timestamp(1_700_000_000_000_000))
+        );
+        assert_eq!(
+            value.get_path("label"),
+            Some(&WindValue::from("not a time"))
+        );
+
+        // Timestamps are written in UTC, and read back the same
+        let taken_at = value.get_path("taken_at").unwrap();
+        assert_eq!(taken_at.to_json(), json!("2023-11-14T22:13:20.123456Z"));
+        assert_eq!(
+            WindValue::from_json_with_timestamps(taken_at.to_json()),
+            *taken_at
+        );
+        assert_eq!(WindValue::Timestamp(i64::MAX).to_json(), json!(i64::MAX));
+    }
+
+    #[test]
+    fn test_narrow_variants_widen() {
+        let value = WindValue::Array(vec![WindValue::I32(1), WindValue::F32(0.5)]);
+        assert_eq!(
+            WindValue::from_json(value.to_json()),
+            WindValue::Array(vec![WindValue::I64(1), WindValue::F64(0.5)])
+        );
+        assert_eq!(WindValue::F64(f64::NAN).to_json(), Value::Null);
+    }
+}
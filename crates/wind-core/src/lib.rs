@@ -1,11 +1,70 @@
+pub mod auth;
 pub mod codec;
+pub mod counting;
+pub mod diagnostics;
 pub mod error;
+pub mod json;
+pub mod net;
 pub mod protocol;
+pub mod recording;
 pub mod schema;
+pub mod serialization;
+pub mod shutdown;
+pub mod spans;
 pub mod types;
 
-pub use codec::*;
-pub use error::*;
-pub use protocol::*;
-pub use schema::*;
-pub use types::*;
+// Re-exported by name rather than by glob, so a new item in one module can't
+// silently collide with (or shadow) one from another
+pub use auth::{authenticate, token_matches, AuthCheck, TokenAuth};
+pub use codec::{Codec, MessageCodec, OversizedFrame, MAX_MESSAGE_SIZE};
+pub use counting::{ByteCounters, CountingStream};
+pub use diagnostics::{ClientDiagnostics, PublisherDiagnostics, SubscriptionDiagnostics};
+pub use error::{Result, WindError};
+pub use json::JSON_BYTES_KEY;
+#[cfg(feature = "tls")]
+pub use net::TlsListener;
+pub use net::{SocketOptions, WindListener, WindStream, TLS_SCHEME, UNIX_SCHEME};
+pub use protocol::{Message, MessagePayload, DEFAULT_MAX_HOPS};
+pub use recording::{replay, Direction, RecordedMessage, RecordingStream, SessionRecorder};
+pub use schema::{CompatibilityResult, Schema, SchemaRegistry};
+pub use serialization::{
+    BincodeSerializer, JsonSerializer, MessagePackSerializer, MessageSerializer,
+    SerializationFormat,
+};
+pub use shutdown::shutdown_signal;
+pub use spans::{message_span, message_span_level, set_message_span_level, MESSAGE_SPAN};
+/// The TLS library behind `tls://` addresses, for building its configs
+#[cfg(feature = "tls")]
+pub use tokio_rustls::rustls;
+pub use types::{
+    unix_time_us, QosParams, ReliabilityLevel, ServiceEvent, ServiceInfo, ServiceType,
+    SubscriptionMode, WindKind, WindType, WindValue,
+};
+
+/// The types most WIND code needs:
+///
+/// ```
+/// use wind_core::prelude::*;
+///
+/// fn reading(celsius: f64) -> Result<WindValue> {
+///     if celsius.is_nan() {
+///         return Err(WindError::Protocol("Not a reading".to_string()));
+///     }
+///     Ok(WindValue::F64(celsius))
+/// }
+///
+/// let _: Option<(QosParams, SubscriptionMode, ServiceInfo)> = None;
+/// let _: Option<(Message, MessagePayload, MessageCodec)> = None;
+/// let _: Option<(Schema, WindType)> = None;
+/// assert!(reading(21.5).is_ok());
+/// ```
+pub mod prelude {
+    pub use crate::codec::MessageCodec;
+    pub use crate::error::{Result, WindError};
+    pub use crate::protocol::{Message, MessagePayload};
+    pub use crate::schema::Schema;
+    pub use crate::types::{
+        QosParams, ReliabilityLevel, ServiceInfo, ServiceType, SubscriptionMode, WindType,
+        WindValue,
+    };
+}
@@ -0,0 +1,461 @@
+use std::io;
+use std::pin::Pin;
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+#[cfg(feature = "tls")]
+use tokio::task::JoinSet;
+#[cfg(feature = "tls")]
+use tokio_rustls::{
+    rustls::{pki_types::ServerName, ClientConfig, ServerConfig},
+    TlsAcceptor, TlsConnector, TlsStream,
+};
+
+/// Address prefix selecting a Unix domain socket, as in `unix:/run/wind.sock`.
+/// Any other address is a TCP `host:port`.
+pub const UNIX_SCHEME: &str = "unix:";
+
+/// Address prefix selecting TCP with TLS, as in `tls://sensors.lab:7001`.
+/// Needs the `tls` feature.
+pub const TLS_SCHEME: &str = "tls://";
+
+/// Longest a TLS client may take over its handshake before it is dropped
+#[cfg(feature = "tls")]
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Socket path of a `unix:` address
+fn unix_path(address: &str) -> Option<&str> {
+    address.strip_prefix(UNIX_SCHEME)
+}
+
+/// `host:port` of a `tls://` address
+fn tls_address(address: &str) -> Option<&str> {
+    address.strip_prefix(TLS_SCHEME)
+}
+
+#[cfg(not(feature = "tls"))]
+fn tls_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "TLS addresses need WIND built with the `tls` feature",
+    )
+}
+
+#[cfg(not(unix))]
+fn unix_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Unix domain sockets are not supported on this platform",
+    )
+}
+
+/// Options applied to every TCP connection a WIND component opens or accepts.
+/// Unix domain sockets ignore them.
+#[derive(Debug, Clone)]
+pub struct SocketOptions {
+    /// Send small frames immediately instead of letting Nagle's algorithm
+    /// hold them back, which can add ~40ms to a request/reply round trip
+    pub nodelay: bool,
+    /// Idle time before TCP keepalive probes start; None leaves keepalive off
+    pub keepalive: Option<Duration>,
+    /// Client config for connecting to `tls://` addresses, which fail
+    /// without one
+    #[cfg(feature = "tls")]
+    pub tls: Option<Arc<ClientConfig>>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: true,
+            keepalive: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+impl SocketOptions {
+    pub fn with_nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Detect dead peers on idle connections by probing after `idle`
+    pub fn with_keepalive(mut self, idle: Duration) -> Self {
+        self.keepalive = Some(idle);
+        self
+    }
+
+    /// Connect to `tls://` addresses with `config`, e.g. trusting a private CA
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, config: ClientConfig) -> Self {
+        self.tls = Some(Arc::new(config));
+        self
+    }
+
+    fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+        if let Some(idle) = self.keepalive {
+            let keepalive = socket2::TcpKeepalive::new().with_time(idle);
+            socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive)?;
+        }
+        Ok(())
+    }
+}
+
+/// A connection to or from a WIND address, over TCP, TLS or a Unix domain
+/// socket
+#[derive(Debug)]
+pub enum WindStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl WindStream {
+    /// Connect with the default `SocketOptions`
+    pub async fn connect(address: &str) -> io::Result<Self> {
+        Self::connect_with(address, &SocketOptions::default()).await
+    }
+
+    pub async fn connect_with(address: &str, options: &SocketOptions) -> io::Result<Self> {
+        if let Some(address) = tls_address(address) {
+            return Self::connect_tls(address, options).await;
+        }
+        match unix_path(address) {
+            #[cfg(unix)]
+            Some(path) => Ok(WindStream::Unix(UnixStream::connect(path).await?)),
+            #[cfg(not(unix))]
+            Some(_) => Err(unix_unsupported()),
+            None => {
+                let stream = TcpStream::connect(address).await?;
+                options.apply(&stream)?;
+                Ok(WindStream::Tcp(stream))
+            }
+        }
+    }
+}
+
+impl WindStream {
+    /// Connect to the `host:port` of a `tls://` address, checking the
+    /// server's certificate against `host`
+    #[cfg(feature = "tls")]
+    async fn connect_tls(address: &str, options: &SocketOptions) -> io::Result<Self> {
+        let config = options.tls.clone().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}{} needs a TLS client config", TLS_SCHEME, address),
+            )
+        })?;
+        let host = address
+            .rsplit_once(':')
+            .map_or(address, |(host, _)| host)
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let server_name = ServerName::try_from(host.to_string())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let stream = TcpStream::connect(address).await?;
+        options.apply(&stream)?;
+        let stream = TlsConnector::from(config)
+            .connect(server_name, stream)
+            .await?;
+        Ok(WindStream::Tls(Box::new(stream.into())))
+    }
+
+    #[cfg(not(feature = "tls"))]
+    async fn connect_tls(_address: &str, _options: &SocketOptions) -> io::Result<Self> {
+        Err(tls_unsupported())
+    }
+}
+
+impl AsyncRead for WindStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            WindStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(unix)]
+            WindStream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            WindStream::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for WindStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            WindStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(unix)]
+            WindStream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            WindStream::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            WindStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(unix)]
+            WindStream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            WindStream::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            WindStream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(unix)]
+            WindStream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+            #[cfg(feature = "tls")]
+            WindStream::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// A listener bound to a WIND address, over TCP, TLS or a Unix domain socket
+#[derive(Debug)]
+pub enum WindListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+    #[cfg(feature = "tls")]
+    Tls(TlsListener),
+}
+
+/// TCP listener whose clients complete a TLS handshake before they are
+/// handed out, each in its own task so a slow one holds up nobody else
+#[cfg(feature = "tls")]
+pub struct TlsListener {
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    handshakes: tokio::sync::Mutex<JoinSet<io::Result<(WindStream, String)>>>,
+}
+
+#[cfg(feature = "tls")]
+impl std::fmt::Debug for TlsListener {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsListener")
+            .field("listener", &self.listener)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "tls")]
+impl TlsListener {
+    async fn accept_with(&self, options: &SocketOptions) -> io::Result<(WindStream, String)> {
+        let mut handshakes = self.handshakes.lock().await;
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (stream, peer) = accepted?;
+                    options.apply(&stream)?;
+                    let acceptor = self.acceptor.clone();
+                    handshakes.spawn(async move {
+                        let stream = tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream))
+                            .await
+                            .map_err(|_| {
+                                io::Error::new(
+                                    io::ErrorKind::TimedOut,
+                                    format!("TLS handshake with {} timed out", peer),
+                                )
+                            })??;
+                        Ok((WindStream::Tls(Box::new(stream.into())), peer.to_string()))
+                    });
+                }
+                Some(handshake) = handshakes.join_next() => {
+                    return handshake.map_err(io::Error::other)?;
+                }
+            }
+        }
+    }
+}
+
+impl WindListener {
+    /// Bind to `address`. A leftover socket file that nothing is listening on
+    /// any more is replaced.
+    pub async fn bind(address: &str) -> io::Result<Self> {
+        match unix_path(address) {
+            #[cfg(unix)]
+            Some(path) => {
+                if std::path::Path::new(path).exists() && UnixStream::connect(path).await.is_err() {
+                    std::fs::remove_file(path)?;
+                }
+                Ok(WindListener::Unix(UnixListener::bind(path)?))
+            }
+            #[cfg(not(unix))]
+            Some(_) => Err(unix_unsupported()),
+            None => Ok(WindListener::Tcp(TcpListener::bind(address).await?)),
+        }
+    }
+
+    /// Bind a TCP `address` whose clients must connect over TLS, as
+    /// `config` sets out. Its `local_address` is a `tls://` one.
+    #[cfg(feature = "tls")]
+    pub async fn bind_tls(address: &str, config: Arc<ServerConfig>) -> io::Result<Self> {
+        let address = tls_address(address).unwrap_or(address);
+        Ok(WindListener::Tls(TlsListener {
+            listener: TcpListener::bind(address).await?,
+            acceptor: TlsAcceptor::from(config),
+            handshakes: tokio::sync::Mutex::new(JoinSet::new()),
+        }))
+    }
+
+    /// The address clients should connect to, in the same form `bind` takes
+    pub fn local_address(&self) -> io::Result<String> {
+        match self {
+            WindListener::Tcp(listener) => Ok(listener.local_addr()?.to_string()),
+            #[cfg(unix)]
+            WindListener::Unix(listener) => Ok(unix_address(&listener.local_addr()?)),
+            #[cfg(feature = "tls")]
+            WindListener::Tls(tls) => Ok(format!("{}{}", TLS_SCHEME, tls.listener.local_addr()?)),
+        }
+    }
+
+    /// Accept a connection with the default `SocketOptions`, returning it
+    /// with a printable peer address
+    pub async fn accept(&self) -> io::Result<(WindStream, String)> {
+        self.accept_with(&SocketOptions::default()).await
+    }
+
+    pub async fn accept_with(&self, options: &SocketOptions) -> io::Result<(WindStream, String)> {
+        match self {
+            WindListener::Tcp(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                options.apply(&stream)?;
+                Ok((WindStream::Tcp(stream), peer.to_string()))
+            }
+            #[cfg(unix)]
+            WindListener::Unix(listener) => {
+                let (stream, peer) = listener.accept().await?;
+                Ok((WindStream::Unix(stream), unix_address(&peer)))
+            }
+            #[cfg(feature = "tls")]
+            WindListener::Tls(tls) => tls.accept_with(options).await,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn unix_address(address: &tokio::net::unix::SocketAddr) -> String {
+    match address.as_pathname() {
+        Some(path) => format!("{}{}", UNIX_SCHEME, path.display()),
+        None => format!("{}(unnamed)", UNIX_SCHEME),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Mean time for a request sent as two small writes (header, then body)
+    /// to be answered, the pattern Nagle's algorithm delays
+    async fn round_trip(address: &str, options: &SocketOptions) -> Duration {
+        let listener = WindListener::bind(address).await.unwrap();
+        let server_options = options.clone();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept_with(&server_options).await.unwrap();
+            let mut request = [0u8; 8];
+            while stream.read_exact(&mut request).await.is_ok() {
+                stream.write_all(&request[..1]).await.unwrap();
+            }
+        });
+
+        let mut stream = WindStream::connect_with(address, options).await.unwrap();
+        let mut reply = [0u8; 1];
+        let rounds = 20;
+        let start = std::time::Instant::now();
+        for _ in 0..rounds {
+            stream.write_all(&[0u8; 4]).await.unwrap();
+            stream.write_all(&[1u8; 4]).await.unwrap();
+            stream.read_exact(&mut reply).await.unwrap();
+        }
+        let elapsed = start.elapsed();
+        drop(stream);
+        server.await.unwrap();
+        elapsed / rounds
+    }
+
+    #[tokio::test]
+    async fn test_nodelay_is_default_and_cuts_round_trip_latency() {
+        let listener = WindListener::bind("127.0.0.1:7221").await.unwrap();
+        let client = WindStream::connect("127.0.0.1:7221").await.unwrap();
+        let (accepted, _) = listener.accept().await.unwrap();
+        for stream in [client, accepted] {
+            match stream {
+                WindStream::Tcp(stream) => assert!(stream.nodelay().unwrap()),
+                _ => panic!("Expected a TCP stream"),
+            }
+        }
+
+        let nagle = round_trip(
+            "127.0.0.1:7222",
+            &SocketOptions::default().with_nodelay(false),
+        )
+        .await;
+        let nodelay = round_trip("127.0.0.1:7223", &SocketOptions::default()).await;
+        assert!(nodelay < nagle, "{:?} vs {:?}", nodelay, nagle);
+    }
+
+    /// A connection to a server at `address` that echoes every frame back
+    #[cfg(unix)]
+    async fn echo_connection(address: &str) -> WindStream {
+        use crate::MessageCodec;
+
+        let listener = WindListener::bind(address).await.unwrap();
+        let address = listener.local_address().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            while let Ok(msg) = MessageCodec::decode(&mut stream).await {
+                MessageCodec::write(&mut stream, &msg).await.unwrap();
+            }
+        });
+        WindStream::connect(&address).await.unwrap()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_socket_p50_beats_tcp_loopback() {
+        use crate::{Message, MessageCodec, MessagePayload};
+
+        let path = std::env::temp_dir().join(format!("wind-{}-latency.sock", std::process::id()));
+        let mut streams = [
+            echo_connection(&format!("{}{}", UNIX_SCHEME, path.display())).await,
+            echo_connection("127.0.0.1:0").await,
+        ];
+
+        // Alternating pings, so load from other tests hits both transports alike
+        let ping = Message::new(MessagePayload::Ping);
+        let mut samples = [Vec::new(), Vec::new()];
+        for _ in 0..2000 {
+            for (stream, samples) in streams.iter_mut().zip(&mut samples) {
+                let start = std::time::Instant::now();
+                MessageCodec::write(stream, &ping).await.unwrap();
+                MessageCodec::decode(stream).await.unwrap();
+                samples.push(start.elapsed());
+            }
+        }
+        let [unix, tcp] = samples.map(|mut samples| {
+            samples.sort();
+            samples[samples.len() / 2]
+        });
+        assert!(unix < tcp, "p50 over unix {:?}, tcp {:?}", unix, tcp);
+    }
+}
@@ -1,7 +1,14 @@
-use crate::{QosParams, SubscriptionMode, WindValue};
+use crate::{
+    PublisherDiagnostics, QosParams, Schema, ServiceEvent, ServiceType, SubscriptionMode, WindValue,
+};
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use uuid::Uuid;
 
+/// Registry-to-registry forwards a discovery or watch may take before it is
+/// refused, so registries that forward to each other can't loop forever
+pub const DEFAULT_MAX_HOPS: u8 = 4;
+
 /// WIND protocol messages
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
@@ -25,13 +32,65 @@ pub enum MessagePayload {
         service: String,
         success: bool,
         error: Option<String>,
+        allowed_subscribers: Option<Vec<String>>, // Subscribe ACL for the service, None = open
+    },
+
+    // Heartbeat extending a registration's TTL, leaving the rest of it as
+    // registered; only the provider at the registered address may renew
+    RenewService {
+        service: String,
+        address: String,
+        ttl_ms: u64,
+    },
+    ServiceRenewed {
+        service: String,
+        success: bool,
+        error: Option<String>,
+    },
+
+    // Admin removal of a registration regardless of its TTL; with an address,
+    // only the registration at that address is removed
+    ForceDeregister {
+        service: String,
+        address: Option<String>,
+    },
+    ServiceDeregistered {
+        service: String,
+        success: bool,
+        error: Option<String>,
     },
 
     DiscoverServices {
         pattern: String, // Glob pattern like "SENSOR/*/TEMP"
+        offset: u32,
+        limit: u32,                        // 0 = registry default page size
+        registered_since_us: Option<u64>,  // Only services first registered at or after this time
+        service_type: Option<ServiceType>, // Only services providing this type
+        max_hops: u8,                      // Forwards left; refused when none are
     },
     ServicesDiscovered {
         services: Vec<crate::ServiceInfo>,
+        total: u32,               // Total matches across all pages
+        next_offset: Option<u32>, // Offset of the next page, None on the last page
+        registry_version: u64,    // Changes whenever a service is added or removed
+    },
+
+    // Keep the connection open and push a ServiceChanged for every matching
+    // service registered or removed, starting with the current matches
+    WatchServices {
+        pattern: String,
+        max_hops: u8, // Forwards left; refused when none are
+    },
+    ServiceChanged {
+        event: ServiceEvent,
+    },
+
+    GetSchema {
+        schema_id: String,
+    },
+    SchemaFound {
+        schema_id: String,
+        schema: Option<Schema>, // None if the registry doesn't know the schema
     },
 
     // Subscription messages
@@ -39,25 +98,93 @@ pub enum MessagePayload {
         service: String,
         mode: SubscriptionMode,
         qos: QosParams,
-        schema_id: Option<String>,
+        schema_id: Option<String>, // Schema the subscriber expects, None = any
+        replay: u32,               // Retained values to replay before live data, 0 = none
     },
     SubscribeAck {
         subscription_id: Uuid,
         success: bool,
         error: Option<String>,
         current_value: Option<WindValue>,
+        schema_id: Option<String>, // The publisher's schema
     },
 
     Unsubscribe {
         subscription_id: Uuid,
     },
 
+    // Change the delivery mode of a live subscription in place; the publisher
+    // answers with a SubscribeAck carrying no value
+    ModifySubscription {
+        subscription_id: Uuid,
+        mode: SubscriptionMode,
+        qos: QosParams,
+    },
+
+    // Free buffer slots advertised by a subscriber; the publisher sends at
+    // most this many updates until the next advertisement
+    FlowControl {
+        available_window: u32,
+    },
+
     // Data messages
     Publish {
         service: String,
         sequence: u64,
-        value: WindValue,
+        value: Arc<WindValue>, // Shared so a publisher can send one value to many clients
         schema_id: Option<String>,
+        // Unix epoch micros after which the value is stale and is dropped
+        // instead of delivered (None = never). Unrelated to the ttl_ms of a
+        // service registration.
+        expires_at_us: Option<u64>,
+    },
+
+    // Values published together by `Publisher::publish_batch`, in one frame:
+    // consecutive sequence numbers starting at `start_sequence`
+    PublishBatch {
+        service: String,
+        start_sequence: u64,
+        values: Vec<WindValue>,
+    },
+
+    // Sent by a `Reliable` subscriber for every value it receives
+    PublishAck {
+        sequence: u64,
+    },
+
+    // A `Bytes` publish above the publisher's chunk size, split into frames
+    // that the subscriber reassembles
+    PublishChunk {
+        service: String,
+        sequence: u64,
+        chunk_index: u32,
+        last: bool,
+        bytes: Vec<u8>,
+        expires_at_us: Option<u64>, // As for Publish
+    },
+
+    // Values a publisher retained, replayed in bulk to a new subscriber ahead
+    // of live data: `(sequence, value)` pairs in order, starting at `from_seq`.
+    // A long replay is split over several batches.
+    ReplayBatch {
+        from_seq: u64,
+        values: Vec<(u64, WindValue)>,
+    },
+
+    // One-off read of a publisher's current value, without subscribing
+    GetCurrent {
+        service: String,
+    },
+    CurrentValue {
+        value: Option<WindValue>,
+        sequence: u64,
+    },
+
+    // Snapshot of a publisher's clients, subscriptions and counters, for
+    // operators; only identities the publisher allows may ask
+    DiagnosticDump,
+    DiagnosticReport {
+        diagnostics: PublisherDiagnostics,
     },
 
     // RPC messages
@@ -73,7 +200,30 @@ pub enum MessagePayload {
         schema_id: Option<String>,
     },
 
+    // Client-streaming RPC: the client opens a call, sends any number of
+    // items and ends the stream; the server answers with one RpcResponse.
+    // Items and the end carry the id of the RpcStreamOpen message.
+    RpcStreamOpen {
+        service: String,
+        method: String,
+        schema_id: Option<String>,
+    },
+    RpcStreamItem {
+        call_id: Uuid,
+        item: WindValue,
+    },
+    RpcStreamEnd {
+        call_id: Uuid,
+    },
+
     // Control messages
+    Authenticate {
+        token: String, // Shared secret, sent first to servers that require one
+    },
+    Authenticated,
+    Identify {
+        client_id: String, // Identity checked against ACLs for the rest of the connection
+    },
     Heartbeat,
     Ping,
     Pong,
@@ -87,11 +237,109 @@ impl Message {
     pub fn new(payload: MessagePayload) -> Self {
         Self {
             id: Uuid::new_v4(),
-            timestamp_us: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_micros() as u64,
+            timestamp_us: crate::unix_time_us(std::time::SystemTime::now()),
             payload,
         }
     }
+
+    /// Give the message `id` rather than a fresh one, e.g. so a value keeps
+    /// the id it was published under in every frame that carries it
+    pub fn with_id(mut self, id: Uuid) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Error reply for a request that `service`, a `service_type` provider,
+    /// doesn't handle, e.g. a `Subscribe` sent to an RPC server
+    pub fn unsupported(request: &MessagePayload, service: &str, service_type: ServiceType) -> Self {
+        Self::new(MessagePayload::Error {
+            error: request.unsupported_by(service, service_type),
+            context: Some(format!("Service type mismatch for: {}", service)),
+        })
+    }
+}
+
+impl MessagePayload {
+    /// Variant name, for diagnostics that shouldn't dump the payload
+    pub fn name(&self) -> &'static str {
+        match self {
+            MessagePayload::RegisterService { .. } => "RegisterService",
+            MessagePayload::ServiceRegistered { .. } => "ServiceRegistered",
+            MessagePayload::RenewService { .. } => "RenewService",
+            MessagePayload::ServiceRenewed { .. } => "ServiceRenewed",
+            MessagePayload::ForceDeregister { .. } => "ForceDeregister",
+            MessagePayload::ServiceDeregistered { .. } => "ServiceDeregistered",
+            MessagePayload::DiscoverServices { .. } => "DiscoverServices",
+            MessagePayload::ServicesDiscovered { .. } => "ServicesDiscovered",
+            MessagePayload::WatchServices { .. } => "WatchServices",
+            MessagePayload::ServiceChanged { .. } => "ServiceChanged",
+            MessagePayload::GetSchema { .. } => "GetSchema",
+            MessagePayload::SchemaFound { .. } => "SchemaFound",
+            MessagePayload::Subscribe { .. } => "Subscribe",
+            MessagePayload::SubscribeAck { .. } => "SubscribeAck",
+            MessagePayload::Unsubscribe { .. } => "Unsubscribe",
+            MessagePayload::ModifySubscription { .. } => "ModifySubscription",
+            MessagePayload::FlowControl { .. } => "FlowControl",
+            MessagePayload::Publish { .. } => "Publish",
+            MessagePayload::PublishBatch { .. } => "PublishBatch",
+            MessagePayload::PublishAck { .. } => "PublishAck",
+            MessagePayload::PublishChunk { .. } => "PublishChunk",
+            MessagePayload::ReplayBatch { .. } => "ReplayBatch",
+            MessagePayload::GetCurrent { .. } => "GetCurrent",
+            MessagePayload::CurrentValue { .. } => "CurrentValue",
+            MessagePayload::DiagnosticDump => "DiagnosticDump",
+            MessagePayload::DiagnosticReport { .. } => "DiagnosticReport",
+            MessagePayload::RpcCall { .. } => "RpcCall",
+            MessagePayload::RpcResponse { .. } => "RpcResponse",
+            MessagePayload::RpcStreamOpen { .. } => "RpcStreamOpen",
+            MessagePayload::RpcStreamItem { .. } => "RpcStreamItem",
+            MessagePayload::RpcStreamEnd { .. } => "RpcStreamEnd",
+            MessagePayload::Authenticate { .. } => "Authenticate",
+            MessagePayload::Authenticated => "Authenticated",
+            MessagePayload::Identify { .. } => "Identify",
+            MessagePayload::Heartbeat => "Heartbeat",
+            MessagePayload::Ping => "Ping",
+            MessagePayload::Pong => "Pong",
+            MessagePayload::Error { .. } => "Error",
+        }
+    }
+
+    /// Kind of service a client request is meant for, None for requests any
+    /// peer answers
+    pub fn served_by(&self) -> Option<ServiceType> {
+        match self {
+            MessagePayload::Subscribe { .. }
+            | MessagePayload::Unsubscribe { .. }
+            | MessagePayload::ModifySubscription { .. }
+            | MessagePayload::FlowControl { .. }
+            | MessagePayload::GetCurrent { .. }
+            | MessagePayload::DiagnosticDump => Some(ServiceType::Publisher),
+            MessagePayload::RpcCall { .. }
+            | MessagePayload::RpcStreamOpen { .. }
+            | MessagePayload::RpcStreamItem { .. }
+            | MessagePayload::RpcStreamEnd { .. } => Some(ServiceType::RpcServer),
+            _ => None,
+        }
+    }
+
+    /// Error if a `service_type` provider can't handle this request, so a
+    /// client can fail before connecting to the wrong kind of service
+    pub fn check_served_by(&self, service: &str, service_type: &ServiceType) -> crate::Result<()> {
+        match self.served_by() {
+            Some(wanted) if !service_type.provides(&wanted) => Err(crate::WindError::Protocol(
+                self.unsupported_by(service, service_type.clone()),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Explanation that `service`, a `service_type` provider, can't handle this request
+    pub fn unsupported_by(&self, service: &str, service_type: ServiceType) -> String {
+        format!(
+            "Unsupported request {}: {} is a {:?} service",
+            self.name(),
+            service,
+            service_type
+        )
+    }
 }
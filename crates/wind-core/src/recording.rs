@@ -0,0 +1,216 @@
+use crate::{Message, MessageCodec, Result};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::time::{Duration, Instant};
+
+const RECORDING_FILE_VERSION: u32 = 1;
+
+/// Which way a recorded message travelled, seen from the recording side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// A message captured by a `SessionRecorder`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedMessage {
+    /// Time since the recording started
+    pub offset_us: u64,
+    pub direction: Direction,
+    pub message: Message,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordingFile {
+    version: u32,
+    messages: Vec<RecordedMessage>,
+}
+
+/// Collects the messages of a session, for saving and later `replay`.
+/// Clones share the same recording.
+#[derive(Debug, Clone)]
+pub struct SessionRecorder {
+    started: Instant,
+    messages: Arc<Mutex<Vec<RecordedMessage>>>,
+}
+
+impl Default for SessionRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionRecorder {
+    /// Start a recording; message offsets are measured from now
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            messages: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn record(&self, direction: Direction, message: Message) {
+        let offset_us = self.started.elapsed().as_micros() as u64;
+        self.messages.lock().unwrap().push(RecordedMessage {
+            offset_us,
+            direction,
+            message,
+        });
+    }
+
+    /// Messages recorded so far, in the order they were seen
+    pub fn messages(&self) -> Vec<RecordedMessage> {
+        self.messages.lock().unwrap().clone()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let file = RecordingFile {
+            version: RECORDING_FILE_VERSION,
+            messages: self.messages(),
+        };
+        std::fs::write(path, bincode::serialize(&file)?)?;
+        Ok(())
+    }
+
+    /// Load the messages of a recording written by `save`
+    pub fn load(path: impl AsRef<Path>) -> Result<Vec<RecordedMessage>> {
+        let file: RecordingFile = bincode::deserialize(&std::fs::read(path)?)?;
+        if file.version != RECORDING_FILE_VERSION {
+            return Err(crate::WindError::Protocol(format!(
+                "Unsupported recording file version {} (expected {})",
+                file.version, RECORDING_FILE_VERSION
+            )));
+        }
+        Ok(file.messages)
+    }
+}
+
+/// Stream wrapper that records every complete message read or written.
+/// Without a recorder it passes bytes straight through.
+#[derive(Debug)]
+pub struct RecordingStream<S> {
+    inner: S,
+    recorder: Option<SessionRecorder>,
+    /// Bytes of frames not yet complete, per direction
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl<S> RecordingStream<S> {
+    pub fn new(inner: S, recorder: Option<SessionRecorder>) -> Self {
+        Self {
+            inner,
+            recorder,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        }
+    }
+}
+
+/// Record every complete frame at the front of `pending`
+fn record_frames(recorder: &SessionRecorder, pending: &mut Vec<u8>, direction: Direction) {
+    while pending.len() >= 4 {
+        let len = u32::from_be_bytes([pending[0], pending[1], pending[2], pending[3]]) as usize;
+        if pending.len() < 4 + len {
+            break;
+        }
+        match MessageCodec::decode_frame(&pending[4..4 + len]) {
+            Ok(message) => recorder.record(direction, message),
+            Err(e) => tracing::warn!("Failed to record {:?} message: {}", direction, e),
+        }
+        pending.drain(..4 + len);
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for RecordingStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let (Poll::Ready(Ok(())), Some(recorder)) = (&result, &this.recorder) {
+            this.incoming.extend_from_slice(&buf.filled()[before..]);
+            record_frames(recorder, &mut this.incoming, Direction::Received);
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for RecordingStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let result = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let (Poll::Ready(Ok(written)), Some(recorder)) = (&result, &this.recorder) {
+            this.outgoing.extend_from_slice(&buf[..*written]);
+            record_frames(recorder, &mut this.outgoing, Direction::Sent);
+        }
+        result
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Replay the `Sent` side of a recording over `stream`: each message is
+/// written at its recorded offset, and whatever the peer sends is collected
+/// until the recording's last offset plus `settle` has passed. Returns the
+/// peer's messages, offset from the start of the replay.
+pub async fn replay<S>(
+    stream: S,
+    recording: &[RecordedMessage],
+    settle: Duration,
+) -> Result<Vec<RecordedMessage>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let started = Instant::now();
+    let last_offset = recording.iter().map(|m| m.offset_us).max().unwrap_or(0);
+    let deadline = started + Duration::from_micros(last_offset) + settle;
+    let (mut reader, mut writer) = tokio::io::split(stream);
+
+    let send = async {
+        for recorded in recording.iter().filter(|m| m.direction == Direction::Sent) {
+            tokio::time::sleep_until(started + Duration::from_micros(recorded.offset_us)).await;
+            MessageCodec::write(&mut writer, &recorded.message).await?;
+        }
+        writer.flush().await?;
+        Ok::<_, crate::WindError>(())
+    };
+    let receive = async {
+        let mut received = Vec::new();
+        while let Ok(decoded) =
+            tokio::time::timeout_at(deadline, MessageCodec::decode(&mut reader)).await
+        {
+            // The peer closing the connection ends the replay early
+            let Ok(message) = decoded else { break };
+            received.push(RecordedMessage {
+                offset_us: started.elapsed().as_micros() as u64,
+                direction: Direction::Received,
+                message,
+            });
+        }
+        received
+    };
+
+    let (sent, received) = tokio::join!(send, receive);
+    sent?;
+    Ok(received)
+}
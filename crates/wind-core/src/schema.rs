@@ -1,6 +1,17 @@
 use crate::{Result, WindType, WindValue};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
+
+/// Version of the on-disk schema registry format written by `SchemaRegistry::save`
+const SCHEMA_FILE_VERSION: u32 = 1;
+
+/// On-disk schema registry: a format version followed by every schema
+#[derive(Serialize, Deserialize)]
+struct SchemaFile {
+    version: u32,
+    schemas: Vec<Schema>,
+}
 
 /// Schema definition for type validation
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,15 +23,126 @@ pub struct Schema {
     pub fields: HashMap<String, WindType>,
 }
 
+/// How two versions of a schema can read each other's values, from
+/// `Schema::is_compatible_with`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompatibilityResult {
+    /// Each version can read values written with the other
+    Compatible,
+    /// Readers of the old version can read new values, but not the reverse
+    ForwardOnly { fields: Vec<String> },
+    /// Readers of the new version can read old values, but not the reverse
+    BackwardOnly { fields: Vec<String> },
+    /// Neither version can read the other's values
+    Incompatible { fields: Vec<String> },
+}
+
+impl CompatibilityResult {
+    /// Fields that keep the versions from being fully compatible, sorted
+    pub fn fields(&self) -> &[String] {
+        match self {
+            CompatibilityResult::Compatible => &[],
+            CompatibilityResult::ForwardOnly { fields }
+            | CompatibilityResult::BackwardOnly { fields }
+            | CompatibilityResult::Incompatible { fields } => fields,
+        }
+    }
+}
+
 impl Schema {
+    /// Compare this schema, as the newer version, with an `older` one.
+    /// Backward compatibility means values written with `older` validate
+    /// against this schema, forward compatibility the reverse. Adding or
+    /// removing an optional field keeps both; adding a required field breaks
+    /// backward, removing one breaks forward, and changing a type to one that
+    /// can't hold the old values breaks both.
+    pub fn is_compatible_with(&self, older: &Schema) -> CompatibilityResult {
+        let backward = Self::unreadable_fields(self, older);
+        let forward = Self::unreadable_fields(older, self);
+
+        let mut fields: Vec<String> = backward.iter().chain(&forward).cloned().collect();
+        fields.sort();
+        fields.dedup();
+
+        match (backward.is_empty(), forward.is_empty()) {
+            (true, true) => CompatibilityResult::Compatible,
+            (true, false) => CompatibilityResult::BackwardOnly { fields },
+            (false, true) => CompatibilityResult::ForwardOnly { fields },
+            (false, false) => CompatibilityResult::Incompatible { fields },
+        }
+    }
+
+    /// Fields of `reader` that a value written with `writer` may fail
+    fn unreadable_fields(reader: &Schema, writer: &Schema) -> Vec<String> {
+        reader
+            .fields
+            .iter()
+            .filter(|(name, reader_type)| match writer.fields.get(*name) {
+                Some(writer_type) => !Self::type_reads(reader_type, writer_type),
+                None => !matches!(reader_type, WindType::Optional(_)),
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Whether every value of type `writer` is also a valid `reader`
+    fn type_reads(reader: &WindType, writer: &WindType) -> bool {
+        match (reader, writer) {
+            _ if reader == writer => true,
+            (WindType::Optional(reader), WindType::Optional(writer)) => {
+                Self::type_reads(reader, writer)
+            }
+            (WindType::Optional(reader), writer) => Self::type_reads(reader, writer),
+            (reader, WindType::Union(members)) => members
+                .iter()
+                .all(|member| Self::type_reads(reader, member)),
+            (WindType::Union(members), writer) => members
+                .iter()
+                .any(|member| Self::type_reads(member, writer)),
+            // Only an optional field accepts the nulls an optional writer allows
+            (_, WindType::Optional(_)) => false,
+            (WindType::Any, _) => true,
+            (WindType::Array(reader), WindType::Array(writer))
+            | (WindType::Map(reader), WindType::Map(writer)) => Self::type_reads(reader, writer),
+            _ => false,
+        }
+    }
+
+    /// Infer a schema from a sample `Map` value, one field per key. Any other
+    /// value yields a schema with no fields.
+    pub fn infer(name: &str, sample: &WindValue) -> Schema {
+        let fields = match sample {
+            WindValue::Map(map) => map
+                .iter()
+                .map(|(field, value)| (field.clone(), WindType::infer(value)))
+                .collect(),
+            _ => HashMap::new(),
+        };
+
+        Schema {
+            id: name.to_string(),
+            version: 1,
+            name: name.to_string(),
+            description: Some("Inferred from a sample value".to_string()),
+            fields,
+        }
+    }
+
+    /// Check `value` against the schema's fields. A `WindType::Struct` field
+    /// can't be checked without the registry holding the struct's schema; see
+    /// `SchemaRegistry::validate_recursive`.
     pub fn validate(&self, value: &WindValue) -> Result<()> {
+        self.validate_in(value, None)
+    }
+
+    fn validate_in(&self, value: &WindValue, registry: Option<&SchemaRegistry>) -> Result<()> {
         match value {
             WindValue::Map(map) => {
                 // Validate all required fields are present and have correct types
                 for (field_name, expected_type) in &self.fields {
                     if let Some(field_value) = map.get(field_name) {
-                        self.validate_type(field_value, expected_type)?;
-                    } else {
+                        Self::validate_type(field_value, expected_type, registry)?;
+                    } else if !matches!(expected_type, WindType::Optional(_)) {
                         return Err(crate::WindError::Schema(format!(
                             "Missing required field: {}",
                             field_name
@@ -35,7 +157,11 @@ impl Schema {
         }
     }
 
-    fn validate_type(&self, value: &WindValue, expected: &WindType) -> Result<()> {
+    fn validate_type(
+        value: &WindValue,
+        expected: &WindType,
+        registry: Option<&SchemaRegistry>,
+    ) -> Result<()> {
         let matches = match (value, expected) {
             (WindValue::Bool(_), WindType::Bool) => true,
             (WindValue::I32(_), WindType::I32) => true,
@@ -44,11 +170,45 @@ impl Schema {
             (WindValue::F64(_), WindType::F64) => true,
             (WindValue::String(_), WindType::String) => true,
             (WindValue::Bytes(_), WindType::Bytes) => true,
+            (WindValue::Timestamp(_), WindType::Timestamp) => true,
+            (WindValue::Null, WindType::Optional(_)) => true,
+            (_, WindType::Optional(inner)) => return Self::validate_type(value, inner, registry),
+            (WindValue::Null, _) => false,
             (WindValue::Array(arr), WindType::Array(inner)) => {
                 // Validate all array elements
-                return arr.iter().try_for_each(|v| self.validate_type(v, inner));
+                return arr
+                    .iter()
+                    .try_for_each(|v| Self::validate_type(v, inner, registry));
+            }
+            (WindValue::Map(map), WindType::Map(inner)) => {
+                // Validate all map values
+                return map
+                    .values()
+                    .try_for_each(|v| Self::validate_type(v, inner, registry));
+            }
+            (WindValue::Map(_), WindType::Struct(schema_id)) => {
+                let registry = registry.ok_or_else(|| {
+                    crate::WindError::Schema(format!(
+                        "Struct {} can only be validated against a schema registry",
+                        schema_id
+                    ))
+                })?;
+                return registry.validate_recursive(schema_id, value);
+            }
+            (_, WindType::Any) => true,
+            (_, WindType::Union(members)) => {
+                if members
+                    .iter()
+                    .any(|member| Self::validate_type(value, member, registry).is_ok())
+                {
+                    return Ok(());
+                }
+                let alternatives: Vec<_> = members.iter().map(|m| format!("{:?}", m)).collect();
+                return Err(crate::WindError::TypeMismatch {
+                    expected: format!("one of {}", alternatives.join(" | ")),
+                    actual: value.type_name().to_string(),
+                });
             }
-            (WindValue::Map(_), WindType::Map(_)) => true, // TODO: Validate map values
             _ => false,
         };
 
@@ -57,12 +217,48 @@ impl Schema {
         } else {
             Err(crate::WindError::TypeMismatch {
                 expected: format!("{:?}", expected),
-                actual: format!("{:?}", value),
+                actual: value.type_name().to_string(),
             })
         }
     }
 }
 
+impl WindType {
+    /// Infer the type of a value. Array and map element types are inferred
+    /// from their contents: a single type if uniform, a `Union` if mixed, and
+    /// `Any` if empty.
+    pub fn infer(value: &WindValue) -> WindType {
+        match value {
+            WindValue::Bool(_) => WindType::Bool,
+            WindValue::I32(_) => WindType::I32,
+            WindValue::I64(_) => WindType::I64,
+            WindValue::F32(_) => WindType::F32,
+            WindValue::F64(_) => WindType::F64,
+            WindValue::String(_) => WindType::String,
+            WindValue::Bytes(_) => WindType::Bytes,
+            WindValue::Array(items) => WindType::Array(Box::new(Self::infer_common(items.iter()))),
+            WindValue::Map(map) => WindType::Map(Box::new(Self::infer_common(map.values()))),
+            WindValue::Null => WindType::Optional(Box::new(WindType::Any)),
+            WindValue::Timestamp(_) => WindType::Timestamp,
+        }
+    }
+
+    fn infer_common<'a>(values: impl Iterator<Item = &'a WindValue>) -> WindType {
+        let mut members: Vec<WindType> = Vec::new();
+        for ty in values.map(WindType::infer) {
+            if !members.contains(&ty) {
+                members.push(ty);
+            }
+        }
+
+        match members.len() {
+            0 => WindType::Any,
+            1 => members.remove(0),
+            _ => WindType::Union(members),
+        }
+    }
+}
+
 /// Schema registry for managing schemas
 #[derive(Debug, Default)]
 pub struct SchemaRegistry {
@@ -82,10 +278,485 @@ impl SchemaRegistry {
         self.schemas.get(id)
     }
 
+    /// All registered schemas, in no particular order
+    pub fn schemas(&self) -> impl Iterator<Item = &Schema> {
+        self.schemas.values()
+    }
+
+    /// Write every registered schema to a single bincode file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut schemas: Vec<Schema> = self.schemas.values().cloned().collect();
+        schemas.sort_by(|a, b| a.id.cmp(&b.id));
+        let file = SchemaFile {
+            version: SCHEMA_FILE_VERSION,
+            schemas,
+        };
+        std::fs::write(path, bincode::serialize(&file)?)?;
+        Ok(())
+    }
+
+    /// Load a registry previously written by `save`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file: SchemaFile = bincode::deserialize(&std::fs::read(path)?)?;
+        if file.version != SCHEMA_FILE_VERSION {
+            return Err(crate::WindError::Schema(format!(
+                "Unsupported schema file version {} (expected {})",
+                file.version, SCHEMA_FILE_VERSION
+            )));
+        }
+
+        let mut registry = Self::new();
+        for schema in file.schemas {
+            registry.register(schema);
+        }
+        Ok(registry)
+    }
+
     pub fn validate(&self, schema_id: &str, value: &WindValue) -> Result<()> {
         let schema = self
             .get(schema_id)
             .ok_or_else(|| crate::WindError::Schema(format!("Schema not found: {}", schema_id)))?;
         schema.validate(value)
     }
+
+    /// Like `validate`, but a `WindType::Struct(id)` field is checked against
+    /// the registry's schema `id`, nested as deep as the value goes
+    pub fn validate_recursive(&self, schema_id: &str, value: &WindValue) -> Result<()> {
+        let schema = self
+            .get(schema_id)
+            .ok_or_else(|| crate::WindError::Schema(format!("Schema not found: {}", schema_id)))?;
+        schema.validate_in(value, Some(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temperature_reading() -> WindValue {
+        let mut reading = HashMap::new();
+        reading.insert("temperature".to_string(), WindValue::F64(21.5));
+        reading.insert("timestamp".to_string(), WindValue::I64(1_700_000_000));
+        reading.insert(
+            "sensor_id".to_string(),
+            WindValue::String("TEMP_001".to_string()),
+        );
+        reading.insert("sequence".to_string(), WindValue::I64(42));
+        WindValue::Map(reading)
+    }
+
+    #[test]
+    fn test_union_field_accepts_any_member() {
+        let schema = Schema {
+            id: "Reading".to_string(),
+            version: 1,
+            name: "Reading".to_string(),
+            description: None,
+            fields: HashMap::from([(
+                "value".to_string(),
+                WindType::Union(vec![WindType::F64, WindType::String]),
+            )]),
+        };
+        let reading =
+            |value: WindValue| WindValue::Map(HashMap::from([("value".to_string(), value)]));
+
+        assert!(schema.validate(&reading(WindValue::F64(21.5))).is_ok());
+        assert!(schema
+            .validate(&reading(WindValue::String("sensor offline".to_string())))
+            .is_ok());
+
+        let error = schema
+            .validate(&reading(WindValue::Bool(true)))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            "Type mismatch: expected one of F64 | String, got Bool"
+        );
+    }
+
+    #[test]
+    fn test_map_field_checks_every_value() {
+        let schema = Schema {
+            id: "Readings".to_string(),
+            version: 1,
+            name: "Readings".to_string(),
+            description: None,
+            fields: HashMap::from([(
+                "by_room".to_string(),
+                WindType::Map(Box::new(WindType::F64)),
+            )]),
+        };
+        let readings = |rooms: Vec<(&str, WindValue)>| {
+            WindValue::Map(HashMap::from([(
+                "by_room".to_string(),
+                WindValue::Map(
+                    rooms
+                        .into_iter()
+                        .map(|(room, value)| (room.to_string(), value))
+                        .collect(),
+                ),
+            )]))
+        };
+
+        assert!(schema.validate(&readings(vec![])).is_ok());
+        assert!(schema
+            .validate(&readings(vec![
+                ("A", WindValue::F64(21.5)),
+                ("B", WindValue::F64(19.0))
+            ]))
+            .is_ok());
+
+        let error = schema
+            .validate(&readings(vec![
+                ("A", WindValue::F64(21.5)),
+                ("B", WindValue::from("warm")),
+            ]))
+            .unwrap_err();
+        assert!(
+            matches!(
+                &error,
+                crate::WindError::TypeMismatch { expected, actual }
+                    if expected == "F64" && actual == "String"
+            ),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_struct_fields_validate_against_the_registry() {
+        let schema = |id: &str, fields: Vec<(&str, WindType)>| Schema {
+            id: id.to_string(),
+            version: 1,
+            name: id.to_string(),
+            description: None,
+            fields: fields
+                .into_iter()
+                .map(|(name, ty)| (name.to_string(), ty))
+                .collect(),
+        };
+        let mut registry = SchemaRegistry::new();
+        registry.register(schema(
+            "Room",
+            vec![
+                ("name", WindType::String),
+                ("reading", WindType::Struct("Reading".to_string())),
+            ],
+        ));
+        registry.register(schema("Reading", vec![("value", WindType::F64)]));
+        let room = |reading: WindValue| {
+            WindValue::Map(HashMap::from([
+                ("name".to_string(), WindValue::from("Lab")),
+                ("reading".to_string(), reading),
+            ]))
+        };
+        let reading =
+            |value: WindValue| WindValue::Map(HashMap::from([("value".to_string(), value)]));
+
+        let valid = room(reading(WindValue::F64(21.5)));
+        assert!(registry.validate_recursive("Room", &valid).is_ok());
+
+        // Fine at the top level, wrong inside the nested struct
+        let invalid = room(reading(WindValue::from("warm")));
+        let error = registry
+            .validate_recursive("Room", &invalid)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "Type mismatch: expected F64, got String");
+        assert!(registry
+            .validate_recursive("Room", &room(WindValue::Map(HashMap::new())))
+            .is_err());
+        assert!(registry
+            .validate_recursive("Room", &room(WindValue::F64(21.5)))
+            .is_err());
+
+        // Without the registry a struct field can't be checked
+        let error = registry.validate("Room", &valid).unwrap_err().to_string();
+        assert!(error.contains("schema registry"), "{}", error);
+
+        registry.register(schema(
+            "Orphan",
+            vec![("child", WindType::Struct("Missing".to_string()))],
+        ));
+        let orphan = WindValue::Map(HashMap::from([(
+            "child".to_string(),
+            WindValue::Map(HashMap::new()),
+        )]));
+        let error = registry
+            .validate_recursive("Orphan", &orphan)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Schema not found: Missing"), "{}", error);
+    }
+
+    #[test]
+    fn test_timestamp_field_requires_a_timestamp() {
+        let schema = Schema {
+            id: "Reading".to_string(),
+            version: 1,
+            name: "Reading".to_string(),
+            description: None,
+            fields: HashMap::from([("taken_at".to_string(), WindType::Timestamp)]),
+        };
+        let reading = |taken_at: WindValue| {
+            WindValue::Map(HashMap::from([("taken_at".to_string(), taken_at)]))
+        };
+
+        assert!(schema.validate(&reading(WindValue::now())).is_ok());
+        // Micros in an I64 are just a number
+        match schema.validate(&reading(WindValue::I64(1_700_000_000_000_000))) {
+            Err(crate::WindError::TypeMismatch { expected, actual }) => {
+                assert_eq!(expected, "Timestamp");
+                assert_eq!(actual, "I64");
+            }
+            other => panic!("Expected a type mismatch, got {:?}", other),
+        }
+        assert_eq!(
+            Schema::infer("Reading", &reading(WindValue::Timestamp(0))).fields,
+            schema.fields
+        );
+    }
+
+    #[test]
+    fn test_null_satisfies_only_optional_fields() {
+        let schema = Schema {
+            id: "Reading".to_string(),
+            version: 1,
+            name: "Reading".to_string(),
+            description: None,
+            fields: HashMap::from([
+                ("value".to_string(), WindType::F64),
+                (
+                    "offset".to_string(),
+                    WindType::Optional(Box::new(WindType::F64)),
+                ),
+            ]),
+        };
+        let reading = |fields: &[(&str, WindValue)]| {
+            WindValue::Map(
+                fields
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.clone()))
+                    .collect(),
+            )
+        };
+
+        assert!(schema
+            .validate(&reading(&[("value", WindValue::F64(21.5))]))
+            .is_ok());
+        assert!(schema
+            .validate(&reading(&[
+                ("value", WindValue::F64(21.5)),
+                ("offset", WindValue::Null)
+            ]))
+            .is_ok());
+        assert!(schema
+            .validate(&reading(&[
+                ("value", WindValue::F64(21.5)),
+                ("offset", WindValue::F64(0.5))
+            ]))
+            .is_ok());
+        assert!(schema
+            .validate(&reading(&[
+                ("value", WindValue::F64(21.5)),
+                ("offset", WindValue::from("none"))
+            ]))
+            .is_err());
+
+        let error = schema
+            .validate(&reading(&[("value", WindValue::Null)]))
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "Type mismatch: expected F64, got Null");
+    }
+
+    fn reading_schema(version: u32, fields: &[(&str, WindType)]) -> Schema {
+        Schema {
+            id: "Reading".to_string(),
+            version,
+            name: "Reading".to_string(),
+            description: None,
+            fields: fields
+                .iter()
+                .map(|(name, field_type)| (name.to_string(), field_type.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_schema_compatibility_across_versions() {
+        let optional = |inner| WindType::Optional(Box::new(inner));
+        let v1 = reading_schema(1, &[("value", WindType::F64), ("unit", WindType::String)]);
+
+        // Adding an optional field keeps both directions
+        let with_note = reading_schema(
+            2,
+            &[
+                ("value", WindType::F64),
+                ("unit", WindType::String),
+                ("note", optional(WindType::String)),
+            ],
+        );
+        assert_eq!(
+            with_note.is_compatible_with(&v1),
+            CompatibilityResult::Compatible
+        );
+        assert_eq!(
+            v1.is_compatible_with(&with_note),
+            CompatibilityResult::Compatible
+        );
+
+        // A new required field can't be read from old values
+        let with_sensor = reading_schema(
+            2,
+            &[
+                ("value", WindType::F64),
+                ("unit", WindType::String),
+                ("sensor", WindType::String),
+            ],
+        );
+        assert_eq!(
+            with_sensor.is_compatible_with(&v1),
+            CompatibilityResult::ForwardOnly {
+                fields: vec!["sensor".to_string()]
+            }
+        );
+
+        // Removing a required field leaves old readers without it
+        let without_unit = reading_schema(2, &[("value", WindType::F64)]);
+        assert_eq!(
+            without_unit.is_compatible_with(&v1),
+            CompatibilityResult::BackwardOnly {
+                fields: vec!["unit".to_string()]
+            }
+        );
+
+        // Widening a type to a union reads old values only
+        let widened = reading_schema(
+            2,
+            &[
+                ("value", WindType::Union(vec![WindType::F64, WindType::I64])),
+                ("unit", WindType::String),
+            ],
+        );
+        assert!(matches!(
+            widened.is_compatible_with(&v1),
+            CompatibilityResult::BackwardOnly { .. }
+        ));
+
+        // Changing a type breaks both directions
+        let retyped = reading_schema(2, &[("value", WindType::I64), ("unit", WindType::Bytes)]);
+        let result = retyped.is_compatible_with(&v1);
+        assert!(matches!(result, CompatibilityResult::Incompatible { .. }));
+        assert_eq!(result.fields(), ["unit", "value"]);
+    }
+
+    #[test]
+    fn test_infer_temperature_reading() {
+        let reading = temperature_reading();
+        let schema = Schema::infer("TemperatureReading", &reading);
+
+        assert_eq!(schema.name, "TemperatureReading");
+        assert_eq!(schema.fields.len(), 4);
+        assert_eq!(schema.fields["temperature"], WindType::F64);
+        assert_eq!(schema.fields["timestamp"], WindType::I64);
+        assert_eq!(schema.fields["sensor_id"], WindType::String);
+        assert_eq!(schema.fields["sequence"], WindType::I64);
+
+        assert!(schema.validate(&reading).is_ok());
+
+        let mut wrong = HashMap::new();
+        wrong.insert(
+            "temperature".to_string(),
+            WindValue::String("hot".to_string()),
+        );
+        assert!(schema.validate(&WindValue::Map(wrong)).is_err());
+    }
+
+    #[test]
+    fn test_schema_registry_round_trip() {
+        let mut registry = SchemaRegistry::new();
+        registry.register(Schema::infer("TemperatureReading", &temperature_reading()));
+        let mut status = HashMap::new();
+        status.insert("ok".to_string(), WindType::Bool);
+        status.insert("message".to_string(), WindType::String);
+        registry.register(Schema {
+            id: "Status".to_string(),
+            version: 2,
+            name: "Status".to_string(),
+            description: None,
+            fields: status,
+        });
+
+        let path = std::env::temp_dir().join(format!("wind-schemas-{}.bin", std::process::id()));
+        registry.save(&path).unwrap();
+        let loaded = SchemaRegistry::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.schemas().count(), 2);
+        assert_eq!(loaded.get("Status").unwrap().version, 2);
+        assert!(loaded
+            .validate("TemperatureReading", &temperature_reading())
+            .is_ok());
+        assert!(loaded
+            .validate("TemperatureReading", &WindValue::F64(1.0))
+            .is_err());
+    }
+
+    #[test]
+    fn test_infer_nested_and_mixed() {
+        let mut location = HashMap::new();
+        location.insert("x".to_string(), WindValue::F64(1.0));
+        location.insert("y".to_string(), WindValue::F64(2.0));
+
+        let mut sample = HashMap::new();
+        sample.insert(
+            "samples".to_string(),
+            WindValue::Array(vec![WindValue::I32(1), WindValue::I32(2)]),
+        );
+        sample.insert(
+            "mixed".to_string(),
+            WindValue::Array(vec![
+                WindValue::F64(1.0),
+                WindValue::String("n/a".to_string()),
+            ]),
+        );
+        sample.insert("empty".to_string(), WindValue::Array(vec![]));
+        sample.insert("location".to_string(), WindValue::Map(location));
+        let sample = WindValue::Map(sample);
+
+        let schema = Schema::infer("Nested", &sample);
+        assert_eq!(
+            schema.fields["samples"],
+            WindType::Array(Box::new(WindType::I32))
+        );
+        assert_eq!(
+            schema.fields["mixed"],
+            WindType::Array(Box::new(WindType::Union(vec![
+                WindType::F64,
+                WindType::String
+            ])))
+        );
+        assert_eq!(
+            schema.fields["empty"],
+            WindType::Array(Box::new(WindType::Any))
+        );
+        assert_eq!(
+            schema.fields["location"],
+            WindType::Map(Box::new(WindType::F64))
+        );
+        assert!(schema.validate(&sample).is_ok());
+
+        // An empty sample array accepts elements of any type later on
+        let mut later = match sample {
+            WindValue::Map(map) => map,
+            _ => unreachable!(),
+        };
+        later.insert(
+            "empty".to_string(),
+            WindValue::Array(vec![WindValue::Bool(true)]),
+        );
+        assert!(schema.validate(&WindValue::Map(later)).is_ok());
+    }
 }
@@ -0,0 +1,155 @@
+use crate::{Message, Result, WindError};
+
+/// Wire format of a frame's message, sent in the frame's flag byte so each
+/// frame can be decoded whichever format its sender chose
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum SerializationFormat {
+    /// Compact and fastest; the default
+    #[default]
+    Bincode = 0,
+    /// Readable on the wire, and by clients in any language
+    Json = 1,
+    /// Compact like bincode, with libraries for most languages
+    MessagePack = 2,
+}
+
+impl SerializationFormat {
+    pub(crate) fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            0 => Some(SerializationFormat::Bincode),
+            1 => Some(SerializationFormat::Json),
+            2 => Some(SerializationFormat::MessagePack),
+            _ => None,
+        }
+    }
+
+    /// The serializer for this format
+    pub fn serializer(self) -> &'static dyn MessageSerializer {
+        match self {
+            SerializationFormat::Bincode => &BincodeSerializer,
+            SerializationFormat::Json => &JsonSerializer,
+            SerializationFormat::MessagePack => &MessagePackSerializer,
+        }
+    }
+}
+
+/// Turns messages into bytes of one format and back
+pub trait MessageSerializer {
+    fn serialize(&self, msg: &Message) -> Result<Vec<u8>>;
+
+    fn deserialize(&self, bytes: &[u8]) -> Result<Message>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeSerializer;
+
+impl MessageSerializer for BincodeSerializer {
+    fn serialize(&self, msg: &Message) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(msg)?)
+    }
+
+    fn deserialize(&self, bytes: &[u8]) -> Result<Message> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonSerializer;
+
+impl MessageSerializer for JsonSerializer {
+    fn serialize(&self, msg: &Message) -> Result<Vec<u8>> {
+        serde_json::to_vec(msg)
+            .map_err(|e| WindError::Protocol(format!("Failed to write JSON message: {}", e)))
+    }
+
+    fn deserialize(&self, bytes: &[u8]) -> Result<Message> {
+        serde_json::from_slice(bytes)
+            .map_err(|e| WindError::Protocol(format!("Invalid JSON message: {}", e)))
+    }
+}
+
+/// Writes structs as maps keyed by field name, for clients that don't share
+/// the Rust field order
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePackSerializer;
+
+impl MessageSerializer for MessagePackSerializer {
+    fn serialize(&self, msg: &Message) -> Result<Vec<u8>> {
+        rmp_serde::to_vec_named(msg)
+            .map_err(|e| WindError::Protocol(format!("Failed to write MessagePack message: {}", e)))
+    }
+
+    fn deserialize(&self, bytes: &[u8]) -> Result<Message> {
+        rmp_serde::from_slice(bytes)
+            .map_err(|e| WindError::Protocol(format!("Invalid MessagePack message: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MessagePayload, QosParams, SubscriptionMode, WindValue};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    const FORMATS: [SerializationFormat; 3] = [
+        SerializationFormat::Bincode,
+        SerializationFormat::Json,
+        SerializationFormat::MessagePack,
+    ];
+
+    #[test]
+    fn test_messages_round_trip_through_every_format() {
+        // One entry, so the map serializes the same way every time
+        let reading = HashMap::from([(
+            "celsius".to_string(),
+            WindValue::Array(vec![
+                WindValue::F64(21.5),
+                WindValue::Bytes(vec![0, 1, 255]),
+            ]),
+        )]);
+        let messages = [
+            Message::new(MessagePayload::Publish {
+                service: "SENSOR/ROOM_A/TEMP".to_string(),
+                sequence: u64::MAX,
+                value: Arc::new(WindValue::Map(reading)),
+                schema_id: None,
+                expires_at_us: Some(1),
+            }),
+            Message::new(MessagePayload::Subscribe {
+                service: "SENSOR/*".to_string(),
+                mode: SubscriptionMode::Periodic { interval_ms: 100 },
+                qos: QosParams::default(),
+                schema_id: Some("temperature".to_string()),
+                replay: 3,
+            }),
+            Message::new(MessagePayload::Ping),
+        ];
+
+        for format in FORMATS {
+            let serializer = format.serializer();
+            for msg in &messages {
+                let bytes = serializer.serialize(msg).unwrap();
+                let decoded = serializer.deserialize(&bytes).unwrap();
+                // Messages don't compare, but their serializations do
+                assert_eq!(
+                    serializer.serialize(&decoded).unwrap(),
+                    bytes,
+                    "{:?}",
+                    format
+                );
+                assert_eq!(decoded.id, msg.id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_is_readable() {
+        let msg = Message::new(MessagePayload::GetCurrent {
+            service: "SENSOR/ROOM_A/TEMP".to_string(),
+        });
+        let json = JsonSerializer.serialize(&msg).unwrap();
+        let text = String::from_utf8(json).unwrap();
+        assert!(text.contains(r#""GetCurrent":{"service":"SENSOR/ROOM_A/TEMP"}"#));
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use tracing::field::Empty;
+use tracing::{Level, Span};
+use uuid::Uuid;
+
+/// Name of the span following a published value, for filters and layers
+/// that pick it out
+pub const MESSAGE_SPAN: &str = "wind_message";
+
+/// `MESSAGE_SPAN_LEVEL` for no spans at all
+const DISABLED: u8 = 0;
+
+/// Level of the per-message spans, as `level_bits`; debug by default
+static MESSAGE_SPAN_LEVEL: AtomicU8 = AtomicU8::new(2);
+
+fn level_bits(level: Option<Level>) -> u8 {
+    match level {
+        None => DISABLED,
+        Some(Level::TRACE) => 1,
+        Some(Level::DEBUG) => 2,
+        Some(Level::INFO) => 3,
+        Some(Level::WARN) => 4,
+        Some(_) => 5, // Level::ERROR
+    }
+}
+
+/// Set the level of the spans that follow each published value, or turn
+/// them off with `None` for paths whose rate makes even a disabled span's
+/// check too dear. Applies process-wide, to spans opened from then on.
+pub fn set_message_span_level(level: Option<Level>) {
+    MESSAGE_SPAN_LEVEL.store(level_bits(level), Ordering::Relaxed);
+}
+
+/// Level of the spans that follow each published value, `None` if they're off
+pub fn message_span_level() -> Option<Level> {
+    match MESSAGE_SPAN_LEVEL.load(Ordering::Relaxed) {
+        1 => Some(Level::TRACE),
+        2 => Some(Level::DEBUG),
+        3 => Some(Level::INFO),
+        4 => Some(Level::WARN),
+        5 => Some(Level::ERROR),
+        _ => None,
+    }
+}
+
+/// Open the span for one `stage` ("publish", "send", "receive") of the value
+/// `sequence` of `service`, sent in message `id`. The same id and sequence
+/// on each side tie a publish to its delivery. `payload.size` and
+/// `subscription.mode` are left for the caller to record, once known.
+pub fn message_span(stage: &'static str, id: Uuid, service: &str, sequence: u64) -> Span {
+    macro_rules! span_at {
+        ($level:expr) => {
+            tracing::span!(
+                $level,
+                "wind_message",
+                stage,
+                message.id = %id,
+                service,
+                sequence,
+                payload.size = Empty,
+                subscription.mode = Empty,
+            )
+        };
+    }
+    match message_span_level() {
+        Some(Level::TRACE) => span_at!(Level::TRACE),
+        Some(Level::DEBUG) => span_at!(Level::DEBUG),
+        Some(Level::INFO) => span_at!(Level::INFO),
+        Some(Level::WARN) => span_at!(Level::WARN),
+        Some(_) => span_at!(Level::ERROR),
+        None => Span::none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_level_can_be_changed_and_disabled() {
+        for level in [Level::TRACE, Level::INFO, Level::ERROR] {
+            set_message_span_level(Some(level));
+            assert_eq!(message_span_level(), Some(level));
+        }
+        set_message_span_level(None);
+        assert_eq!(message_span_level(), None);
+        assert!(message_span("publish", Uuid::new_v4(), "TEST/SPANS", 1).is_disabled());
+        set_message_span_level(Some(Level::DEBUG));
+    }
+}
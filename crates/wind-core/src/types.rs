@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use std::collections::HashMap;
 
 /// Core WIND value types with compile-time type safety
@@ -12,7 +12,244 @@ pub enum WindValue {
     String(String),
     Bytes(Vec<u8>),
     Array(Vec<WindValue>),
-    Map(HashMap<String, WindValue>),
+    /// Serialized in key order, so equal maps encode to the same bytes
+    /// however they were built
+    Map(#[serde(serialize_with = "serialize_sorted")] HashMap<String, WindValue>),
+    /// No value, e.g. a JSON `null`; only satisfies `WindType::Optional`
+    Null,
+    /// Microseconds since the Unix epoch, negative before it. Kept apart
+    /// from `I64` so schemas can require a time and display code format one.
+    Timestamp(i64),
+}
+
+fn serialize_sorted<S: Serializer>(
+    map: &HashMap<String, WindValue>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    let mut entries: Vec<_> = map.iter().collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    serializer.collect_map(entries)
+}
+
+/// The variant of a `WindValue`, without its data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WindKind {
+    Bool,
+    I32,
+    I64,
+    F32,
+    F64,
+    String,
+    Bytes,
+    Array,
+    Map,
+    Null,
+    Timestamp,
+}
+
+impl WindKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            WindKind::Bool => "Bool",
+            WindKind::I32 => "I32",
+            WindKind::I64 => "I64",
+            WindKind::F32 => "F32",
+            WindKind::F64 => "F64",
+            WindKind::String => "String",
+            WindKind::Bytes => "Bytes",
+            WindKind::Array => "Array",
+            WindKind::Map => "Map",
+            WindKind::Null => "Null",
+            WindKind::Timestamp => "Timestamp",
+        }
+    }
+}
+
+impl std::fmt::Display for WindKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl WindValue {
+    pub fn kind(&self) -> WindKind {
+        match self {
+            WindValue::Bool(_) => WindKind::Bool,
+            WindValue::I32(_) => WindKind::I32,
+            WindValue::I64(_) => WindKind::I64,
+            WindValue::F32(_) => WindKind::F32,
+            WindValue::F64(_) => WindKind::F64,
+            WindValue::String(_) => WindKind::String,
+            WindValue::Bytes(_) => WindKind::Bytes,
+            WindValue::Array(_) => WindKind::Array,
+            WindValue::Map(_) => WindKind::Map,
+            WindValue::Null => WindKind::Null,
+            WindValue::Timestamp(_) => WindKind::Timestamp,
+        }
+    }
+
+    /// The current time as a `Timestamp`
+    pub fn now() -> WindValue {
+        WindValue::from(std::time::SystemTime::now())
+    }
+
+    /// The time a `Timestamp` stands for, `None` for any other value
+    pub fn as_system_time(&self) -> Option<std::time::SystemTime> {
+        let WindValue::Timestamp(us) = self else {
+            return None;
+        };
+        let offset = std::time::Duration::from_micros(us.unsigned_abs());
+        if *us >= 0 {
+            std::time::UNIX_EPOCH.checked_add(offset)
+        } else {
+            std::time::UNIX_EPOCH.checked_sub(offset)
+        }
+    }
+
+    /// Short name of the value's variant, for error messages that shouldn't
+    /// dump the (possibly huge) value itself
+    pub fn type_name(&self) -> &'static str {
+        self.kind().name()
+    }
+
+    /// Hash of the value's encoding, the same across runs and processes, for
+    /// caching and deduplicating values. Values with equal encodings hash
+    /// equally; `PartialEq`-equal floats such as `0.0` and `-0.0` may not.
+    pub fn content_hash(&self) -> u64 {
+        let bytes = bincode::serialize(self).expect("WindValue always serializes");
+        // FNV-1a: unlike `DefaultHasher`, its output is fixed
+        bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// The value at a dotted path through nested maps, e.g. `data.sensor_id`.
+    /// `None` if a key is missing or something on the way isn't a map.
+    pub fn get_path(&self, path: &str) -> Option<&WindValue> {
+        path.split('.').try_fold(self, |value, key| match value {
+            WindValue::Map(map) => map.get(key),
+            _ => None,
+        })
+    }
+
+    /// Like `get_path`, but for changing the value in place
+    pub fn get_path_mut(&mut self, path: &str) -> Option<&mut WindValue> {
+        path.split('.').try_fold(self, |value, key| match value {
+            WindValue::Map(map) => map.get_mut(key),
+            _ => None,
+        })
+    }
+
+    /// Length of the value's bincode encoding, worked out without encoding
+    /// it: a 4-byte variant tag, then the contents, with an 8-byte length
+    /// before each string, byte array, array and map.
+    pub fn encoded_size(&self) -> usize {
+        const TAG: usize = 4;
+        const LEN: usize = 8;
+        TAG + match self {
+            WindValue::Null => 0,
+            WindValue::Bool(_) => 1,
+            WindValue::I32(_) | WindValue::F32(_) => 4,
+            WindValue::I64(_) | WindValue::F64(_) | WindValue::Timestamp(_) => 8,
+            WindValue::String(s) => LEN + s.len(),
+            WindValue::Bytes(b) => LEN + b.len(),
+            WindValue::Array(items) => {
+                LEN + items.iter().map(WindValue::encoded_size).sum::<usize>()
+            }
+            WindValue::Map(map) => {
+                LEN + map
+                    .iter()
+                    .map(|(k, v)| LEN + k.len() + v.encoded_size())
+                    .sum::<usize>()
+            }
+        }
+    }
+}
+
+/// Values of the same kind compare naturally: numbers numerically, strings
+/// and bytes lexicographically, arrays element by element. Integer and float
+/// variants compare with each other by exact numeric value, without rounding
+/// large integers through `f64`; numerically equal values of different
+/// variants are ordered `I32 < I64 < F32 < F64` so the ordering agrees with
+/// `PartialEq`.
+/// Timestamps compare in time order, but not with numbers. Nulls are equal
+/// to each other. Anything else, including NaN, maps and
+/// mixed kinds, is incomparable.
+impl PartialOrd for WindValue {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        use WindValue::*;
+        match (self, other) {
+            (Bool(a), Bool(b)) => a.partial_cmp(b),
+            (String(a), String(b)) => a.partial_cmp(b),
+            (Bytes(a), Bytes(b)) => a.partial_cmp(b),
+            (Array(a), Array(b)) => a.partial_cmp(b),
+            (Map(_), Map(_)) => (self == other).then_some(std::cmp::Ordering::Equal),
+            (Null, Null) => Some(std::cmp::Ordering::Equal),
+            (Timestamp(a), Timestamp(b)) => a.partial_cmp(b),
+            _ => {
+                let ordering = match (self.as_i64(), other.as_i64()) {
+                    (Some(a), Some(b)) => a.cmp(&b),
+                    (Some(a), None) => cmp_int_float(a, other.as_f64()?)?,
+                    (None, Some(b)) => cmp_int_float(b, self.as_f64()?)?.reverse(),
+                    (None, None) => self.as_f64()?.partial_cmp(&other.as_f64()?)?,
+                };
+                Some(ordering.then_with(|| self.numeric_rank().cmp(&other.numeric_rank())))
+            }
+        }
+    }
+}
+
+/// Compare an integer with a float exactly. Casting the integer to `f64`
+/// would round it above 2^53, making distinct values compare equal.
+fn cmp_int_float(int: i64, float: f64) -> Option<std::cmp::Ordering> {
+    use std::cmp::Ordering;
+    // 2^63, exact as an f64: floats from here up are past every i64
+    const I64_END: f64 = 9_223_372_036_854_775_808.0;
+    if float.is_nan() {
+        return None;
+    }
+    if float >= I64_END {
+        return Some(Ordering::Less);
+    }
+    if float < -I64_END {
+        return Some(Ordering::Greater);
+    }
+    // In range, so the whole part converts without rounding
+    let whole = float.trunc();
+    Some(match int.cmp(&(whole as i64)) {
+        Ordering::Equal => whole.partial_cmp(&float)?,
+        ordering => ordering,
+    })
+}
+
+impl WindValue {
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            WindValue::I32(v) => Some(*v as i64),
+            WindValue::I64(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            WindValue::I32(v) => Some(*v as f64),
+            WindValue::I64(v) => Some(*v as f64),
+            WindValue::F32(v) => Some(*v as f64),
+            WindValue::F64(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Tie-break between numerically equal values of different variants
+    fn numeric_rank(&self) -> u8 {
+        match self {
+            WindValue::I32(_) => 0,
+            WindValue::I64(_) => 1,
+            WindValue::F32(_) => 2,
+            _ => 3,
+        }
+    }
 }
 
 /// Type definitions for schema validation
@@ -27,11 +264,15 @@ pub enum WindType {
     Bytes,
     Array(Box<WindType>),
     Map(Box<WindType>),
-    Struct(String), // Named struct type
+    Struct(String),          // Named struct type
+    Union(Vec<WindType>),    // Matches any of the member types
+    Any,                     // Unknown type, e.g. the element type of an empty array
+    Optional(Box<WindType>), // The inner type or Null; an optional field may also be missing
+    Timestamp,               // Microseconds since the Unix epoch, see `WindValue::Timestamp`
 }
 
 /// Service metadata
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ServiceInfo {
     pub name: String,
     pub address: String,
@@ -39,15 +280,40 @@ pub struct ServiceInfo {
     pub schema_id: Option<String>,
     pub ttl_ms: u64,
     pub tags: Vec<String>,
+    /// First registration time as Unix epoch micros, set by the registry
+    pub registered_at_us: u64,
+    /// Latest registration renewal as Unix epoch micros, set by the registry
+    pub last_heartbeat_us: u64,
 }
 
+/// Change to the set of registered services, delivered to watchers
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ServiceEvent {
+    Registered(ServiceInfo),
+    Removed(ServiceInfo),
+}
+
+/// Microseconds since the Unix epoch
+pub fn unix_time_us(at: std::time::SystemTime) -> u64 {
+    at.duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ServiceType {
     Publisher,
     RpcServer,
     Both,
 }
 
+impl ServiceType {
+    /// Whether a service of this type can serve `wanted` requests
+    pub fn provides(&self, wanted: &ServiceType) -> bool {
+        *self == ServiceType::Both || self == wanted
+    }
+}
+
 /// Subscription modes matching DIM functionality
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SubscriptionMode {
@@ -60,8 +326,9 @@ pub enum SubscriptionMode {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QosParams {
     pub reliability: ReliabilityLevel,
-    pub durability: bool,    // Retain last value for late joiners
-    pub max_queue_size: u32, // Backpressure control
+    pub durability: bool,             // Send the current value to late joiners
+    pub max_queue_size: u32,          // Backpressure control
+    pub max_queue_bytes: Option<u64>, // Cap on buffered encoded bytes (None = unbounded)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,8 +341,9 @@ impl Default for QosParams {
     fn default() -> Self {
         Self {
             reliability: ReliabilityLevel::BestEffort,
-            durability: false,
+            durability: true,
             max_queue_size: 1000,
+            max_queue_bytes: None,
         }
     }
 }
@@ -107,6 +375,18 @@ impl From<i64> for WindValue {
     }
 }
 
+/// A `Timestamp`, saturating for times too far from the epoch for `i64`
+/// microseconds (about 292,000 years)
+impl From<std::time::SystemTime> for WindValue {
+    fn from(at: std::time::SystemTime) -> Self {
+        let us = match at.duration_since(std::time::UNIX_EPOCH) {
+            Ok(after) => i64::try_from(after.as_micros()).unwrap_or(i64::MAX),
+            Err(before) => i64::try_from(before.duration().as_micros()).map_or(i64::MIN, |us| -us),
+        };
+        WindValue::Timestamp(us)
+    }
+}
+
 impl From<f32> for WindValue {
     fn from(v: f32) -> Self {
         WindValue::F32(v)
@@ -138,41 +418,408 @@ impl From<Vec<u8>> for WindValue {
 }
 
 // Conversions from WindValue to Rust types
-impl TryFrom<WindValue> for bool {
+macro_rules! impl_try_from_wind_value {
+    ($($target:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl TryFrom<WindValue> for $target {
+                type Error = crate::WindError;
+                fn try_from(v: WindValue) -> std::result::Result<$target, Self::Error> {
+                    match v {
+                        WindValue::$variant(inner) => Ok(inner),
+                        _ => Err(crate::WindError::TypeMismatch {
+                            expected: WindKind::$variant.name().to_string(),
+                            actual: v.type_name().to_string(),
+                        }),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_wind_value! {
+    bool => Bool,
+    i32 => I32,
+    i64 => I64,
+    f32 => F32,
+    f64 => F64,
+    String => String,
+    Vec<u8> => Bytes,
+}
+
+impl TryFrom<WindValue> for std::time::SystemTime {
     type Error = crate::WindError;
-    fn try_from(v: WindValue) -> std::result::Result<bool, Self::Error> {
-        match v {
-            WindValue::Bool(b) => Ok(b),
-            _ => Err(crate::WindError::TypeMismatch {
-                expected: "bool".to_string(),
-                actual: format!("{:?}", v),
-            }),
-        }
+    fn try_from(v: WindValue) -> std::result::Result<std::time::SystemTime, Self::Error> {
+        v.as_system_time()
+            .ok_or_else(|| crate::WindError::TypeMismatch {
+                expected: WindKind::Timestamp.name().to_string(),
+                actual: v.type_name().to_string(),
+            })
+    }
+}
+
+// Collections convert element by element. `Vec<u8>` is the exception: it
+// converts to and from `Bytes` (above), not an `Array` of numbers, and as `u8`
+// has no `WindValue` conversion the generic impls below don't cover it.
+impl<T: Into<WindValue>> From<Vec<T>> for WindValue {
+    fn from(items: Vec<T>) -> Self {
+        WindValue::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<WindValue>> From<HashMap<String, T>> for WindValue {
+    fn from(map: HashMap<String, T>) -> Self {
+        WindValue::Map(map.into_iter().map(|(k, v)| (k, v.into())).collect())
     }
 }
 
-impl TryFrom<WindValue> for i32 {
+impl<T> TryFrom<WindValue> for Vec<T>
+where
+    T: TryFrom<WindValue>,
+    T::Error: Into<crate::WindError>,
+{
     type Error = crate::WindError;
-    fn try_from(v: WindValue) -> std::result::Result<i32, Self::Error> {
+    fn try_from(v: WindValue) -> std::result::Result<Vec<T>, Self::Error> {
         match v {
-            WindValue::I32(i) => Ok(i),
+            WindValue::Array(items) => items
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    T::try_from(item).map_err(|e| crate::WindError::InvalidElement {
+                        location: format!("index {}", index),
+                        source: Box::new(e.into()),
+                    })
+                })
+                .collect(),
             _ => Err(crate::WindError::TypeMismatch {
-                expected: "i32".to_string(),
-                actual: format!("{:?}", v),
+                expected: WindKind::Array.name().to_string(),
+                actual: v.type_name().to_string(),
             }),
         }
     }
 }
 
-impl TryFrom<WindValue> for String {
+impl<T> TryFrom<WindValue> for HashMap<String, T>
+where
+    T: TryFrom<WindValue>,
+    T::Error: Into<crate::WindError>,
+{
     type Error = crate::WindError;
-    fn try_from(v: WindValue) -> std::result::Result<String, Self::Error> {
+    fn try_from(v: WindValue) -> std::result::Result<HashMap<String, T>, Self::Error> {
         match v {
-            WindValue::String(s) => Ok(s),
+            WindValue::Map(map) => map
+                .into_iter()
+                .map(|(key, value)| match T::try_from(value) {
+                    Ok(value) => Ok((key, value)),
+                    Err(e) => Err(crate::WindError::InvalidElement {
+                        location: format!("key '{}'", key),
+                        source: Box::new(e.into()),
+                    }),
+                })
+                .collect(),
             _ => Err(crate::WindError::TypeMismatch {
-                expected: "String".to_string(),
-                actual: format!("{:?}", v),
+                expected: WindKind::Map.name().to_string(),
+                actual: v.type_name().to_string(),
             }),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_of_each_variant() {
+        let cases = [
+            (WindValue::Bool(true), WindKind::Bool),
+            (WindValue::I32(1), WindKind::I32),
+            (WindValue::I64(1), WindKind::I64),
+            (WindValue::F32(1.0), WindKind::F32),
+            (WindValue::F64(1.0), WindKind::F64),
+            (WindValue::String("s".to_string()), WindKind::String),
+            (WindValue::Bytes(vec![1]), WindKind::Bytes),
+            (WindValue::Array(vec![]), WindKind::Array),
+            (WindValue::Map(HashMap::new()), WindKind::Map),
+            (WindValue::Null, WindKind::Null),
+            (WindValue::Timestamp(0), WindKind::Timestamp),
+        ];
+        for (value, kind) in cases {
+            assert_eq!(value.kind(), kind);
+            assert_eq!(value.type_name(), format!("{:?}", kind));
+        }
+    }
+
+    #[test]
+    fn test_numeric_ordering_across_families() {
+        assert!(WindValue::I32(5) < WindValue::F64(5.5));
+        assert!(WindValue::F32(-1.5) < WindValue::I64(-1));
+        assert!(WindValue::I64(i64::MAX) > WindValue::I64(i64::MAX - 1));
+        assert!(WindValue::I32(5) < WindValue::F64(5.0));
+        assert_ne!(WindValue::I32(5), WindValue::F64(5.0));
+        assert_eq!(
+            WindValue::F64(f64::NAN).partial_cmp(&WindValue::F64(1.0)),
+            None
+        );
+
+        // Exact beyond 2^53, where i64 values no longer fit an f64
+        let big = 1i64 << 53;
+        assert!(WindValue::I64(big + 1) > WindValue::F64(big as f64));
+        assert!(WindValue::I64(big - 1) < WindValue::F64(big as f64));
+        assert!(WindValue::I64(i64::MAX) < WindValue::F64(i64::MAX as f64));
+        assert!(WindValue::I64(i64::MIN) < WindValue::F64(-0.5));
+        assert!(WindValue::F64(-1e300) < WindValue::I64(i64::MIN));
+        assert!(WindValue::F64(-5.5) < WindValue::I64(-5));
+        assert_eq!(
+            WindValue::I64(1).partial_cmp(&WindValue::F64(f64::NAN)),
+            None
+        );
+
+        let mut values = vec![WindValue::F64(2.5), WindValue::I32(-3), WindValue::I64(1)];
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            values,
+            vec![WindValue::I32(-3), WindValue::I64(1), WindValue::F64(2.5)]
+        );
+    }
+
+    #[test]
+    fn test_string_and_array_ordering() {
+        assert!(WindValue::from("apple") < WindValue::from("banana"));
+        assert!(WindValue::from("Z") < WindValue::from("a"));
+        let short = WindValue::Array(vec![WindValue::I32(1)]);
+        let long = WindValue::Array(vec![WindValue::I32(1), WindValue::I32(0)]);
+        assert!(short < long);
+    }
+
+    #[test]
+    fn test_cross_kind_values_are_incomparable() {
+        let number = WindValue::I32(1);
+        let text = WindValue::from("1");
+        assert_eq!(number.partial_cmp(&text), None);
+        assert!(number.partial_cmp(&text).is_none() && text.partial_cmp(&number).is_none());
+        assert_eq!(WindValue::Bool(true).partial_cmp(&number), None);
+
+        let map = WindValue::Map(HashMap::new());
+        assert_eq!(
+            map.partial_cmp(&map.clone()),
+            Some(std::cmp::Ordering::Equal)
+        );
+        let other = WindValue::Map(HashMap::from([("k".to_string(), WindValue::I32(1))]));
+        assert_eq!(map.partial_cmp(&other), None);
+    }
+
+    #[test]
+    fn test_equal_maps_encode_identically() {
+        let keys: Vec<String> = (0..32).map(|i| format!("key_{}", i)).collect();
+        let build = |keys: Vec<&String>| {
+            let inner: HashMap<_, _> = keys
+                .iter()
+                .map(|key| (key.to_string(), WindValue::I32(key.len() as i32)))
+                .collect();
+            let mut outer: HashMap<_, _> = keys
+                .iter()
+                .map(|key| (key.to_string(), WindValue::from(key.as_str())))
+                .collect();
+            outer.insert("inner".to_string(), WindValue::Map(inner));
+            WindValue::Map(outer)
+        };
+        let forward = build(keys.iter().collect());
+        let backward = build(keys.iter().rev().collect());
+        assert_eq!(forward, backward);
+
+        let encoded = bincode::serialize(&forward).unwrap();
+        assert_eq!(encoded, bincode::serialize(&backward).unwrap());
+        assert_eq!(forward.content_hash(), backward.content_hash());
+        assert_ne!(forward.content_hash(), WindValue::I32(1).content_hash());
+        assert_eq!(
+            bincode::deserialize::<WindValue>(&encoded).unwrap(),
+            forward
+        );
+    }
+
+    #[test]
+    fn test_encoded_size_matches_codec() {
+        use crate::{Message, MessageCodec, MessagePayload};
+
+        let publish = |value: &WindValue| {
+            Message::new(MessagePayload::Publish {
+                service: "S".to_string(),
+                sequence: 1,
+                value: value.clone().into(),
+                schema_id: None,
+                expires_at_us: None,
+            })
+        };
+        let values = [
+            WindValue::Null,
+            WindValue::Bool(true),
+            WindValue::I32(-1),
+            WindValue::F64(0.5),
+            WindValue::Timestamp(-1),
+            WindValue::from("héllo"),
+            WindValue::Bytes(vec![7; 1000]),
+            WindValue::Array(vec![WindValue::I64(1), WindValue::from("two")]),
+            WindValue::Map(HashMap::from([
+                ("a".to_string(), WindValue::F32(1.0)),
+                (
+                    "nested".to_string(),
+                    WindValue::Map(HashMap::from([("b".to_string(), WindValue::Null)])),
+                ),
+            ])),
+        ];
+
+        for value in &values {
+            assert_eq!(
+                value.encoded_size(),
+                bincode::serialize(value).unwrap().len(),
+                "{:?}",
+                value
+            );
+            // The rest of the frame is the length prefix and message envelope
+            let frame = MessageCodec::encode(&publish(value)).unwrap().len();
+            let framing = frame - value.encoded_size();
+            assert!(framing <= 128, "{} bytes of framing", framing);
+        }
+    }
+
+    #[test]
+    fn test_timestamps_convert_from_system_time() {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        let after = UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456);
+        assert_eq!(
+            WindValue::from(after),
+            WindValue::Timestamp(1_700_000_000_123_456)
+        );
+        let before = UNIX_EPOCH - Duration::from_micros(1_500);
+        assert_eq!(WindValue::from(before), WindValue::Timestamp(-1_500));
+        for at in [after, before, UNIX_EPOCH] {
+            assert_eq!(WindValue::from(at).as_system_time(), Some(at));
+        }
+
+        let started = SystemTime::now();
+        let now = WindValue::now();
+        assert!(now >= WindValue::from(started));
+        assert!(now.as_system_time().unwrap() <= SystemTime::now());
+        assert_eq!(SystemTime::try_from(WindValue::from(after)).unwrap(), after);
+        // A time, not a number
+        assert_eq!(WindValue::I64(0).as_system_time(), None);
+        assert!(SystemTime::try_from(WindValue::I64(0)).is_err());
+        assert_eq!(
+            WindValue::Timestamp(0).partial_cmp(&WindValue::I64(0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_get_path_descends_through_maps() {
+        let mut value = WindValue::Map(HashMap::from([
+            (
+                "data".to_string(),
+                WindValue::Map(HashMap::from([
+                    ("sensor_id".to_string(), WindValue::from("TEMP_001")),
+                    ("readings".to_string(), WindValue::from(vec![21.5])),
+                ])),
+            ),
+            ("seq".to_string(), WindValue::I64(7)),
+        ]));
+
+        assert_eq!(value.get_path("seq"), Some(&WindValue::I64(7)));
+        assert_eq!(
+            value.get_path("data.sensor_id"),
+            Some(&WindValue::from("TEMP_001"))
+        );
+        assert!(value.get_path("data").is_some());
+        assert_eq!(value.get_path("data.missing"), None);
+        assert_eq!(value.get_path("seq.value"), None);
+        assert_eq!(value.get_path("data.readings.0"), None);
+        assert_eq!(value.get_path(""), None);
+        assert_eq!(WindValue::I64(1).get_path("seq"), None);
+
+        *value.get_path_mut("data.sensor_id").unwrap() = WindValue::from("TEMP_002");
+        assert_eq!(
+            value.get_path("data.sensor_id"),
+            Some(&WindValue::from("TEMP_002"))
+        );
+        assert!(value.get_path_mut("seq.value").is_none());
+    }
+
+    #[test]
+    fn test_conversions_round_trip() {
+        assert_eq!(i64::try_from(WindValue::from(42i64)).unwrap(), 42);
+        assert_eq!(f32::try_from(WindValue::from(1.5f32)).unwrap(), 1.5);
+        assert_eq!(f64::try_from(WindValue::from(2.5f64)).unwrap(), 2.5);
+        assert_eq!(
+            Vec::<u8>::try_from(WindValue::from(vec![1u8, 2])).unwrap(),
+            vec![1, 2]
+        );
+        let items = vec![WindValue::I32(1), WindValue::from("two")];
+        assert_eq!(
+            Vec::<WindValue>::try_from(WindValue::Array(items.clone())).unwrap(),
+            items
+        );
+        let fields = HashMap::from([("a".to_string(), WindValue::F64(1.0))]);
+        assert_eq!(
+            HashMap::try_from(WindValue::Map(fields.clone())).unwrap(),
+            fields
+        );
+
+        // No implicit widening: an I32 isn't an i64
+        match i64::try_from(WindValue::I32(1)) {
+            Err(crate::WindError::TypeMismatch { expected, actual }) => {
+                assert_eq!((expected.as_str(), actual.as_str()), ("I64", "I32"));
+            }
+            other => panic!("Expected a type mismatch, got {:?}", other),
+        }
+        assert!(Vec::<u8>::try_from(WindValue::Array(Vec::new())).is_err());
+        assert!(HashMap::<String, WindValue>::try_from(WindValue::Array(Vec::new())).is_err());
+    }
+
+    #[test]
+    fn test_collections_convert_element_by_element() {
+        let readings = vec![21.5, 22.0, 22.5];
+        let value = WindValue::from(readings.clone());
+        assert_eq!(
+            value,
+            WindValue::Array(readings.iter().map(|r| WindValue::F64(*r)).collect())
+        );
+        assert_eq!(Vec::<f64>::try_from(value).unwrap(), readings);
+
+        let names = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(
+            Vec::<String>::try_from(WindValue::from(names.clone())).unwrap(),
+            names
+        );
+        let offsets = HashMap::from([("x".to_string(), 0.5), ("y".to_string(), -1.0)]);
+        assert_eq!(
+            HashMap::<String, f64>::try_from(WindValue::from(offsets.clone())).unwrap(),
+            offsets
+        );
+
+        // Bytes stay bytes
+        assert_eq!(WindValue::from(vec![1u8, 2]), WindValue::Bytes(vec![1, 2]));
+
+        let mixed = WindValue::Array(vec![WindValue::F64(1.0), WindValue::from("two")]);
+        let error = Vec::<f64>::try_from(mixed).unwrap_err();
+        assert!(
+            matches!(&error, crate::WindError::InvalidElement { location, .. } if location == "index 1"),
+            "{:?}",
+            error
+        );
+        assert_eq!(
+            error.to_string(),
+            "Invalid element at index 1: Type mismatch: expected F64, got String"
+        );
+        let map = WindValue::Map(HashMap::from([("x".to_string(), WindValue::I32(1))]));
+        let error = HashMap::<String, f64>::try_from(map).unwrap_err();
+        assert!(error.to_string().starts_with("Invalid element at key 'x'"));
+    }
+
+    #[test]
+    fn test_type_mismatch_message_is_compact() {
+        let huge = WindValue::Bytes(vec![0u8; 1_000_000]);
+        let error = String::try_from(huge).unwrap_err().to_string();
+        assert_eq!(error, "Type mismatch: expected String, got Bytes");
+    }
+}
@@ -0,0 +1,138 @@
+use glob::PatternError;
+
+use crate::ServicePattern;
+
+/// Operations an ACL rule can grant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AclOperation {
+    Register,
+    Discover,
+    Subscribe,
+    /// Forcibly remove another provider's registration
+    Deregister,
+}
+
+/// Grants `identities` the listed operations on services matching `pattern`
+#[derive(Debug, Clone)]
+pub struct AclRule {
+    pattern: ServicePattern,
+    identities: Vec<String>,
+    operations: Vec<AclOperation>,
+}
+
+impl AclRule {
+    fn covers(&self, operation: AclOperation, service: &str) -> bool {
+        self.operations.contains(&operation) && self.pattern.matches(service)
+    }
+}
+
+/// Per-service access control list.
+///
+/// A service is open for an operation until some rule covers it; from then on
+/// only the identities granted by the covering rules are allowed.
+/// Identities come from the client id a connection presents with `Identify`,
+/// which any client can claim: without tokens the rules are advisory, keeping
+/// well-behaved clients apart rather than stopping hostile ones. A client
+/// token (`RegistryServer::with_client_token`, `Publisher::with_client_token`)
+/// binds its id to the connections that present it.
+#[derive(Debug, Clone, Default)]
+pub struct Acl {
+    rules: Vec<AclRule>,
+}
+
+impl Acl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow `identities` to perform `operations` on services matching `pattern`
+    pub fn allow(
+        mut self,
+        pattern: &str,
+        identities: &[&str],
+        operations: &[AclOperation],
+    ) -> Result<Self, PatternError> {
+        self.rules.push(AclRule {
+            pattern: ServicePattern::new(pattern)?,
+            identities: identities.iter().map(|id| id.to_string()).collect(),
+            operations: operations.to_vec(),
+        });
+        Ok(self)
+    }
+
+    /// Identities allowed to perform `operation` on `service`, or `None` if
+    /// no rule restricts it
+    pub fn allowed_identities(
+        &self,
+        operation: AclOperation,
+        service: &str,
+    ) -> Option<Vec<String>> {
+        let mut covered = false;
+        let mut identities = Vec::new();
+        for rule in self.rules.iter().filter(|r| r.covers(operation, service)) {
+            covered = true;
+            for identity in &rule.identities {
+                if !identities.contains(identity) {
+                    identities.push(identity.clone());
+                }
+            }
+        }
+        covered.then_some(identities)
+    }
+
+    pub fn is_allowed(
+        &self,
+        identity: Option<&str>,
+        operation: AclOperation,
+        service: &str,
+    ) -> bool {
+        match self.allowed_identities(operation, service) {
+            None => true,
+            Some(allowed) => identity.is_some_and(|id| allowed.iter().any(|a| a == id)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_restrict_only_covered_services() {
+        let acl = Acl::new()
+            .allow("SENSOR/**", &["sensor-gw"], &[AclOperation::Register])
+            .unwrap()
+            .allow(
+                "CONTROL/**",
+                &["operator"],
+                &[AclOperation::Register, AclOperation::Subscribe],
+            )
+            .unwrap();
+
+        assert!(acl.is_allowed(
+            Some("sensor-gw"),
+            AclOperation::Register,
+            "SENSOR/ROOM_1/TEMP"
+        ));
+        assert!(!acl.is_allowed(
+            Some("intruder"),
+            AclOperation::Register,
+            "SENSOR/ROOM_1/TEMP"
+        ));
+        assert!(!acl.is_allowed(None, AclOperation::Register, "SENSOR/ROOM_1/TEMP"));
+        assert!(!acl.is_allowed(Some("sensor-gw"), AclOperation::Register, "CONTROL/VALVE_1"));
+
+        // No rule covers subscribing to sensors or anything under DETECTOR
+        assert!(acl.is_allowed(None, AclOperation::Subscribe, "SENSOR/ROOM_1/TEMP"));
+        assert!(acl.is_allowed(None, AclOperation::Register, "DETECTOR/HALL_1/STATUS"));
+
+        assert_eq!(
+            acl.allowed_identities(AclOperation::Subscribe, "CONTROL/VALVE_1"),
+            Some(vec!["operator".to_string()])
+        );
+        assert_eq!(
+            acl.allowed_identities(AclOperation::Subscribe, "SENSOR/ROOM_1/TEMP"),
+            None
+        );
+    }
+}
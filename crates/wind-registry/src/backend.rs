@@ -0,0 +1,149 @@
+use dashmap::DashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::pattern::ServicePattern;
+use crate::registry::{ServiceEntry, ServiceEvent};
+
+/// Future returned by the `DiscoveryBackend` methods, boxed so that a
+/// backend can await a remote store
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Where a `Registry` keeps its service registrations. The registry handles
+/// TTLs, versioning and watcher notifications on top; a backend only stores
+/// entries, so an external store (etcd, Consul, ...) can stand in for the
+/// default `InMemoryBackend`.
+///
+/// Entries are returned whether or not they have expired, the registry
+/// filters them.
+pub trait DiscoveryBackend: Send + Sync + 'static {
+    /// Store `entry`, replacing any registration of the same name
+    fn register(&self, entry: ServiceEntry) -> BackendFuture<'_, ()>;
+
+    /// Extend the registration of `name` at `address` by `ttl`. False if
+    /// there is no such registration.
+    fn renew<'a>(
+        &'a self,
+        name: &'a str,
+        address: &'a str,
+        ttl: Duration,
+    ) -> BackendFuture<'a, bool>;
+
+    /// The registration of `name`
+    fn lookup<'a>(&'a self, name: &'a str) -> BackendFuture<'a, Option<ServiceEntry>>;
+
+    /// Registrations whose name matches `pattern`, in no particular order
+    fn discover<'a>(&'a self, pattern: &'a ServicePattern) -> BackendFuture<'a, Vec<ServiceEntry>>;
+
+    /// Remove the registration of `name`, if there is one and, given an
+    /// `address`, it is at that address
+    fn remove<'a>(
+        &'a self,
+        name: &'a str,
+        address: Option<&'a str>,
+    ) -> BackendFuture<'a, Option<ServiceEntry>>;
+
+    /// Drop expired registrations, returning how many there were
+    fn cleanup(&self) -> BackendFuture<'_, usize>;
+
+    /// Number of registrations held, expired or not
+    fn len(&self) -> BackendFuture<'_, usize>;
+
+    fn is_empty(&self) -> BackendFuture<'_, bool> {
+        Box::pin(async move { self.len().await == 0 })
+    }
+
+    /// Told of every change the registry makes, after its own watchers
+    /// are, so a shared store can tell the other registries using it
+    fn notify(&self, _event: ServiceEvent) -> BackendFuture<'_, ()> {
+        Box::pin(async {})
+    }
+
+    /// Changes other registries sharing the store have made, for the
+    /// registry to pass on to its watchers (see `Registry::follow_backend`).
+    /// None, the default, for a store no one else writes to.
+    fn watch(&self) -> BackendFuture<'_, Option<mpsc::Receiver<ServiceEvent>>> {
+        Box::pin(async { None })
+    }
+}
+
+/// Registrations kept in memory, lost when the registry stops
+#[derive(Debug, Default)]
+pub struct InMemoryBackend {
+    services: DashMap<String, ServiceEntry>,
+}
+
+impl InMemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl DiscoveryBackend for InMemoryBackend {
+    fn register(&self, entry: ServiceEntry) -> BackendFuture<'_, ()> {
+        self.services.insert(entry.info.name.clone(), entry);
+        Box::pin(async {})
+    }
+
+    fn renew<'a>(
+        &'a self,
+        name: &'a str,
+        address: &'a str,
+        ttl: Duration,
+    ) -> BackendFuture<'a, bool> {
+        let renewed = match self.services.get_mut(name) {
+            Some(mut entry) if entry.info.address == address => {
+                entry.renew(ttl);
+                true
+            }
+            _ => false,
+        };
+        Box::pin(async move { renewed })
+    }
+
+    fn lookup<'a>(&'a self, name: &'a str) -> BackendFuture<'a, Option<ServiceEntry>> {
+        let entry = self.services.get(name).map(|entry| entry.value().clone());
+        Box::pin(async move { entry })
+    }
+
+    fn discover<'a>(&'a self, pattern: &'a ServicePattern) -> BackendFuture<'a, Vec<ServiceEntry>> {
+        let entries = self
+            .services
+            .iter()
+            .filter(|entry| pattern.matches(entry.key()))
+            .map(|entry| entry.value().clone())
+            .collect();
+        Box::pin(async move { entries })
+    }
+
+    fn remove<'a>(
+        &'a self,
+        name: &'a str,
+        address: Option<&'a str>,
+    ) -> BackendFuture<'a, Option<ServiceEntry>> {
+        let removed = self
+            .services
+            .remove_if(name, |_, entry| {
+                address.is_none_or(|address| entry.info.address == address)
+            })
+            .map(|(_, entry)| entry);
+        Box::pin(async move { removed })
+    }
+
+    fn cleanup(&self) -> BackendFuture<'_, usize> {
+        let mut removed = 0;
+        self.services.retain(|_, entry| {
+            let expired = entry.is_expired();
+            removed += expired as usize;
+            !expired
+        });
+        Box::pin(async move { removed })
+    }
+
+    fn len(&self) -> BackendFuture<'_, usize> {
+        let len = self.services.len();
+        Box::pin(async move { len })
+    }
+}
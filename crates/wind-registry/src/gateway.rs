@@ -0,0 +1,256 @@
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+use crate::{
+    Acl, AclOperation, DiscoveryBackend, InMemoryBackend, Registry, RegistryServer, ServiceEvent,
+};
+
+/// HTTP/JSON view of a registry for web dashboards:
+///
+/// - `GET /services?pattern=SENSOR/*` lists matching services, ordered by name
+/// - `GET /events?pattern=SENSOR/*` streams `registered`/`removed` Server-Sent
+///   Events, starting with every service already registered
+///
+/// Requests are anonymous, so the registry's ACL hides any service it
+/// restricts discovery of.
+pub struct HttpGateway<B = InMemoryBackend> {
+    registry: Arc<Registry<B>>,
+    acl: Arc<Acl>,
+    bind_address: String,
+}
+
+struct GatewayState<B> {
+    registry: Arc<Registry<B>>,
+    acl: Arc<Acl>,
+}
+
+// Derived `Clone` would needlessly require `B: Clone`
+impl<B> Clone for GatewayState<B> {
+    fn clone(&self) -> Self {
+        Self {
+            registry: self.registry.clone(),
+            acl: self.acl.clone(),
+        }
+    }
+}
+
+impl<B> GatewayState<B> {
+    fn visible(&self, service: &str) -> bool {
+        self.acl.is_allowed(None, AclOperation::Discover, service)
+    }
+}
+
+#[derive(Deserialize)]
+struct PatternQuery {
+    #[serde(default = "match_all")]
+    pattern: String,
+}
+
+fn match_all() -> String {
+    "*".to_string()
+}
+
+impl<B: DiscoveryBackend> HttpGateway<B> {
+    /// Gateway to `server`'s registry, listening on a TCP `bind_address`
+    pub fn new(server: &RegistryServer<B>, bind_address: String) -> Self {
+        Self {
+            registry: server.registry(),
+            acl: server.acl(),
+            bind_address,
+        }
+    }
+
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/services", get(list_services::<B>))
+            .route("/events", get(stream_events::<B>))
+            .with_state(GatewayState {
+                registry: self.registry.clone(),
+                acl: self.acl.clone(),
+            })
+    }
+
+    pub async fn run(&self) -> wind_core::Result<()> {
+        self.run_until(std::future::pending()).await
+    }
+
+    /// Serve until `shutdown` resolves
+    pub async fn run_until<F>(&self, shutdown: F) -> wind_core::Result<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let listener = TcpListener::bind(&self.bind_address).await?;
+        info!("WIND HTTP gateway listening on {}", self.bind_address);
+        axum::serve(listener, self.router())
+            .with_graceful_shutdown(shutdown)
+            .await?;
+        Ok(())
+    }
+}
+
+async fn list_services<B: DiscoveryBackend>(
+    State(state): State<GatewayState<B>>,
+    Query(query): Query<PatternQuery>,
+) -> Response {
+    match state.registry.discover_services(&query.pattern).await {
+        Ok(mut services) => {
+            services.retain(|service| state.visible(&service.name));
+            services.sort_by(|a, b| a.name.cmp(&b.name));
+            Json(services).into_response()
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+async fn stream_events<B: DiscoveryBackend>(
+    State(state): State<GatewayState<B>>,
+    Query(query): Query<PatternQuery>,
+) -> Response {
+    let watch = match state.registry.watch_services(&query.pattern).await {
+        Ok(watch) => watch,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    let events = futures_util::stream::unfold(watch, move |mut watch| {
+        let state = state.clone();
+        async move {
+            loop {
+                let (name, service) = match watch.recv().await {
+                    Ok(ServiceEvent::Registered(service)) => ("registered", service),
+                    Ok(ServiceEvent::Removed(service)) => ("removed", service),
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("HTTP event stream missed {} notifications", missed);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return None,
+                };
+                if !state.visible(&service.name) {
+                    continue;
+                }
+                let event = Event::default().event(name).json_data(&service);
+                match event {
+                    Ok(event) => return Some((Ok::<_, Infallible>(event), watch)),
+                    Err(e) => warn!("Failed to encode service event: {}", e),
+                }
+            }
+        }
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::time::Duration;
+    use wind_core::{ServiceInfo, ServiceType};
+
+    fn service(name: &str) -> ServiceInfo {
+        ServiceInfo {
+            name: name.to_string(),
+            address: "127.0.0.1:9000".to_string(),
+            service_type: ServiceType::Publisher,
+            schema_id: None,
+            ttl_ms: 60000,
+            tags: Vec::new(),
+            registered_at_us: 0,
+            last_heartbeat_us: 0,
+        }
+    }
+
+    async fn start_gateway(server: &RegistryServer, addr: &str) {
+        let gateway = HttpGateway::new(server, addr.to_string());
+        tokio::spawn(async move {
+            let _ = gateway.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    async fn get(addr: &str, path: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            path, addr
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        stream
+    }
+
+    #[tokio::test]
+    async fn test_services_endpoint_lists_registry_state() {
+        let addr = "127.0.0.1:7225";
+        let server = RegistryServer::new("127.0.0.1:0".to_string());
+        let registry = server.registry();
+        for name in ["SENSOR/B/TEMP", "SENSOR/A/TEMP", "DETECTOR/HALL/STATUS"] {
+            registry
+                .register_service(service(name), 60000)
+                .await
+                .unwrap();
+        }
+        start_gateway(&server, addr).await;
+
+        let mut response = String::new();
+        get(addr, "/services?pattern=SENSOR/*")
+            .await
+            .read_to_string(&mut response)
+            .await
+            .unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let services: Vec<ServiceInfo> = serde_json::from_str(body).unwrap();
+        let names: Vec<_> = services.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["SENSOR/A/TEMP", "SENSOR/B/TEMP"]);
+
+        let mut response = String::new();
+        get(addr, "/services")
+            .await
+            .read_to_string(&mut response)
+            .await
+            .unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let services: Vec<ServiceInfo> = serde_json::from_str(body).unwrap();
+        assert_eq!(services.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_events_endpoint_streams_registrations() {
+        let addr = "127.0.0.1:7226";
+        let server = RegistryServer::new("127.0.0.1:0".to_string());
+        start_gateway(&server, addr).await;
+
+        let mut stream = get(addr, "/events?pattern=SENSOR/*").await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        server
+            .registry()
+            .register_service(service("SENSOR/C/TEMP"), 60000)
+            .await
+            .unwrap();
+
+        let mut received = String::new();
+        let mut buf = [0u8; 1024];
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+        while !received.contains("SENSOR/C/TEMP") {
+            let read = tokio::time::timeout_at(deadline, stream.read(&mut buf))
+                .await
+                .expect("No event before the deadline")
+                .unwrap();
+            assert!(read > 0, "Stream closed: {}", received);
+            received.push_str(&String::from_utf8_lossy(&buf[..read]));
+        }
+        assert!(received.contains("text/event-stream"), "{}", received);
+        assert!(received.contains("event: registered"), "{}", received);
+    }
+}
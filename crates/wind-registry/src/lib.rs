@@ -1,7 +1,19 @@
+pub mod acl;
+pub mod backend;
+#[cfg(feature = "http-gateway")]
+pub mod gateway;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod pattern;
 pub mod registry;
 pub mod server;
 
+pub use acl::*;
+pub use backend::*;
+#[cfg(feature = "http-gateway")]
+pub use gateway::*;
+#[cfg(feature = "metrics")]
+pub use metrics::*;
 pub use pattern::*;
 pub use registry::*;
 pub use server::*;
@@ -1,4 +1,7 @@
 use clap::Parser;
+use std::path::PathBuf;
+use tracing::info;
+use wind_core::SchemaRegistry;
 use wind_registry::RegistryServer;
 
 #[derive(Parser)]
@@ -10,6 +13,28 @@ struct Args {
 
     #[arg(long, default_value = "info")]
     log_level: String,
+
+    /// Preload schemas from a file written by `SchemaRegistry::save`
+    #[arg(long)]
+    load_schemas: Option<PathBuf>,
+
+    /// Copy the services of this peer registry on startup
+    #[arg(long)]
+    peer: Option<String>,
+
+    /// Shared secret clients must present before anything else
+    #[arg(long)]
+    token: Option<String>,
+
+    /// Also serve the HTTP/JSON gateway for web dashboards on this address
+    #[cfg(feature = "http-gateway")]
+    #[arg(long)]
+    http_bind: Option<String>,
+
+    /// Also serve Prometheus metrics at /metrics on this address
+    #[cfg(feature = "metrics")]
+    #[arg(long)]
+    metrics_bind: Option<String>,
 }
 
 #[tokio::main]
@@ -21,8 +46,42 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .with_env_filter(&args.log_level)
         .init();
 
-    let server = RegistryServer::new(args.bind);
-    server.run().await?;
+    let mut server = RegistryServer::new(args.bind);
+    if let Some(peer) = args.peer {
+        server = server.with_peer(peer);
+    }
+    if let Some(token) = args.token {
+        server = server.with_token(token);
+    }
+    #[cfg(feature = "metrics")]
+    if let Some(metrics_bind) = args.metrics_bind {
+        server = server.with_metrics_addr(metrics_bind);
+    }
+
+    if let Some(path) = &args.load_schemas {
+        let schemas = SchemaRegistry::load(path)?;
+        let registry = server.registry();
+        for schema in schemas.schemas() {
+            registry.register_schema(schema.clone())?;
+        }
+        info!(
+            "Loaded {} schema(s) from {}",
+            schemas.schemas().count(),
+            path.display()
+        );
+    }
+
+    #[cfg(feature = "http-gateway")]
+    if let Some(http_bind) = args.http_bind {
+        let gateway = wind_registry::HttpGateway::new(&server, http_bind);
+        tokio::spawn(async move {
+            if let Err(e) = gateway.run_until(wind_core::shutdown_signal()).await {
+                tracing::error!("HTTP gateway error: {}", e);
+            }
+        });
+    }
+
+    server.run_until(wind_core::shutdown_signal()).await?;
 
     Ok(())
 }
@@ -0,0 +1,285 @@
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::net::TcpListener;
+use tracing::{debug, warn};
+use wind_core::unix_time_us;
+
+use crate::{DiscoveryBackend, Histogram, Registry};
+
+/// Content type of the Prometheus text exposition format
+const TEXT_FORMAT: &str = "text/plain; version=0.0.4";
+
+/// Serve `GET /metrics` on `listener` until the task is aborted
+pub(crate) async fn serve_metrics<B: DiscoveryBackend>(
+    registry: Arc<Registry<B>>,
+    listener: TcpListener,
+) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Failed to accept metrics connection: {}", e);
+                continue;
+            }
+        };
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            let service = service_fn(|request| {
+                let registry = registry.clone();
+                async move { Ok::<_, Infallible>(respond(&registry, request).await) }
+            });
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Metrics connection error: {}", e);
+            }
+        });
+    }
+}
+
+async fn respond<B: DiscoveryBackend>(
+    registry: &Registry<B>,
+    request: Request<Incoming>,
+) -> Response<Full<Bytes>> {
+    if request.method() != Method::GET || request.uri().path() != "/metrics" {
+        let mut response = Response::new(Full::from("Not found\n"));
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return response;
+    }
+    let mut response = Response::new(Full::from(render_metrics(registry).await));
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static(TEXT_FORMAT));
+    response
+}
+
+/// `registry`'s metrics in the Prometheus text format: its counters, one
+/// gauge per registered service and histograms of discovery calls
+pub async fn render_metrics<B: DiscoveryBackend>(registry: &Registry<B>) -> String {
+    let metrics = registry.metrics();
+    let mut out = String::new();
+    let mut single = |name: &str, kind: &str, help: &str, value: u64| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "{} {}", name, value);
+    };
+    single(
+        "wind_registry_active_services",
+        "gauge",
+        "Services currently registered",
+        metrics.active_services.load(Ordering::Relaxed),
+    );
+    single(
+        "wind_registry_total_registrations",
+        "counter",
+        "Registrations accepted",
+        metrics.total_registrations.load(Ordering::Relaxed),
+    );
+    single(
+        "wind_registry_total_lookups",
+        "counter",
+        "Lookups of a service by name",
+        metrics.total_lookups.load(Ordering::Relaxed),
+    );
+    single(
+        "wind_registry_active_watches",
+        "gauge",
+        "Open service watches",
+        metrics.active_watches.load(Ordering::Relaxed),
+    );
+    single(
+        "wind_registry_dropped_notifications",
+        "counter",
+        "Watch notifications lost to slow watchers",
+        metrics.dropped_notifications.load(Ordering::Relaxed),
+    );
+    single(
+        "wind_registry_bytes_in",
+        "counter",
+        "Bytes received from registry clients",
+        metrics.traffic.bytes_in(),
+    );
+    single(
+        "wind_registry_bytes_out",
+        "counter",
+        "Bytes sent to registry clients",
+        metrics.traffic.bytes_out(),
+    );
+
+    let mut services = registry.list_services().await;
+    services.sort_by(|a, b| a.name.cmp(&b.name));
+    let now_us = unix_time_us(SystemTime::now());
+    let _ = writeln!(
+        out,
+        "# HELP wind_registry_service_up Registered services, by name, type and address"
+    );
+    let _ = writeln!(out, "# TYPE wind_registry_service_up gauge");
+    for service in &services {
+        let _ = writeln!(
+            out,
+            "wind_registry_service_up{{service=\"{}\",type=\"{:?}\",address=\"{}\"}} 1",
+            escape_label(&service.name),
+            service.service_type,
+            escape_label(&service.address)
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP wind_registry_service_heartbeat_age_seconds Time since each service last renewed its registration"
+    );
+    let _ = writeln!(
+        out,
+        "# TYPE wind_registry_service_heartbeat_age_seconds gauge"
+    );
+    for service in &services {
+        let age_us = now_us.saturating_sub(service.last_heartbeat_us);
+        let _ = writeln!(
+            out,
+            "wind_registry_service_heartbeat_age_seconds{{service=\"{}\"}} {}",
+            escape_label(&service.name),
+            age_us as f64 / 1e6
+        );
+    }
+
+    histogram(
+        &mut out,
+        "wind_registry_discover_duration_seconds",
+        "Time taken by each discovery page",
+        &metrics.discover_latency_us,
+        1e6,
+    );
+    histogram(
+        &mut out,
+        "wind_registry_discover_results",
+        "Services returned by each discovery page",
+        &metrics.discover_results,
+        1.0,
+    );
+    out
+}
+
+/// Write `histogram` with its observations divided by `scale`, e.g. to
+/// report microseconds in seconds
+fn histogram(out: &mut String, name: &str, help: &str, histogram: &Histogram, scale: f64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    let counts = histogram.cumulative_counts();
+    for (bound, count) in histogram.bounds().iter().zip(&counts) {
+        let _ = writeln!(
+            out,
+            "{}_bucket{{le=\"{}\"}} {}",
+            name,
+            *bound as f64 / scale,
+            count
+        );
+    }
+    let total = counts.last().copied().unwrap_or(0);
+    let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, total);
+    let _ = writeln!(out, "{}_sum {}", name, histogram.sum() as f64 / scale);
+    let _ = writeln!(out, "{}_count {}", name, total);
+}
+
+/// `value` with the characters the text format reserves in labels escaped
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RegistryServer;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::time::Duration;
+    use wind_core::{Message, MessageCodec, MessagePayload, ServiceType, DEFAULT_MAX_HOPS};
+
+    async fn scrape(addr: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET /metrics HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            addr
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+        assert!(response.contains(TEXT_FORMAT), "{}", response);
+        response
+    }
+
+    #[tokio::test]
+    async fn test_metrics_count_registrations_and_discovery() {
+        let addr = "127.0.0.1:7108";
+        let metrics_addr = "127.0.0.1:7109";
+        let server =
+            RegistryServer::new(addr.to_string()).with_metrics_addr(metrics_addr.to_string());
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let before = scrape(metrics_addr).await;
+        assert!(before.contains("\nwind_registry_total_registrations 0\n"));
+        assert!(before.contains("\nwind_registry_discover_duration_seconds_count 0\n"));
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let register = Message::new(MessagePayload::RegisterService {
+            service: "SENSOR/ROOM_1/TEMP".to_string(),
+            address: "127.0.0.1:9000".to_string(),
+            service_type: ServiceType::Publisher,
+            schema_id: None,
+            ttl_ms: 60000,
+            tags: Vec::new(),
+        });
+        MessageCodec::write(&mut client, &register).await.unwrap();
+        MessageCodec::decode(&mut client).await.unwrap();
+        let discover = Message::new(MessagePayload::DiscoverServices {
+            pattern: "SENSOR/*/TEMP".to_string(),
+            offset: 0,
+            limit: 0,
+            registered_since_us: None,
+            service_type: None,
+            max_hops: DEFAULT_MAX_HOPS,
+        });
+        MessageCodec::write(&mut client, &discover).await.unwrap();
+        MessageCodec::decode(&mut client).await.unwrap();
+
+        let after = scrape(metrics_addr).await;
+        assert!(
+            after.contains("\nwind_registry_total_registrations 1\n"),
+            "{}",
+            after
+        );
+        assert!(
+            after.contains("\nwind_registry_active_services 1\n"),
+            "{}",
+            after
+        );
+        assert!(after.contains(
+            "\nwind_registry_service_up{service=\"SENSOR/ROOM_1/TEMP\",type=\"Publisher\",address=\"127.0.0.1:9000\"} 1\n"
+        ));
+        assert!(after.contains("\nwind_registry_discover_duration_seconds_count 1\n"));
+        // The one discovery returned one service
+        assert!(after.contains("\nwind_registry_discover_results_bucket{le=\"0\"} 0\n"));
+        assert!(after.contains("\nwind_registry_discover_results_bucket{le=\"1\"} 1\n"));
+    }
+
+    #[test]
+    fn test_label_values_are_escaped() {
+        assert_eq!(escape_label("a\"b\\c\nd"), r#"a\"b\\c\nd"#);
+    }
+}
@@ -1,12 +1,29 @@
 use dashmap::DashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::{broadcast, RwLock};
 use tracing::{debug, info};
 use uuid::Uuid;
 
+use crate::backend::{DiscoveryBackend, InMemoryBackend};
 use crate::pattern::ServicePattern;
-use wind_core::{Result, ServiceInfo, WindError};
+use wind_core::{unix_time_us, CompatibilityResult, Result, ServiceInfo, WindError};
+
+/// Notifications buffered per watch before the oldest is overwritten. A power
+/// of two, as `broadcast` rounds its capacity up to one.
+const WATCH_CAPACITY: usize = 1024;
+
+/// Most times a discovery rescans because the registry changed under it
+const MAX_DISCOVERY_SCANS: usize = 3;
+
+/// Bucket bounds of `RegistryMetrics::discover_latency_us`
+const DISCOVER_LATENCY_BOUNDS_US: &[u64] = &[
+    50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000,
+];
+
+/// Bucket bounds of `RegistryMetrics::discover_results`
+const DISCOVER_RESULT_BOUNDS: &[u64] = &[0, 1, 5, 10, 50, 100, 500, 1_000];
 
 /// Service entry with TTL and metadata
 #[derive(Debug, Clone)]
@@ -18,8 +35,10 @@ pub struct ServiceEntry {
 }
 
 impl ServiceEntry {
-    pub fn new(info: ServiceInfo, ttl: Duration) -> Self {
+    pub fn new(mut info: ServiceInfo, ttl: Duration) -> Self {
         let now = Instant::now();
+        info.registered_at_us = unix_time_us(SystemTime::now());
+        info.last_heartbeat_us = info.registered_at_us;
         Self {
             info,
             registered_at: now,
@@ -32,6 +51,7 @@ impl ServiceEntry {
         let now = Instant::now();
         self.last_heartbeat = now;
         self.expires_at = now + ttl;
+        self.info.last_heartbeat_us = unix_time_us(SystemTime::now());
     }
 
     pub fn is_expired(&self) -> bool {
@@ -39,41 +59,157 @@ impl ServiceEntry {
     }
 }
 
+pub use wind_core::ServiceEvent;
+
 /// Watch subscription for service discovery notifications
 #[derive(Debug)]
 pub struct ServiceWatch {
     pub id: Uuid,
     pub pattern: ServicePattern,
-    pub sender: broadcast::Sender<ServiceInfo>,
+    pub sender: broadcast::Sender<ServiceEvent>,
+    /// Notifications overwritten before the watcher received them
+    pub dropped: Arc<AtomicU64>,
+}
+
+impl ServiceWatch {
+    fn send(&self, event: ServiceEvent, metrics: &RegistryMetrics) {
+        // A full channel overwrites the oldest notification the watcher hasn't read
+        if self.sender.len() >= WATCH_CAPACITY {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            metrics
+                .dropped_notifications
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        let _ = self.sender.send(event);
+    }
+}
+
+/// Receiving end of `Registry::watch_services`
+#[derive(Debug)]
+pub struct WatchHandle {
+    pub id: Uuid,
+    receiver: broadcast::Receiver<ServiceEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl WatchHandle {
+    /// Next notification. A watcher that falls more than the channel capacity
+    /// behind gets `RecvError::Lagged` and loses the oldest notifications.
+    pub async fn recv(&mut self) -> std::result::Result<ServiceEvent, broadcast::error::RecvError> {
+        self.receiver.recv().await
+    }
+
+    /// Notifications lost because this watcher didn't keep up
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
 }
 
 /// Main registry that manages service discovery with pattern matching
 #[derive(Debug)]
-pub struct Registry {
+pub struct Registry<B = InMemoryBackend> {
     /// Active services by name
-    services: DashMap<String, ServiceEntry>,
+    services: B,
     /// Active watchers for pattern-based discovery
     watches: Arc<RwLock<Vec<ServiceWatch>>>,
     /// Schema registry for type validation
     schemas: DashMap<String, wind_core::Schema>,
+    /// Bumped on every change to the set of registered services
+    version: AtomicU64,
     /// Metrics
     metrics: RegistryMetrics,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct RegistryMetrics {
     pub total_registrations: std::sync::atomic::AtomicU64,
     pub active_services: std::sync::atomic::AtomicU64,
     pub total_lookups: std::sync::atomic::AtomicU64,
     pub active_watches: std::sync::atomic::AtomicU64,
+    /// Watch notifications lost to slow watchers, across all watches
+    pub dropped_notifications: std::sync::atomic::AtomicU64,
+    /// Bytes exchanged with all registry clients
+    pub traffic: Arc<wind_core::ByteCounters>,
+    /// Time taken by each discovery page, in microseconds
+    pub discover_latency_us: Histogram,
+    /// Services returned by each discovery page
+    pub discover_results: Histogram,
+}
+
+impl Default for RegistryMetrics {
+    fn default() -> Self {
+        Self {
+            total_registrations: Default::default(),
+            active_services: Default::default(),
+            total_lookups: Default::default(),
+            active_watches: Default::default(),
+            dropped_notifications: Default::default(),
+            traffic: Default::default(),
+            discover_latency_us: Histogram::new(DISCOVER_LATENCY_BOUNDS_US),
+            discover_results: Histogram::new(DISCOVER_RESULT_BOUNDS),
+        }
+    }
+}
+
+/// Distribution of observations over fixed buckets, as Prometheus histograms
+/// expose them
+#[derive(Debug)]
+pub struct Histogram {
+    /// Inclusive upper bound of each bucket but the last, which has none
+    bounds: &'static [u64],
+    buckets: Vec<AtomicU64>,
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [u64]) -> Self {
+        Self {
+            bounds,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: u64) {
+        let bucket = self.bounds.partition_point(|&bound| bound < value);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn bounds(&self) -> &[u64] {
+        self.bounds
+    }
+
+    /// Observations at or below each bound, then all of them
+    pub fn cumulative_counts(&self) -> Vec<u64> {
+        self.buckets
+            .iter()
+            .scan(0, |total, bucket| {
+                *total += bucket.load(Ordering::Relaxed);
+                Some(*total)
+            })
+            .collect()
+    }
+
+    pub fn sum(&self) -> u64 {
+        self.sum.load(Ordering::Relaxed)
+    }
 }
 
 impl Registry {
     pub fn new() -> Self {
+        Self::with_backend(InMemoryBackend::new())
+    }
+}
+
+impl<B: DiscoveryBackend> Registry<B> {
+    /// Registry keeping its services in `backend`
+    pub fn with_backend(backend: B) -> Self {
         Self {
-            services: DashMap::new(),
+            services: backend,
             watches: Arc::new(RwLock::new(Vec::new())),
             schemas: DashMap::new(),
+            version: AtomicU64::new(0),
             metrics: RegistryMetrics::default(),
         }
     }
@@ -81,88 +217,222 @@ impl Registry {
     /// Register a service with TTL
     pub async fn register_service(&self, info: ServiceInfo, ttl_ms: u64) -> Result<()> {
         let ttl = Duration::from_millis(ttl_ms);
-        let entry = ServiceEntry::new(info.clone(), ttl);
+        let mut entry = ServiceEntry::new(info.clone(), ttl);
+
+        // Re-registering at the same address (e.g. a publisher heartbeat) keeps
+        // the original registration time, and is only a renewal if nothing
+        // but the times changed
+        let mut renewal = false;
+        if let Some(existing) = self.services.lookup(&info.name).await {
+            if existing.info.address == info.address && !existing.is_expired() {
+                entry.registered_at = existing.registered_at;
+                entry.info.registered_at_us = existing.info.registered_at_us;
+                renewal = existing.info
+                    == ServiceInfo {
+                        last_heartbeat_us: existing.info.last_heartbeat_us,
+                        ..entry.info.clone()
+                    };
+            }
+        }
+        let info = entry.info.clone();
 
         info!("Registering service: {} at {}", info.name, info.address);
 
         // Store the service
-        self.services.insert(info.name.clone(), entry);
+        self.services.register(entry).await;
 
         // Update metrics
         self.metrics
             .total_registrations
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
         self.metrics.active_services.store(
-            self.services.len() as u64,
+            self.services.len().await as u64,
             std::sync::atomic::Ordering::Relaxed,
         );
 
-        // Notify watchers
-        self.notify_watchers(&info).await;
+        // Notify watchers. Renewals aren't news, and staying quiet about them
+        // keeps a watcher that re-registers on every event from looping.
+        if !renewal {
+            self.version.fetch_add(1, Ordering::SeqCst);
+            self.notify_watchers(ServiceEvent::Registered(info)).await;
+        }
 
         Ok(())
     }
 
-    /// Renew service registration
-    pub fn renew_service(&self, name: &str, address: &str, ttl_ms: u64) -> Result<()> {
+    /// Add a service learned from a peer registry, keeping its registration
+    /// times, to expire after `remaining` unless renewed. A live local
+    /// registration of the same name takes precedence.
+    pub async fn import_service(&self, info: ServiceInfo, remaining: Duration) {
+        if self
+            .services
+            .lookup(&info.name)
+            .await
+            .is_some_and(|existing| !existing.is_expired())
+        {
+            return;
+        }
+
+        let now = Instant::now();
+        let entry = ServiceEntry {
+            info: info.clone(),
+            registered_at: now,
+            expires_at: now + remaining,
+            last_heartbeat: now,
+        };
+        debug!("Imported service: {} at {}", info.name, info.address);
+        self.services.register(entry).await;
+        self.version.fetch_add(1, Ordering::SeqCst);
+        self.metrics.active_services.store(
+            self.services.len().await as u64,
+            std::sync::atomic::Ordering::Relaxed,
+        );
+
+        self.notify_watchers(ServiceEvent::Registered(info)).await;
+    }
+
+    /// Remove a registration immediately, regardless of its TTL. With an
+    /// `address`, only a registration at that address is removed, so a
+    /// provider that has since re-registered elsewhere is left alone.
+    pub async fn deregister_service(
+        &self,
+        name: &str,
+        address: Option<&str>,
+    ) -> Result<ServiceInfo> {
+        let entry = self
+            .services
+            .remove(name, address)
+            .await
+            .ok_or_else(|| WindError::ServiceNotFound(name.to_string()))?;
+
+        info!("Deregistered service: {} at {}", name, entry.info.address);
+        self.version.fetch_add(1, Ordering::SeqCst);
+        self.metrics.active_services.store(
+            self.services.len().await as u64,
+            std::sync::atomic::Ordering::Relaxed,
+        );
+
+        self.notify_watchers(ServiceEvent::Removed(entry.info.clone()))
+            .await;
+        Ok(entry.info)
+    }
+
+    /// Renew service registration. Only the registration at `address` is
+    /// renewed, so a stale provider can't keep another's entry alive.
+    pub async fn renew_service(&self, name: &str, address: &str, ttl_ms: u64) -> Result<()> {
         let ttl = Duration::from_millis(ttl_ms);
 
-        if let Some(mut entry) = self.services.get_mut(name) {
-            if entry.info.address == address {
-                entry.renew(ttl);
-                debug!("Renewed service: {} at {}", name, address);
-                return Ok(());
-            }
+        if self.services.renew(name, address, ttl).await {
+            debug!("Renewed service: {} at {}", name, address);
+            return Ok(());
         }
 
-        Err(WindError::ServiceNotFound(name.to_string()))
+        match self.services.lookup(name).await {
+            Some(entry) => Err(WindError::Registry(format!(
+                "{} is registered at {}, not {}",
+                name, entry.info.address, address
+            ))),
+            None => Err(WindError::ServiceNotFound(name.to_string())),
+        }
     }
 
     /// Lookup specific service by exact name
-    pub fn lookup_service(&self, name: &str) -> Option<ServiceInfo> {
+    pub async fn lookup_service(&self, name: &str) -> Option<ServiceInfo> {
         self.metrics
             .total_lookups
             .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
         self.services
-            .get(name)
+            .lookup(name)
+            .await
             .filter(|entry| !entry.is_expired())
-            .map(|entry| entry.info.clone())
+            .map(|entry| entry.info)
     }
 
     /// Discover services matching a pattern
-    pub fn discover_services(&self, pattern: &str) -> Result<Vec<ServiceInfo>> {
+    pub async fn discover_services(&self, pattern: &str) -> Result<Vec<ServiceInfo>> {
         let matcher = ServicePattern::new(pattern)
             .map_err(|e| WindError::Registry(format!("Invalid pattern: {}", e)))?;
 
         let services = self
             .services
-            .iter()
-            .filter(|entry| !entry.value().is_expired())
-            .filter(|entry| matcher.matches(entry.key()))
-            .map(|entry| entry.value().info.clone())
+            .discover(&matcher)
+            .await
+            .into_iter()
+            .filter(|entry| !entry.is_expired())
+            .map(|entry| entry.info)
             .collect();
 
         Ok(services)
     }
 
+    /// Discover one page of the services matching a pattern that pass
+    /// `visible`, ordered by name. Returns the page, the total number of
+    /// visible matches and the registry version they were read at.
+    pub async fn discover_services_page(
+        &self,
+        pattern: &str,
+        offset: usize,
+        limit: usize,
+        visible: impl Fn(&ServiceInfo) -> bool,
+    ) -> Result<(Vec<ServiceInfo>, usize, u64)> {
+        let started = Instant::now();
+        // Expired services are dropped first, so that expiry shows in the version
+        self.purge_expired().await;
+
+        // The scan isn't atomic; a version unchanged across it means nothing
+        // was registered or removed meanwhile
+        let mut scans = 0;
+        let (mut services, version) = loop {
+            let version = self.version();
+            let services = self.discover_services(pattern).await?;
+            scans += 1;
+            if version == self.version() || scans == MAX_DISCOVERY_SCANS {
+                break (services, version);
+            }
+        };
+        services.retain(|service| visible(service));
+        services.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let total = services.len();
+        let page: Vec<_> = services.into_iter().skip(offset).take(limit).collect();
+
+        self.metrics
+            .discover_latency_us
+            .observe(started.elapsed().as_micros() as u64);
+        self.metrics.discover_results.observe(page.len() as u64);
+        Ok((page, total, version))
+    }
+
+    /// Counter bumped whenever a service is registered, moves or is removed,
+    /// for telling whether an earlier discovery is still current
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
     /// Watch for services matching a pattern
-    pub async fn watch_services(&self, pattern: &str) -> Result<broadcast::Receiver<ServiceInfo>> {
+    pub async fn watch_services(&self, pattern: &str) -> Result<WatchHandle> {
         let matcher = ServicePattern::new(pattern)
             .map_err(|e| WindError::Registry(format!("Invalid pattern: {}", e)))?;
 
-        let (tx, rx) = broadcast::channel(1000);
+        let (tx, rx) = broadcast::channel(WATCH_CAPACITY);
 
         let watch = ServiceWatch {
             id: Uuid::new_v4(),
             pattern: matcher.clone(),
             sender: tx,
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+        let handle = WatchHandle {
+            id: watch.id,
+            receiver: rx,
+            dropped: watch.dropped.clone(),
         };
 
         // Send current matching services
-        let current_services = self.discover_services(pattern)?;
+        let current_services = self.discover_services(pattern).await?;
         for service in current_services {
-            let _ = watch.sender.send(service);
+            watch.send(ServiceEvent::Registered(service), &self.metrics);
         }
 
         // Add to active watches
@@ -176,36 +446,58 @@ impl Registry {
             std::sync::atomic::Ordering::Relaxed,
         );
 
-        Ok(rx)
+        Ok(handle)
     }
 
     /// Remove expired services (called periodically)
     pub async fn cleanup_expired(&self) {
-        let before = self.services.len();
-        self.services.retain(|_, entry| !entry.is_expired());
-        let after = self.services.len();
-
-        if before != after {
-            info!("Cleaned up {} expired services", before - after);
-            self.metrics
-                .active_services
-                .store(after as u64, std::sync::atomic::Ordering::Relaxed);
+        let removed = self.purge_expired().await;
+        if removed > 0 {
+            info!("Cleaned up {} expired services", removed);
         }
 
         // Clean up closed watchers
         {
             let mut watches = self.watches.write().await;
-            watches.retain(|watch| !watch.sender.receiver_count() == 0);
+            watches.retain(|watch| watch.sender.receiver_count() > 0);
             self.metrics
                 .active_watches
                 .store(watches.len() as u64, std::sync::atomic::Ordering::Relaxed);
         }
     }
 
-    /// Register a schema for type validation
-    pub fn register_schema(&self, schema: wind_core::Schema) {
+    /// Drop expired services, returning how many there were
+    async fn purge_expired(&self) -> usize {
+        let removed = self.services.cleanup().await;
+        if removed > 0 {
+            self.version.fetch_add(1, Ordering::SeqCst);
+            self.metrics.active_services.store(
+                self.services.len().await as u64,
+                std::sync::atomic::Ordering::Relaxed,
+            );
+        }
+        removed
+    }
+
+    /// Register a schema for type validation. Re-registering an id is
+    /// rejected if neither version can read the other's values.
+    pub fn register_schema(&self, schema: wind_core::Schema) -> Result<()> {
+        if let Some(existing) = self.schemas.get(&schema.id) {
+            if let CompatibilityResult::Incompatible { fields } =
+                schema.is_compatible_with(existing.value())
+            {
+                return Err(WindError::Schema(format!(
+                    "Schema {} v{} is incompatible with v{}: {}",
+                    schema.id,
+                    schema.version,
+                    existing.version,
+                    fields.join(", ")
+                )));
+            }
+        }
         info!("Registering schema: {} v{}", schema.name, schema.version);
         self.schemas.insert(schema.id.clone(), schema);
+        Ok(())
     }
 
     /// Get schema by ID
@@ -214,11 +506,14 @@ impl Registry {
     }
 
     /// List all active services (for debugging/monitoring)
-    pub fn list_services(&self) -> Vec<ServiceInfo> {
+    pub async fn list_services(&self) -> Vec<ServiceInfo> {
+        let everything = ServicePattern::new("*").expect("valid pattern");
         self.services
-            .iter()
-            .filter(|entry| !entry.value().is_expired())
-            .map(|entry| entry.value().info.clone())
+            .discover(&everything)
+            .await
+            .into_iter()
+            .filter(|entry| !entry.is_expired())
+            .map(|entry| entry.info)
             .collect()
     }
 
@@ -227,11 +522,36 @@ impl Registry {
         &self.metrics
     }
 
-    async fn notify_watchers(&self, service: &ServiceInfo) {
+    /// Pass on changes that other registries sharing the backend make to
+    /// this registry's watchers, until the backend's watch ends. Returns at
+    /// once if the backend has none.
+    pub async fn follow_backend(&self) {
+        let Some(mut events) = self.services.watch().await else {
+            return;
+        };
+        while let Some(event) = events.recv().await {
+            self.version.fetch_add(1, Ordering::SeqCst);
+            self.metrics.active_services.store(
+                self.services.len().await as u64,
+                std::sync::atomic::Ordering::Relaxed,
+            );
+            self.notify_local_watchers(&event).await;
+        }
+    }
+
+    async fn notify_watchers(&self, event: ServiceEvent) {
+        self.notify_local_watchers(&event).await;
+        self.services.notify(event).await;
+    }
+
+    async fn notify_local_watchers(&self, event: &ServiceEvent) {
+        let service = match event {
+            ServiceEvent::Registered(service) | ServiceEvent::Removed(service) => service,
+        };
         let watches = self.watches.read().await;
         for watch in watches.iter() {
             if watch.pattern.matches(&service.name) {
-                let _ = watch.sender.send(service.clone());
+                watch.send(event.clone(), &self.metrics);
             }
         }
     }
@@ -242,3 +562,181 @@ impl Default for Registry {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wind_core::ServiceType;
+
+    fn service(name: &str) -> ServiceInfo {
+        ServiceInfo {
+            name: name.to_string(),
+            address: "127.0.0.1:9000".to_string(),
+            service_type: ServiceType::Publisher,
+            schema_id: None,
+            ttl_ms: 60000,
+            tags: Vec::new(),
+            registered_at_us: 0,
+            last_heartbeat_us: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_watcher_drops_are_counted() {
+        let registry = Registry::new();
+        let mut watcher = registry.watch_services("SENSOR/**").await.unwrap();
+
+        for i in 0..WATCH_CAPACITY + 5 {
+            let name = format!("SENSOR/{}/TEMP", i);
+            registry
+                .register_service(service(&name), 60000)
+                .await
+                .unwrap();
+        }
+        assert_eq!(watcher.dropped_count(), 5);
+        assert_eq!(
+            registry
+                .metrics()
+                .dropped_notifications
+                .load(Ordering::Relaxed),
+            5
+        );
+        assert!(matches!(
+            watcher.recv().await,
+            Err(broadcast::error::RecvError::Lagged(5))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_renewal_does_not_notify_watchers() {
+        let registry = Registry::new();
+        let mut watcher = registry.watch_services("SENSOR/**").await.unwrap();
+
+        registry
+            .register_service(service("SENSOR/A"), 60000)
+            .await
+            .unwrap();
+        registry
+            .register_service(service("SENSOR/A"), 60000)
+            .await
+            .unwrap();
+        let mut moved = service("SENSOR/A");
+        moved.address = "127.0.0.1:9001".to_string();
+        registry.register_service(moved, 60000).await.unwrap();
+
+        let first = watcher.recv().await.unwrap();
+        assert!(matches!(first, ServiceEvent::Registered(info) if info.address.ends_with(":9000")));
+        // The renewal at :9000 was skipped
+        let second = watcher.recv().await.unwrap();
+        assert!(
+            matches!(second, ServiceEvent::Registered(info) if info.address.ends_with(":9001"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reregistration_with_changes_notifies_watchers() {
+        let registry = Registry::new();
+        registry
+            .register_service(service("SENSOR/A"), 60000)
+            .await
+            .unwrap();
+        let version = registry.version();
+        let mut watcher = registry.watch_services("SENSOR/**").await.unwrap();
+        assert!(matches!(
+            watcher.recv().await.unwrap(),
+            ServiceEvent::Registered(_)
+        ));
+
+        let mut retagged = service("SENSOR/A");
+        retagged.tags = vec!["calibrated".to_string()];
+        registry.register_service(retagged, 60000).await.unwrap();
+
+        match watcher.recv().await.unwrap() {
+            ServiceEvent::Registered(info) => assert_eq!(info.tags, ["calibrated"]),
+            other => panic!("Expected the new tags, got {:?}", other),
+        }
+        assert!(registry.version() > version);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_prunes_dropped_watchers() {
+        let registry = Registry::new();
+        let kept = registry.watch_services("SENSOR/**").await.unwrap();
+        let dropped = registry.watch_services("DETECTOR/**").await.unwrap();
+        drop(dropped);
+
+        registry.cleanup_expired().await;
+        assert_eq!(registry.watches.read().await.len(), 1);
+        assert_eq!(registry.metrics().active_watches.load(Ordering::Relaxed), 1);
+
+        drop(kept);
+        registry.cleanup_expired().await;
+        assert!(registry.watches.read().await.is_empty());
+        assert_eq!(registry.metrics().active_watches.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_incompatible_schema_reregistration_is_rejected() {
+        let registry = Registry::new();
+        let schema = |version, value_type| wind_core::Schema {
+            id: "Reading".to_string(),
+            version,
+            name: "Reading".to_string(),
+            description: None,
+            fields: [("value".to_string(), value_type)].into(),
+        };
+
+        registry
+            .register_schema(schema(1, wind_core::WindType::F64))
+            .unwrap();
+        // Widening keeps old values readable
+        let widened =
+            wind_core::WindType::Union(vec![wind_core::WindType::F64, wind_core::WindType::I64]);
+        registry.register_schema(schema(2, widened)).unwrap();
+
+        let err = registry
+            .register_schema(schema(3, wind_core::WindType::String))
+            .unwrap_err();
+        assert!(matches!(err, WindError::Schema(_)), "{:?}", err);
+        assert_eq!(registry.get_schema("Reading").unwrap().version, 2);
+    }
+
+    #[tokio::test]
+    async fn test_version_tracks_registrations_and_expiry() {
+        let registry = Registry::new();
+        let version = registry.version();
+
+        registry
+            .register_service(service("SENSOR/A"), 60000)
+            .await
+            .unwrap();
+        let registered = registry.version();
+        assert!(registered > version);
+
+        // A renewal changes nothing a discovery would see
+        registry
+            .register_service(service("SENSOR/A"), 60000)
+            .await
+            .unwrap();
+        assert_eq!(registry.version(), registered);
+
+        registry
+            .register_service(service("SENSOR/B"), 50)
+            .await
+            .unwrap();
+        let (services, _, before_expiry) = registry
+            .discover_services_page("SENSOR/*", 0, 10, |_| true)
+            .await
+            .unwrap();
+        assert_eq!(services.len(), 2);
+        assert!(before_expiry > registered);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let (services, _, after_expiry) = registry
+            .discover_services_page("SENSOR/*", 0, 10, |_| true)
+            .await
+            .unwrap();
+        assert_eq!(services.len(), 1);
+        assert!(after_expiry > before_expiry);
+    }
+}
@@ -1,15 +1,39 @@
+use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
+use std::time::SystemTime;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
 use tokio::time::{interval, Duration};
 use tracing::{error, info, warn};
 
-use crate::Registry;
-use wind_core::{Message, MessageCodec, MessagePayload, WindError};
+use crate::{Acl, AclOperation, DiscoveryBackend, InMemoryBackend, Registry, ServiceEvent};
+use wind_core::{
+    unix_time_us, AuthCheck, CountingStream, Message, MessageCodec, MessagePayload, SocketOptions,
+    TokenAuth, WindError, WindListener, WindStream, DEFAULT_MAX_HOPS,
+};
+
+/// How long in-flight client connections get to finish after shutdown is requested
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default cap on the number of services returned in one discovery response
+pub const DEFAULT_DISCOVERY_PAGE_SIZE: usize = 500;
 
 /// Registry server that handles client connections
-pub struct RegistryServer {
-    registry: Arc<Registry>,
+pub struct RegistryServer<B = InMemoryBackend> {
+    registry: Arc<Registry<B>>,
     bind_address: String,
+    max_page_size: usize,
+    acl: Arc<Acl>,
+    socket_options: SocketOptions,
+    peer: Option<String>,
+    token: Option<String>,
+    client_tokens: Arc<HashMap<String, String>>,
+    #[cfg(feature = "metrics")]
+    metrics_addr: Option<String>,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<wind_core::rustls::ServerConfig>>,
 }
 
 impl RegistryServer {
@@ -17,17 +41,155 @@ impl RegistryServer {
         Self {
             registry: Arc::new(Registry::new()),
             bind_address,
+            max_page_size: DEFAULT_DISCOVERY_PAGE_SIZE,
+            acl: Arc::new(Acl::new()),
+            socket_options: SocketOptions::default(),
+            peer: None,
+            token: None,
+            client_tokens: Arc::default(),
+            #[cfg(feature = "metrics")]
+            metrics_addr: None,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+impl<B: DiscoveryBackend> RegistryServer<B> {
+    /// Keep registrations in `backend` instead of in memory
+    pub fn with_backend<C: DiscoveryBackend>(self, backend: C) -> RegistryServer<C> {
+        RegistryServer {
+            registry: Arc::new(Registry::with_backend(backend)),
+            bind_address: self.bind_address,
+            max_page_size: self.max_page_size,
+            acl: self.acl,
+            socket_options: self.socket_options,
+            peer: self.peer,
+            token: self.token,
+            client_tokens: self.client_tokens,
+            #[cfg(feature = "metrics")]
+            metrics_addr: self.metrics_addr,
+            #[cfg(feature = "tls")]
+            tls: self.tls,
+        }
+    }
+
+    /// Set the maximum number of services returned per discovery page
+    pub fn with_max_page_size(mut self, max_page_size: usize) -> Self {
+        self.max_page_size = max_page_size.max(1);
+        self
+    }
+
+    /// Restrict register/discover/subscribe access per service pattern
+    pub fn with_acl(mut self, acl: Acl) -> Self {
+        self.acl = Arc::new(acl);
+        self
+    }
+
+    /// TCP options for accepted client connections
+    pub fn with_socket_options(mut self, options: SocketOptions) -> Self {
+        self.socket_options = options;
+        self
+    }
+
+    /// Copy the services of the registry at `peer_address` on startup, before
+    /// accepting clients, so a restarted member of a registry pair doesn't
+    /// answer discovery with nothing. Copied entries keep their remaining TTL
+    /// and expire normally unless their providers renew them here. Only
+    /// services the peer lets anonymous clients discover are copied.
+    pub fn with_peer(mut self, peer_address: String) -> Self {
+        self.peer = Some(peer_address);
+        self
+    }
+
+    /// Refuse clients until they `Authenticate` with `token`. It is also
+    /// presented to the peer registry, which is expected to share it.
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// Accept `token` as authenticating its connection as `client_id`, the
+    /// identity ACL rules see. The id then can't be claimed with `Identify`,
+    /// by that connection or any other.
+    pub fn with_client_token(mut self, client_id: String, token: String) -> Self {
+        Arc::make_mut(&mut self.client_tokens).insert(client_id, token);
+        self
+    }
+
+    /// Serve Prometheus metrics at `http://<addr>/metrics` while running
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics_addr(mut self, addr: String) -> Self {
+        self.metrics_addr = Some(addr);
+        self
+    }
+
+    /// Accept clients over TLS only; they connect to `tls://` plus the bind
+    /// address
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, config: wind_core::rustls::ServerConfig) -> Self {
+        self.tls = Some(Arc::new(config));
+        self
+    }
+
+    /// Bind `bind_address`, over TLS when `with_tls` was given a config
+    async fn bind(&self) -> std::io::Result<WindListener> {
+        #[cfg(feature = "tls")]
+        if let Some(config) = &self.tls {
+            return WindListener::bind_tls(&self.bind_address, config.clone()).await;
         }
+        WindListener::bind(&self.bind_address).await
     }
 
     pub async fn run(&self) -> wind_core::Result<()> {
-        let listener = TcpListener::bind(&self.bind_address).await?;
-        info!("WIND Registry listening on {}", self.bind_address);
+        self.run_until(std::future::pending()).await
+    }
+
+    /// Run the registry until `shutdown` resolves, then stop accepting,
+    /// drain connected clients and return.
+    pub async fn run_until<F>(&self, shutdown: F) -> wind_core::Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        let listener = self.bind().await?;
+        self.serve_until(listener, shutdown).await
+    }
+
+    /// Like `run_until`, on a listener the caller bound, e.g. to port 0 to
+    /// learn the address before the registry starts
+    pub async fn serve_until<F>(&self, listener: WindListener, shutdown: F) -> wind_core::Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        info!("WIND Registry listening on {}", listener.local_address()?);
+
+        // Clients connecting meanwhile wait in the listen backlog
+        if let Some(peer) = &self.peer {
+            match self.warm_start(peer).await {
+                Ok(count) => info!("Copied {} service(s) from peer registry {}", count, peer),
+                Err(e) => warn!("Failed to copy services from peer registry {}: {}", peer, e),
+            }
+        }
+
+        let mut background = JoinSet::new();
+
+        #[cfg(feature = "metrics")]
+        if let Some(addr) = &self.metrics_addr {
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            info!(
+                "Registry metrics at http://{}/metrics",
+                listener.local_addr()?
+            );
+            background.spawn(crate::metrics::serve_metrics(
+                self.registry.clone(),
+                listener,
+            ));
+        }
 
         // Start cleanup task
         {
             let registry = self.registry.clone();
-            tokio::spawn(async move {
+            background.spawn(async move {
                 let mut cleanup_interval = interval(Duration::from_secs(10));
                 loop {
                     cleanup_interval.tick().await;
@@ -36,67 +198,268 @@ impl RegistryServer {
             });
         }
 
+        // Pass on changes that other registries sharing the backend make
+        {
+            let registry = self.registry.clone();
+            background.spawn(async move { registry.follow_backend().await });
+        }
+
         // Start metrics reporting task
         {
             let registry = self.registry.clone();
-            tokio::spawn(async move {
+            background.spawn(async move {
                 let mut metrics_interval = interval(Duration::from_secs(30));
                 loop {
                     metrics_interval.tick().await;
                     let metrics = registry.metrics();
                     info!(
-                        "Registry metrics: {} active services, {} total registrations, {} total lookups, {} active watches",
+                        "Registry metrics: {} active services, {} total registrations, {} total lookups, {} active watches, {} dropped watch notifications, {} bytes in, {} bytes out",
                         metrics.active_services.load(std::sync::atomic::Ordering::Relaxed),
                         metrics.total_registrations.load(std::sync::atomic::Ordering::Relaxed),
                         metrics.total_lookups.load(std::sync::atomic::Ordering::Relaxed),
                         metrics.active_watches.load(std::sync::atomic::Ordering::Relaxed),
+                        metrics.dropped_notifications.load(std::sync::atomic::Ordering::Relaxed),
+                        metrics.traffic.bytes_in(),
+                        metrics.traffic.bytes_out(),
                     );
                 }
             });
         }
 
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let mut clients = JoinSet::new();
+        tokio::pin!(shutdown);
+
         loop {
-            match listener.accept().await {
-                Ok((socket, addr)) => {
-                    info!("New client connected: {}", addr);
-                    let registry = self.registry.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = Self::handle_client(registry, socket).await {
-                            error!("Client {} error: {}", addr, e);
-                        }
-                    });
-                }
-                Err(e) => {
-                    error!("Failed to accept connection: {}", e);
+            tokio::select! {
+                _ = &mut shutdown => break,
+                accepted = listener.accept_with(&self.socket_options) => match accepted {
+                    Ok((socket, addr)) => {
+                        info!("New client connected: {}", addr);
+                        let registry = self.registry.clone();
+                        let shutdown_rx = shutdown_rx.clone();
+                        let max_page_size = self.max_page_size;
+                        let acl = self.acl.clone();
+                        let auth = TokenAuth::new(self.token.clone())
+                            .with_client_tokens(self.client_tokens.clone());
+                        clients.spawn(async move {
+                            if let Err(e) = Self::handle_client(registry, acl, auth, socket, max_page_size, shutdown_rx).await {
+                                error!("Client {} error: {}", addr, e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("Failed to accept connection: {}", e);
+                    }
+                },
+            }
+        }
+
+        info!(
+            "WIND Registry shutting down, draining {} client(s)",
+            clients.len()
+        );
+        drop(listener);
+        let _ = shutdown_tx.send(true);
+
+        let drained = tokio::time::timeout(DRAIN_TIMEOUT, async {
+            while clients.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            warn!("Drain timed out, aborting {} client(s)", clients.len());
+            clients.abort_all();
+        }
+
+        background.abort_all();
+        self.registry.cleanup_expired().await;
+        info!("WIND Registry stopped");
+
+        Ok(())
+    }
+
+    /// Import every service the peer registry lists, page by page
+    async fn warm_start(&self, peer: &str) -> wind_core::Result<usize> {
+        let mut conn = WindStream::connect_with(peer, &self.socket_options).await?;
+        if let Some(token) = &self.token {
+            wind_core::authenticate(&mut conn, token).await?;
+        }
+        let mut offset = 0;
+        let mut imported = 0;
+        loop {
+            let request = Message::new(MessagePayload::DiscoverServices {
+                pattern: "*".to_string(),
+                offset,
+                limit: 0,
+                registered_since_us: None,
+                service_type: None,
+                // Passing on the discovery this registry would have served
+                max_hops: DEFAULT_MAX_HOPS - 1,
+            });
+            MessageCodec::write(&mut conn, &request).await?;
+            let (services, next_offset) = match MessageCodec::decode(&mut conn).await?.payload {
+                MessagePayload::ServicesDiscovered {
+                    services,
+                    next_offset,
+                    ..
+                } => (services, next_offset),
+                MessagePayload::Error { error, .. } => return Err(WindError::Registry(error)),
+                _ => {
+                    return Err(WindError::Protocol(
+                        "Unexpected peer registry response".to_string(),
+                    ))
                 }
+            };
+
+            let now_us = unix_time_us(SystemTime::now());
+            for service in services {
+                let age = Duration::from_micros(now_us.saturating_sub(service.last_heartbeat_us));
+                let Some(remaining) = Duration::from_millis(service.ttl_ms).checked_sub(age) else {
+                    continue;
+                };
+                self.registry.import_service(service, remaining).await;
+                imported += 1;
+            }
+            match next_offset {
+                Some(next) => offset = next,
+                None => return Ok(imported),
             }
         }
     }
 
     async fn handle_client(
-        registry: Arc<Registry>,
-        mut socket: TcpStream,
+        registry: Arc<Registry<B>>,
+        acl: Arc<Acl>,
+        mut auth: TokenAuth,
+        socket: WindStream,
+        max_page_size: usize,
+        mut shutdown: watch::Receiver<bool>,
     ) -> wind_core::Result<()> {
+        let mut socket =
+            CountingStream::new(socket).with_aggregate(registry.metrics().traffic.clone());
+        let mut identity = None;
         loop {
-            let msg = match MessageCodec::decode(&mut socket).await {
-                Ok(msg) => msg,
+            let decoded = tokio::select! {
+                decoded = MessageCodec::decode_with_format(&mut socket) => decoded,
+                _ = shutdown.changed() => break,
+            };
+            let (msg, format) = match decoded {
+                Ok(decoded) => decoded,
                 Err(WindError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
                     // Client closed the connection gracefully
                     break;
                 }
                 Err(e) => return Err(e),
             };
-            let response = Self::handle_message(&registry, msg).await;
+            // Reply in whatever format the client chose
+            let codec = MessageCodec::default().with_format(format);
+            match auth.check(&msg.payload) {
+                AuthCheck::Proceed => {}
+                AuthCheck::Accepted(reply) => {
+                    if let Some(client_id) = auth.identity() {
+                        identity = Some(client_id.to_string());
+                    }
+                    codec.write_message(&mut socket, &reply).await?;
+                    continue;
+                }
+                AuthCheck::Rejected(reply) => {
+                    warn!("Refused unauthenticated {}", msg.payload.name());
+                    return codec.write_message(&mut socket, &reply).await;
+                }
+            }
+            // Watches out of hops are refused by `handle_message`
+            if let MessagePayload::WatchServices {
+                pattern,
+                max_hops: 1..,
+            } = msg.payload
+            {
+                // The connection belongs to the watch from here on
+                return Self::stream_service_events(
+                    &registry,
+                    &acl,
+                    identity.as_deref(),
+                    socket,
+                    codec,
+                    &pattern,
+                    shutdown,
+                )
+                .await;
+            }
+            let response =
+                Self::handle_message(&registry, &acl, &mut identity, msg, max_page_size).await;
 
             if let Some(response) = response {
-                MessageCodec::write(&mut socket, &response).await?;
+                codec.write_message(&mut socket, &response).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Push a `ServiceChanged` for every visible change matching `pattern`
+    /// until the client hangs up or the registry shuts down
+    async fn stream_service_events<S>(
+        registry: &Arc<Registry<B>>,
+        acl: &Acl,
+        identity: Option<&str>,
+        mut socket: S,
+        codec: MessageCodec,
+        pattern: &str,
+        mut shutdown: watch::Receiver<bool>,
+    ) -> wind_core::Result<()>
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        let mut watch = match registry.watch_services(pattern).await {
+            Ok(watch) => watch,
+            Err(e) => {
+                let error = Message::new(MessagePayload::Error {
+                    error: e.to_string(),
+                    context: Some(format!("Watching pattern: {}", pattern)),
+                });
+                return codec.write_message(&mut socket, &error).await;
+            }
+        };
+        loop {
+            let event = tokio::select! {
+                event = watch.recv() => event,
+                // Watchers send nothing more, so any read means the client is gone
+                _ = MessageCodec::decode(&mut socket) => break,
+                _ = shutdown.changed() => break,
+            };
+            let event = match event {
+                Ok(event) => event,
+                Err(RecvError::Lagged(missed)) => {
+                    warn!(
+                        "Service watch on '{}' missed {} notifications",
+                        pattern, missed
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let (ServiceEvent::Registered(service) | ServiceEvent::Removed(service)) = &event;
+            if !acl.is_allowed(identity, AclOperation::Discover, &service.name) {
+                continue;
             }
+            let changed = Message::new(MessagePayload::ServiceChanged { event });
+            codec.write_message(&mut socket, &changed).await?;
         }
         Ok(())
     }
 
-    async fn handle_message(registry: &Arc<Registry>, msg: Message) -> Option<Message> {
+    async fn handle_message(
+        registry: &Arc<Registry<B>>,
+        acl: &Acl,
+        identity: &mut Option<String>,
+        msg: Message,
+        max_page_size: usize,
+    ) -> Option<Message> {
         match msg.payload {
+            MessagePayload::Identify { client_id } => {
+                *identity = Some(client_id);
+                None
+            }
+
             MessagePayload::RegisterService {
                 service,
                 address,
@@ -105,6 +468,23 @@ impl RegistryServer {
                 ttl_ms,
                 tags,
             } => {
+                if !acl.is_allowed(identity.as_deref(), AclOperation::Register, &service) {
+                    warn!(
+                        "Denied registration of '{}' to {:?}",
+                        service,
+                        identity.as_deref()
+                    );
+                    return Some(Message::new(MessagePayload::Error {
+                        error: format!(
+                            "Permission denied: {} may not register {}",
+                            identity.as_deref().unwrap_or("anonymous client"),
+                            service
+                        ),
+                        context: Some(format!("Registering service: {}", service)),
+                    }));
+                }
+
+                let allowed_subscribers = acl.allowed_identities(AclOperation::Subscribe, &service);
                 let info = wind_core::ServiceInfo {
                     name: service.clone(),
                     address,
@@ -112,6 +492,8 @@ impl RegistryServer {
                     schema_id,
                     ttl_ms,
                     tags,
+                    registered_at_us: 0,
+                    last_heartbeat_us: 0,
                 };
 
                 match registry.register_service(info, ttl_ms).await {
@@ -119,20 +501,95 @@ impl RegistryServer {
                         service,
                         success: true,
                         error: None,
+                        allowed_subscribers,
                     })),
                     Err(e) => Some(Message::new(MessagePayload::ServiceRegistered {
                         service,
                         success: false,
                         error: Some(e.to_string()),
+                        allowed_subscribers: None,
                     })),
                 }
             }
 
-            MessagePayload::DiscoverServices { pattern } => {
-                match registry.discover_services(&pattern) {
-                    Ok(services) => Some(Message::new(MessagePayload::ServicesDiscovered {
-                        services,
-                    })),
+            MessagePayload::RenewService {
+                service,
+                address,
+                ttl_ms,
+            } => {
+                if !acl.is_allowed(identity.as_deref(), AclOperation::Register, &service) {
+                    return Some(Message::new(MessagePayload::Error {
+                        error: format!(
+                            "Permission denied: {} may not renew {}",
+                            identity.as_deref().unwrap_or("anonymous client"),
+                            service
+                        ),
+                        context: Some(format!("Renewing service: {}", service)),
+                    }));
+                }
+
+                let result = registry.renew_service(&service, &address, ttl_ms).await;
+                Some(Message::new(MessagePayload::ServiceRenewed {
+                    service,
+                    success: result.is_ok(),
+                    error: result.err().map(|e| e.to_string()),
+                }))
+            }
+
+            MessagePayload::DiscoverServices {
+                pattern,
+                max_hops: 0,
+                ..
+            }
+            | MessagePayload::WatchServices {
+                pattern,
+                max_hops: 0,
+            } => {
+                warn!("Refused request for '{}' past its hop limit", pattern);
+                Some(Message::new(MessagePayload::Error {
+                    error: "Hop limit exceeded".to_string(),
+                    context: Some(format!("Pattern: {}", pattern)),
+                }))
+            }
+
+            MessagePayload::DiscoverServices {
+                pattern,
+                offset,
+                limit,
+                registered_since_us,
+                service_type,
+                ..
+            } => {
+                let limit = match limit as usize {
+                    0 => max_page_size,
+                    requested => requested.min(max_page_size),
+                };
+                // Services the client may not discover are left out entirely
+                let visible = |service: &wind_core::ServiceInfo| {
+                    registered_since_us.is_none_or(|since| service.registered_at_us >= since)
+                        && service_type
+                            .as_ref()
+                            .is_none_or(|wanted| service.service_type.provides(wanted))
+                        && acl.is_allowed(
+                            identity.as_deref(),
+                            AclOperation::Discover,
+                            &service.name,
+                        )
+                };
+                match registry
+                    .discover_services_page(&pattern, offset as usize, limit, visible)
+                    .await
+                {
+                    Ok((services, total, registry_version)) => {
+                        let end = offset as usize + services.len();
+                        let next_offset = (end < total).then_some(end as u32);
+                        Some(Message::new(MessagePayload::ServicesDiscovered {
+                            services,
+                            total: total as u32,
+                            next_offset,
+                            registry_version,
+                        }))
+                    }
                     Err(e) => Some(Message::new(MessagePayload::Error {
                         error: e.to_string(),
                         context: Some(format!("Discovering pattern: {}", pattern)),
@@ -140,6 +597,41 @@ impl RegistryServer {
                 }
             }
 
+            MessagePayload::GetSchema { schema_id } => {
+                let schema = registry.get_schema(&schema_id);
+                Some(Message::new(MessagePayload::SchemaFound {
+                    schema_id,
+                    schema,
+                }))
+            }
+
+            MessagePayload::ForceDeregister { service, address } => {
+                if !acl.is_allowed(identity.as_deref(), AclOperation::Deregister, &service) {
+                    warn!(
+                        "Denied deregistration of '{}' to {:?}",
+                        service,
+                        identity.as_deref()
+                    );
+                    return Some(Message::new(MessagePayload::Error {
+                        error: format!(
+                            "Permission denied: {} may not deregister {}",
+                            identity.as_deref().unwrap_or("anonymous client"),
+                            service
+                        ),
+                        context: Some(format!("Deregistering service: {}", service)),
+                    }));
+                }
+
+                let result = registry
+                    .deregister_service(&service, address.as_deref())
+                    .await;
+                Some(Message::new(MessagePayload::ServiceDeregistered {
+                    service,
+                    success: result.is_ok(),
+                    error: result.err().map(|e| e.to_string()),
+                }))
+            }
+
             MessagePayload::Ping => Some(Message::new(MessagePayload::Pong)),
 
             _ => {
@@ -149,7 +641,778 @@ impl RegistryServer {
         }
     }
 
-    pub fn registry(&self) -> Arc<Registry> {
+    pub fn registry(&self) -> Arc<Registry<B>> {
         self.registry.clone()
     }
+
+    pub fn acl(&self) -> Arc<Acl> {
+        self.acl.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackendFuture, ServiceEvent};
+    use tokio::net::TcpStream;
+    use tokio::sync::{mpsc, oneshot};
+    use uuid::Uuid;
+    use wind_core::SerializationFormat;
+    use wind_core::ServiceInfo;
+
+    #[tokio::test]
+    async fn test_run_until_drains_and_returns() {
+        let addr = "127.0.0.1:7101";
+        let server = RegistryServer::new(addr.to_string());
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            server
+                .run_until(async {
+                    let _ = shutdown_rx.await;
+                })
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        MessageCodec::write(&mut client, &Message::new(MessagePayload::Ping))
+            .await
+            .unwrap();
+        let pong = MessageCodec::decode(&mut client).await.unwrap();
+        assert!(matches!(pong.payload, MessagePayload::Pong));
+
+        shutdown_tx.send(()).unwrap();
+
+        // Must finish well within DRAIN_TIMEOUT, i.e. the idle client was drained, not aborted
+        let result = tokio::time::timeout(Duration::from_secs(2), handle)
+            .await
+            .expect("registry did not shut down")
+            .unwrap();
+        assert!(result.is_ok());
+
+        assert!(MessageCodec::decode(&mut client).await.is_err());
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replies_in_the_format_of_the_request() {
+        let addr = "127.0.0.1:7106";
+        let server = RegistryServer::new(addr.to_string());
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        for format in [
+            SerializationFormat::Json,
+            SerializationFormat::MessagePack,
+            SerializationFormat::Bincode,
+        ] {
+            let codec = MessageCodec::default().with_format(format);
+            let request = Message::new(MessagePayload::GetSchema {
+                schema_id: "missing".to_string(),
+            });
+            codec.write_message(&mut client, &request).await.unwrap();
+            let (reply, reply_format) =
+                MessageCodec::decode_with_format(&mut client).await.unwrap();
+            assert_eq!(reply_format, format);
+            assert!(matches!(reply.payload, MessagePayload::SchemaFound { .. }));
+        }
+    }
+
+    async fn register_as(addr: &str, identity: &str, service: &str) -> MessagePayload {
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let identify = Message::new(MessagePayload::Identify {
+            client_id: identity.to_string(),
+        });
+        MessageCodec::write(&mut client, &identify).await.unwrap();
+        let register = Message::new(MessagePayload::RegisterService {
+            service: service.to_string(),
+            address: "127.0.0.1:9000".to_string(),
+            service_type: wind_core::ServiceType::Publisher,
+            schema_id: None,
+            ttl_ms: 60000,
+            tags: Vec::new(),
+        });
+        MessageCodec::write(&mut client, &register).await.unwrap();
+        MessageCodec::decode(&mut client).await.unwrap().payload
+    }
+
+    #[tokio::test]
+    async fn test_acl_enforced_on_register() {
+        let addr = "127.0.0.1:7102";
+        let acl = Acl::new()
+            .allow("SENSOR/**", &["sensor-gw"], &[AclOperation::Register])
+            .unwrap()
+            .allow(
+                "CONTROL/**",
+                &["operator"],
+                &[AclOperation::Register, AclOperation::Subscribe],
+            )
+            .unwrap();
+        let server = RegistryServer::new(addr.to_string()).with_acl(acl);
+        let registry = server.registry();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        match register_as(addr, "sensor-gw", "SENSOR/ROOM_1/TEMP").await {
+            MessagePayload::ServiceRegistered {
+                success,
+                allowed_subscribers,
+                ..
+            } => {
+                assert!(success);
+                assert_eq!(allowed_subscribers, None);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        match register_as(addr, "sensor-gw", "CONTROL/VALVE_1").await {
+            MessagePayload::Error { error, .. } => assert!(error.contains("Permission denied")),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        assert!(registry.lookup_service("CONTROL/VALVE_1").await.is_none());
+
+        // The subscribe ACL is handed to the publisher on registration
+        match register_as(addr, "operator", "CONTROL/VALVE_1").await {
+            MessagePayload::ServiceRegistered {
+                success,
+                allowed_subscribers,
+                ..
+            } => {
+                assert!(success);
+                assert_eq!(allowed_subscribers, Some(vec!["operator".to_string()]));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_token_fixes_the_acl_identity() {
+        let addr = "127.0.0.1:7110";
+        let acl = Acl::new()
+            .allow("CONTROL/**", &["operator"], &[AclOperation::Register])
+            .unwrap();
+        let server = RegistryServer::new(addr.to_string())
+            .with_token("s3cret".to_string())
+            .with_client_token("operator".to_string(), "op-s3cret".to_string())
+            .with_acl(acl);
+        let registry = server.registry();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let register = Message::new(MessagePayload::RegisterService {
+            service: "CONTROL/VALVE_1".to_string(),
+            address: "127.0.0.1:9000".to_string(),
+            service_type: wind_core::ServiceType::Publisher,
+            schema_id: None,
+            ttl_ms: 60000,
+            tags: Vec::new(),
+        });
+
+        // The shared token doesn't let a client claim the operator's identity
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        wind_core::authenticate(&mut client, "s3cret")
+            .await
+            .unwrap();
+        let identify = Message::new(MessagePayload::Identify {
+            client_id: "operator".to_string(),
+        });
+        MessageCodec::write(&mut client, &identify).await.unwrap();
+        match MessageCodec::decode(&mut client).await.unwrap().payload {
+            MessagePayload::Error { error, .. } => {
+                assert_eq!(error, "Identifying as operator requires its token")
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        assert!(MessageCodec::decode(&mut client).await.is_err());
+
+        // While the operator's token is enough without any Identify
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        wind_core::authenticate(&mut client, "op-s3cret")
+            .await
+            .unwrap();
+        MessageCodec::write(&mut client, &register).await.unwrap();
+        assert!(matches!(
+            MessageCodec::decode(&mut client).await.unwrap().payload,
+            MessagePayload::ServiceRegistered { success: true, .. }
+        ));
+        assert!(registry.lookup_service("CONTROL/VALVE_1").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_requests_out_of_hops_are_rejected() {
+        let addr = "127.0.0.1:7111";
+        let server = RegistryServer::new(addr.to_string());
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let watch = Message::new(MessagePayload::WatchServices {
+            pattern: "SENSOR/*".to_string(),
+            max_hops: 0,
+        });
+        let discover = Message::new(MessagePayload::DiscoverServices {
+            pattern: "SENSOR/*".to_string(),
+            offset: 0,
+            limit: 0,
+            registered_since_us: None,
+            service_type: None,
+            max_hops: 0,
+        });
+        for request in [&watch, &discover] {
+            MessageCodec::write(&mut client, request).await.unwrap();
+            match MessageCodec::decode(&mut client).await.unwrap().payload {
+                MessagePayload::Error { error, .. } => assert_eq!(error, "Hop limit exceeded"),
+                other => panic!("Unexpected response: {:?}", other),
+            }
+        }
+
+        // The connection is still usable, and a hop to spare is enough
+        let discover = Message::new(MessagePayload::DiscoverServices {
+            pattern: "SENSOR/*".to_string(),
+            offset: 0,
+            limit: 0,
+            registered_since_us: None,
+            service_type: None,
+            max_hops: 1,
+        });
+        MessageCodec::write(&mut client, &discover).await.unwrap();
+        assert!(matches!(
+            MessageCodec::decode(&mut client).await.unwrap().payload,
+            MessagePayload::ServicesDiscovered { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_token_required_to_register_and_discover() {
+        let addr = "127.0.0.1:7107";
+        let server = RegistryServer::new(addr.to_string()).with_token("s3cret".to_string());
+        let registry = server.registry();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let register = Message::new(MessagePayload::RegisterService {
+            service: "SENSOR/ROOM_1/TEMP".to_string(),
+            address: "127.0.0.1:9000".to_string(),
+            service_type: wind_core::ServiceType::Publisher,
+            schema_id: None,
+            ttl_ms: 60000,
+            tags: Vec::new(),
+        });
+        let discover = Message::new(MessagePayload::DiscoverServices {
+            pattern: "*".to_string(),
+            offset: 0,
+            limit: 0,
+            registered_since_us: None,
+            service_type: None,
+            max_hops: DEFAULT_MAX_HOPS,
+        });
+
+        // Anything before `Authenticate` is refused and the connection closed
+        for request in [&register, &discover] {
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            MessageCodec::write(&mut client, request).await.unwrap();
+            match MessageCodec::decode(&mut client).await.unwrap().payload {
+                MessagePayload::Error { error, .. } => {
+                    assert!(error.contains("Authentication required"), "{}", error)
+                }
+                other => panic!("Unexpected response: {:?}", other),
+            }
+            assert!(MessageCodec::decode(&mut client).await.is_err());
+        }
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        match wind_core::authenticate(&mut client, "guess").await {
+            Err(WindError::Unauthorized(error)) => assert_eq!(error, "Invalid token"),
+            other => panic!("Expected Unauthorized, got {:?}", other),
+        }
+        assert!(MessageCodec::decode(&mut client).await.is_err());
+        assert!(registry
+            .lookup_service("SENSOR/ROOM_1/TEMP")
+            .await
+            .is_none());
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        wind_core::authenticate(&mut client, "s3cret")
+            .await
+            .unwrap();
+        MessageCodec::write(&mut client, &register).await.unwrap();
+        assert!(matches!(
+            MessageCodec::decode(&mut client).await.unwrap().payload,
+            MessagePayload::ServiceRegistered { success: true, .. }
+        ));
+        MessageCodec::write(&mut client, &discover).await.unwrap();
+        match MessageCodec::decode(&mut client).await.unwrap().payload {
+            MessagePayload::ServicesDiscovered { services, .. } => {
+                assert_eq!(services.len(), 1);
+                assert_eq!(services[0].name, "SENSOR/ROOM_1/TEMP");
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    async fn force_deregister(addr: &str, service: &str, address: Option<&str>) -> MessagePayload {
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let request = Message::new(MessagePayload::ForceDeregister {
+            service: service.to_string(),
+            address: address.map(str::to_string),
+        });
+        MessageCodec::write(&mut client, &request).await.unwrap();
+        MessageCodec::decode(&mut client).await.unwrap().payload
+    }
+
+    #[tokio::test]
+    async fn test_renewal_keeps_registered_tags() {
+        let addr = "127.0.0.1:7105";
+        let server = RegistryServer::new(addr.to_string());
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let register = Message::new(MessagePayload::RegisterService {
+            service: "SENSOR/ROOM_1/TEMP".to_string(),
+            address: "127.0.0.1:9000".to_string(),
+            service_type: wind_core::ServiceType::Publisher,
+            schema_id: Some("temperature".to_string()),
+            ttl_ms: 60000,
+            tags: vec!["building-a".to_string()],
+        });
+        MessageCodec::write(&mut client, &register).await.unwrap();
+        MessageCodec::decode(&mut client).await.unwrap();
+
+        let renew = |address: &str| {
+            Message::new(MessagePayload::RenewService {
+                service: "SENSOR/ROOM_1/TEMP".to_string(),
+                address: address.to_string(),
+                ttl_ms: 60000,
+            })
+        };
+        MessageCodec::write(&mut client, &renew("127.0.0.1:9000"))
+            .await
+            .unwrap();
+        assert!(matches!(
+            MessageCodec::decode(&mut client).await.unwrap().payload,
+            MessagePayload::ServiceRenewed { success: true, .. }
+        ));
+
+        // Only the registered provider may renew
+        MessageCodec::write(&mut client, &renew("127.0.0.1:9001"))
+            .await
+            .unwrap();
+        match MessageCodec::decode(&mut client).await.unwrap().payload {
+            MessagePayload::ServiceRenewed { success, error, .. } => {
+                assert!(!success);
+                assert!(error.unwrap().contains("127.0.0.1:9000"));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        let discover = Message::new(MessagePayload::DiscoverServices {
+            pattern: "SENSOR/*/TEMP".to_string(),
+            offset: 0,
+            limit: 0,
+            registered_since_us: None,
+            service_type: None,
+            max_hops: DEFAULT_MAX_HOPS,
+        });
+        MessageCodec::write(&mut client, &discover).await.unwrap();
+        match MessageCodec::decode(&mut client).await.unwrap().payload {
+            MessagePayload::ServicesDiscovered { services, .. } => {
+                assert_eq!(services.len(), 1);
+                assert_eq!(services[0].tags, vec!["building-a".to_string()]);
+                assert_eq!(services[0].schema_id.as_deref(), Some("temperature"));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_force_deregister_evicts_live_entry() {
+        let addr = "127.0.0.1:7103";
+        let server = RegistryServer::new(addr.to_string());
+        let registry = server.registry();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut watcher = registry.watch_services("SENSOR/**").await.unwrap();
+        register_as(addr, "sensor-gw", "SENSOR/ROOM_1/TEMP").await;
+        assert!(matches!(
+            watcher.recv().await.unwrap(),
+            ServiceEvent::Registered(info) if info.name == "SENSOR/ROOM_1/TEMP"
+        ));
+
+        // A different address doesn't match the live registration
+        match force_deregister(addr, "SENSOR/ROOM_1/TEMP", Some("127.0.0.1:1")).await {
+            MessagePayload::ServiceDeregistered { success, error, .. } => {
+                assert!(!success);
+                assert!(error.unwrap().contains("Service not found"));
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        assert!(registry
+            .lookup_service("SENSOR/ROOM_1/TEMP")
+            .await
+            .is_some());
+
+        // Gone immediately, despite its 60s TTL
+        match force_deregister(addr, "SENSOR/ROOM_1/TEMP", Some("127.0.0.1:9000")).await {
+            MessagePayload::ServiceDeregistered { success, .. } => assert!(success),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        assert!(registry
+            .lookup_service("SENSOR/ROOM_1/TEMP")
+            .await
+            .is_none());
+        assert!(registry
+            .discover_services("SENSOR/**")
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            watcher.recv().await.unwrap(),
+            ServiceEvent::Removed(info) if info.name == "SENSOR/ROOM_1/TEMP"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_acl_guards_force_deregister() {
+        let addr = "127.0.0.1:7104";
+        let acl = Acl::new()
+            .allow("SENSOR/**", &["operator"], &[AclOperation::Deregister])
+            .unwrap();
+        let server = RegistryServer::new(addr.to_string()).with_acl(acl);
+        let registry = server.registry();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        register_as(addr, "sensor-gw", "SENSOR/ROOM_1/TEMP").await;
+
+        match force_deregister(addr, "SENSOR/ROOM_1/TEMP", None).await {
+            MessagePayload::Error { error, .. } => assert!(error.contains("Permission denied")),
+            other => panic!("Unexpected response: {:?}", other),
+        }
+        assert!(registry
+            .lookup_service("SENSOR/ROOM_1/TEMP")
+            .await
+            .is_some());
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let identify = Message::new(MessagePayload::Identify {
+            client_id: "operator".to_string(),
+        });
+        MessageCodec::write(&mut client, &identify).await.unwrap();
+        let request = Message::new(MessagePayload::ForceDeregister {
+            service: "SENSOR/ROOM_1/TEMP".to_string(),
+            address: None,
+        });
+        MessageCodec::write(&mut client, &request).await.unwrap();
+        assert!(matches!(
+            MessageCodec::decode(&mut client).await.unwrap().payload,
+            MessagePayload::ServiceDeregistered { success: true, .. }
+        ));
+        assert!(registry
+            .lookup_service("SENSOR/ROOM_1/TEMP")
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_peer_warm_start_copies_services() {
+        let peer_addr = "127.0.0.1:7231";
+        let addr = "127.0.0.1:7232";
+        let peer = RegistryServer::new(peer_addr.to_string()).with_max_page_size(2);
+        let peer_registry = peer.registry();
+        for (name, ttl_ms) in [
+            ("SENSOR/ROOM_1/TEMP", 60000),
+            ("SENSOR/ROOM_2/TEMP", 60000),
+            ("DETECTOR/HALL/STATUS", 300),
+        ] {
+            let info = wind_core::ServiceInfo {
+                name: name.to_string(),
+                address: "127.0.0.1:9000".to_string(),
+                service_type: wind_core::ServiceType::Publisher,
+                schema_id: None,
+                ttl_ms,
+                tags: Vec::new(),
+                registered_at_us: 0,
+                last_heartbeat_us: 0,
+            };
+            peer_registry.register_service(info, ttl_ms).await.unwrap();
+        }
+        tokio::spawn(async move {
+            let _ = peer.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let server = RegistryServer::new(addr.to_string()).with_peer(peer_addr.to_string());
+        let registry = server.registry();
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The first discovery already sees every service of the peer
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let discover = Message::new(MessagePayload::DiscoverServices {
+            pattern: "*".to_string(),
+            offset: 0,
+            limit: 0,
+            registered_since_us: None,
+            service_type: None,
+            max_hops: DEFAULT_MAX_HOPS,
+        });
+        MessageCodec::write(&mut client, &discover).await.unwrap();
+        match MessageCodec::decode(&mut client).await.unwrap().payload {
+            MessagePayload::ServicesDiscovered { services, .. } => {
+                let names: Vec<_> = services.iter().map(|s| s.name.as_str()).collect();
+                assert_eq!(
+                    names,
+                    vec![
+                        "DETECTOR/HALL/STATUS",
+                        "SENSOR/ROOM_1/TEMP",
+                        "SENSOR/ROOM_2/TEMP"
+                    ]
+                );
+                let original = peer_registry
+                    .lookup_service("SENSOR/ROOM_1/TEMP")
+                    .await
+                    .unwrap();
+                assert_eq!(services[1].registered_at_us, original.registered_at_us);
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        // Copies keep the peer's remaining TTL
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(registry
+            .lookup_service("DETECTOR/HALL/STATUS")
+            .await
+            .is_none());
+        assert!(registry
+            .lookup_service("SENSOR/ROOM_1/TEMP")
+            .await
+            .is_some());
+    }
+
+    /// Backend keeping its entries where the test can see them. Clones made
+    /// by `sharing` see the same entries and hear of each other's changes,
+    /// as registries sharing an external store would.
+    #[derive(Clone)]
+    struct MockBackend {
+        entries: Arc<std::sync::Mutex<std::collections::HashMap<String, crate::ServiceEntry>>>,
+        changes: tokio::sync::broadcast::Sender<(Uuid, ServiceEvent)>,
+        origin: Uuid,
+    }
+
+    impl Default for MockBackend {
+        fn default() -> Self {
+            Self {
+                entries: Default::default(),
+                changes: tokio::sync::broadcast::channel(16).0,
+                origin: Uuid::new_v4(),
+            }
+        }
+    }
+
+    impl MockBackend {
+        fn sharing(&self) -> Self {
+            Self {
+                origin: Uuid::new_v4(),
+                ..self.clone()
+            }
+        }
+    }
+
+    impl DiscoveryBackend for MockBackend {
+        fn register(&self, entry: crate::ServiceEntry) -> BackendFuture<'_, ()> {
+            Box::pin(async move {
+                let mut entries = self.entries.lock().unwrap();
+                entries.insert(entry.info.name.clone(), entry);
+            })
+        }
+
+        fn renew<'a>(
+            &'a self,
+            name: &'a str,
+            address: &'a str,
+            ttl: Duration,
+        ) -> BackendFuture<'a, bool> {
+            Box::pin(async move {
+                let mut entries = self.entries.lock().unwrap();
+                match entries.get_mut(name) {
+                    Some(entry) if entry.info.address == address => {
+                        entry.renew(ttl);
+                        true
+                    }
+                    _ => false,
+                }
+            })
+        }
+
+        fn lookup<'a>(&'a self, name: &'a str) -> BackendFuture<'a, Option<crate::ServiceEntry>> {
+            Box::pin(async move { self.entries.lock().unwrap().get(name).cloned() })
+        }
+
+        fn discover<'a>(
+            &'a self,
+            pattern: &'a crate::ServicePattern,
+        ) -> BackendFuture<'a, Vec<crate::ServiceEntry>> {
+            Box::pin(async move {
+                let entries = self.entries.lock().unwrap();
+                entries
+                    .values()
+                    .filter(|entry| pattern.matches(&entry.info.name))
+                    .cloned()
+                    .collect()
+            })
+        }
+
+        fn remove<'a>(
+            &'a self,
+            name: &'a str,
+            address: Option<&'a str>,
+        ) -> BackendFuture<'a, Option<crate::ServiceEntry>> {
+            Box::pin(async move {
+                let mut entries = self.entries.lock().unwrap();
+                let at_address = entries.get(name).is_some_and(|entry| {
+                    address.is_none_or(|address| entry.info.address == address)
+                });
+                at_address.then(|| entries.remove(name)).flatten()
+            })
+        }
+
+        fn cleanup(&self) -> BackendFuture<'_, usize> {
+            Box::pin(async move {
+                let mut entries = self.entries.lock().unwrap();
+                let before = entries.len();
+                entries.retain(|_, entry| !entry.is_expired());
+                before - entries.len()
+            })
+        }
+
+        fn len(&self) -> BackendFuture<'_, usize> {
+            Box::pin(async move { self.entries.lock().unwrap().len() })
+        }
+
+        fn notify(&self, event: ServiceEvent) -> BackendFuture<'_, ()> {
+            let _ = self.changes.send((self.origin, event));
+            Box::pin(async {})
+        }
+
+        fn watch(&self) -> BackendFuture<'_, Option<mpsc::Receiver<ServiceEvent>>> {
+            let mut changes = self.changes.subscribe();
+            let (tx, rx) = mpsc::channel(16);
+            let origin = self.origin;
+            tokio::spawn(async move {
+                while let Ok((from, event)) = changes.recv().await {
+                    if from != origin && tx.send(event).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            Box::pin(async move { Some(rx) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_protocol_over_custom_backend() {
+        let addr = "127.0.0.1:7240";
+        let backend = MockBackend::default();
+        let server = RegistryServer::new(addr.to_string()).with_backend(backend.clone());
+        tokio::spawn(async move {
+            let _ = server.run().await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert!(matches!(
+            register_as(addr, "sensor-gw", "SENSOR/ROOM_1/TEMP").await,
+            MessagePayload::ServiceRegistered { success: true, .. }
+        ));
+        assert!(backend.lookup("SENSOR/ROOM_1/TEMP").await.is_some());
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let discover = Message::new(MessagePayload::DiscoverServices {
+            pattern: "SENSOR/*".to_string(),
+            offset: 0,
+            limit: 0,
+            registered_since_us: None,
+            service_type: None,
+            max_hops: DEFAULT_MAX_HOPS,
+        });
+        MessageCodec::write(&mut client, &discover).await.unwrap();
+        match MessageCodec::decode(&mut client).await.unwrap().payload {
+            MessagePayload::ServicesDiscovered { services, .. } => {
+                assert_eq!(services.len(), 1);
+                assert_eq!(services[0].name, "SENSOR/ROOM_1/TEMP");
+            }
+            other => panic!("Unexpected response: {:?}", other),
+        }
+
+        let deregister = Message::new(MessagePayload::ForceDeregister {
+            service: "SENSOR/ROOM_1/TEMP".to_string(),
+            address: Some("127.0.0.1:9000".to_string()),
+        });
+        MessageCodec::write(&mut client, &deregister).await.unwrap();
+        assert!(matches!(
+            MessageCodec::decode(&mut client).await.unwrap().payload,
+            MessagePayload::ServiceDeregistered { success: true, .. }
+        ));
+        assert!(backend.is_empty().await);
+    }
+
+    #[tokio::test]
+    async fn test_watchers_hear_of_changes_through_a_shared_backend() {
+        let backend = MockBackend::default();
+        let writer = Registry::with_backend(backend.clone());
+        let reader = Arc::new(Registry::with_backend(backend.sharing()));
+        tokio::spawn({
+            let reader = reader.clone();
+            async move { reader.follow_backend().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut watcher = reader.watch_services("SENSOR/**").await.unwrap();
+
+        let info = ServiceInfo {
+            name: "SENSOR/ROOM_1/TEMP".to_string(),
+            address: "127.0.0.1:9000".to_string(),
+            service_type: wind_core::ServiceType::Publisher,
+            schema_id: None,
+            ttl_ms: 60000,
+            tags: Vec::new(),
+            registered_at_us: 0,
+            last_heartbeat_us: 0,
+        };
+        let version = reader.version();
+        writer.register_service(info, 60000).await.unwrap();
+        let registered = tokio::time::timeout(Duration::from_secs(2), watcher.recv()).await;
+        assert!(matches!(
+            registered.unwrap().unwrap(),
+            ServiceEvent::Registered(info) if info.name == "SENSOR/ROOM_1/TEMP"
+        ));
+        assert!(reader.version() > version);
+        assert!(reader.lookup_service("SENSOR/ROOM_1/TEMP").await.is_some());
+
+        writer
+            .deregister_service("SENSOR/ROOM_1/TEMP", None)
+            .await
+            .unwrap();
+        let removed = tokio::time::timeout(Duration::from_secs(2), watcher.recv()).await;
+        assert!(matches!(
+            removed.unwrap().unwrap(),
+            ServiceEvent::Removed(info) if info.name == "SENSOR/ROOM_1/TEMP"
+        ));
+    }
 }
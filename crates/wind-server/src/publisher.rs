@@ -1,34 +1,71 @@
-use std::collections::HashMap;
+use bytes::Bytes;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
 use std::sync::{
     atomic::{AtomicU64, Ordering},
-    Arc,
+    Arc, Mutex,
 };
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, RwLock};
+use std::time::SystemTime;
+use tokio::io::{ReadHalf, WriteHalf};
+use tokio::sync::{broadcast, oneshot, RwLock};
+use tokio::task::{AbortHandle, JoinHandle, JoinSet};
 use tokio::time::{interval, Duration, Instant};
-use tracing::{debug, error, info, warn};
+use tracing::field::debug as debug_field;
+use tracing::{debug, error, info, warn, Span};
 use uuid::Uuid;
 
 use wind_core::{
-    Message, MessageCodec, MessagePayload, Result, ServiceType, SubscriptionMode, WindError,
-    WindValue,
+    message_span, unix_time_us, AuthCheck, ByteCounters, ClientDiagnostics, Codec, CountingStream,
+    Message, MessageCodec, MessagePayload, PublisherDiagnostics, QosParams, ReliabilityLevel,
+    Result, SerializationFormat, ServiceType, SocketOptions, SubscriptionDiagnostics,
+    SubscriptionMode, TokenAuth, WindError, WindListener, WindStream, WindValue, MAX_MESSAGE_SIZE,
 };
 
 /// Subscription tracking for a single client
-
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 struct ClientSubscription {
+    /// Names the subscription in `ModifySubscription` and `Unsubscribe`,
+    /// apart from any others on the same connection
+    id: Uuid,
     mode: SubscriptionMode,
+    /// The subscriber acknowledges every value it receives
+    reliable: bool,
     last_sent_at: Option<Instant>,
-    last_sent_value: Option<WindValue>,
+    last_sent_value: Option<Arc<WindValue>>,
+    /// Sequence of the last value replayed on subscribing; live updates up to
+    /// it were already delivered
+    replayed_through: u64,
+    /// Most values held back while the client has no flow-control credit
+    max_queue_size: usize,
+    /// Values held back for want of credit, oldest first
+    backlog: VecDeque<Update>,
+    /// Re-sends the current value while the mode is `Periodic`
+    periodic_timer: Option<PeriodicTimer>,
+}
+
+/// Task behind a `Periodic` subscription's re-sends, stopped along with the
+/// subscription
+#[derive(Debug)]
+struct PeriodicTimer(AbortHandle);
+
+impl Drop for PeriodicTimer {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
 }
 
 impl ClientSubscription {
-    fn new(mode: SubscriptionMode) -> Self {
+    fn new(mode: SubscriptionMode, qos: &QosParams) -> Self {
         Self {
+            id: Uuid::new_v4(),
             mode,
+            reliable: matches!(qos.reliability, ReliabilityLevel::Reliable),
             last_sent_at: None,
             last_sent_value: None,
+            replayed_through: 0,
+            max_queue_size: qos.max_queue_size.max(1) as usize,
+            backlog: VecDeque::new(),
+            periodic_timer: None,
         }
     }
 
@@ -40,7 +77,7 @@ impl ClientSubscription {
             }
             SubscriptionMode::OnChange => {
                 // send if payload changed
-                self.last_sent_value.as_ref() != Some(next)
+                self.last_sent_value.as_deref() != Some(next)
             }
             SubscriptionMode::Periodic { interval_ms } => {
                 let p = Duration::from_millis(interval_ms);
@@ -52,86 +89,684 @@ impl ClientSubscription {
         }
     }
 
-    fn mark_sent(&mut self, now: Instant, sent: &WindValue) {
+    /// Switch the delivery mode and QoS mid-stream, keeping the send history
+    /// so the new mode continues from the last value sent. A live
+    /// subscription can't become `Once`: its single value has already been
+    /// delivered.
+    fn modify(
+        &mut self,
+        mode: SubscriptionMode,
+        qos: &QosParams,
+    ) -> std::result::Result<(), String> {
+        if matches!(mode, SubscriptionMode::Once) {
+            return Err("Cannot switch a live subscription to Once".to_string());
+        }
+        self.mode = mode;
+        self.reliable = matches!(qos.reliability, ReliabilityLevel::Reliable);
+        self.max_queue_size = qos.max_queue_size.max(1) as usize;
+        Ok(())
+    }
+
+    fn mark_sent(&mut self, now: Instant, sent: &Arc<WindValue>) {
         self.last_sent_at = Some(now);
         self.last_sent_value = Some(sent.clone());
     }
+
+    /// Hold `update` back until the client has credit again. Returns false if
+    /// that overflows `max_queue_size`: a `BestEffort` subscription drops its
+    /// oldest held value, and a `Reliable` one must be disconnected.
+    fn hold_back(&mut self, update: &Update) -> bool {
+        // Its confirmation already counts it as throttled
+        self.backlog.push_back(Update {
+            confirmation: None,
+            ..update.clone()
+        });
+        if self.backlog.len() <= self.max_queue_size {
+            return true;
+        }
+        if !self.reliable {
+            self.backlog.pop_front();
+        }
+        false
+    }
+}
+
+type ClientStream = CountingStream<WindStream>;
+
+/// Outcome of a `Publisher::publish_confirmed` call, counted per subscription
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeliveryReport {
+    pub sequence: u64,
+    /// Written to the subscriber and, if it is `Reliable`, acknowledged
+    pub delivered: usize,
+    /// The write failed, or a `Reliable` subscriber disconnected before acknowledging
+    pub failed: usize,
+    /// Withheld because the subscriber had no flow-control credit left
+    pub throttled: usize,
+    /// Withheld because the value's TTL ran out before it could be sent
+    pub expired: usize,
+}
+
+/// Collects a `DeliveryReport` while the update sender works through the
+/// subscribers and `Reliable` ones acknowledge, then hands it to the caller
+#[derive(Debug)]
+struct Confirmation {
+    state: Mutex<ConfirmationState>,
+}
+
+#[derive(Debug)]
+struct ConfirmationState {
+    report: DeliveryReport,
+    sending: bool,
+    awaiting_acks: usize,
+    reply: Option<oneshot::Sender<DeliveryReport>>,
+}
+
+impl Confirmation {
+    fn new(sequence: u64, reply: oneshot::Sender<DeliveryReport>) -> Self {
+        Self {
+            state: Mutex::new(ConfirmationState {
+                report: DeliveryReport {
+                    sequence,
+                    ..Default::default()
+                },
+                sending: true,
+                awaiting_acks: 0,
+                reply: Some(reply),
+            }),
+        }
+    }
+
+    fn record(&self, update: impl FnOnce(&mut ConfirmationState)) {
+        let mut state = self.state.lock().unwrap();
+        update(&mut state);
+        if !state.sending && state.awaiting_acks == 0 {
+            if let Some(reply) = state.reply.take() {
+                let _ = reply.send(state.report.clone());
+            }
+        }
+    }
+
+    fn acknowledged(&self, delivered: bool) {
+        self.record(|state| {
+            state.awaiting_acks -= 1;
+            if delivered {
+                state.report.delivered += 1;
+            } else {
+                state.report.failed += 1;
+            }
+        });
+    }
+}
+
+impl Drop for Confirmation {
+    fn drop(&mut self) {
+        // Report whatever was collected if the update never completed
+        let state = self.state.get_mut().unwrap();
+        if let Some(reply) = state.reply.take() {
+            let _ = reply.send(state.report.clone());
+        }
+    }
+}
+
+/// A published value on its way to the update sender
+#[derive(Clone, Debug)]
+struct Update {
+    topic: String,
+    sequence: u64,
+    /// Id of the message(s) carrying the value, shared by the values of one
+    /// `publish_batch` so their spans correlate with the `PublishBatch` frame
+    message_id: Uuid,
+    /// Shared by the topic's current value and every client it is sent to
+    value: Arc<WindValue>,
+    /// Unix epoch micros after which the value is no longer sent
+    expires_at_us: Option<u64>,
+    confirmation: Option<Arc<Confirmation>>,
+    /// Published by `publish_batch`, along with the updates around it
+    batched: bool,
+}
+
+impl Update {
+    fn is_expired(&self) -> bool {
+        is_expired(self.expires_at_us)
+    }
+
+    /// Whether the update can share a `PublishBatch` frame with its batch:
+    /// one without an expiry, small enough to gain from the shared frame
+    fn is_packable(&self, chunk_size: usize) -> bool {
+        self.batched
+            && self.expires_at_us.is_none()
+            && !matches!(self.value.as_ref(), WindValue::Bytes(bytes) if bytes.len() > chunk_size)
+            && self.value.encoded_size() < DIRECT_WRITE_BYTES
+    }
+
+    /// Open the span for `stage` of the update, recording the mode of the
+    /// subscription it's being sent on, if any
+    fn span(&self, stage: &'static str, mode: Option<&SubscriptionMode>) -> Span {
+        let span = message_span(stage, self.message_id, &self.topic, self.sequence);
+        if !span.is_disabled() {
+            span.record("payload.size", self.value.encoded_size());
+            if let Some(mode) = mode {
+                span.record("subscription.mode", debug_field(mode));
+            }
+        }
+        span
+    }
+
+    fn record(&self, update: fn(&mut ConfirmationState)) {
+        if let Some(confirmation) = &self.confirmation {
+            confirmation.record(update);
+        }
+    }
+}
+
+/// State of one service name served by a publisher
+#[derive(Debug, Default)]
+struct Topic {
+    current_value: Option<Arc<WindValue>>,
+    /// Sequence number of `current_value`
+    sequence: u64,
+    /// Expiry of `current_value`, as Unix epoch micros
+    expires_at_us: Option<u64>,
+    /// Access control, as propagated by the registry (None = open)
+    allowed_subscribers: Option<Vec<String>>,
+    /// Most recent values, oldest first, for replay to new subscribers
+    history: VecDeque<Retained>,
+}
+
+/// A published value kept for replay
+#[derive(Debug)]
+struct Retained {
+    sequence: u64,
+    value: Arc<WindValue>,
+    expires_at_us: Option<u64>,
+}
+
+impl Topic {
+    /// Make `value` the current value, retaining it for replay if the
+    /// publisher keeps `history_size` values
+    fn set_current(
+        &mut self,
+        sequence: u64,
+        value: &Arc<WindValue>,
+        expires_at_us: Option<u64>,
+        history_size: usize,
+    ) {
+        self.sequence = sequence;
+        self.current_value = Some(value.clone());
+        self.expires_at_us = expires_at_us;
+        if history_size > 0 {
+            if self.history.len() == history_size {
+                self.history.pop_front();
+            }
+            self.history.push_back(Retained {
+                sequence,
+                value: value.clone(),
+                expires_at_us,
+            });
+        }
+    }
+
+    /// The current value, unless its TTL has run out
+    fn live_value(&self) -> Option<&Arc<WindValue>> {
+        self.current_value
+            .as_ref()
+            .filter(|_| !is_expired(self.expires_at_us))
+    }
+
+    /// Up to `count` of the most recent retained values still live, oldest first
+    fn replay(&self, count: usize) -> Vec<&Retained> {
+        let mut values: Vec<_> = self
+            .history
+            .iter()
+            .rev()
+            .filter(|retained| !is_expired(retained.expires_at_us))
+            .take(count)
+            .collect();
+        values.reverse();
+        values
+    }
+}
+
+/// Why a subscriber expecting the `expected` schema can't read values of the
+/// publisher's `actual` one. Without a declared schema the publisher can't
+/// tell, so it lets the subscriber through.
+fn schema_mismatch(expected: Option<&str>, actual: Option<&str>) -> Option<String> {
+    match (expected, actual) {
+        (Some(expected), Some(actual)) if expected != actual => Some(format!(
+            "Schema mismatch: subscriber expects {}, publisher sends {}",
+            expected, actual
+        )),
+        (Some(expected), None) => {
+            warn!(
+                "Subscriber expects schema {}, but the publisher declares none",
+                expected
+            );
+            None
+        }
+        _ => None,
+    }
+}
+
+fn is_expired(expires_at_us: Option<u64>) -> bool {
+    expires_at_us.is_some_and(|at| unix_time_us(SystemTime::now()) >= at)
+}
+
+/// Default size above which `Bytes` values are sent in chunks
+pub const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Most queued updates coalesced into a single write to a client
+const MAX_BATCH: usize = 64;
+
+/// Published updates queued for the update sender before it lags
+const UPDATE_QUEUE_SIZE: usize = 1000;
+
+/// Default most services one client connection may subscribe to
+pub const DEFAULT_MAX_SUBSCRIPTIONS_PER_CLIENT: usize = 256;
+
+/// Default most subscriptions across all of a publisher's clients
+pub const DEFAULT_MAX_TOTAL_SUBSCRIPTIONS: usize = 65536;
+
+/// Replayed values are packed into `ReplayBatch` frames of about this many
+/// encoded bytes
+const REPLAY_BATCH_BYTES: usize = 1024 * 1024;
+
+/// `ReplayBatch` frames serializing to more than this many bytes are sent
+/// lz4-compressed; history tends to repeat itself, and lz4 costs little
+const REPLAY_COMPRESSION_BYTES: usize = 4 * 1024;
+
+/// Encoded updates at least this large are written on their own rather than
+/// copied into a client's coalesced write
+const DIRECT_WRITE_BYTES: usize = 64 * 1024;
+
+/// Values from one `publish_batch` bound for a client, consecutive in a
+/// topic, gathered by the update sender into one `PublishBatch` frame
+#[derive(Debug, Default)]
+struct PackedRun {
+    /// Positions in the sender's batch of updates
+    indices: Vec<usize>,
+    bytes: usize,
+    reliable: bool,
+}
+
+impl PackedRun {
+    /// Whether `update` can join the run rather than follow it
+    fn continues_with(&self, batch: &[Update], update: &Update, chunk_size: usize) -> bool {
+        let Some(&last) = self.indices.last() else {
+            return true;
+        };
+        update.is_packable(chunk_size)
+            && batch[last].topic == update.topic
+            && batch[last].sequence + 1 == update.sequence
+            && self.bytes + update.value.encoded_size() <= REPLAY_BATCH_BYTES
+    }
+
+    fn push(&mut self, index: usize, update: &Update, reliable: bool) {
+        self.indices.push(index);
+        self.bytes += update.value.encoded_size();
+        self.reliable = reliable;
+    }
+
+    /// Add the run's frame to `frames`, a plain `Publish` for a run of one,
+    /// and start a new run
+    fn flush<'a>(
+        &mut self,
+        batch: &'a [Update],
+        format: SerializationFormat,
+        encoded_runs: &mut HashMap<(SerializationFormat, usize, usize), Option<Bytes>>,
+        frames: &mut Vec<Bytes>,
+        encoded: &mut Vec<(&'a Update, bool)>,
+    ) {
+        let Some(&first) = self.indices.first() else {
+            return;
+        };
+        let updates: Vec<&Update> = self.indices.iter().map(|&index| &batch[index]).collect();
+        let run_frame = encoded_runs
+            .entry((format, first, updates.len()))
+            .or_insert_with(|| {
+                let run_frame = match updates.as_slice() {
+                    [single] => Publisher::encode_update(single, usize::MAX, format),
+                    _ => Publisher::encode_batch(&updates, format),
+                };
+                run_frame
+                    .inspect_err(|e| {
+                        warn!(
+                            "Failed to encode batch from {}: {}",
+                            batch[first].sequence, e
+                        )
+                    })
+                    .ok()
+            });
+        if let Some(run_frame) = run_frame {
+            frames.push(run_frame.clone());
+            encoded.extend(updates.iter().map(|&update| (update, self.reliable)));
+        }
+        self.indices.clear();
+        self.bytes = 0;
+    }
 }
 
 /// Active client connection state
 #[derive(Debug)]
 struct ActiveClient {
-    stream: TcpStream,
+    writer: WriteHalf<ClientStream>,
+    traffic: Arc<ByteCounters>,
     subscriptions: HashMap<String, ClientSubscription>,
+    /// Remaining send credit from the client's last FlowControl (None = not flow controlled)
+    credit: Option<u64>,
+    /// Confirmed publishes still awaiting this client's ack, by sequence
+    pending_acks: HashMap<u64, Arc<Confirmation>>,
+    /// Identity from the client's `Identify`, if it sent one
+    identity: Option<String>,
+    /// Format of the client's latest message, which everything sent to it uses
+    format: SerializationFormat,
+    /// Liveness pings sent since the client last answered one
+    unanswered_pings: u32,
+    /// The task reading the client's messages, stopped if the client is
+    /// reaped since a dead peer would leave it waiting forever
+    listener: AbortHandle,
+}
+
+impl ActiveClient {
+    /// Send held-back values, oldest first across subscriptions, as far as
+    /// the client's credit goes
+    async fn send_backlog(&mut self, chunk_size: usize, expired: &AtomicU64) -> Result<()> {
+        let mut frames = Vec::new();
+        while self.credit.is_none_or(|credit| credit > 0) {
+            let Some(subscription) = self
+                .subscriptions
+                .values_mut()
+                .filter(|subscription| !subscription.backlog.is_empty())
+                .min_by_key(|subscription| subscription.backlog[0].sequence)
+            else {
+                break;
+            };
+            let Some(update) = subscription.backlog.pop_front() else {
+                break;
+            };
+            if update.is_expired() {
+                expired.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            let encoded = update
+                .span("send", Some(&subscription.mode))
+                .in_scope(|| Publisher::encode_update(&update, chunk_size, self.format));
+            match encoded {
+                Ok(update_frames) => frames.push(update_frames),
+                Err(e) => {
+                    warn!("Failed to encode update {}: {}", update.sequence, e);
+                    continue;
+                }
+            }
+            subscription.mark_sent(Instant::now(), &update.value);
+            if let Some(credit) = self.credit.as_mut() {
+                *credit -= 1;
+            }
+        }
+        if frames.is_empty() {
+            return Ok(());
+        }
+        Publisher::write_frames(&mut self.writer, &frames).await
+    }
+
+    fn diagnostics(&self, connection_id: Uuid) -> ClientDiagnostics {
+        let mut subscriptions: Vec<_> = self
+            .subscriptions
+            .iter()
+            .map(|(service, subscription)| SubscriptionDiagnostics {
+                service: service.clone(),
+                mode: subscription.mode.clone(),
+                reliable: subscription.reliable,
+            })
+            .collect();
+        subscriptions.sort_by(|a, b| a.service.cmp(&b.service));
+        ClientDiagnostics {
+            connection_id,
+            identity: self.identity.clone(),
+            subscriptions,
+            credit: self.credit,
+            pending_acks: self.pending_acks.len(),
+            bytes_in: self.traffic.bytes_in(),
+            bytes_out: self.traffic.bytes_out(),
+        }
+    }
+}
+
+impl Drop for ActiveClient {
+    fn drop(&mut self) {
+        for (_, confirmation) in self.pending_acks.drain() {
+            confirmation.acknowledged(false);
+        }
+    }
 }
 
 /// High-performance publisher for WIND services
 pub struct Publisher {
+    /// The first of `topic_names`, which `publish` and `current_value` use
     service_name: String,
+    topic_names: Vec<String>,
     bind_address: String,
     registry_address: String,
     schema_id: Option<String>,
+    client_id: Option<String>,
+    token: Option<String>,
+    client_tokens: Arc<HashMap<String, String>>,
 
-    // Data management
-    current_value: Arc<RwLock<Option<WindValue>>>,
+    // Data management, by service name. Sequence numbers are shared by all
+    // topics so acknowledgements stay unambiguous.
+    topics: Arc<RwLock<HashMap<String, Topic>>>,
     sequence_number: Arc<AtomicU64>,
 
     // Client management
     clients: Arc<RwLock<HashMap<Uuid, ActiveClient>>>,
+    traffic: Arc<ByteCounters>,
+    throttled: Arc<AtomicU64>,
+    dropped: Arc<AtomicU64>,
+    expired: Arc<AtomicU64>,
 
     // Update notification
-    update_tx: broadcast::Sender<WindValue>,
-    _update_rx: broadcast::Receiver<WindValue>,
+    update_tx: broadcast::Sender<Update>,
+    _update_rx: broadcast::Receiver<Update>,
 
     // Configuration
     heartbeat_interval: Duration,
     ttl_ms: u64,
     tags: Vec<String>,
+    chunk_size: usize,
+    history_size: usize,
+    max_subscriptions_per_client: usize,
+    max_total_subscriptions: usize,
+    socket_options: SocketOptions,
+    /// Client identities allowed a `DiagnosticDump`
+    diagnostic_clients: Vec<String>,
+    /// Ping interval and unanswered pings before a client is dropped (None = off)
+    liveness: Option<(Duration, u32)>,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<wind_core::rustls::ServerConfig>>,
 }
 
 impl Publisher {
     /// Create a new publisher for the specified service
     pub fn new(service_name: String, bind_address: String, registry_address: String) -> Self {
-        let (update_tx, update_rx) = broadcast::channel(1000);
+        Self::new_multi(vec![service_name], bind_address, registry_address)
+    }
+
+    /// Create a publisher serving several service names from one listener.
+    /// Each name is registered at the shared address and keeps its own
+    /// current value and subscribers; see `publish_to`.
+    ///
+    /// # Panics
+    ///
+    /// If `service_names` is empty.
+    pub fn new_multi(
+        service_names: Vec<String>,
+        bind_address: String,
+        registry_address: String,
+    ) -> Self {
+        assert!(
+            !service_names.is_empty(),
+            "A publisher needs at least one service name"
+        );
+        let (update_tx, update_rx) = broadcast::channel(UPDATE_QUEUE_SIZE);
+        let topics = service_names
+            .iter()
+            .map(|name| (name.clone(), Topic::default()))
+            .collect();
 
         Self {
-            service_name,
+            service_name: service_names[0].clone(),
+            topic_names: service_names,
             bind_address,
             registry_address,
             schema_id: None,
-            current_value: Arc::new(RwLock::new(None)),
+            client_id: None,
+            token: None,
+            client_tokens: Arc::default(),
+            topics: Arc::new(RwLock::new(topics)),
             sequence_number: Arc::new(AtomicU64::new(0)),
             clients: Arc::new(RwLock::new(HashMap::new())),
+            traffic: Arc::new(ByteCounters::new()),
+            throttled: Arc::new(AtomicU64::new(0)),
+            dropped: Arc::new(AtomicU64::new(0)),
+            expired: Arc::new(AtomicU64::new(0)),
             update_tx,
             _update_rx: update_rx,
             heartbeat_interval: Duration::from_secs(30),
             ttl_ms: 60000, // 1 minute TTL
             tags: Vec::new(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            history_size: 0,
+            max_subscriptions_per_client: DEFAULT_MAX_SUBSCRIPTIONS_PER_CLIENT,
+            max_total_subscriptions: DEFAULT_MAX_TOTAL_SUBSCRIPTIONS,
+            socket_options: SocketOptions::default(),
+            diagnostic_clients: Vec::new(),
+            liveness: None,
+            #[cfg(feature = "tls")]
+            tls: None,
         }
     }
 
+    /// Service names this publisher serves, its primary name first
+    pub fn service_names(&self) -> &[String] {
+        &self.topic_names
+    }
+
     /// Set optional schema ID for type validation
     pub fn with_schema(mut self, schema_id: String) -> Self {
         self.schema_id = Some(schema_id);
         self
     }
 
+    /// Identify to the registry as `client_id`, for registration ACLs
+    pub fn with_client_id(mut self, client_id: String) -> Self {
+        self.client_id = Some(client_id);
+        self
+    }
+
+    /// Refuse subscribers until they `Authenticate` with `token`, and present
+    /// it to the registry
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// Accept `token` from subscribers as authenticating them as `client_id`,
+    /// which subscribe ACLs then check instead of whatever they `Identify` as
+    pub fn with_client_token(mut self, client_id: String, token: String) -> Self {
+        Arc::make_mut(&mut self.client_tokens).insert(client_id, token);
+        self
+    }
+
     /// Set custom TTL for service registration
     pub fn with_ttl_ms(mut self, ttl_ms: u64) -> Self {
         self.ttl_ms = ttl_ms;
         self
     }
 
+    /// Accept subscribers over TLS only. The service registers with a
+    /// `tls://` address so discovering clients know to connect with TLS.
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, config: wind_core::rustls::ServerConfig) -> Self {
+        self.tls = Some(Arc::new(config));
+        self
+    }
+
+    /// `Bytes` values larger than this are streamed as `PublishChunk` frames
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Keep the last `values` published to each service name, replayed in
+    /// bulk to subscribers that ask for them
+    pub fn with_history(mut self, values: usize) -> Self {
+        self.history_size = values;
+        self
+    }
+
+    /// Refuse a client connection's subscriptions beyond the first `max`
+    pub fn with_max_subscriptions_per_client(mut self, max: usize) -> Self {
+        self.max_subscriptions_per_client = max;
+        self
+    }
+
+    /// Refuse new subscriptions while all clients together hold `max`
+    pub fn with_max_total_subscriptions(mut self, max: usize) -> Self {
+        self.max_total_subscriptions = max;
+        self
+    }
+
+    /// TCP options for accepted connections and the registry connection
+    pub fn with_socket_options(mut self, options: SocketOptions) -> Self {
+        self.socket_options = options;
+        self
+    }
+
+    /// Let clients identifying as one of `client_ids` read a diagnostic dump
+    /// of the publisher's state (`wind inspect`); nobody may by default
+    pub fn with_diagnostic_access(mut self, client_ids: Vec<String>) -> Self {
+        self.diagnostic_clients = client_ids;
+        self
+    }
+
+    /// Ping each client every `interval`, dropping one that leaves
+    /// `max_missed` pings in a row without a `Pong`. Catches subscribers that
+    /// vanished without closing their connection, which would otherwise only
+    /// be noticed on a failed write, or never for an idle subscription.
+    pub fn with_liveness(mut self, interval: Duration, max_missed: u32) -> Self {
+        self.liveness = Some((interval, max_missed.max(1)));
+        self
+    }
+
     /// Add tags for service discovery
     pub fn with_tags(mut self, tags: Vec<String>) -> Self {
         self.tags = tags;
         self
     }
 
+    /// Bind `bind_address`, over TLS when `with_tls` was given a config
+    async fn bind(&self) -> std::io::Result<WindListener> {
+        #[cfg(feature = "tls")]
+        if let Some(config) = &self.tls {
+            return WindListener::bind_tls(&self.bind_address, config.clone()).await;
+        }
+        WindListener::bind(&self.bind_address).await
+    }
+
     /// Start the publisher server
     pub async fn start(&self) -> Result<()> {
-        let listener = TcpListener::bind(&self.bind_address).await?;
-        let actual_address = listener.local_addr()?.to_string();
+        self.start_until(std::future::pending()).await
+    }
+
+    /// Start the publisher server and run until `shutdown` resolves, then stop
+    /// accepting, disconnect subscribers, deregister from the registry and
+    /// return.
+    pub async fn start_until<F>(&self, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        let listener = self.bind().await?;
+        let actual_address = listener.local_address()?;
 
         info!(
             "Publisher '{}' listening on {}",
@@ -140,59 +775,214 @@ impl Publisher {
 
         // Register with the registry and start heartbeat
         self.register_service(&actual_address).await?;
-        self.start_heartbeat_task(actual_address.clone());
+        let heartbeat = self.start_heartbeat_task(actual_address.clone());
 
         // Start the client handler loop
-        self.start_update_sender().await; // Renamed for clarity
+        let update_sender = self.start_update_sender().await; // Renamed for clarity
+        let liveness = self.start_liveness_task();
+
+        let mut client_listeners = JoinSet::new();
+        tokio::pin!(shutdown);
 
         // Accept and handle client connections
         loop {
-            match listener.accept().await {
-                Ok((stream, addr)) => {
-                    info!("New subscriber connected: {}", addr);
-                    let client_id = Uuid::new_v4();
-                    let mut clients = self.clients.write().await;
-                    clients.insert(
-                        client_id,
-                        ActiveClient {
-                            stream,
-                            subscriptions: HashMap::new(),
-                        },
-                    );
-                    // Spawn a task to handle this specific client's messages
-                    self.spawn_client_listener(client_id).await;
-                }
-                Err(e) => {
-                    error!("Failed to accept subscriber connection: {}", e);
-                }
+            tokio::select! {
+                _ = &mut shutdown => break,
+                Some(_) = client_listeners.join_next(), if !client_listeners.is_empty() => {}
+                accepted = listener.accept_with(&self.socket_options) => match accepted {
+                    Ok((stream, addr)) => {
+                        info!("New subscriber connected: {}", addr);
+                        let client_id = Uuid::new_v4();
+                        let stream = CountingStream::new(stream).with_aggregate(self.traffic.clone());
+                        let traffic = stream.counters();
+                        let (reader, writer) = tokio::io::split(stream);
+                        let mut clients = self.clients.write().await;
+                        // Spawn a task to handle this specific client's messages,
+                        // under the lock so the client is in place for the first one
+                        let listener = client_listeners.spawn(self.client_listener(client_id, reader));
+                        clients.insert(
+                            client_id,
+                            ActiveClient {
+                                writer,
+                                traffic,
+                                subscriptions: HashMap::new(),
+                                credit: None,
+                                pending_acks: HashMap::new(),
+                                identity: None,
+                                format: SerializationFormat::default(),
+                                unanswered_pings: 0,
+                                listener,
+                            },
+                        );
+                    }
+                    Err(e) => {
+                        error!("Failed to accept subscriber connection: {}", e);
+                    }
+                },
             }
         }
+
+        info!("Publisher '{}' shutting down", self.service_name);
+        drop(listener);
+        heartbeat.abort();
+        update_sender.abort();
+        if let Some(liveness) = liveness {
+            liveness.abort();
+        }
+        client_listeners.abort_all();
+        self.clients.write().await.clear();
+
+        // Don't leave the address behind until its TTL runs out
+        if let Err(e) = self.deregister_service(&actual_address).await {
+            warn!(
+                "Failed to deregister '{}' from the registry: {}",
+                self.service_name, e
+            );
+        }
+
+        Ok(())
     }
 
     /// Publish a new value to all subscribers
     pub async fn publish(&self, value: WindValue) -> Result<()> {
-        let seq = self.sequence_number.fetch_add(1, Ordering::SeqCst) + 1;
+        self.publish_to(&self.service_name, value).await
+    }
 
-        // Update current value
-        {
-            let mut current = self.current_value.write().await;
-            *current = Some(value.clone());
+    /// Publish a new value to the subscribers of one of the publisher's
+    /// service names
+    pub async fn publish_to(&self, topic: &str, value: WindValue) -> Result<()> {
+        self.publish_update(topic, value, None, |_| None).await?;
+        Ok(())
+    }
+
+    /// Publish a value that goes stale `ttl` from now. Once expired it is no
+    /// longer sent, not even to subscribers that are behind, nor handed out as
+    /// the current value; subscribers drop it if it reaches them late. This
+    /// is separate from the registration TTL set by `with_ttl_ms`.
+    pub async fn publish_with_ttl(&self, value: WindValue, ttl: Duration) -> Result<()> {
+        let expires_at_us = unix_time_us(SystemTime::now() + ttl);
+        self.publish_update(&self.service_name, value, Some(expires_at_us), |_| None)
+            .await?;
+        Ok(())
+    }
+
+    /// Publish a new value and wait until delivery to every current
+    /// subscriber has been attempted and, for `Reliable` subscribers,
+    /// acknowledged
+    pub async fn publish_confirmed(&self, value: WindValue) -> DeliveryReport {
+        let (reply, report) = oneshot::channel();
+        let published = self
+            .publish_update(&self.service_name, value, None, |sequence| {
+                Some(Arc::new(Confirmation::new(sequence, reply)))
+            })
+            .await;
+        // The primary service name always exists
+        let sequence = published.unwrap_or_default();
+        report.await.unwrap_or(DeliveryReport {
+            sequence,
+            ..Default::default()
+        })
+    }
+
+    /// Publish several values in one go, in order. Subscribers see each of
+    /// them as if published on its own, `OnChange` ones skipping repeats,
+    /// but consecutive values share a `PublishBatch` frame rather than
+    /// taking one each. At most `UPDATE_QUEUE_SIZE` (1000) values per call.
+    pub async fn publish_batch(&self, values: Vec<WindValue>) -> Result<()> {
+        if values.len() > UPDATE_QUEUE_SIZE {
+            return Err(WindError::Protocol(format!(
+                "Batch of {} values is over the limit of {}",
+                values.len(),
+                UPDATE_QUEUE_SIZE
+            )));
+        }
+        if values.is_empty() {
+            return Ok(());
         }
+        let topic = &self.service_name;
+        let mut topics = self.topics.write().await;
+        let state = topics
+            .get_mut(topic)
+            .ok_or_else(|| WindError::ServiceNotFound(topic.to_string()))?;
 
-        // Notify all clients via broadcast
-        let _ = self.update_tx.send(value.clone());
+        // The write lock keeps the batch's sequence numbers, and its place in
+        // the update queue, contiguous
+        let count = values.len() as u64;
+        let first = self.sequence_number.fetch_add(count, Ordering::SeqCst) + 1;
+        let message_id = Uuid::new_v4();
+        for (seq, value) in (first..).zip(values) {
+            let value = Arc::new(value);
+            state.set_current(seq, &value, None, self.history_size);
+            let update = Update {
+                topic: topic.clone(),
+                sequence: seq,
+                message_id,
+                value,
+                expires_at_us: None,
+                confirmation: None,
+                batched: true,
+            };
+            let _span = update.span("publish", None).entered();
+            let _ = self.update_tx.send(update);
+        }
 
         debug!(
-            "Published value for '{}' with sequence {}",
-            self.service_name, seq
+            "Published {} values for '{}' from sequence {}",
+            count, topic, first
         );
 
         Ok(())
     }
 
+    async fn publish_update(
+        &self,
+        topic: &str,
+        value: WindValue,
+        expires_at_us: Option<u64>,
+        confirmation: impl FnOnce(u64) -> Option<Arc<Confirmation>>,
+    ) -> Result<u64> {
+        let mut topics = self.topics.write().await;
+        let state = topics
+            .get_mut(topic)
+            .ok_or_else(|| WindError::ServiceNotFound(topic.to_string()))?;
+
+        // Update current value
+        let seq = self.sequence_number.fetch_add(1, Ordering::SeqCst) + 1;
+        let value = Arc::new(value);
+        state.set_current(seq, &value, expires_at_us, self.history_size);
+
+        // Notify all clients via broadcast
+        let update = Update {
+            topic: topic.to_string(),
+            sequence: seq,
+            message_id: Uuid::new_v4(),
+            value,
+            expires_at_us,
+            confirmation: confirmation(seq),
+            batched: false,
+        };
+        let span = update.span("publish", None);
+        let _ = self.update_tx.send(update);
+
+        span.in_scope(|| debug!("Published value for '{}' with sequence {}", topic, seq));
+
+        Ok(seq)
+    }
+
     /// Get the current published value
     pub async fn current_value(&self) -> Option<WindValue> {
-        self.current_value.read().await.clone()
+        self.current_value_of(&self.service_name).await
+    }
+
+    /// Get the current value of one of the publisher's service names
+    pub async fn current_value_of(&self, topic: &str) -> Option<WindValue> {
+        self.topics
+            .read()
+            .await
+            .get(topic)?
+            .current_value
+            .as_deref()
+            .cloned()
     }
 
     /// Get number of active subscribers
@@ -200,68 +990,227 @@ impl Publisher {
         self.clients.read().await.len()
     }
 
+    /// Total bytes exchanged with all subscribers
+    pub fn traffic(&self) -> Arc<ByteCounters> {
+        self.traffic.clone()
+    }
+
+    /// Bytes in/out per connected subscriber
+    pub async fn client_traffic(&self) -> HashMap<Uuid, (u64, u64)> {
+        self.clients
+            .read()
+            .await
+            .iter()
+            .map(|(id, client)| (*id, (client.traffic.bytes_in(), client.traffic.bytes_out())))
+            .collect()
+    }
+
+    /// Number of updates withheld from subscribers that had no flow-control
+    /// credit left. Each is held back and sent once credit returns, unless
+    /// it is dropped first.
+    pub fn throttled_count(&self) -> u64 {
+        self.throttled.load(Ordering::Relaxed)
+    }
+
+    /// Number of held-back updates dropped because more than a subscription's
+    /// `max_queue_size` were waiting. A `BestEffort` subscription loses its
+    /// oldest held value; a `Reliable` one is disconnected instead.
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Number of updates not sent to a subscriber because their TTL ran out first
+    pub fn expired_count(&self) -> u64 {
+        self.expired.load(Ordering::Relaxed)
+    }
+
     async fn register_service(&self, actual_address: &str) -> Result<()> {
-        let mut registry_conn = tokio::net::TcpStream::connect(&self.registry_address).await?;
-
-        let register_msg = Message::new(MessagePayload::RegisterService {
-            service: self.service_name.clone(),
-            address: actual_address.to_string(),
-            service_type: ServiceType::Publisher,
-            schema_id: self.schema_id.clone(),
-            ttl_ms: self.ttl_ms,
-            tags: self.tags.clone(),
-        });
+        let mut registry_conn =
+            WindStream::connect_with(&self.registry_address, &self.socket_options).await?;
+        if let Some(token) = &self.token {
+            wind_core::authenticate(&mut registry_conn, token).await?;
+        }
+        if let Some(client_id) = &self.client_id {
+            let identify = Message::new(MessagePayload::Identify {
+                client_id: client_id.clone(),
+            });
+            MessageCodec::write(&mut registry_conn, &identify).await?;
+        }
+
+        for service_name in &self.topic_names {
+            let register_msg = Message::new(MessagePayload::RegisterService {
+                service: service_name.clone(),
+                address: actual_address.to_string(),
+                service_type: ServiceType::Publisher,
+                schema_id: self.schema_id.clone(),
+                ttl_ms: self.ttl_ms,
+                tags: self.tags.clone(),
+            });
 
-        MessageCodec::write(&mut registry_conn, &register_msg).await?;
-        let response = MessageCodec::decode(&mut registry_conn).await?;
+            MessageCodec::write(&mut registry_conn, &register_msg).await?;
+            let response = MessageCodec::decode(&mut registry_conn).await?;
 
-        match response.payload {
-            MessagePayload::ServiceRegistered { success, error, .. } => {
-                if success {
+            match response.payload {
+                MessagePayload::ServiceRegistered {
+                    success,
+                    error,
+                    allowed_subscribers,
+                    ..
+                } => {
+                    if !success {
+                        return Err(WindError::Registry(
+                            error.unwrap_or("Registration failed".to_string()),
+                        ));
+                    }
                     info!(
                         "Successfully registered service '{}' with registry",
-                        self.service_name
+                        service_name
                     );
-                    Ok(())
-                } else {
-                    Err(WindError::Registry(
-                        error.unwrap_or("Registration failed".to_string()),
+                    if let Some(topic) = self.topics.write().await.get_mut(service_name) {
+                        topic.allowed_subscribers = allowed_subscribers;
+                    }
+                }
+                MessagePayload::Error { error, .. } => return Err(WindError::Registry(error)),
+                _ => {
+                    return Err(WindError::Protocol(
+                        "Unexpected registry response".to_string(),
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn deregister_service(&self, actual_address: &str) -> Result<()> {
+        let mut registry_conn =
+            WindStream::connect_with(&self.registry_address, &self.socket_options).await?;
+        if let Some(token) = &self.token {
+            wind_core::authenticate(&mut registry_conn, token).await?;
+        }
+        if let Some(client_id) = &self.client_id {
+            let identify = Message::new(MessagePayload::Identify {
+                client_id: client_id.clone(),
+            });
+            MessageCodec::write(&mut registry_conn, &identify).await?;
+        }
+
+        for service_name in &self.topic_names {
+            let deregister_msg = Message::new(MessagePayload::ForceDeregister {
+                service: service_name.clone(),
+                address: Some(actual_address.to_string()),
+            });
+            MessageCodec::write(&mut registry_conn, &deregister_msg).await?;
+
+            match MessageCodec::decode(&mut registry_conn).await?.payload {
+                MessagePayload::ServiceDeregistered { success: true, .. } => {
+                    info!("Deregistered service '{}' from registry", service_name)
+                }
+                MessagePayload::ServiceDeregistered { error, .. } => warn!(
+                    "Failed to deregister '{}': {}",
+                    service_name,
+                    error.unwrap_or_default()
+                ),
+                MessagePayload::Error { error, .. } => {
+                    warn!("Failed to deregister '{}': {}", service_name, error)
+                }
+                _ => {
+                    return Err(WindError::Protocol(
+                        "Unexpected registry response".to_string(),
                     ))
                 }
             }
-            _ => Err(WindError::Protocol(
-                "Unexpected registry response".to_string(),
-            )),
         }
+        Ok(())
     }
 
-    fn start_heartbeat_task(&self, address: String) {
+    fn start_heartbeat_task(&self, address: String) -> JoinHandle<()> {
         let registry_address = self.registry_address.clone();
-        let service_name = self.service_name.clone();
+        let service_names = self.topic_names.clone();
+        let client_id = self.client_id.clone();
+        let token = self.token.clone();
         let ttl_ms = self.ttl_ms;
+        let schema_id = self.schema_id.clone();
+        let tags = self.tags.clone();
         let heartbeat_duration = self.heartbeat_interval;
+        let socket_options = self.socket_options.clone();
 
         tokio::spawn(async move {
             let mut heartbeat_timer = interval(heartbeat_duration);
             loop {
                 heartbeat_timer.tick().await;
 
-                // Renew registration (simplified - would need proper renewal message)
-                match tokio::net::TcpStream::connect(&registry_address).await {
+                match WindStream::connect_with(&registry_address, &socket_options).await {
                     Ok(mut conn) => {
-                        let renew_msg = Message::new(MessagePayload::RegisterService {
-                            service: service_name.clone(),
-                            address: address.clone(),
-                            service_type: ServiceType::Publisher,
-                            schema_id: None,
-                            ttl_ms,
-                            tags: Vec::new(),
-                        });
+                        if let Some(token) = &token {
+                            if let Err(e) = wind_core::authenticate(&mut conn, token).await {
+                                warn!("Failed to authenticate for heartbeat: {}", e);
+                                continue;
+                            }
+                        }
+                        if let Some(client_id) = &client_id {
+                            let identify = Message::new(MessagePayload::Identify {
+                                client_id: client_id.clone(),
+                            });
+                            if let Err(e) = MessageCodec::write(&mut conn, &identify).await {
+                                warn!("Failed to identify for heartbeat: {}", e);
+                                continue;
+                            }
+                        }
 
-                        if let Err(e) = MessageCodec::write(&mut conn, &renew_msg).await {
-                            warn!("Failed to send heartbeat: {}", e);
-                        } else {
-                            debug!("Sent heartbeat for service '{}'", service_name);
+                        for service_name in &service_names {
+                            let renew_msg = Message::new(MessagePayload::RenewService {
+                                service: service_name.clone(),
+                                address: address.clone(),
+                                ttl_ms,
+                            });
+
+                            // Wait for each reply so the registry has read every
+                            // renewal before the connection closes
+                            let renewed = match MessageCodec::write(&mut conn, &renew_msg).await {
+                                Ok(()) => MessageCodec::decode(&mut conn).await,
+                                Err(e) => Err(e),
+                            };
+                            let rejection = match renewed {
+                                Ok(msg) => match msg.payload {
+                                    MessagePayload::ServiceRenewed { success: true, .. } => {
+                                        debug!("Sent heartbeat for service '{}'", service_name);
+                                        continue;
+                                    }
+                                    MessagePayload::ServiceRenewed { error, .. } => {
+                                        error.unwrap_or_default()
+                                    }
+                                    MessagePayload::Error { error, .. } => error,
+                                    other => format!("unexpected {}", other.name()),
+                                },
+                                Err(e) => {
+                                    warn!("Failed to send heartbeat: {}", e);
+                                    break;
+                                }
+                            };
+
+                            // The registry lost the registration, e.g. it restarted or
+                            // the entry expired, so register it again in full
+                            warn!(
+                                "Renewal of '{}' rejected ({}), registering again",
+                                service_name, rejection
+                            );
+                            let register_msg = Message::new(MessagePayload::RegisterService {
+                                service: service_name.clone(),
+                                address: address.clone(),
+                                service_type: ServiceType::Publisher,
+                                schema_id: schema_id.clone(),
+                                ttl_ms,
+                                tags: tags.clone(),
+                            });
+                            let registered =
+                                match MessageCodec::write(&mut conn, &register_msg).await {
+                                    Ok(()) => MessageCodec::decode(&mut conn).await.map(|_| ()),
+                                    Err(e) => Err(e),
+                                };
+                            if let Err(e) = registered {
+                                warn!("Failed to re-register '{}': {}", service_name, e);
+                                break;
+                            }
                         }
                     }
                     Err(e) => {
@@ -269,46 +1218,191 @@ impl Publisher {
                     }
                 }
             }
-        });
+        })
+    }
+
+    /// Ping every client each liveness interval, if `with_liveness` was set,
+    /// and drop those that stopped answering
+    fn start_liveness_task(&self) -> Option<JoinHandle<()>> {
+        let (ping_interval, max_missed) = self.liveness?;
+        let clients = self.clients.clone();
+
+        Some(tokio::spawn(async move {
+            let mut ping_timer = interval(ping_interval);
+            // The first tick is immediate; nobody has had a ping to answer yet
+            ping_timer.tick().await;
+            loop {
+                ping_timer.tick().await;
+                let mut clients_guard = clients.write().await;
+                let mut dead = Vec::new();
+                for (client_id, client) in clients_guard.iter_mut() {
+                    if client.unanswered_pings >= max_missed {
+                        warn!(
+                            "Client {} missed {} pings, disconnecting",
+                            client_id, client.unanswered_pings
+                        );
+                        dead.push(*client_id);
+                        continue;
+                    }
+                    let ping = Message::new(MessagePayload::Ping);
+                    let codec = MessageCodec::default().with_format(client.format);
+                    if let Err(e) = codec.write_message(&mut client.writer, &ping).await {
+                        warn!("Failed to ping client {}: {}", client_id, e);
+                        dead.push(*client_id);
+                        continue;
+                    }
+                    client.unanswered_pings += 1;
+                }
+                for client_id in dead {
+                    if let Some(client) = clients_guard.remove(&client_id) {
+                        client.listener.abort();
+                    }
+                }
+            }
+        }))
     }
 
-    async fn start_update_sender(&self) {
+    async fn start_update_sender(&self) -> JoinHandle<()> {
         let clients = self.clients.clone();
         let mut update_rx = self.update_tx.subscribe();
-        let sequence_number = self.sequence_number.clone();
+        let throttled = self.throttled.clone();
+        let dropped = self.dropped.clone();
+        let expired = self.expired.clone();
+        let chunk_size = self.chunk_size;
 
         tokio::spawn(async move {
             loop {
-                let new_value = match update_rx.recv().await {
-                    Ok(val) => val,
+                let mut batch = match update_rx.recv().await {
+                    Ok(update) => vec![update],
                     Err(_) => continue, // Channel lagged or closed
                 };
-                let seq = sequence_number.load(Ordering::SeqCst);
+                // Coalesce updates that are already queued into one write per
+                // client; a shallow queue gives a batch of one, sent right away
+                while batch.len() < MAX_BATCH {
+                    match update_rx.try_recv() {
+                        Ok(update) => batch.push(update),
+                        Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                        Err(_) => break,
+                    }
+                }
 
                 let mut clients_guard = clients.write().await;
                 let mut clients_to_remove = Vec::new();
+                // Each update is serialized once per format, on first use, and
+                // the frames shared by every client it goes to in that format,
+                // by index. None = encoding failed.
+                let mut encoded_updates = HashMap::new();
+                // Likewise for runs of `publish_batch` values, by first index and length
+                let mut encoded_runs = HashMap::new();
 
                 for (client_id, client) in clients_guard.iter_mut() {
-                    for (service, subscription) in client.subscriptions.iter_mut() {
-                        if subscription.should_send(Instant::now(), &new_value) {
-                            let publish_msg = Message::new(MessagePayload::Publish {
-                                service: service.clone(),
-                                sequence: seq,
-                                value: new_value.clone(),
-                                schema_id: None,
-                            });
+                    let format = client.format;
+                    let mut frames = Vec::new();
+                    // Updates whose frames are in `frames`, with whether the subscription is reliable
+                    let mut encoded = Vec::new();
+                    // Consecutive batched values for this client not yet in `frames`
+                    let mut run = PackedRun::default();
+                    let mut overflowed = false;
 
-                            match MessageCodec::write(&mut client.stream, &publish_msg).await {
-                                Ok(()) => {
-                                    subscription.mark_sent(Instant::now(), &new_value);
-                                    debug!("Sent update to client {}", client_id);
+                    for (index, update) in batch.iter().enumerate() {
+                        let Some(subscription) = client.subscriptions.get_mut(&update.topic) else {
+                            continue;
+                        };
+                        if update.sequence <= subscription.replayed_through
+                            || !subscription.should_send(Instant::now(), &update.value)
+                        {
+                            continue;
+                        }
+                        if update.is_expired() {
+                            // Stale by the time this subscriber's turn came
+                            expired.fetch_add(1, Ordering::Relaxed);
+                            update.record(|state| state.report.expired += 1);
+                            continue;
+                        }
+                        if client.credit == Some(0) || !subscription.backlog.is_empty() {
+                            // Subscriber has no free buffer; hold the value back
+                            // rather than overrun it
+                            throttled.fetch_add(1, Ordering::Relaxed);
+                            update.record(|state| state.report.throttled += 1);
+                            if !subscription.hold_back(update) {
+                                dropped.fetch_add(1, Ordering::Relaxed);
+                                if subscription.reliable {
+                                    overflowed = true;
+                                    break;
                                 }
-                                Err(e) => {
-                                    warn!("Failed to send to client {}: {}", client_id, e);
-                                    clients_to_remove.push(*client_id);
-                                    break; // Stop trying to send to this broken client
+                            }
+                            continue;
+                        }
+                        let _span = update.span("send", Some(&subscription.mode)).entered();
+                        if !run.continues_with(&batch, update, chunk_size) {
+                            run.flush(&batch, format, &mut encoded_runs, &mut frames, &mut encoded);
+                        }
+                        if update.is_packable(chunk_size) {
+                            run.push(index, update, subscription.reliable);
+                            subscription.mark_sent(Instant::now(), &update.value);
+                            if let Some(credit) = client.credit.as_mut() {
+                                *credit -= 1;
+                            }
+                            continue;
+                        }
+                        let update_frames =
+                            encoded_updates.entry((format, index)).or_insert_with(|| {
+                                Self::encode_update(update, chunk_size, format)
+                                    .inspect_err(|e| {
+                                        warn!("Failed to encode update {}: {}", update.sequence, e)
+                                    })
+                                    .ok()
+                            });
+                        let Some(update_frames) = update_frames else {
+                            update.record(|state| state.report.failed += 1);
+                            continue;
+                        };
+                        frames.push(update_frames.clone());
+
+                        subscription.mark_sent(Instant::now(), &update.value);
+                        if let Some(credit) = client.credit.as_mut() {
+                            *credit -= 1;
+                        }
+                        encoded.push((update, subscription.reliable));
+                    }
+                    run.flush(&batch, format, &mut encoded_runs, &mut frames, &mut encoded);
+                    if overflowed {
+                        warn!(
+                            "Client {} fell more than its queue size behind, disconnecting",
+                            client_id
+                        );
+                        for (update, _) in &encoded {
+                            update.record(|state| state.report.failed += 1);
+                        }
+                        clients_to_remove.push(*client_id);
+                        continue;
+                    }
+                    if encoded.is_empty() {
+                        continue;
+                    }
+
+                    let written = Self::write_frames(&mut client.writer, &frames).await;
+                    match written {
+                        Ok(()) => {
+                            for (update, reliable) in &encoded {
+                                match &update.confirmation {
+                                    Some(confirmation) if *reliable => {
+                                        confirmation.record(|state| state.awaiting_acks += 1);
+                                        client
+                                            .pending_acks
+                                            .insert(update.sequence, confirmation.clone());
+                                    }
+                                    _ => update.record(|state| state.report.delivered += 1),
                                 }
                             }
+                            debug!("Sent {} updates to client {}", encoded.len(), client_id);
+                        }
+                        Err(e) => {
+                            warn!("Failed to send to client {}: {}", client_id, e);
+                            for (update, _) in &encoded {
+                                update.record(|state| state.report.failed += 1);
+                            }
+                            clients_to_remove.push(*client_id);
                         }
                     }
                 }
@@ -317,65 +1411,675 @@ impl Publisher {
                     clients_guard.remove(&client_id);
                     info!("Removed disconnected client {}", client_id);
                 }
+                for update in &batch {
+                    update.record(|state| state.sending = false);
+                }
             }
-        });
+        })
     }
 
-    async fn spawn_client_listener(&self, client_id: Uuid) {
-        let clients = self.clients.clone();
-        let current_value = self.current_value.clone();
+    /// Encode the frames for one update: a single `Publish`, or a series of
+    /// `PublishChunk`s for a `Bytes` payload over `chunk_size`
+    fn encode_update(
+        update: &Update,
+        chunk_size: usize,
+        format: SerializationFormat,
+    ) -> Result<Bytes> {
+        let codec = MessageCodec::default().with_format(format);
+        let mut frames = Vec::new();
+        let bytes = match update.value.as_ref() {
+            WindValue::Bytes(bytes) if bytes.len() > chunk_size => bytes,
+            _ => {
+                // Checked before the value is copied into a message the codec
+                // would only reject
+                let size = update.value.encoded_size();
+                if size > MAX_MESSAGE_SIZE {
+                    return Err(WindError::Protocol(format!(
+                        "Value too large to publish: {} bytes",
+                        size
+                    )));
+                }
+                let publish_msg = Message::new(MessagePayload::Publish {
+                    service: update.topic.clone(),
+                    sequence: update.sequence,
+                    value: update.value.clone(),
+                    schema_id: None,
+                    expires_at_us: update.expires_at_us,
+                })
+                .with_id(update.message_id);
+                codec.encode_message_into(&mut frames, &publish_msg)?;
+                return Ok(frames.into());
+            }
+        };
 
-        tokio::spawn(async move {
-            // The stream is inside the client map, so we need to lock it to read
-            // This is a bit tricky. A better design would be to pass the stream here.
-            // For now, we'll read one message and then the update loop takes over.
-            let mut clients_guard = clients.write().await;
-            let client = if let Some(c) = clients_guard.get_mut(&client_id) {
-                c
-            } else {
-                return; // Client disconnected before we could handle it
+        let chunk_count = bytes.len().div_ceil(chunk_size);
+        for (chunk_index, chunk) in bytes.chunks(chunk_size).enumerate() {
+            let chunk_msg = Message::new(MessagePayload::PublishChunk {
+                service: update.topic.clone(),
+                sequence: update.sequence,
+                chunk_index: chunk_index as u32,
+                last: chunk_index + 1 == chunk_count,
+                bytes: chunk.to_vec(),
+                expires_at_us: update.expires_at_us,
+            });
+            codec.encode_message_into(&mut frames, &chunk_msg)?;
+        }
+        Ok(frames.into())
+    }
+
+    /// Encode a single `PublishBatch` frame for consecutive values of a topic
+    fn encode_batch(updates: &[&Update], format: SerializationFormat) -> Result<Bytes> {
+        let batch_msg = Message::new(MessagePayload::PublishBatch {
+            service: updates[0].topic.clone(),
+            start_sequence: updates[0].sequence,
+            values: updates
+                .iter()
+                .map(|update| (*update.value).clone())
+                .collect(),
+        })
+        .with_id(updates[0].message_id);
+        let mut frame = Vec::new();
+        MessageCodec::default()
+            .with_format(format)
+            .encode_message_into(&mut frame, &batch_msg)?;
+        Ok(frame.into())
+    }
+
+    /// Encode the `ReplayBatch` frames for `values`, split so that no frame
+    /// is much over `REPLAY_BATCH_BYTES` and compressed if large
+    fn encode_replay(values: &[&Retained], format: SerializationFormat) -> Result<Vec<u8>> {
+        let codec = MessageCodec::with_compression(Codec::Lz4, REPLAY_COMPRESSION_BYTES)
+            .with_format(format);
+        let mut frames = Vec::new();
+        let mut batch = Vec::new();
+        let mut batch_bytes = 0;
+        for (index, retained) in values.iter().enumerate() {
+            batch_bytes += retained.value.encoded_size();
+            batch.push((retained.sequence, (*retained.value).clone()));
+            if batch_bytes >= REPLAY_BATCH_BYTES || index + 1 == values.len() {
+                let replay_msg = Message::new(MessagePayload::ReplayBatch {
+                    from_seq: batch[0].0,
+                    values: std::mem::take(&mut batch),
+                });
+                codec.encode_message_into(&mut frames, &replay_msg)?;
+                batch_bytes = 0;
+            }
+        }
+        Ok(frames)
+    }
+
+    /// Write a client's share of a batch. Small frames are coalesced into one
+    /// write; large ones are written straight from the shared encoding.
+    async fn write_frames(writer: &mut WriteHalf<ClientStream>, frames: &[Bytes]) -> Result<()> {
+        if let [single] = frames {
+            return MessageCodec::write_encoded(writer, single).await;
+        }
+        let mut coalesced = Vec::new();
+        for frame in frames {
+            if frame.len() < DIRECT_WRITE_BYTES {
+                coalesced.extend_from_slice(frame);
+                continue;
+            }
+            if !coalesced.is_empty() {
+                MessageCodec::write_encoded(writer, &coalesced).await?;
+                coalesced.clear();
+            }
+            MessageCodec::write_encoded(writer, frame).await?;
+        }
+        if !coalesced.is_empty() {
+            MessageCodec::write_encoded(writer, &coalesced).await?;
+        }
+        Ok(())
+    }
+
+    /// Give each of the client's `Periodic` subscriptions that lacks one a
+    /// timer re-sending the current value
+    fn start_periodic_timers(
+        client_id: Uuid,
+        client: &mut ActiveClient,
+        clients: &Arc<RwLock<HashMap<Uuid, ActiveClient>>>,
+        topics: &Arc<RwLock<HashMap<String, Topic>>>,
+        chunk_size: usize,
+    ) {
+        for (service, subscription) in client.subscriptions.iter_mut() {
+            let running = subscription
+                .periodic_timer
+                .as_ref()
+                .is_some_and(|timer| !timer.0.is_finished());
+            if running || !matches!(subscription.mode, SubscriptionMode::Periodic { .. }) {
+                continue;
+            }
+            let resender = Self::periodic_resender(
+                client_id,
+                service.clone(),
+                clients.clone(),
+                topics.clone(),
+                chunk_size,
+            );
+            subscription.periodic_timer =
+                Some(PeriodicTimer(tokio::spawn(resender).abort_handle()));
+        }
+    }
+
+    /// Send a `Periodic` subscription the current value once an interval
+    /// passes without anything being sent, whether or not it changed. Ends
+    /// once the subscription is gone or no longer periodic.
+    async fn periodic_resender(
+        client_id: Uuid,
+        service: String,
+        clients: Arc<RwLock<HashMap<Uuid, ActiveClient>>>,
+        topics: Arc<RwLock<HashMap<String, Topic>>>,
+        chunk_size: usize,
+    ) {
+        let mut ticked_at = Instant::now();
+        loop {
+            let due = {
+                let clients_guard = clients.read().await;
+                let Some(subscription) = clients_guard
+                    .get(&client_id)
+                    .and_then(|client| client.subscriptions.get(&service))
+                else {
+                    return;
+                };
+                let SubscriptionMode::Periodic { interval_ms } = subscription.mode else {
+                    return;
+                };
+                let last = subscription
+                    .last_sent_at
+                    .map_or(ticked_at, |sent| sent.max(ticked_at));
+                last + Duration::from_millis(interval_ms)
             };
+            tokio::time::sleep_until(due).await;
+            ticked_at = Instant::now();
 
-            let msg = match MessageCodec::decode(&mut client.stream).await {
-                Ok(m) => m,
+            let current = topics.read().await.get(&service).and_then(|topic| {
+                Some(Update {
+                    topic: service.clone(),
+                    sequence: topic.sequence,
+                    // A fresh message re-sending the current value
+                    message_id: Uuid::new_v4(),
+                    value: topic.live_value()?.clone(),
+                    expires_at_us: topic.expires_at_us,
+                    confirmation: None,
+                    batched: false,
+                })
+            });
+            let mut clients_guard = clients.write().await;
+            let Some(client) = clients_guard.get_mut(&client_id) else {
+                return;
+            };
+            let Some(subscription) = client.subscriptions.get_mut(&service) else {
+                return;
+            };
+            let Some(update) = current else {
+                continue;
+            };
+            // Skip the tick if a publish went out meanwhile, or there's no room
+            if !subscription.should_send(ticked_at, &update.value)
+                || client.credit == Some(0)
+                || !subscription.backlog.is_empty()
+            {
+                continue;
+            }
+            let encoded = update
+                .span("send", Some(&subscription.mode))
+                .in_scope(|| Self::encode_update(&update, chunk_size, client.format));
+            let frames = match encoded {
+                Ok(frames) => frames,
                 Err(e) => {
-                    warn!(
-                        "Failed to decode subscription from client {}: {}",
-                        client_id, e
-                    );
-                    clients_guard.remove(&client_id);
-                    return;
+                    warn!("Failed to encode update {}: {}", update.sequence, e);
+                    continue;
                 }
             };
+            if let Err(e) = MessageCodec::write_encoded(&mut client.writer, &frames).await {
+                warn!(
+                    "Failed to send periodic update to client {}: {}",
+                    client_id, e
+                );
+                return;
+            }
+            subscription.mark_sent(ticked_at, &update.value);
+            if let Some(credit) = client.credit.as_mut() {
+                *credit -= 1;
+            }
+        }
+    }
+
+    fn client_listener(
+        &self,
+        client_id: Uuid,
+        mut reader: ReadHalf<ClientStream>,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        let clients = self.clients.clone();
+        let topics = self.topics.clone();
+        let service_name = self.service_name.clone();
+        let schema_id = self.schema_id.clone();
+        let max_per_client = self.max_subscriptions_per_client;
+        let max_total = self.max_total_subscriptions;
+        let diagnostic_clients = self.diagnostic_clients.clone();
+        let sequence_number = self.sequence_number.clone();
+        let traffic = self.traffic.clone();
+        let throttled = self.throttled.clone();
+        let dropped = self.dropped.clone();
+        let expired = self.expired.clone();
+        let chunk_size = self.chunk_size;
+        let mut auth =
+            TokenAuth::new(self.token.clone()).with_client_tokens(self.client_tokens.clone());
+
+        async move {
+            let mut identity: Option<String> = None;
+            loop {
+                let (msg, format) = match MessageCodec::decode_with_format(&mut reader).await {
+                    Ok(decoded) => decoded,
+                    Err(WindError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                        debug!("Client {} disconnected", client_id);
+                        break;
+                    }
+                    Err(e) => {
+                        warn!("Failed to decode message from client {}: {}", client_id, e);
+                        break;
+                    }
+                };
+                // Reply in whatever format the client chose
+                let codec = MessageCodec::default().with_format(format);
+
+                let (reply, refused) = match auth.check(&msg.payload) {
+                    AuthCheck::Proceed => (None, false),
+                    AuthCheck::Accepted(reply) => (Some(reply), false),
+                    AuthCheck::Rejected(reply) => (Some(reply), true),
+                };
+                if let Some(reply) = reply {
+                    if refused {
+                        warn!(
+                            "Refused unauthenticated {} from client {}",
+                            msg.payload.name(),
+                            client_id
+                        );
+                    }
+                    if let Some(id) = auth.identity() {
+                        identity = Some(id.to_string());
+                    }
+                    let sent = match clients.write().await.get_mut(&client_id) {
+                        Some(client) => {
+                            client.identity.clone_from(&identity);
+                            client.format = format;
+                            codec.write_message(&mut client.writer, &reply).await
+                        }
+                        None => return, // Client was removed by the update sender
+                    };
+                    if let Err(e) = sent {
+                        warn!("Failed to answer Authenticate from {}: {}", client_id, e);
+                        break;
+                    }
+                    if refused {
+                        break;
+                    }
+                    continue;
+                }
+
+                // Reads must name one of our services and pass its ACL
+                let rejection = match &msg.payload {
+                    MessagePayload::Subscribe { service, .. }
+                    | MessagePayload::GetCurrent { service } => {
+                        match topics.read().await.get(service) {
+                            None => Some((
+                                service.clone(),
+                                format!("Service {} is not served here", service),
+                            )),
+                            Some(topic)
+                                if topic.allowed_subscribers.as_ref().is_some_and(|allowed| {
+                                    !identity.as_ref().is_some_and(|id| allowed.contains(id))
+                                }) =>
+                            {
+                                Some((
+                                    service.clone(),
+                                    format!(
+                                        "Permission denied: {} may not subscribe to {}",
+                                        identity.as_deref().unwrap_or("anonymous client"),
+                                        service
+                                    ),
+                                ))
+                            }
+                            Some(_) => None,
+                        }
+                    }
+                    _ => None,
+                };
 
-            match msg.payload {
-                MessagePayload::Subscribe { service, mode, .. } => {
-                    client
-                        .subscriptions
-                        .insert(service, ClientSubscription::new(mode));
-
-                    let ack = Message::new(MessagePayload::SubscribeAck {
-                        subscription_id: client_id,
-                        success: true,
-                        error: None,
-                        current_value: current_value.read().await.clone(),
+                let mut clients_guard = clients.write().await;
+                let total_subscriptions: usize = match msg.payload {
+                    MessagePayload::Subscribe { .. } => clients_guard
+                        .values()
+                        .map(|client| client.subscriptions.len())
+                        .sum(),
+                    _ => 0,
+                };
+                // Snapshot taken before `client` borrows the map; the asking
+                // connection itself isn't listed
+                let allowed_dump = identity
+                    .as_ref()
+                    .is_some_and(|id| diagnostic_clients.contains(id));
+                let diagnostics = match msg.payload {
+                    MessagePayload::DiagnosticDump if allowed_dump => {
+                        let mut services: Vec<_> = topics.read().await.keys().cloned().collect();
+                        services.sort();
+                        Some(PublisherDiagnostics {
+                            services,
+                            sequence: sequence_number.load(Ordering::SeqCst),
+                            clients: clients_guard
+                                .iter()
+                                .filter(|(id, _)| **id != client_id)
+                                .map(|(id, client)| client.diagnostics(*id))
+                                .collect(),
+                            bytes_in: traffic.bytes_in(),
+                            bytes_out: traffic.bytes_out(),
+                            throttled: throttled.load(Ordering::Relaxed),
+                            dropped: dropped.load(Ordering::Relaxed),
+                            expired: expired.load(Ordering::Relaxed),
+                        })
+                    }
+                    _ => None,
+                };
+                let client = if let Some(c) = clients_guard.get_mut(&client_id) {
+                    c
+                } else {
+                    return; // Client was removed by the update sender
+                };
+                client.format = format;
+
+                if let Some((service, error)) = rejection {
+                    warn!("Denied subscription from {:?}: {}", identity, error);
+                    let error = Message::new(MessagePayload::Error {
+                        error,
+                        context: Some(format!("Subscribing to service: {}", service)),
                     });
+                    if let Err(e) = codec.write_message(&mut client.writer, &error).await {
+                        warn!("Failed to send Error to client {}: {}", client_id, e);
+                    }
+                    break;
+                }
 
-                    if let Err(e) = MessageCodec::write(&mut client.stream, &ack).await {
-                        warn!("Failed to send SubscribeAck to client {}: {}", client_id, e);
-                        clients_guard.remove(&client_id);
-                    } else {
+                match msg.payload {
+                    MessagePayload::Identify { client_id: id } => {
+                        client.identity = Some(id.clone());
+                        identity = Some(id);
+                    }
+                    MessagePayload::Subscribe {
+                        service,
+                        mode,
+                        qos,
+                        schema_id: expected_schema,
+                        replay,
+                    } => {
+                        if let Some(error) =
+                            schema_mismatch(expected_schema.as_deref(), schema_id.as_deref())
+                        {
+                            warn!("Refused subscription from {}: {}", client_id, error);
+                            let ack = Message::new(MessagePayload::SubscribeAck {
+                                subscription_id: Uuid::nil(),
+                                success: false,
+                                error: Some(error),
+                                current_value: None,
+                                schema_id: schema_id.clone(),
+                            });
+                            if let Err(e) = codec.write_message(&mut client.writer, &ack).await {
+                                warn!("Failed to send SubscribeAck to client {}: {}", client_id, e);
+                            }
+                            break;
+                        }
+
+                        // Resubscribing replaces a subscription, so only new ones count
+                        let limit = if client.subscriptions.contains_key(&service) {
+                            None
+                        } else if client.subscriptions.len() >= max_per_client {
+                            Some(format!(
+                                "Subscription limit reached: at most {} per client",
+                                max_per_client
+                            ))
+                        } else if total_subscriptions >= max_total {
+                            Some(format!(
+                                "Subscription limit reached: at most {} in total",
+                                max_total
+                            ))
+                        } else {
+                            None
+                        };
+                        if let Some(error) = limit {
+                            warn!("Refused subscription from {}: {}", client_id, error);
+                            let ack = Message::new(MessagePayload::SubscribeAck {
+                                subscription_id: Uuid::nil(),
+                                success: false,
+                                error: Some(error),
+                                current_value: None,
+                                schema_id: schema_id.clone(),
+                            });
+                            if let Err(e) = codec.write_message(&mut client.writer, &ack).await {
+                                warn!("Failed to send SubscribeAck to client {}: {}", client_id, e);
+                                break;
+                            }
+                            // The client's existing subscriptions carry on
+                            continue;
+                        }
+
+                        // Evaluate the current value now instead of waiting for the
+                        // next publish; a value delivered with the ack counts as sent.
+                        // A replay ends with the current value, so replaces it.
+                        let mut subscription = ClientSubscription::new(mode, &qos);
+                        let topics_guard = topics.read().await;
+                        let topic = topics_guard.get(&service);
+                        let replayed = topic
+                            .map(|topic| topic.replay(replay as usize))
+                            .unwrap_or_default();
+                        let current = match replayed.last() {
+                            Some(last) => {
+                                subscription.mark_sent(Instant::now(), &last.value);
+                                subscription.replayed_through = last.sequence;
+                                None
+                            }
+                            // Only a durable subscription gets the value
+                            // published before it joined
+                            None if qos.durability => topic
+                                .and_then(|topic| {
+                                    Some((topic.sequence, topic.live_value()?.clone()))
+                                })
+                                .filter(|(_, value)| {
+                                    subscription.should_send(Instant::now(), value)
+                                })
+                                .map(|(sequence, value)| {
+                                    // Its live update may still be on the way
+                                    subscription.replayed_through = sequence;
+                                    value
+                                }),
+                            None => None,
+                        };
+                        if let Some(value) = &current {
+                            subscription.mark_sent(Instant::now(), value);
+                        }
+                        let current = current.as_deref().cloned();
+                        let replay_frames = Self::encode_replay(&replayed, format);
+                        drop(topics_guard);
+                        let subscription_id = subscription.id;
+                        client.subscriptions.insert(service, subscription);
+                        Self::start_periodic_timers(
+                            client_id, client, &clients, &topics, chunk_size,
+                        );
+
+                        let ack = Message::new(MessagePayload::SubscribeAck {
+                            subscription_id,
+                            success: true,
+                            error: None,
+                            current_value: current,
+                            schema_id: schema_id.clone(),
+                        });
+
+                        if let Err(e) = codec.write_message(&mut client.writer, &ack).await {
+                            warn!("Failed to send SubscribeAck to client {}: {}", client_id, e);
+                            break;
+                        }
+                        // Written while holding the client, so ahead of any live update
+                        let replayed = match replay_frames {
+                            Ok(frames) if frames.is_empty() => Ok(()),
+                            Ok(frames) => {
+                                MessageCodec::write_encoded(&mut client.writer, &frames).await
+                            }
+                            Err(e) => Err(e),
+                        };
+                        if let Err(e) = replayed {
+                            warn!("Failed to send replay to client {}: {}", client_id, e);
+                            break;
+                        }
                         info!("Client {} subscribed successfully", client_id);
                     }
-                }
-                _ => {
-                    warn!(
-                        "Unexpected message from client {}: {:?}",
-                        client_id, msg.payload
-                    );
-                    clients_guard.remove(&client_id);
+                    MessagePayload::ModifySubscription {
+                        subscription_id,
+                        mode,
+                        qos,
+                    } => {
+                        let result = match client
+                            .subscriptions
+                            .values_mut()
+                            .find(|subscription| subscription.id == subscription_id)
+                        {
+                            Some(subscription) => subscription.modify(mode.clone(), &qos),
+                            None => Err(format!("Unknown subscription {}", subscription_id)),
+                        };
+                        debug!(
+                            "Client {} changed mode to {:?}: {:?}",
+                            client_id, mode, result
+                        );
+                        Self::start_periodic_timers(
+                            client_id, client, &clients, &topics, chunk_size,
+                        );
+
+                        let ack = Message::new(MessagePayload::SubscribeAck {
+                            subscription_id,
+                            success: result.is_ok(),
+                            error: result.err(),
+                            current_value: None,
+                            schema_id: schema_id.clone(),
+                        });
+                        if let Err(e) = codec.write_message(&mut client.writer, &ack).await {
+                            warn!("Failed to send SubscribeAck to client {}: {}", client_id, e);
+                            break;
+                        }
+                    }
+                    MessagePayload::GetCurrent { service } => {
+                        let (value, sequence) = match topics.read().await.get(&service) {
+                            Some(topic) => {
+                                (topic.live_value().map(|v| (**v).clone()), topic.sequence)
+                            }
+                            None => (None, 0),
+                        };
+                        let reply = Message::new(MessagePayload::CurrentValue { value, sequence });
+
+                        if let Err(e) = codec.write_message(&mut client.writer, &reply).await {
+                            warn!("Failed to send CurrentValue to client {}: {}", client_id, e);
+                        }
+                        // One-off read: the client never becomes a subscriber
+                        break;
+                    }
+                    MessagePayload::DiagnosticDump => {
+                        let reply = match diagnostics {
+                            Some(diagnostics) => {
+                                info!("Client {} read a diagnostic dump", client_id);
+                                MessagePayload::DiagnosticReport { diagnostics }
+                            }
+                            None => {
+                                warn!("Denied diagnostic dump to {:?}", identity);
+                                MessagePayload::Error {
+                                    error: format!(
+                                        "Permission denied: {} may not inspect {}",
+                                        identity.as_deref().unwrap_or("anonymous client"),
+                                        service_name
+                                    ),
+                                    context: Some(format!("Inspecting service: {}", service_name)),
+                                }
+                            }
+                        };
+                        if let Err(e) = codec
+                            .write_message(&mut client.writer, &Message::new(reply))
+                            .await
+                        {
+                            warn!(
+                                "Failed to send DiagnosticReport to client {}: {}",
+                                client_id, e
+                            );
+                        }
+                        break;
+                    }
+                    MessagePayload::Unsubscribe { subscription_id } => {
+                        let before = client.subscriptions.len();
+                        client
+                            .subscriptions
+                            .retain(|_, subscription| subscription.id != subscription_id);
+                        if client.subscriptions.len() == before {
+                            warn!(
+                                "Client {} unsubscribed from unknown subscription {}",
+                                client_id, subscription_id
+                            );
+                        } else if client.subscriptions.is_empty() {
+                            // Closing the connection tells the subscriber it is gone
+                            info!("Client {} unsubscribed", client_id);
+                            break;
+                        } else {
+                            info!(
+                                "Client {} dropped subscription {}",
+                                client_id, subscription_id
+                            );
+                        }
+                    }
+                    MessagePayload::FlowControl { available_window } => {
+                        debug!(
+                            "Client {} advertised window {}",
+                            client_id, available_window
+                        );
+                        client.credit = Some(available_window as u64);
+                        if let Err(e) = client.send_backlog(chunk_size, &expired).await {
+                            warn!(
+                                "Failed to send held-back updates to client {}: {}",
+                                client_id, e
+                            );
+                            break;
+                        }
+                    }
+                    MessagePayload::PublishAck { sequence } => {
+                        if let Some(confirmation) = client.pending_acks.remove(&sequence) {
+                            confirmation.acknowledged(true);
+                        }
+                    }
+                    MessagePayload::Ping => {
+                        let pong = Message::new(MessagePayload::Pong);
+                        if let Err(e) = codec.write_message(&mut client.writer, &pong).await {
+                            warn!("Failed to send Pong to client {}: {}", client_id, e);
+                            break;
+                        }
+                    }
+                    MessagePayload::Pong => client.unanswered_pings = 0,
+                    MessagePayload::RpcCall { .. } => {
+                        warn!("Client {} sent an RpcCall to a publisher", client_id);
+                        // Answer as an RPC response so the caller's pending call fails
+                        let response = Message::new(MessagePayload::RpcResponse {
+                            call_id: msg.id,
+                            result: Err(msg
+                                .payload
+                                .unsupported_by(&service_name, ServiceType::Publisher)),
+                            schema_id: None,
+                        });
+                        if let Err(e) = codec.write_message(&mut client.writer, &response).await {
+                            warn!("Failed to send RpcResponse to client {}: {}", client_id, e);
+                            break;
+                        }
+                    }
+                    _ => {
+                        warn!(
+                            "Unexpected message from client {}: {:?}",
+                            client_id, msg.payload
+                        );
+                    }
                 }
             }
-        });
+
+            clients.write().await.remove(&client_id);
+        }
     }
 }
@@ -1,12 +1,20 @@
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
 use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::RwLock;
+use tokio::sync::{watch, Mutex, RwLock};
+use tokio::task::JoinSet;
+use tokio::time::Duration;
 use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 
-use wind_core::{Message, MessageCodec, MessagePayload, Result, ServiceType, WindError, WindValue};
+use wind_core::{
+    AuthCheck, Message, MessageCodec, MessagePayload, OversizedFrame, Result, ServiceType,
+    SocketOptions, TokenAuth, WindError, WindListener, WindStream, WindValue,
+};
 
 /// RPC method handler trait - using Box<dyn Fn> instead of async trait for object safety
 pub type RpcHandlerFn =
@@ -56,15 +64,75 @@ where
     }
 }
 
+/// Items of a client-streaming call, ending when the client ends the stream
+pub type RpcItemStream = futures::stream::BoxStream<'static, WindValue>;
+
+/// Handler for a client-streaming RPC method: consumes the client's items and
+/// returns a single result
+pub trait RpcStreamHandler: Send + Sync {
+    fn handle(
+        &self,
+        items: RpcItemStream,
+    ) -> Pin<Box<dyn Future<Output = Result<WindValue>> + Send + '_>>;
+}
+
+/// Function-based client-streaming RPC handler
+pub struct StreamFunctionHandler<F> {
+    handler: F,
+}
+
+impl<F> StreamFunctionHandler<F> {
+    pub fn new(handler: F) -> Self {
+        Self { handler }
+    }
+}
+
+impl<F, Fut> RpcStreamHandler for StreamFunctionHandler<F>
+where
+    F: Fn(RpcItemStream) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<WindValue>> + Send + 'static,
+{
+    fn handle(
+        &self,
+        items: RpcItemStream,
+    ) -> Pin<Box<dyn Future<Output = Result<WindValue>> + Send + '_>> {
+        Box::pin((self.handler)(items))
+    }
+}
+
+type StreamMethods = Arc<RwLock<HashMap<String, Arc<dyn RpcStreamHandler>>>>;
+
+/// Items buffered per client-streaming call before reading from the client
+/// waits for the handler
+const STREAM_ITEM_BUFFER: usize = 64;
+
+/// How long in-flight RPC connections get to finish after shutdown is requested
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default cap on the encoded size of a call's params
+pub const DEFAULT_MAX_RPC_PARAM_BYTES: usize = 1024 * 1024;
+
+/// Frame bytes allowed on top of the params limit for the rest of an `RpcCall`
+/// (ids, service and method names)
+const RPC_ENVELOPE_BYTES: usize = 64 * 1024;
+
 /// RPC server for handling remote procedure calls
 pub struct RpcServer {
     service_name: String,
     bind_address: String,
     registry_address: String,
     schema_id: Option<String>,
+    client_id: Option<String>,
+    token: Option<String>,
     methods: Arc<RwLock<HashMap<String, Arc<dyn RpcHandler>>>>,
+    stream_methods: StreamMethods,
+    max_rpc_param_bytes: usize,
     ttl_ms: u64,
     tags: Vec<String>,
+    socket_options: SocketOptions,
+    accepted_connections: AtomicU64,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<wind_core::rustls::ServerConfig>>,
 }
 
 impl RpcServer {
@@ -75,9 +143,17 @@ impl RpcServer {
             bind_address,
             registry_address,
             schema_id: None,
+            client_id: None,
+            token: None,
             methods: Arc::new(RwLock::new(HashMap::new())),
+            stream_methods: Arc::new(RwLock::new(HashMap::new())),
+            max_rpc_param_bytes: DEFAULT_MAX_RPC_PARAM_BYTES,
             ttl_ms: 60000,
             tags: Vec::new(),
+            socket_options: SocketOptions::default(),
+            accepted_connections: AtomicU64::new(0),
+            #[cfg(feature = "tls")]
+            tls: None,
         }
     }
 
@@ -87,6 +163,48 @@ impl RpcServer {
         self
     }
 
+    /// Identify to the registry as `client_id`, for registration ACLs
+    pub fn with_client_id(mut self, client_id: String) -> Self {
+        self.client_id = Some(client_id);
+        self
+    }
+
+    /// Refuse callers until they `Authenticate` with `token`, and present it
+    /// to the registry
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// Reject calls whose params encode to more than this many bytes, before
+    /// they are deserialized
+    pub fn with_max_rpc_param_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_rpc_param_bytes = max_bytes;
+        self
+    }
+
+    /// TCP options for accepted connections and the registry connection
+    pub fn with_socket_options(mut self, options: SocketOptions) -> Self {
+        self.socket_options = options;
+        self
+    }
+
+    /// Accept callers over TLS only, registering a `tls://` address
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, config: wind_core::rustls::ServerConfig) -> Self {
+        self.tls = Some(Arc::new(config));
+        self
+    }
+
+    /// Bind `bind_address`, over TLS when `with_tls` was given a config
+    async fn bind(&self) -> std::io::Result<WindListener> {
+        #[cfg(feature = "tls")]
+        if let Some(config) = &self.tls {
+            return WindListener::bind_tls(&self.bind_address, config.clone()).await;
+        }
+        WindListener::bind(&self.bind_address).await
+    }
+
     /// Register an RPC method with a handler
     pub async fn register_method<H>(&self, method_name: String, handler: H) -> Result<()>
     where
@@ -108,10 +226,50 @@ impl RpcServer {
             .await
     }
 
+    /// Register a client-streaming RPC method with a handler
+    pub async fn register_stream_method<H>(&self, method_name: String, handler: H) -> Result<()>
+    where
+        H: RpcStreamHandler + 'static,
+    {
+        let mut methods = self.stream_methods.write().await;
+        methods.insert(method_name.clone(), Arc::new(handler));
+        info!("Registered client-streaming RPC method: {}", method_name);
+        Ok(())
+    }
+
+    /// Register a function consuming a stream of items as a client-streaming
+    /// RPC method
+    pub async fn register_stream_function<F, Fut>(
+        &self,
+        method_name: String,
+        handler: F,
+    ) -> Result<()>
+    where
+        F: Fn(RpcItemStream) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<WindValue>> + Send + 'static,
+    {
+        self.register_stream_method(method_name, StreamFunctionHandler::new(handler))
+            .await
+    }
+
+    /// Number of client connections accepted since the server started
+    pub fn accepted_connections(&self) -> u64 {
+        self.accepted_connections.load(Ordering::Relaxed)
+    }
+
     /// Start the RPC server
     pub async fn start(&self) -> Result<()> {
-        let listener = TcpListener::bind(&self.bind_address).await?;
-        let actual_address = listener.local_addr()?.to_string();
+        self.start_until(std::future::pending()).await
+    }
+
+    /// Start the RPC server and run until `shutdown` resolves, then stop
+    /// accepting, let in-flight calls finish and return.
+    pub async fn start_until<F>(&self, shutdown: F) -> Result<()>
+    where
+        F: Future<Output = ()>,
+    {
+        let listener = self.bind().await?;
+        let actual_address = listener.local_address()?;
 
         info!(
             "RPC Server '{}' listening on {}",
@@ -121,27 +279,65 @@ impl RpcServer {
         // Register with the registry
         self.register_service(&actual_address).await?;
 
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let mut clients = JoinSet::new();
+        tokio::pin!(shutdown);
+
         // Accept and handle client connections
         loop {
-            match listener.accept().await {
-                Ok((stream, addr)) => {
-                    info!("New RPC client connected: {}", addr);
-                    let methods = self.methods.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = Self::handle_client(methods, stream).await {
-                            error!("RPC client {} error: {}", addr, e);
-                        }
-                    });
-                }
-                Err(e) => {
-                    error!("Failed to accept RPC connection: {}", e);
-                }
+            tokio::select! {
+                _ = &mut shutdown => break,
+                accepted = listener.accept_with(&self.socket_options) => match accepted {
+                    Ok((stream, addr)) => {
+                        info!("New RPC client connected: {}", addr);
+                        self.accepted_connections.fetch_add(1, Ordering::Relaxed);
+                        let methods = self.methods.clone();
+                        let stream_methods = self.stream_methods.clone();
+                        let service_name = self.service_name.clone();
+                        let shutdown_rx = shutdown_rx.clone();
+                        let max_frame = self.max_rpc_param_bytes.saturating_add(RPC_ENVELOPE_BYTES);
+                        let auth = TokenAuth::new(self.token.clone());
+                        clients.spawn(async move {
+                            if let Err(e) = Self::handle_client(service_name, methods, stream_methods, auth, stream, max_frame, shutdown_rx).await {
+                                error!("RPC client {} error: {}", addr, e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("Failed to accept RPC connection: {}", e);
+                    }
+                },
             }
         }
+
+        info!("RPC Server '{}' shutting down", self.service_name);
+        drop(listener);
+        let _ = shutdown_tx.send(true);
+
+        let drained = tokio::time::timeout(DRAIN_TIMEOUT, async {
+            while clients.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            warn!("Drain timed out, aborting {} RPC client(s)", clients.len());
+            clients.abort_all();
+        }
+
+        Ok(())
     }
 
     async fn register_service(&self, actual_address: &str) -> Result<()> {
-        let mut registry_conn = tokio::net::TcpStream::connect(&self.registry_address).await?;
+        let mut registry_conn =
+            WindStream::connect_with(&self.registry_address, &self.socket_options).await?;
+        if let Some(token) = &self.token {
+            wind_core::authenticate(&mut registry_conn, token).await?;
+        }
+        if let Some(client_id) = &self.client_id {
+            let identify = Message::new(MessagePayload::Identify {
+                client_id: client_id.clone(),
+            });
+            MessageCodec::write(&mut registry_conn, &identify).await?;
+        }
 
         let register_msg = Message::new(MessagePayload::RegisterService {
             service: self.service_name.clone(),
@@ -169,6 +365,7 @@ impl RpcServer {
                     ))
                 }
             }
+            MessagePayload::Error { error, .. } => Err(WindError::Registry(error)),
             _ => Err(WindError::Protocol(
                 "Unexpected registry response".to_string(),
             )),
@@ -176,18 +373,74 @@ impl RpcServer {
     }
 
     async fn handle_client(
+        service_name: String,
         methods: Arc<RwLock<HashMap<String, Arc<dyn RpcHandler>>>>,
-        mut stream: TcpStream,
+        stream_methods: StreamMethods,
+        mut auth: TokenAuth,
+        stream: WindStream,
+        max_frame: usize,
+        mut shutdown: watch::Receiver<bool>,
     ) -> Result<()> {
-        loop {
-            let request = match MessageCodec::decode(&mut stream).await {
-                Ok(msg) => msg,
+        // Handlers answer from their own tasks, so the write half is shared and
+        // a slow call doesn't hold up the ones pipelined behind it
+        let (mut reader, writer) = tokio::io::split(stream);
+        let writer = Arc::new(Mutex::new(writer));
+        let mut open_streams: HashMap<Uuid, mpsc::Sender<WindValue>> = HashMap::new();
+        let mut calls = JoinSet::new();
+        // Answers go out in the format of the client's latest request
+        let mut codec = MessageCodec::default();
+        let result = loop {
+            // Forget calls that have answered, so a long-lived connection doesn't pile them up
+            while calls.try_join_next().is_some() {}
+            let decoded = tokio::select! {
+                decoded = MessageCodec::decode_within(&mut reader, max_frame) => decoded,
+                _ = shutdown.changed() => break Ok(()),
+            };
+            let request = match decoded {
+                Ok(Ok((msg, format))) => {
+                    codec = MessageCodec::default().with_format(format);
+                    msg
+                }
+                Ok(Err(OversizedFrame { len, id })) => {
+                    // Only the header was read; a frame too compressed to
+                    // show its id gets a nil one, which no call will claim
+                    warn!("Rejected oversized RPC request of {} bytes", len);
+                    let response = Message::new(MessagePayload::RpcResponse {
+                        call_id: id.unwrap_or_else(Uuid::nil),
+                        result: Err(format!(
+                            "InvalidParams: params too large ({} byte request, limit {} bytes)",
+                            len,
+                            max_frame - RPC_ENVELOPE_BYTES
+                        )),
+                        schema_id: None,
+                    });
+                    if let Err(e) = codec
+                        .write_message(&mut *writer.lock().await, &response)
+                        .await
+                    {
+                        break Err(e);
+                    }
+                    continue;
+                }
                 Err(WindError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
                     // Client closed the connection gracefully
-                    break;
+                    break Ok(());
                 }
-                Err(e) => return Err(e),
+                Err(e) => break Err(e),
             };
+            match auth.check(&request.payload) {
+                AuthCheck::Proceed => {}
+                AuthCheck::Accepted(reply) => {
+                    if let Err(e) = codec.write_message(&mut *writer.lock().await, &reply).await {
+                        break Err(e);
+                    }
+                    continue;
+                }
+                AuthCheck::Rejected(reply) => {
+                    warn!("Refused unauthenticated {}", request.payload.name());
+                    break codec.write_message(&mut *writer.lock().await, &reply).await;
+                }
+            }
 
             match request.payload {
                 MessagePayload::RpcCall {
@@ -198,45 +451,129 @@ impl RpcServer {
                 } => {
                     debug!("Received RPC call: {}::{}", service, method);
 
-                    let response = {
-                        let methods_guard = methods.read().await;
-                        if let Some(handler) = methods_guard.get(&method) {
-                            match handler.handle(params).await {
-                                Ok(result) => MessagePayload::RpcResponse {
-                                    call_id: request.id,
-                                    result: Ok(result),
-                                    schema_id,
-                                },
-                                Err(e) => MessagePayload::RpcResponse {
-                                    call_id: request.id,
-                                    result: Err(e.to_string()),
-                                    schema_id: None,
-                                },
-                            }
-                        } else {
-                            MessagePayload::RpcResponse {
-                                call_id: request.id,
-                                result: Err(format!("Method not found: {}", method)),
+                    let call_id = request.id;
+                    let Some(handler) = methods.read().await.get(&method).cloned() else {
+                        let response = Message::new(MessagePayload::RpcResponse {
+                            call_id,
+                            result: Err(format!("Method not found: {}", method)),
+                            schema_id: None,
+                        });
+                        if let Err(e) = codec
+                            .write_message(&mut *writer.lock().await, &response)
+                            .await
+                        {
+                            break Err(e);
+                        }
+                        continue;
+                    };
+
+                    let writer = writer.clone();
+                    calls.spawn(async move {
+                        let response = match handler.handle(params).await {
+                            Ok(result) => MessagePayload::RpcResponse {
+                                call_id,
+                                result: Ok(result),
+                                schema_id,
+                            },
+                            Err(e) => MessagePayload::RpcResponse {
+                                call_id,
+                                result: Err(e.to_string()),
                                 schema_id: None,
-                            }
+                            },
+                        };
+                        let response = Message::new(response);
+                        if let Err(e) = codec
+                            .write_message(&mut *writer.lock().await, &response)
+                            .await
+                        {
+                            warn!("Failed to send RPC response {}: {}", call_id, e);
+                        }
+                    });
+                }
+                MessagePayload::RpcStreamOpen {
+                    service,
+                    method,
+                    schema_id,
+                } => {
+                    debug!(
+                        "Received client-streaming RPC call: {}::{}",
+                        service, method
+                    );
+                    let call_id = request.id;
+                    let Some(handler) = stream_methods.read().await.get(&method).cloned() else {
+                        let response = Message::new(MessagePayload::RpcResponse {
+                            call_id,
+                            result: Err(format!("Method not found: {}", method)),
+                            schema_id: None,
+                        });
+                        if let Err(e) = codec
+                            .write_message(&mut *writer.lock().await, &response)
+                            .await
+                        {
+                            break Err(e);
                         }
+                        continue;
                     };
 
-                    let response_msg = Message::new(response);
-                    MessageCodec::write(&mut stream, &response_msg).await?;
+                    let (items_tx, items_rx) = mpsc::channel(STREAM_ITEM_BUFFER);
+                    open_streams.insert(call_id, items_tx);
+                    let writer = writer.clone();
+                    calls.spawn(async move {
+                        let response = match handler.handle(items_rx.boxed()).await {
+                            Ok(result) => MessagePayload::RpcResponse {
+                                call_id,
+                                result: Ok(result),
+                                schema_id,
+                            },
+                            Err(e) => MessagePayload::RpcResponse {
+                                call_id,
+                                result: Err(e.to_string()),
+                                schema_id: None,
+                            },
+                        };
+                        let response = Message::new(response);
+                        if let Err(e) = codec
+                            .write_message(&mut *writer.lock().await, &response)
+                            .await
+                        {
+                            warn!("Failed to send streaming RPC response {}: {}", call_id, e);
+                        }
+                    });
+                }
+                MessagePayload::RpcStreamItem { call_id, item } => {
+                    if let Some(items) = open_streams.get_mut(&call_id) {
+                        // The handler may finish without reading every item
+                        if items.send(item).await.is_err() {
+                            open_streams.remove(&call_id);
+                        }
+                    } else {
+                        debug!("Dropped item for unknown RPC stream {}", call_id);
+                    }
+                }
+                MessagePayload::RpcStreamEnd { call_id } => {
+                    open_streams.remove(&call_id);
                 }
                 MessagePayload::Ping => {
                     let pong = Message::new(MessagePayload::Pong);
-                    MessageCodec::write(&mut stream, &pong).await?;
+                    if let Err(e) = codec.write_message(&mut *writer.lock().await, &pong).await {
+                        break Err(e);
+                    }
                 }
-                _ => {
-                    warn!(
-                        "Unexpected message type in RPC server: {:?}",
-                        request.payload
-                    );
+                // Sent on connect by clients with an id; RPC servers have no ACL
+                MessagePayload::Identify { .. } => {}
+                other => {
+                    warn!("Unsupported message type in RPC server: {}", other.name());
+                    let error = Message::unsupported(&other, &service_name, ServiceType::RpcServer);
+                    if let Err(e) = codec.write_message(&mut *writer.lock().await, &error).await {
+                        break Err(e);
+                    }
                 }
             }
-        }
-        Ok(())
+        };
+
+        // Streams the client never ended stop here; their handlers still answer
+        open_streams.clear();
+        while calls.join_next().await.is_some() {}
+        result
     }
 }
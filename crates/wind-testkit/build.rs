@@ -0,0 +1,15 @@
+//! Generates the types of `tests/fixtures/reading_idl.json` with wind-codegen,
+//! for `codegen_test` to compile and exercise
+
+use std::path::Path;
+
+fn main() {
+    let idl = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../tests/fixtures/reading_idl.json");
+    println!("cargo:rerun-if-changed={}", idl.display());
+
+    let idl = std::fs::read_to_string(&idl).expect("read the fixture IDL");
+    let code = wind_codegen::generate_rust_types(&idl).expect("generate the fixture types");
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is set for build scripts");
+    std::fs::write(Path::new(&out_dir).join("reading_types.rs"), code)
+        .expect("write the generated types");
+}
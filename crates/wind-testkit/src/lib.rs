@@ -0,0 +1,172 @@
+//! An in-process WIND cluster for integration tests: a registry on an
+//! ephemeral port plus publishers and RPC servers that are ready (registered
+//! and discoverable) by the time they're handed back, so tests need neither
+//! fixed ports nor sleeps.
+
+use std::sync::Arc;
+use tokio::task::JoinSet;
+use tokio::time::Duration;
+use tracing::warn;
+use wind_client::WindClient;
+use wind_core::{Result, ServiceInfo, WindError, WindListener};
+use wind_registry::{Registry, RegistryServer};
+use wind_server::{Publisher, RpcServer};
+
+/// How long a spawned service may take to register before it counts as failed
+const READY_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often readiness is re-checked while waiting
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// A registry and the services spawned against it, all stopped when the
+/// cluster is dropped
+pub struct TestCluster {
+    registry_address: String,
+    registry: Arc<Registry>,
+    tasks: JoinSet<()>,
+}
+
+impl TestCluster {
+    /// Start a registry on an ephemeral localhost port. It accepts
+    /// connections as soon as this returns.
+    pub async fn start() -> Result<Self> {
+        let listener = WindListener::bind("127.0.0.1:0").await?;
+        let registry_address = listener.local_address()?;
+        let server = RegistryServer::new(registry_address.clone());
+        let registry = server.registry();
+
+        let mut tasks = JoinSet::new();
+        tasks.spawn(async move {
+            if let Err(e) = server.serve_until(listener, std::future::pending()).await {
+                warn!("Test registry error: {}", e);
+            }
+        });
+
+        Ok(Self {
+            registry_address,
+            registry,
+            tasks,
+        })
+    }
+
+    pub fn registry_address(&self) -> &str {
+        &self.registry_address
+    }
+
+    /// The registry itself, for inspecting or changing it directly
+    pub fn registry(&self) -> Arc<Registry> {
+        self.registry.clone()
+    }
+
+    /// A client of the cluster's registry
+    pub fn client(&self) -> WindClient {
+        WindClient::new(self.registry_address.clone())
+    }
+
+    /// Start a publisher of `service_name` on an ephemeral port and wait
+    /// until it is discoverable
+    pub async fn spawn_publisher(&mut self, service_name: &str) -> Result<Arc<Publisher>> {
+        let publisher = Publisher::new(
+            service_name.to_string(),
+            "127.0.0.1:0".to_string(),
+            self.registry_address.clone(),
+        );
+        self.spawn_configured_publisher(publisher).await
+    }
+
+    /// Start a publisher built by the test, e.g. with tags or history, and
+    /// wait until all its services are discoverable. It must point at
+    /// `registry_address`.
+    pub async fn spawn_configured_publisher(
+        &mut self,
+        publisher: Publisher,
+    ) -> Result<Arc<Publisher>> {
+        let publisher = Arc::new(publisher);
+        let running = publisher.clone();
+        self.tasks.spawn(async move {
+            if let Err(e) = running.start().await {
+                warn!("Test publisher error: {}", e);
+            }
+        });
+        for service_name in publisher.service_names() {
+            self.wait_for_service(service_name).await?;
+        }
+        Ok(publisher)
+    }
+
+    /// Start an RPC server for `service_name` on an ephemeral port and wait
+    /// until it is discoverable. Methods can be registered on the returned
+    /// server at any time.
+    pub async fn spawn_rpc_server(&mut self, service_name: &str) -> Result<Arc<RpcServer>> {
+        let server = Arc::new(RpcServer::new(
+            service_name.to_string(),
+            "127.0.0.1:0".to_string(),
+            self.registry_address.clone(),
+        ));
+        let running = server.clone();
+        self.tasks.spawn(async move {
+            if let Err(e) = running.start().await {
+                warn!("Test RPC server error: {}", e);
+            }
+        });
+        self.wait_for_service(service_name).await?;
+        Ok(server)
+    }
+
+    /// Wait until `service_name` is registered, returning its registration
+    pub async fn wait_for_service(&self, service_name: &str) -> Result<ServiceInfo> {
+        let registered = tokio::time::timeout(READY_TIMEOUT, async {
+            loop {
+                if let Some(info) = self.registry.lookup_service(service_name).await {
+                    return info;
+                }
+                tokio::time::sleep(READY_POLL_INTERVAL).await;
+            }
+        })
+        .await;
+        registered.map_err(|_| {
+            WindError::Timeout(format!(
+                "{} did not register within {:?}",
+                service_name, READY_TIMEOUT
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wind_core::WindValue;
+
+    #[tokio::test]
+    async fn test_cluster_pub_sub_and_rpc_without_sleeps() {
+        let mut cluster = TestCluster::start().await.unwrap();
+        let publisher = cluster.spawn_publisher("TEST/TESTKIT").await.unwrap();
+        let rpc_server = cluster.spawn_rpc_server("TEST/TESTKIT_RPC").await.unwrap();
+        rpc_server
+            .register_function("echo".to_string(), |params| async move { Ok(params) })
+            .await
+            .unwrap();
+
+        let mut client = cluster.client();
+        let services = client.discover("TEST/*").await.unwrap();
+        assert_eq!(services.len(), 2);
+
+        let mut subscription = client.subscribe("TEST/TESTKIT").await.unwrap();
+        publisher.publish(WindValue::from("hello")).await.unwrap();
+        let received = tokio::time::timeout(Duration::from_secs(5), subscription.next())
+            .await
+            .unwrap();
+        assert_eq!(received, Some(WindValue::from("hello")));
+
+        let echoed = client
+            .call("TEST/TESTKIT_RPC", "echo", WindValue::I64(7))
+            .await
+            .unwrap();
+        assert_eq!(echoed, WindValue::I64(7));
+
+        // Each cluster gets its own registry, so clusters don't collide
+        let other = TestCluster::start().await.unwrap();
+        assert_ne!(other.registry_address(), cluster.registry_address());
+        assert!(other.client().discover("*").await.unwrap().is_empty());
+    }
+}
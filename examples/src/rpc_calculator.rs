@@ -31,31 +31,19 @@ async fn main() -> Result<()> {
     // Register calculator methods
     calc_server
         .register_function("add".to_string(), |params| async move {
-            if let WindValue::Map(map) = params {
-                let a = extract_f64(&map, "a")?;
-                let b = extract_f64(&map, "b")?;
-                Ok(WindValue::F64(a + b))
-            } else {
-                Err(wind_core::WindError::TypeMismatch {
-                    expected: "Map with 'a' and 'b' fields".to_string(),
-                    actual: format!("{:?}", params),
-                })
-            }
+            let map = HashMap::try_from(params)?;
+            let a = extract_f64(&map, "a")?;
+            let b = extract_f64(&map, "b")?;
+            Ok(WindValue::F64(a + b))
         })
         .await?;
 
     calc_server
         .register_function("multiply".to_string(), |params| async move {
-            if let WindValue::Map(map) = params {
-                let a = extract_f64(&map, "a")?;
-                let b = extract_f64(&map, "b")?;
-                Ok(WindValue::F64(a * b))
-            } else {
-                Err(wind_core::WindError::TypeMismatch {
-                    expected: "Map with 'a' and 'b' fields".to_string(),
-                    actual: format!("{:?}", params),
-                })
-            }
+            let map = HashMap::try_from(params)?;
+            let a = extract_f64(&map, "a")?;
+            let b = extract_f64(&map, "b")?;
+            Ok(WindValue::F64(a * b))
         })
         .await?;
 
@@ -98,16 +86,11 @@ async fn main() -> Result<()> {
 }
 
 fn extract_f64(map: &HashMap<String, WindValue>, key: &str) -> Result<f64> {
-    map.get(key)
-        .and_then(|v| {
-            if let WindValue::F64(n) = v {
-                Some(*n)
-            } else {
-                None
-            }
-        })
+    let value = map
+        .get(key)
         .ok_or_else(|| wind_core::WindError::TypeMismatch {
             expected: format!("f64 field '{}'", key),
-            actual: "missing or wrong type".to_string(),
-        })
+            actual: "missing".to_string(),
+        })?;
+    f64::try_from(value.clone())
 }
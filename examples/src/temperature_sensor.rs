@@ -55,20 +55,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             // Simulate temperature variation
             temperature += (rand::random::<f64>() - 0.5) * 2.0;
-            temperature = temperature.max(15.0).min(30.0);
+            temperature = temperature.clamp(15.0, 30.0);
 
             // Create temperature reading with multiple fields
             let mut reading = HashMap::new();
             reading.insert("temperature".to_string(), WindValue::F64(temperature));
-            reading.insert(
-                "timestamp".to_string(),
-                WindValue::I64(
-                    std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs() as i64,
-                ),
-            );
+            reading.insert("timestamp".to_string(), WindValue::now());
             reading.insert(
                 "sensor_id".to_string(),
                 WindValue::String("TEMP_001".to_string()),
@@ -111,6 +103,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 reliability: wind_core::ReliabilityLevel::Reliable,
                 durability: true,
                 max_queue_size: 100,
+                max_queue_bytes: None,
             },
         )
         .await?;
@@ -125,42 +118,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     while let Some(value) = subscription.next().await {
         sample_count += 1;
 
-        if let WindValue::Map(reading) = value {
-            let temp = if let Some(WindValue::F64(t)) = reading.get("temperature") {
-                *t
-            } else {
-                continue;
-            };
-
-            let _timestamp = if let Some(WindValue::I64(ts)) = reading.get("timestamp") {
-                *ts
-            } else {
-                0
-            };
-
-            let sensor_id = if let Some(WindValue::String(id)) = reading.get("sensor_id") {
-                id.clone()
-            } else {
-                "unknown".to_string()
-            };
-
-            let change = temp - last_temp;
-            last_temp = temp;
-
-            info!(
-                "🌡️  {} | {:.2}°C | Change: {:.2}°C | Samples: {} | Rate: {:.1}/s",
-                sensor_id,
-                temp,
-                change,
-                sample_count,
-                sample_count as f64 / start_time.elapsed().as_secs_f64()
-            );
-
-            // Stop after 30 seconds for demo
-            if start_time.elapsed().as_secs() > 30 {
-                info!("Demo completed after 30 seconds");
-                break;
-            }
+        let Some(&WindValue::F64(temp)) = value.get_path("temperature") else {
+            continue;
+        };
+
+        let sensor_id = match value.get_path("sensor_id") {
+            Some(WindValue::String(id)) => id.as_str(),
+            _ => "unknown",
+        };
+
+        let change = temp - last_temp;
+        last_temp = temp;
+
+        info!(
+            "🌡️  {} | {:.2}°C | Change: {:.2}°C | Samples: {} | Rate: {:.1}/s",
+            sensor_id,
+            temp,
+            change,
+            sample_count,
+            sample_count as f64 / start_time.elapsed().as_secs_f64()
+        );
+
+        // Stop after 30 seconds for demo
+        if start_time.elapsed().as_secs() > 30 {
+            info!("Demo completed after 30 seconds");
+            break;
         }
     }
 
@@ -38,8 +38,7 @@ fn bench_message_serialization(c: &mut Criterion) {
             payload_size,
             |b, _| {
                 b.to_async(&rt).iter(|| async {
-                    let mut cursor = std::io::Cursor::new(&encoded[4..]); // Skip length prefix
-                    let decoded: Message = bincode::deserialize_from(cursor).unwrap();
+                    let (decoded, _) = MessageCodec::decode_from_slice(&encoded).unwrap();
                     black_box(decoded);
                 });
             }
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::time::{Duration, UNIX_EPOCH};
+use wind_core::{WindError, WindValue};
+
+/// The types wind-codegen generates from `tests/fixtures/reading_idl.json`,
+/// written by the testkit's build script
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/reading_types.rs"));
+}
+
+use generated::{Location, Mode, Reading, Sample, Status, When};
+
+fn reading() -> Reading {
+    Reading {
+        sensor_id: "TEMP_001".to_string(),
+        celsius: 21.5,
+        gain: 0.5,
+        count: 3,
+        total: i64::MAX,
+        valid: true,
+        raw: vec![0, 1, 255],
+        taken_at: UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456),
+        history: vec![20.5, 21.0],
+        gaps: vec![Some(20.5), None, Some(21.5)],
+        location: Location {
+            latitude: 46.2,
+            longitude: 6.1,
+            label: Some("hall".to_string()),
+        },
+        nearby: vec![Location {
+            latitude: 46.3,
+            longitude: 6.2,
+            label: None,
+        }],
+        status: Status::Active,
+        offset: None,
+        note: Some("calibrated".to_string()),
+    }
+}
+
+fn fields(value: &WindValue) -> &HashMap<String, WindValue> {
+    match value {
+        WindValue::Map(map) => map,
+        other => panic!("Expected a map, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_generated_struct_round_trips_through_wind_value() {
+    let original = reading();
+    let value = WindValue::from(original.clone());
+
+    let map = fields(&value);
+    assert_eq!(map["sensor_id"], WindValue::from("TEMP_001"));
+    assert_eq!(map["count"], WindValue::I32(3));
+    assert_eq!(map["raw"], WindValue::Bytes(vec![0, 1, 255]));
+    assert_eq!(map["taken_at"], WindValue::Timestamp(1_700_000_000_123_456));
+    assert_eq!(
+        map["gaps"],
+        WindValue::Array(vec![
+            WindValue::F64(20.5),
+            WindValue::Null,
+            WindValue::F64(21.5)
+        ])
+    );
+    assert_eq!(fields(&map["location"])["label"], WindValue::from("hall"));
+    // An absent optional field is left out
+    match &map["nearby"] {
+        WindValue::Array(nearby) => assert!(!fields(&nearby[0]).contains_key("label")),
+        other => panic!("Expected an array, got {:?}", other),
+    }
+    assert_eq!(map["status"], WindValue::from("Status::Active"));
+    assert!(!map.contains_key("offset"));
+    assert_eq!(map["note"], WindValue::from("calibrated"));
+
+    assert_eq!(Reading::try_from(value).unwrap(), original);
+
+    let sparse = Reading {
+        note: None,
+        offset: Some(-0.5),
+        ..reading()
+    };
+    let value = WindValue::from(sparse.clone());
+    // While an absent value of an optional type is null
+    assert_eq!(fields(&value)["note"], WindValue::Null);
+    assert_eq!(Reading::try_from(value).unwrap(), sparse);
+}
+
+#[test]
+fn test_generated_struct_checks_field_types() {
+    let with = |field: &str, replacement: Option<WindValue>| {
+        let mut value = WindValue::from(reading());
+        let WindValue::Map(map) = &mut value else {
+            unreachable!()
+        };
+        match replacement {
+            Some(replacement) => map.insert(field.to_string(), replacement),
+            None => map.remove(field),
+        };
+        Reading::try_from(value)
+    };
+
+    let error = with("celsius", Some(WindValue::from("warm"))).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Invalid element at field 'celsius': Type mismatch: expected F64, got String"
+    );
+    // Micros in an I64 aren't a time
+    let error = with("taken_at", Some(WindValue::I64(0))).unwrap_err();
+    assert!(
+        error.to_string().ends_with("expected Timestamp, got I64"),
+        "{}",
+        error
+    );
+    let error = with("gaps", Some(WindValue::Array(vec![WindValue::I32(1)]))).unwrap_err();
+    assert!(
+        error
+            .to_string()
+            .starts_with("Invalid element at field 'gaps': Invalid element at index 0"),
+        "{}",
+        error
+    );
+    match with("location", None) {
+        Err(WindError::Schema(error)) => assert_eq!(error, "Missing required field: location"),
+        other => panic!("Expected a missing field error, got {:?}", other),
+    }
+    // Optional fields may be missing or null
+    assert_eq!(with("offset", Some(WindValue::Null)).unwrap(), reading());
+    assert_eq!(
+        with("note", None).unwrap(),
+        Reading {
+            note: None,
+            ..reading()
+        }
+    );
+
+    match Reading::try_from(WindValue::I32(1)) {
+        Err(WindError::TypeMismatch { expected, actual }) => {
+            assert_eq!((expected.as_str(), actual.as_str()), ("Reading", "I32"));
+        }
+        other => panic!("Expected a type mismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_generated_enum_round_trips_by_variant_name() {
+    for status in [Status::Idle, Status::Active, Status::Fault] {
+        let value = WindValue::from(status.clone());
+        assert_eq!(value, WindValue::String(format!("Status::{:?}", status)));
+        assert_eq!(Status::try_from(value).unwrap(), status);
+    }
+    // Bare names, as publishers in other languages might send, still match
+    assert_eq!(
+        Status::try_from(WindValue::from("Fault")).unwrap(),
+        Status::Fault
+    );
+
+    match Status::try_from(WindValue::from("Broken")) {
+        Err(WindError::Schema(error)) => assert_eq!(error, "Unknown Status variant: Broken"),
+        other => panic!("Expected an unknown variant error, got {:?}", other),
+    }
+    // Another enum's variant of the same name isn't this one's
+    let active = WindValue::from(Mode::Active);
+    assert_eq!(active, WindValue::from("Mode::Active"));
+    assert!(Status::try_from(active).is_err());
+    assert_eq!(
+        Mode::try_from(WindValue::from(Mode::Off)).unwrap(),
+        Mode::Off
+    );
+    match Status::try_from(WindValue::I32(1)) {
+        Err(WindError::TypeMismatch { expected, actual }) => {
+            assert_eq!((expected.as_str(), actual.as_str()), ("Status", "I32"));
+        }
+        other => panic!("Expected a type mismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_generated_union_round_trips_each_member() {
+    let taken_at = UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456);
+    let value = WindValue::from(When::Timestamp(taken_at));
+    assert_eq!(value, WindValue::Timestamp(1_700_000_000_123_456));
+    assert_eq!(When::try_from(value).unwrap(), When::Timestamp(taken_at));
+    assert_eq!(
+        When::try_from(WindValue::from("dawn")).unwrap(),
+        When::String("dawn".to_string())
+    );
+
+    let samples = [
+        Sample::Variant0(vec![20.5, 21.0]),
+        Sample::Variant1(Some(7)),
+        Sample::Variant1(None),
+        Sample::Location(Location {
+            latitude: 46.2,
+            longitude: 6.1,
+            label: None,
+        }),
+    ];
+    for sample in samples {
+        let value = WindValue::from(sample.clone());
+        assert_eq!(Sample::try_from(value).unwrap(), sample);
+    }
+    assert_eq!(WindValue::from(Sample::Variant1(None)), WindValue::Null);
+
+    match When::try_from(WindValue::I64(0)) {
+        Err(WindError::TypeMismatch { expected, actual }) => {
+            assert_eq!(
+                (expected.as_str(), actual.as_str()),
+                ("one of Timestamp | String", "I64")
+            );
+        }
+        other => panic!("Expected a type mismatch, got {:?}", other),
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use tokio::time::{timeout, Duration};
+use wind_client::WindClient;
+use wind_core::rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
+use wind_core::rustls::{ClientConfig, RootCertStore, ServerConfig};
+use wind_core::{SocketOptions, WindListener, WindValue, TLS_SCHEME};
+use wind_registry::RegistryServer;
+use wind_server::Publisher;
+
+/// Server and client configs for a certificate valid for 127.0.0.1 only,
+/// which the client trusts directly
+fn self_signed_configs() -> (ServerConfig, ClientConfig) {
+    let certified = rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()]).unwrap();
+    let cert = certified.cert.der().clone();
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der()));
+
+    let server = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert.clone()], key)
+        .unwrap();
+
+    let mut roots = RootCertStore::empty();
+    roots.add(cert).unwrap();
+    let client = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    (server, client)
+}
+
+#[tokio::test]
+async fn test_pub_sub_over_tls() {
+    let _ = tracing_subscriber::fmt().try_init();
+    let (server_config, client_config) = self_signed_configs();
+    let server_config = Arc::new(server_config);
+    let options = SocketOptions::default().with_tls(client_config);
+
+    // Registry
+    let listener = WindListener::bind_tls("127.0.0.1:0", server_config.clone())
+        .await
+        .unwrap();
+    let registry_address = listener.local_address().unwrap();
+    assert!(registry_address.starts_with(TLS_SCHEME));
+    let registry = RegistryServer::new(registry_address.clone());
+    tokio::spawn(async move { registry.serve_until(listener, std::future::pending()).await });
+
+    // Publisher, registering with the registry over TLS too
+    let publisher = Arc::new(
+        Publisher::new(
+            "SECURE/ROOM_A/TEMP".to_string(),
+            "127.0.0.1:0".to_string(),
+            registry_address.clone(),
+        )
+        .with_tls((*server_config).clone())
+        .with_socket_options(options.clone()),
+    );
+    let running = publisher.clone();
+    tokio::spawn(async move { running.start().await });
+
+    let mut client = WindClient::new(registry_address).with_socket_options(options);
+    let service = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(mut services) = client.discover("SECURE/ROOM_A/TEMP").await {
+                if let Some(service) = services.pop() {
+                    return service;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("Publisher never registered");
+    // Discovery tells clients to connect with TLS
+    assert!(
+        service.address.starts_with(TLS_SCHEME),
+        "{}",
+        service.address
+    );
+
+    let mut subscription = client.subscribe("SECURE/ROOM_A/TEMP").await.unwrap();
+    publisher.publish(WindValue::F64(21.5)).await.unwrap();
+
+    let received = timeout(Duration::from_secs(5), subscription.next())
+        .await
+        .expect("Timeout waiting for message")
+        .expect("Expected message");
+    assert_eq!(received, WindValue::F64(21.5));
+}